@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What happened to a path
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityAction {
+    Delete,
+    Trash,
+    /// Put back from the trash
+    Restore,
+    /// Permanently deleted from the trash
+    Purge,
+}
+
+/// A single entry in a vault's activity log
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityEntry {
+    /// Unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub path: String,
+    pub action: ActivityAction,
+}
+
+/// Location of the activity log within a vault
+fn activity_log_path(vault_root: &Path) -> PathBuf {
+    vault_root.join(".manza").join("activity.log")
+}
+
+/// Current time as unix milliseconds
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Append an entry to the vault's activity log, one JSON object per line
+pub fn log_activity(vault_root: &str, path: &str, action: ActivityAction) -> Result<(), String> {
+    let log_path = activity_log_path(Path::new(vault_root));
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create activity log directory: {}", e))?;
+    }
+
+    let entry = ActivityEntry {
+        timestamp: now_millis(),
+        path: path.to_string(),
+        action,
+    };
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| format!("Failed to serialize activity entry: {}", e))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| format!("Failed to open activity log: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write activity log: {}", e))
+}
+
+/// Run a delete operation and, once it succeeds, record it in the vault's
+/// activity log. Logging failures are reported but don't fail the delete.
+pub fn logged_delete<E>(
+    vault_root: Option<&str>,
+    path: &str,
+    action: ActivityAction,
+    delete: impl FnOnce(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    delete(path)?;
+    record_activity(vault_root, path, action);
+    Ok(())
+}
+
+/// Record something that already happened in the vault's activity log, if
+/// there is a vault. Logging failures are reported rather than returned.
+pub fn record_activity(vault_root: Option<&str>, path: &str, action: ActivityAction) {
+    if let Some(root) = vault_root {
+        if let Err(e) = log_activity(root, path, action) {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Read the most recent activity entries, newest first
+pub fn read_activity_log(vault_root: &str, limit: usize) -> Result<Vec<ActivityEntry>, String> {
+    let log_path = activity_log_path(Path::new(vault_root));
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(&log_path).map_err(|e| format!("Failed to read activity log: {}", e))?;
+
+    // Skip lines that don't parse, e.g. a partial write after a crash
+    Ok(content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::delete_file;
+
+    #[test]
+    fn test_delete_appends_activity_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "content").unwrap();
+        let note = note.to_string_lossy().to_string();
+
+        logged_delete(Some(&root), &note, ActivityAction::Delete, delete_file).unwrap();
+
+        let entries = read_activity_log(&root, 10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, note);
+        assert_eq!(entries[0].action, ActivityAction::Delete);
+    }
+}
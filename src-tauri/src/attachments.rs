@@ -0,0 +1,152 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{unique_path, write_file_atomic};
+use crate::vault::relative_link_target;
+
+/// Encoding to save optimized images in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageOutput {
+    Png,
+    Jpeg,
+    /// Lossless WebP
+    Webp,
+}
+
+impl ImageOutput {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageOutput::Png => "png",
+            ImageOutput::Jpeg => "jpg",
+            ImageOutput::Webp => "webp",
+        }
+    }
+}
+
+/// How pasted images are shrunk before saving
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ImageOptimizeOptions {
+    /// Longest allowed width or height; larger images are scaled down to fit
+    pub max_dimension: u32,
+    pub format: ImageOutput,
+    /// JPEG quality from 1 to 100
+    pub quality: u8,
+}
+
+impl Default for ImageOptimizeOptions {
+    fn default() -> Self {
+        ImageOptimizeOptions {
+            max_dimension: 2048,
+            format: ImageOutput::Webp,
+            quality: 80,
+        }
+    }
+}
+
+/// Where an attachment was saved and how much optimizing it saved
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedAttachment {
+    pub path: String,
+    /// Path relative to the note, ready to use as a link target
+    pub relative_path: String,
+    pub original_bytes: u64,
+    pub saved_bytes: u64,
+}
+
+/// Downscale an image to fit within `max_dimension` and re-encode it
+pub fn optimize_image(bytes: &[u8], options: &ImageOptimizeOptions) -> Result<Vec<u8>, String> {
+    let mut image =
+        image::load_from_memory(bytes).map_err(|e| format!("Failed to decode image: {}", e))?;
+    let max = options.max_dimension.max(1);
+    if image.width() > max || image.height() > max {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    let mut encoded = Vec::new();
+    let result = match options.format {
+        ImageOutput::Png => image.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png),
+        ImageOutput::Webp => image.write_to(&mut Cursor::new(&mut encoded), ImageFormat::WebP),
+        // JPEG has no alpha channel
+        ImageOutput::Jpeg => DynamicImage::from(image.to_rgb8()).write_with_encoder(
+            JpegEncoder::new_with_quality(&mut encoded, options.quality.clamp(1, 100)),
+        ),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(encoded)
+}
+
+/// Save pasted bytes next to a note. Images are optimized and take the
+/// output format's extension; anything that isn't a decodable image is
+/// saved as-is. Existing files are never overwritten.
+pub fn save_optimized_attachment(
+    note_path: &str,
+    file_name: &str,
+    bytes: &[u8],
+    options: &ImageOptimizeOptions,
+) -> Result<SavedAttachment, String> {
+    let note_dir = Path::new(note_path)
+        .parent()
+        .ok_or_else(|| format!("Note has no parent directory: {}", note_path))?;
+    let requested = note_dir.join(
+        Path::new(file_name)
+            .file_name()
+            .ok_or_else(|| format!("Invalid file name: {}", file_name))?,
+    );
+
+    let (target, content): (PathBuf, Vec<u8>) = match optimize_image(bytes, options) {
+        Ok(optimized) => (
+            requested.with_extension(options.format.extension()),
+            optimized,
+        ),
+        Err(_) => (requested, bytes.to_vec()),
+    };
+    let target = if target.exists() {
+        unique_path(&target)
+    } else {
+        target
+    };
+
+    write_file_atomic(&target.to_string_lossy(), &content)?;
+    Ok(SavedAttachment {
+        path: target.to_string_lossy().to_string(),
+        relative_path: relative_link_target(note_dir, &target),
+        original_bytes: bytes.len() as u64,
+        saved_bytes: content.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_large_image_is_downscaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "").unwrap();
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(3000, 1500)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let options = ImageOptimizeOptions {
+            max_dimension: 1000,
+            format: ImageOutput::Jpeg,
+            quality: 70,
+        };
+
+        let saved = save_optimized_attachment(&note.to_string_lossy(), "paste.png", &png, &options)
+            .unwrap();
+
+        assert_eq!(saved.relative_path, "paste.jpg");
+        let image = image::load_from_memory(&fs::read(&saved.path).unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (1000, 500));
+        assert!(saved.saved_bytes < saved.original_bytes);
+    }
+}
@@ -0,0 +1,343 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{write_file, write_file_atomic};
+use crate::vault::{vault_files, IgnoreRules};
+
+/// Name format of snapshot folders, e.g. `2024-05-01-093000`
+const SNAPSHOT_FORMAT: &str = "%Y-%m-%d-%H%M%S";
+
+/// Timestamp in save backup names; milliseconds keep quick saves apart
+const FILE_BACKUP_FORMAT: &str = "%Y-%m-%d-%H%M%S-%3f";
+
+/// Folder, beside the saved file, that save backups go in by default
+pub const DEFAULT_BACKUP_DIR: &str = ".manza-backups";
+
+/// Progress is reported after this many files, and once at the end
+const PROGRESS_INTERVAL: usize = 100;
+
+/// How far a snapshot has got
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct SnapshotProgress {
+    pub copied_files: usize,
+    pub total_files: usize,
+    pub copied_bytes: u64,
+}
+
+/// Copy every non-ignored file of the vault into a new
+/// `backup_root/YYYY-MM-DD-HHMMSS/` folder, returning its path. Large
+/// vaults report progress every `PROGRESS_INTERVAL` files. The files are
+/// copied into a hidden folder that only takes the snapshot's name once
+/// every file is in, so a failed snapshot leaves nothing behind.
+pub fn snapshot_vault(
+    root: &str,
+    backup_root: &str,
+    ignore: &IgnoreRules,
+    mut on_progress: impl FnMut(&SnapshotProgress),
+) -> Result<String, String> {
+    let root = fs::canonicalize(root).map_err(|e| format!("Failed to resolve {}: {}", root, e))?;
+    fs::create_dir_all(backup_root)
+        .map_err(|e| format!("Failed to create backup folder: {}", e))?;
+    let backup_root = fs::canonicalize(backup_root)
+        .map_err(|e| format!("Failed to resolve {}: {}", backup_root, e))?;
+
+    let name = chrono::Local::now().format(SNAPSHOT_FORMAT).to_string();
+    let snapshot = backup_root.join(&name);
+    if snapshot.exists() {
+        return Err(format!("Snapshot already exists: {}", snapshot.display()));
+    }
+
+    // Backups kept inside the vault must not be copied into each other
+    let files: Vec<PathBuf> = vault_files(&root, ignore)
+        .into_iter()
+        .filter(|file| !file.starts_with(&backup_root))
+        .collect();
+    let mut progress = SnapshotProgress {
+        copied_files: 0,
+        total_files: files.len(),
+        copied_bytes: 0,
+    };
+
+    // Removed when dropped, so on any early return
+    let partial = tempfile::Builder::new()
+        .prefix(&format!(".{}.partial-", name))
+        .tempdir_in(&backup_root)
+        .map_err(|e| format!("Failed to create snapshot: {}", e))?;
+    for file in &files {
+        let relative = file.strip_prefix(&root).unwrap_or(file);
+        let target = partial.path().join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        progress.copied_bytes += fs::copy(file, &target)
+            .map_err(|e| format!("Failed to copy {}: {}", relative.display(), e))?;
+        progress.copied_files += 1;
+        if progress.copied_files.is_multiple_of(PROGRESS_INTERVAL) {
+            on_progress(&progress);
+        }
+    }
+    if progress.total_files >= PROGRESS_INTERVAL {
+        on_progress(&progress);
+    }
+
+    let partial = partial.keep();
+    if let Err(e) = fs::rename(&partial, &snapshot) {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(format!("Failed to create snapshot: {}", e));
+    }
+    Ok(snapshot.to_string_lossy().to_string())
+}
+
+/// A saved copy of a file's previous contents
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct FileBackup {
+    pub path: String,
+    /// When the backup was taken, as `YYYY-MM-DDTHH:MM:SS.mmm` local time
+    pub taken: String,
+}
+
+/// The folder holding save backups of `path`: `backup_dir` resolved against
+/// the file's own folder
+fn file_backup_dir(path: &Path, backup_dir: &str) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).join(backup_dir)
+}
+
+/// Backups of `path` in `backup_dir`, oldest first
+fn file_backups(path: &Path, backup_dir: &str) -> Vec<(NaiveDateTime, PathBuf)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}.", name);
+    let Ok(entries) = fs::read_dir(file_backup_dir(path, backup_dir)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(NaiveDateTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let entry_name = entry.file_name().to_string_lossy().to_string();
+            let stamp = entry_name.strip_prefix(&prefix)?.strip_suffix(".bak")?;
+            let taken = NaiveDateTime::parse_from_str(stamp, FILE_BACKUP_FORMAT).ok()?;
+            Some((taken, entry.path()))
+        })
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// Copy the current contents of `path` into its backup folder as
+/// `<name>.<timestamp>.bak`, then prune all but the newest `keep` backups.
+/// Returns the backup's path, or `None` when there was no file to back up.
+pub fn backup_file(path: &str, backup_dir: &str, keep: usize) -> Result<Option<String>, String> {
+    let file = Path::new(path);
+    if !file.is_file() {
+        return Ok(None);
+    }
+    let dir = file_backup_dir(file, backup_dir);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup folder: {}", e))?;
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let stamp = chrono::Local::now().format(FILE_BACKUP_FORMAT);
+    let backup = dir.join(format!("{}.{}.bak", name, stamp));
+    fs::copy(file, &backup).map_err(|e| format!("Failed to back up {}: {}", path, e))?;
+
+    let backups = file_backups(file, backup_dir);
+    let excess = backups.len().saturating_sub(keep.max(1));
+    for (_, old) in backups.into_iter().take(excess) {
+        fs::remove_file(&old).map_err(|e| format!("Failed to remove {}: {}", old.display(), e))?;
+    }
+    Ok(Some(backup.to_string_lossy().to_string()))
+}
+
+/// Back up the file at `path`, if any, then write `content` over it
+pub fn write_file_with_backup(
+    path: &str,
+    content: &str,
+    backup_dir: &str,
+    keep: usize,
+) -> Result<Option<String>, String> {
+    let backup = backup_file(path, backup_dir, keep)?;
+    write_file(path, content)?;
+    Ok(backup)
+}
+
+/// Save backups of `path`, newest first
+pub fn list_file_backups(path: &str, backup_dir: &str) -> Vec<FileBackup> {
+    file_backups(Path::new(path), backup_dir)
+        .into_iter()
+        .rev()
+        .map(|(taken, path)| FileBackup {
+            path: path.to_string_lossy().to_string(),
+            taken: taken.format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+        })
+        .collect()
+}
+
+/// Replace `target` with the contents of a backup
+pub fn restore_file_backup(backup_path: &str, target: &str) -> Result<(), String> {
+    let content = fs::read(backup_path).map_err(|e| format!("Failed to read backup: {}", e))?;
+    write_file_atomic(target, &content).map_err(String::from)
+}
+
+/// Delete all but the `keep` most recent snapshots in `backup_root`,
+/// returning the removed paths oldest first. Entries not named like a
+/// snapshot folder are left alone.
+pub fn prune_snapshots(backup_root: &str, keep: usize) -> Result<Vec<String>, String> {
+    let entries =
+        fs::read_dir(backup_root).map_err(|e| format!("Failed to read backup folder: {}", e))?;
+    let mut snapshots: Vec<(NaiveDateTime, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let taken = NaiveDateTime::parse_from_str(&name, SNAPSHOT_FORMAT).ok()?;
+            Some((taken, entry.path()))
+        })
+        .collect();
+    snapshots.sort();
+
+    let excess = snapshots.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for (_, path) in snapshots.into_iter().take(excess) {
+        fs::remove_dir_all(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        removed.push(path.to_string_lossy().to_string());
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Every file under `dir` as sorted relative paths with contents
+    fn tree(dir: &Path) -> Vec<(PathBuf, String)> {
+        vault_files(dir, &IgnoreRules::default())
+            .into_iter()
+            .map(|file| {
+                let content = fs::read_to_string(&file).unwrap();
+                (file.strip_prefix(dir).unwrap().to_path_buf(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_file_backups_are_capped_and_restorable() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        let path = note.to_string_lossy().to_string();
+
+        // Nothing to back up on the first save
+        assert_eq!(
+            write_file_with_backup(&path, "v0", DEFAULT_BACKUP_DIR, 3),
+            Ok(None)
+        );
+        for version in 1..=4 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            let content = format!("v{}", version);
+            assert!(
+                write_file_with_backup(&path, &content, DEFAULT_BACKUP_DIR, 3)
+                    .unwrap()
+                    .is_some()
+            );
+        }
+
+        let backups = list_file_backups(&path, DEFAULT_BACKUP_DIR);
+        assert_eq!(backups.len(), 3);
+        let contents: Vec<String> = backups
+            .iter()
+            .map(|backup| fs::read_to_string(&backup.path).unwrap())
+            .collect();
+        assert_eq!(contents, vec!["v3", "v2", "v1"]);
+
+        restore_file_backup(&backups[2].path, &path).unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "v1");
+    }
+
+    #[test]
+    fn test_snapshot_copies_vault_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir_all(vault.join("projects/alpha")).unwrap();
+        fs::write(vault.join("inbox.md"), "# Inbox").unwrap();
+        fs::write(vault.join("projects/alpha/plan.md"), "steps").unwrap();
+        fs::write(vault.join("projects/alpha/chart.png"), "png").unwrap();
+        let backups = vault.join("backups");
+
+        let snapshot = snapshot_vault(
+            &vault.to_string_lossy(),
+            &backups.to_string_lossy(),
+            &IgnoreRules::default(),
+            |_| {},
+        )
+        .unwrap();
+
+        let snapshot = PathBuf::from(snapshot);
+        let name = snapshot.file_name().unwrap().to_string_lossy();
+        assert!(NaiveDateTime::parse_from_str(&name, SNAPSHOT_FORMAT).is_ok());
+        let mut expected = tree(&vault);
+        expected.retain(|(path, _)| !path.starts_with("backups"));
+        assert_eq!(tree(&snapshot), expected);
+        assert_eq!(expected.len(), 3);
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_snapshot_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir(&vault).unwrap();
+        fs::write(vault.join("inbox.md"), "# Inbox").unwrap();
+        // A socket has no contents to copy
+        let _socket = std::os::unix::net::UnixListener::bind(vault.join("z.sock")).unwrap();
+        let backups = dir.path().join("backups");
+
+        assert!(snapshot_vault(
+            &vault.to_string_lossy(),
+            &backups.to_string_lossy(),
+            &IgnoreRules::default(),
+            |_| {},
+        )
+        .is_err());
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_prune_keeps_two_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = [
+            "2024-03-01-120000",
+            "2023-12-31-235959",
+            "2024-03-01-090000",
+            "2024-01-15-080000",
+            "notes",
+        ];
+        for name in names {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("2020-01-01-000000"), "not a folder").unwrap();
+
+        let removed = prune_snapshots(&dir.path().to_string_lossy(), 2).unwrap();
+
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        assert_eq!(
+            removed,
+            vec![path("2023-12-31-235959"), path("2024-01-15-080000")]
+        );
+        let mut remaining: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                "2020-01-01-000000",
+                "2024-03-01-090000",
+                "2024-03-01-120000",
+                "notes"
+            ]
+        );
+    }
+}
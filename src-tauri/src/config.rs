@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::backup::DEFAULT_BACKUP_DIR;
+use crate::fs::write_file;
+use crate::render::DEFAULT_RENDER_CACHE_ENTRIES;
+use crate::vault::IgnoreRules;
+
+/// Settings stored per vault in `.manza/config.json`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct VaultConfig {
+    /// Gitignore-style patterns skipped by vault-wide scans
+    pub ignore_patterns: Vec<String>,
+    /// File extensions treated as markdown, without the leading dot
+    pub markdown_extensions: Vec<String>,
+    /// Watcher debounce interval in milliseconds
+    pub debounce_ms: u64,
+    /// Folder, relative to the vault root, that notes organized by tag are
+    /// moved under; the vault root itself when unset
+    pub organize_root: Option<String>,
+    /// Folder, relative to each note's own folder, that save backups go in
+    pub backup_dir: String,
+    /// Save backups kept per file before the oldest are pruned
+    pub backups_per_file: usize,
+    /// Rendered previews kept in memory before the least recently used
+    /// are dropped
+    pub render_cache_entries: usize,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        VaultConfig {
+            ignore_patterns: Vec::new(),
+            markdown_extensions: vec!["md".into(), "markdown".into(), "mdown".into()],
+            debounce_ms: 500,
+            organize_root: None,
+            backup_dir: DEFAULT_BACKUP_DIR.to_string(),
+            backups_per_file: 10,
+            render_cache_entries: DEFAULT_RENDER_CACHE_ENTRIES,
+        }
+    }
+}
+
+fn config_path(vault_root: &Path) -> PathBuf {
+    vault_root.join(".manza").join("config.json")
+}
+
+/// Load a vault's config, falling back to defaults when the file is absent
+pub fn load_vault_config(vault_root: &Path) -> Result<VaultConfig, String> {
+    let path = config_path(vault_root);
+    if !path.exists() {
+        return Ok(VaultConfig::default());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read vault config: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid vault config: {}", e))
+}
+
+/// Persist a vault's config
+pub fn save_vault_config(vault_root: &Path, config: &VaultConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize vault config: {}", e))?;
+    write_file(&config_path(vault_root).to_string_lossy(), &content).map_err(String::from)
+}
+
+/// Recursively merge `partial` into `base`, replacing non-object values
+fn merge_json(base: &mut Value, partial: Value) {
+    match (base, partial) {
+        (Value::Object(base), Value::Object(partial)) => {
+            for (key, value) in partial {
+                merge_json(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, partial) => *base = partial,
+    }
+}
+
+/// The currently open vault and its configuration
+pub struct VaultState {
+    root: Mutex<Option<PathBuf>>,
+    config: Mutex<VaultConfig>,
+}
+
+impl VaultState {
+    pub fn new() -> Self {
+        VaultState {
+            root: Mutex::new(None),
+            config: Mutex::new(VaultConfig::default()),
+        }
+    }
+
+    /// Open a vault, loading its config
+    pub fn open(&self, vault_root: &str) -> Result<VaultConfig, String> {
+        let root = PathBuf::from(vault_root);
+        if !root.is_dir() {
+            return Err(format!("Path is not a directory: {}", vault_root));
+        }
+
+        let config = load_vault_config(&root)?;
+        *self.config.lock().unwrap() = config.clone();
+        *self.root.lock().unwrap() = Some(root);
+        Ok(config)
+    }
+
+    pub fn root(&self) -> Option<PathBuf> {
+        self.root.lock().unwrap().clone()
+    }
+
+    pub fn config(&self) -> VaultConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Merge a partial config into the current one and persist it
+    pub fn update(&self, partial: Value) -> Result<VaultConfig, String> {
+        let root = self.root().ok_or_else(|| "No vault is open".to_string())?;
+        let mut config = self.config.lock().unwrap();
+
+        let mut merged = serde_json::to_value(&*config)
+            .map_err(|e| format!("Failed to serialize vault config: {}", e))?;
+        merge_json(&mut merged, partial);
+        let updated: VaultConfig =
+            serde_json::from_value(merged).map_err(|e| format!("Invalid vault config: {}", e))?;
+
+        save_vault_config(&root, &updated)?;
+        *config = updated.clone();
+        Ok(updated)
+    }
+
+    /// Replace the extensions treated as markdown, lowercased and without
+    /// leading dots; an empty list restores the defaults. Persisted when a
+    /// vault is open, otherwise kept for this session.
+    pub fn set_markdown_extensions(&self, extensions: Vec<String>) -> Result<VaultConfig, String> {
+        let mut normalized: Vec<String> = Vec::new();
+        for extension in extensions {
+            let extension = extension.trim().trim_start_matches('.').to_lowercase();
+            if !extension.is_empty() && !normalized.contains(&extension) {
+                normalized.push(extension);
+            }
+        }
+        if normalized.is_empty() {
+            normalized = VaultConfig::default().markdown_extensions;
+        }
+
+        if self.root().is_some() {
+            return self.update(serde_json::json!({ "markdown_extensions": normalized }));
+        }
+        let mut config = self.config.lock().unwrap();
+        config.markdown_extensions = normalized;
+        Ok(config.clone())
+    }
+
+    /// Ignore rules from explicit patterns, or the vault's configured ones
+    pub fn ignore_rules(&self, patterns: Option<Vec<String>>) -> Result<IgnoreRules, String> {
+        let patterns = patterns.unwrap_or_else(|| self.config().ignore_patterns);
+        IgnoreRules::new(&patterns)
+    }
+}
+
+impl Default for VaultState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+
+        let state = VaultState::new();
+        assert_eq!(state.open(&root).unwrap(), VaultConfig::default());
+        state.update(json!({ "debounce_ms": 250 })).unwrap();
+
+        let reopened = VaultState::new();
+        let config = reopened.open(&root).unwrap();
+        assert_eq!(config.debounce_ms, 250);
+        assert_eq!(
+            config.markdown_extensions,
+            VaultConfig::default().markdown_extensions
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use git2::{DiffOptions, Patch};
+use serde::{Deserialize, Serialize};
+
+use crate::fs::read_file;
+
+/// Largest input, per side, that is diffed; bigger content is refused
+/// rather than held in memory several times over
+pub const MAX_DIFF_BYTES: usize = 8 * 1024 * 1024;
+
+/// Unchanged lines shown around each change when not specified
+pub const DEFAULT_CONTEXT_LINES: u32 = 3;
+
+/// How a diff line relates the old content to the new
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffLineKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A line of a hunk, numbered in whichever versions contain it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line number in the old content; `None` for inserts
+    pub old_line: Option<u32>,
+    /// 1-based line number in the new content; `None` for deletes
+    pub new_line: Option<u32>,
+    /// The line without its line break
+    pub content: String,
+}
+
+/// A run of changes with its surrounding context, like a unified diff hunk
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// Unified diff header, e.g. `@@ -3,4 +3,5 @@`
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+fn check_size(label: &str, content: &str) -> Result<(), String> {
+    if content.len() > MAX_DIFF_BYTES {
+        return Err(format!(
+            "{} content is too large to diff ({} bytes, limit {})",
+            label,
+            content.len(),
+            MAX_DIFF_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Line diff of two texts as unified-diff-style hunks, each with up to
+/// `context_lines` unchanged lines around its changes. Identical texts give
+/// no hunks.
+pub fn diff_text(old: &str, new: &str, context_lines: u32) -> Result<Vec<DiffHunk>, String> {
+    check_size("Old", old)?;
+    check_size("New", new)?;
+
+    let mut options = DiffOptions::new();
+    options.context_lines(context_lines).force_text(true);
+    let patch = Patch::from_buffers(
+        old.as_bytes(),
+        None,
+        new.as_bytes(),
+        None,
+        Some(&mut options),
+    )
+    .map_err(|e| format!("Failed to diff content: {}", e))?;
+
+    let mut hunks = Vec::with_capacity(patch.num_hunks());
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch
+            .hunk(hunk_index)
+            .map_err(|e| format!("Failed to read diff hunk: {}", e))?;
+        let mut lines = Vec::with_capacity(line_count);
+        for line_index in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_index, line_index)
+                .map_err(|e| format!("Failed to read diff line: {}", e))?;
+            let kind = match line.origin() {
+                ' ' => DiffLineKind::Equal,
+                '+' => DiffLineKind::Insert,
+                '-' => DiffLineKind::Delete,
+                // "No newline at end of file" markers
+                _ => continue,
+            };
+            let content = String::from_utf8_lossy(line.content());
+            lines.push(DiffLine {
+                kind,
+                old_line: line.old_lineno(),
+                new_line: line.new_lineno(),
+                content: content.trim_end_matches(['\n', '\r']).to_string(),
+            });
+        }
+        hunks.push(DiffHunk {
+            header: format!(
+                "@@ -{},{} +{},{} @@",
+                hunk.old_start(),
+                hunk.old_lines(),
+                hunk.new_start(),
+                hunk.new_lines()
+            ),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines,
+        });
+    }
+    Ok(hunks)
+}
+
+/// Line diff of two files' contents; see `diff_text`
+pub fn diff_files(path_a: &str, path_b: &str, context_lines: u32) -> Result<Vec<DiffHunk>, String> {
+    for path in [path_a, path_b] {
+        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_DIFF_BYTES as u64 {
+            return Err(format!("File is too large to diff: {}", path));
+        }
+    }
+    diff_text(&read_file(path_a)?, &read_file(path_b)?, context_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_text_numbers_changed_lines() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let new = "one\ntwo\nthree\nFOUR\nfive\nsix\nseven\neight\n";
+
+        let hunks = diff_text(old, new, 1).unwrap();
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header, "@@ -3,3 +3,3 @@");
+        let kinds: Vec<_> = hunks[0]
+            .lines
+            .iter()
+            .map(|l| (l.kind, l.content.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (DiffLineKind::Equal, "three"),
+                (DiffLineKind::Delete, "four"),
+                (DiffLineKind::Insert, "FOUR"),
+                (DiffLineKind::Equal, "five"),
+            ]
+        );
+        assert_eq!(hunks[0].lines[1].old_line, Some(4));
+        assert_eq!(hunks[0].lines[1].new_line, None);
+        assert_eq!(hunks[1].lines.last().unwrap().new_line, Some(8));
+
+        // Enough context merges the two changes into one hunk
+        assert_eq!(diff_text(old, new, 3).unwrap().len(), 1);
+        assert!(diff_text(old, old, 3).unwrap().is_empty());
+        let huge = "x".repeat(MAX_DIFF_BYTES + 1);
+        assert!(diff_text(&huge, "", 3).is_err());
+    }
+}
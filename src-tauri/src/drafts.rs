@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activity::now_millis;
+use crate::fs::{normalize_path, to_hex, write_file_atomic};
+
+/// Unsaved content kept for a note, described by the metadata file stored
+/// beside it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Draft {
+    /// The note the content belongs to
+    pub path: String,
+    /// Unix timestamp in milliseconds of the last stash
+    pub stashed_at: u64,
+}
+
+/// Drafts are stored as `<key>.draft` with `<key>.json` beside it, the key
+/// being a hash of the note's path so any path maps to a safe file name
+fn draft_files(drafts_dir: &Path, path: &str) -> (PathBuf, PathBuf) {
+    let normalized = normalize_path(Path::new(path));
+    let key = to_hex(&Sha256::digest(normalized.to_string_lossy().as_bytes()));
+    (
+        drafts_dir.join(format!("{}.draft", key)),
+        drafts_dir.join(format!("{}.json", key)),
+    )
+}
+
+/// Keep `content` as the unsaved draft of `path`, replacing any earlier one
+pub fn stash_draft(drafts_dir: &Path, path: &str, content: &str) -> Result<(), String> {
+    fs::create_dir_all(drafts_dir).map_err(|e| format!("Failed to create drafts folder: {}", e))?;
+    let (content_file, meta_file) = draft_files(drafts_dir, path);
+    let draft = Draft {
+        path: path.to_string(),
+        stashed_at: now_millis(),
+    };
+    let meta = serde_json::to_string_pretty(&draft)
+        .map_err(|e| format!("Failed to serialize draft: {}", e))?;
+    write_file_atomic(&content_file.to_string_lossy(), content.as_bytes())?;
+    write_file_atomic(&meta_file.to_string_lossy(), meta.as_bytes())?;
+    Ok(())
+}
+
+/// Every stashed draft, newest first. Drafts whose metadata can't be read
+/// or whose content is missing are skipped.
+pub fn list_drafts(drafts_dir: &Path) -> Result<Vec<Draft>, String> {
+    if !drafts_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries =
+        fs::read_dir(drafts_dir).map_err(|e| format!("Failed to read drafts folder: {}", e))?;
+    let mut drafts: Vec<Draft> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+        .filter(|file| file.with_extension("draft").is_file())
+        .filter_map(|file| {
+            let meta = fs::read_to_string(&file).ok()?;
+            serde_json::from_str(&meta).ok()
+        })
+        .collect();
+    drafts.sort_by_key(|draft| std::cmp::Reverse(draft.stashed_at));
+    Ok(drafts)
+}
+
+/// The stashed content of `path`, if there is a draft of it
+pub fn recover_draft(drafts_dir: &Path, path: &str) -> Result<Option<String>, String> {
+    let (content_file, _) = draft_files(drafts_dir, path);
+    if !content_file.exists() {
+        return Ok(None);
+    }
+    fs::read_to_string(&content_file)
+        .map(Some)
+        .map_err(|e| format!("Failed to read draft: {}", e))
+}
+
+/// Drop the draft of `path`, returning whether there was one
+pub fn discard_draft(drafts_dir: &Path, path: &str) -> Result<bool, String> {
+    let (content_file, meta_file) = draft_files(drafts_dir, path);
+    let existed = content_file.exists();
+    for file in [content_file, meta_file] {
+        if file.exists() {
+            fs::remove_file(&file).map_err(|e| format!("Failed to discard draft: {}", e))?;
+        }
+    }
+    Ok(existed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drafts_stash_recover_and_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let drafts = dir.path().join("drafts");
+        let note = "/vault/notes/today.md";
+        let other = "/vault/inbox.md";
+
+        assert!(list_drafts(&drafts).unwrap().is_empty());
+        assert_eq!(recover_draft(&drafts, note), Ok(None));
+
+        stash_draft(&drafts, note, "first").unwrap();
+        stash_draft(&drafts, note, "second").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        stash_draft(&drafts, other, "elsewhere").unwrap();
+
+        let listed: Vec<String> = list_drafts(&drafts)
+            .unwrap()
+            .into_iter()
+            .map(|draft| draft.path)
+            .collect();
+        assert_eq!(listed, vec![other, note]);
+        assert_eq!(recover_draft(&drafts, note), Ok(Some("second".to_string())));
+        // The same note spelled differently maps to the same draft
+        assert_eq!(
+            recover_draft(&drafts, "/vault/notes/./today.md"),
+            Ok(Some("second".to_string()))
+        );
+
+        assert_eq!(discard_draft(&drafts, note), Ok(true));
+        assert_eq!(discard_draft(&drafts, note), Ok(false));
+        assert_eq!(recover_draft(&drafts, note), Ok(None));
+        assert_eq!(list_drafts(&drafts).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::fs::{read_file, write_file};
+use crate::markdown::{derive_title, render_markdown, render_markdown_to_html, RenderOptions};
+
+/// External renderer used to turn note HTML into PDF
+const PDF_RENDERER: &str = "wkhtmltopdf";
+
+const PDF_STYLE: &str = "body { font-family: Georgia, serif; line-height: 1.5; margin: 2em; }
+pre, code { font-family: Menlo, monospace; background: #f4f4f4; }
+pre { padding: 0.75em; white-space: pre-wrap; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; }
+img { max-width: 100%; }
+";
+
+/// Colors layered over `PDF_STYLE` for each HTML export theme
+const LIGHT_THEME: &str = "body { color: #24292f; background: #ffffff; }
+a { color: #0969da; }
+";
+
+const DARK_THEME: &str = "body { color: #e6edf3; background: #0d1117; }
+a { color: #4493f8; }
+pre, code { background: #161b22; }
+th, td { border-color: #30363d; }
+";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Wrap a rendered note body in a complete HTML document
+fn html_document(head: &str, title: &str, style: &str, body: &str) -> String {
+    format!(
+        concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n{}\n",
+            "<title>{}</title>\n<style>\n{}</style>\n</head>\n",
+            "<body>\n{}</body>\n</html>\n",
+        ),
+        head,
+        escape_html(title),
+        style,
+        body,
+    )
+}
+
+/// Render a note to a standalone HTML document for the PDF stage. A
+/// `<base>` pointing at the note's folder keeps relative images working.
+pub fn note_html_document(path: &str) -> Result<String, String> {
+    let content = read_file(path)?;
+    let note = Path::new(path);
+    let base = note
+        .parent()
+        .map(|dir| {
+            format!(
+                "<base href=\"file://{}/\">",
+                escape_html(&dir.to_string_lossy())
+            )
+        })
+        .unwrap_or_default();
+    Ok(html_document(
+        &base,
+        &derive_title(&content, note),
+        PDF_STYLE,
+        &render_markdown_to_html(&content),
+    ))
+}
+
+/// Export a note to a self-contained HTML file at `dest_path`, with its
+/// styles inlined for the `light` (default) or `dark` theme and its local
+/// images embedded. Only images inside `root`, e.g. the vault, are embedded,
+/// or inside the note's folder when there is none. Missing folders are
+/// created, and `.html` is added when `dest_path` has no extension. Returns
+/// the path written.
+pub fn export_markdown_to_html(
+    src_path: &str,
+    dest_path: &str,
+    theme: Option<&str>,
+    root: Option<&str>,
+) -> Result<String, String> {
+    let theme = match theme.unwrap_or("light") {
+        "light" => LIGHT_THEME,
+        "dark" => DARK_THEME,
+        other => return Err(format!("Unknown theme: {}", other)),
+    };
+    let content = read_file(src_path)?;
+    let note = Path::new(src_path);
+    let note_dir = note.parent().map(|dir| dir.to_string_lossy().to_string());
+    let options = RenderOptions {
+        embed_images_within: root.map(str::to_string).or_else(|| note_dir.clone()),
+        embed_images_from: note_dir,
+        ..Default::default()
+    };
+    let html = html_document(
+        "",
+        &derive_title(&content, note),
+        &format!("{}{}", PDF_STYLE, theme),
+        &render_markdown(&content, &options),
+    );
+
+    let mut dest = Path::new(dest_path).to_path_buf();
+    if dest.extension().is_none() {
+        dest.set_extension("html");
+    }
+    let dest = dest.to_string_lossy().to_string();
+    write_file(&dest, &html)?;
+    Ok(dest)
+}
+
+/// Export a note to PDF at `output_path` by rendering it to HTML and handing
+/// that to `wkhtmltopdf`, which must be installed and on the `PATH`
+pub fn export_pdf(path: &str, output_path: &str) -> Result<(), String> {
+    let html = note_html_document(path)?;
+
+    let available = Command::new(PDF_RENDERER)
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if !available {
+        return Err(format!(
+            "No PDF renderer available: install {} to export PDFs",
+            PDF_RENDERER
+        ));
+    }
+
+    // Named uniquely and removed when dropped, however the export ends
+    let mut html_file = tempfile::Builder::new()
+        .prefix("manza-export-")
+        .suffix(".html")
+        .tempfile()
+        .map_err(|e| format!("Failed to write HTML: {}", e))?;
+    html_file
+        .write_all(html.as_bytes())
+        .and_then(|_| html_file.flush())
+        .map_err(|e| format!("Failed to write HTML: {}", e))?;
+    let output = Command::new(PDF_RENDERER)
+        .args(["--quiet", "--enable-local-file-access"])
+        .arg(html_file.path())
+        .arg(output_path)
+        .output();
+
+    let output = output.map_err(|e| format!("Failed to run {}: {}", PDF_RENDERER, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to export PDF: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_note_html_document_renders_body() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("report.md");
+        fs::write(
+            &note,
+            "---\ntitle: Q3 <Report>\n---\n# Results\n\nSales **grew**.\n",
+        )
+        .unwrap();
+
+        let html = note_html_document(&note.to_string_lossy()).unwrap();
+
+        assert!(html.contains("<title>Q3 &lt;Report&gt;</title>"));
+        assert!(html.contains("<h1>Results</h1>"));
+        assert!(html.contains("<p>Sales <strong>grew</strong>.</p>"));
+        assert!(!html.contains("title: Q3"));
+    }
+
+    #[test]
+    fn test_export_markdown_to_html_embeds_images() {
+        let outside = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir(&vault).unwrap();
+        let note = vault.join("trip.md");
+        fs::write(vault.join("map.png"), b"\x89PNG").unwrap();
+        fs::write(dir.path().join("secret.png"), b"secret").unwrap();
+        fs::write(outside.path().join("key.png"), b"key").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(outside.path().join("key.png"), vault.join("key.png")).unwrap();
+        fs::write(
+            &note,
+            "# Trip\n\n![map](map.png) ![gone](gone.png) ![s](../secret.png) ![k](key.png)\n",
+        )
+        .unwrap();
+        let dest = vault.join("out/trip");
+        let root = vault.to_string_lossy();
+
+        let written = export_markdown_to_html(
+            &note.to_string_lossy(),
+            &dest.to_string_lossy(),
+            Some("dark"),
+            Some(&root),
+        )
+        .unwrap();
+
+        assert_eq!(written, vault.join("out/trip.html").to_string_lossy());
+        let html = fs::read_to_string(&written).unwrap();
+        assert!(html.contains("src=\"data:image/png;base64,iVBORw==\""));
+        assert!(html.contains("src=\"gone.png\""));
+        // Images outside the vault, directly or through a link, aren't read
+        assert!(html.contains("src=\"../secret.png\""));
+        assert!(html.contains("src=\"key.png\""));
+        assert!(html.contains("background: #0d1117"));
+        assert!(export_markdown_to_html(
+            &note.to_string_lossy(),
+            &written,
+            Some("sepia"),
+            Some(&root)
+        )
+        .is_err());
+    }
+}
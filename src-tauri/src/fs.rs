@@ -1,7 +1,132 @@
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+use crate::markdown::derive_title;
+
+/// Cap on the compiled size of a user-supplied pattern, e.g. a search or
+/// rename. The regex crate matches in linear time, so this bounds the
+/// remaining cost: patterns like `a{1000}{1000}` that would compile into
+/// something huge are refused instead.
+pub const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Why a filesystem operation failed. Serializes as `{ code, message }` so
+/// the frontend can branch on `code` and still show `message`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum FsError {
+    NotFound {
+        message: String,
+    },
+    PermissionDenied {
+        message: String,
+    },
+    AlreadyExists {
+        message: String,
+    },
+    NotADirectory {
+        message: String,
+    },
+    NotAFile {
+        message: String,
+    },
+    /// A path or name that can't be used as given, e.g. copying a
+    /// directory into itself
+    InvalidPath {
+        message: String,
+    },
+    /// A pattern that doesn't compile, e.g. a bulk rename's regex
+    InvalidPattern {
+        message: String,
+    },
+    /// Any other I/O failure
+    Io {
+        message: String,
+    },
+    /// A tracked operation stopped early because it was cancelled
+    Cancelled {
+        message: String,
+    },
+    /// A text read of a file that holds binary data
+    BinaryFile {
+        message: String,
+    },
+    /// An unknown encoding, or content it can't represent
+    Encoding {
+        message: String,
+    },
+    /// A file another window or app instance holds locked for editing
+    Locked {
+        message: String,
+    },
+    /// Something this platform can't do, e.g. browse the trash on macOS
+    Unsupported {
+        message: String,
+    },
+}
+
+impl FsError {
+    /// Classify an I/O error by its kind, prefixing its message with
+    /// `context` like `Failed to read file`
+    pub fn io(context: impl std::fmt::Display, e: std::io::Error) -> Self {
+        let message = format!("{}: {}", context, e);
+        match e.kind() {
+            ErrorKind::NotFound => FsError::NotFound { message },
+            ErrorKind::PermissionDenied => FsError::PermissionDenied { message },
+            ErrorKind::AlreadyExists => FsError::AlreadyExists { message },
+            ErrorKind::NotADirectory => FsError::NotADirectory { message },
+            ErrorKind::IsADirectory => FsError::NotAFile { message },
+            _ => FsError::Io { message },
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FsError::NotFound { message }
+            | FsError::PermissionDenied { message }
+            | FsError::AlreadyExists { message }
+            | FsError::NotADirectory { message }
+            | FsError::NotAFile { message }
+            | FsError::InvalidPath { message }
+            | FsError::InvalidPattern { message }
+            | FsError::Io { message }
+            | FsError::Cancelled { message }
+            | FsError::BinaryFile { message }
+            | FsError::Encoding { message }
+            | FsError::Locked { message }
+            | FsError::Unsupported { message } => message,
+        }
+    }
+}
+
+impl std::fmt::Display for FsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Callers outside this module still report errors as plain strings
+impl From<FsError> for String {
+    fn from(e: FsError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Errors from elsewhere, e.g. the watcher, joining a filesystem batch
+impl From<String> for FsError {
+    fn from(message: String) -> Self {
+        FsError::Io { message }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileItem {
@@ -9,107 +134,4794 @@ pub struct FileItem {
     pub path: String,
     pub is_directory: bool,
     pub is_markdown: bool,
+    /// Broad category of the file, for icons and choosing a viewer
+    #[serde(default)]
+    pub kind: FileKind,
+    /// Whether the file can be opened as text
+    #[serde(default)]
+    pub is_text: bool,
+    /// Size in bytes; 0 for directories
+    #[serde(default)]
+    pub size: u64,
+    /// Last modification time in unix milliseconds, when the platform has it
+    #[serde(default)]
+    pub modified: Option<u64>,
+    /// Whether a markdown file opens with a `---` frontmatter fence; only
+    /// set when requested with `check_frontmatter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_frontmatter: Option<bool>,
+    /// Display title of a markdown file (frontmatter `title`, first H1, or
+    /// file name); only set when requested with `with_titles`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Whether the entry is a symbolic link; `is_directory` and `size`
+    /// describe what it points to
+    #[serde(default)]
+    pub is_symlink: bool,
+    /// Where a symbolic link points, as written in the link
+    #[serde(default)]
+    pub symlink_target: Option<String>,
+    /// Whether a symbolic link points at nothing, leaving its type unknown
+    #[serde(default)]
+    pub is_broken_symlink: bool,
+    /// Whether the entry is marked read-only, so the editor can be locked
+    #[serde(default)]
+    pub readonly: bool,
+}
+
+/// Broad category of a file, judged by its extension
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Markdown,
+    Text,
+    Image,
+    Pdf,
+    Code,
+    Binary,
+    /// Directories, files without an extension and unknown extensions
+    #[default]
+    Other,
+}
+
+impl FileKind {
+    /// Whether files of this kind can be opened as text
+    pub fn is_text(self) -> bool {
+        matches!(self, FileKind::Markdown | FileKind::Text | FileKind::Code)
+    }
+}
+
+/// How directory entries are ordered. Entries the key can't tell apart are
+/// ordered by name.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Case-insensitive alphabetical order
+    #[default]
+    Name,
+    /// Alphabetical, but embedded numbers compare numerically (`file2` < `file10`)
+    Natural,
+    /// Last modification time, oldest first; entries without one come first
+    Modified,
+    /// Size in bytes, smallest first; directories count as empty
+    Size,
+}
+
+/// Options controlling how `read_directory` lists entries
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DirectoryOptions {
+    pub sort: SortBy,
+    /// Reverse the `sort` order; the name tie-break stays ascending
+    pub descending: bool,
+    /// List directories before files regardless of `sort`
+    pub dirs_first: bool,
+    /// Peek at each markdown file to fill in `has_frontmatter`
+    pub check_frontmatter: bool,
+    /// Read each markdown file to fill in `title`
+    pub with_titles: bool,
+    /// Include dotfiles and entries the OS marks hidden
+    pub show_hidden: bool,
+    /// Extensions that count as markdown; `is_markdown_file`'s built-in set
+    /// when unset
+    pub markdown_extensions: Option<Vec<String>>,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        DirectoryOptions {
+            sort: SortBy::default(),
+            descending: false,
+            dirs_first: true,
+            check_frontmatter: false,
+            with_titles: false,
+            show_hidden: false,
+            markdown_extensions: None,
+        }
+    }
+}
+
+/// Order two entries as `options` asks
+fn compare_items(a: &FileItem, b: &FileItem, options: &DirectoryOptions) -> Ordering {
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    if options.dirs_first && a.is_directory != b.is_directory {
+        return b.is_directory.cmp(&a.is_directory);
+    }
+
+    let ordering = match options.sort {
+        SortBy::Name => by_name(),
+        SortBy::Natural => natural_cmp(&a.name, &b.name),
+        SortBy::Modified => a.modified.cmp(&b.modified),
+        SortBy::Size => a.size.cmp(&b.size),
+    };
+    let ordering = if options.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    };
+    ordering
+        .then_with(by_name)
+        .then_with(|| a.name.cmp(&b.name))
+}
+
+/// Whether a directory entry is hidden: a dotfile, or on Windows anything
+/// with the hidden attribute
+fn is_hidden_entry(name: &str, metadata: &fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0 {
+            return true;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = metadata;
+    name.starts_with('.')
+}
+
+/// Compare two names treating runs of digits as numbers
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<(bool, String)> {
+        let mut chunks: Vec<(bool, String)> = Vec::new();
+        for c in s.chars() {
+            let is_digit = c.is_ascii_digit();
+            match chunks.last_mut() {
+                Some((digits, chunk)) if *digits == is_digit => chunk.push(c),
+                _ => chunks.push((is_digit, c.to_string())),
+            }
+        }
+        chunks
+    }
+
+    let (left, right) = (chunks(a), chunks(b));
+    for ((a_digits, a_chunk), (b_digits, b_chunk)) in left.iter().zip(right.iter()) {
+        let ordering = if *a_digits && *b_digits {
+            let a_trimmed = a_chunk.trim_start_matches('0');
+            let b_trimmed = b_chunk.trim_start_matches('0');
+            a_trimmed
+                .len()
+                .cmp(&b_trimmed.len())
+                .then_with(|| a_trimmed.cmp(b_trimmed))
+        } else {
+            a_chunk.to_lowercase().cmp(&b_chunk.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+}
+
+/// Metadata of what `path` points to when it is a symlink, or of `path`
+/// itself otherwise; `None` for a broken link
+fn resolved_metadata(path: &Path, link_metadata: &fs::Metadata) -> Option<fs::Metadata> {
+    if link_metadata.file_type().is_symlink() {
+        fs::metadata(path).ok()
+    } else {
+        Some(link_metadata.clone())
+    }
+}
+
+/// Describe the entry at `path` with its own `link_metadata` and the
+/// `resolved` metadata of its target. A broken link has no target to
+/// describe, so it falls back to the link's own metadata.
+fn file_item(
+    path: &Path,
+    name: String,
+    link_metadata: &fs::Metadata,
+    resolved: Option<&fs::Metadata>,
+    options: &DirectoryOptions,
+) -> FileItem {
+    let is_symlink = link_metadata.file_type().is_symlink();
+    let metadata = resolved.unwrap_or(link_metadata);
+    let is_directory = metadata.is_dir();
+    let is_markdown = resolved.is_some()
+        && !is_directory
+        && is_markdown_name(&name, options.markdown_extensions.as_deref());
+    let kind = item_kind(&name, is_directory, is_markdown);
+
+    FileItem {
+        name,
+        path: path.to_string_lossy().to_string(),
+        is_directory,
+        is_markdown,
+        kind,
+        is_text: kind.is_text(),
+        size: if is_directory { 0 } else { metadata.len() },
+        modified: modified_millis(metadata),
+        has_frontmatter: (options.check_frontmatter && is_markdown)
+            .then(|| starts_with_frontmatter(path)),
+        title: (options.with_titles && is_markdown)
+            .then(|| read_title(path))
+            .flatten(),
+        is_symlink,
+        symlink_target: is_symlink
+            .then(|| fs::read_link(path).ok())
+            .flatten()
+            .map(|target| target.to_string_lossy().to_string()),
+        is_broken_symlink: is_symlink && resolved.is_none(),
+        readonly: metadata.permissions().readonly(),
+    }
 }
 
 /// Read directory contents and return structured file items
-pub fn read_directory(path: &str) -> Result<Vec<FileItem>, String> {
+pub fn read_directory(path: &str, options: &DirectoryOptions) -> Result<Vec<FileItem>, FsError> {
     let dir_path = PathBuf::from(path);
 
     if !dir_path.exists() {
-        return Err(format!("Directory does not exist: {}", path));
+        return Err(FsError::NotFound {
+            message: format!("Directory does not exist: {}", path),
+        });
     }
 
     if !dir_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", path));
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", path),
+        });
     }
 
     let entries = fs::read_dir(&dir_path)
-        .map_err(|e| format!("Failed to read directory: {}", e))?;
+        .map_err(|e| FsError::io("Failed to read directory", e))?;
 
     let mut files: Vec<FileItem> = Vec::new();
 
     for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let entry = entry.map_err(|e| FsError::io("Failed to read entry", e))?;
         let entry_path = entry.path();
-        let metadata = entry
-            .metadata()
-            .map_err(|e| format!("Failed to read metadata: {}", e))?;
+        let link_metadata = fs::symlink_metadata(&entry_path)
+            .map_err(|e| FsError::io("Failed to read metadata", e))?;
+        let resolved = resolved_metadata(&entry_path, &link_metadata);
+        let metadata = resolved.as_ref().unwrap_or(&link_metadata);
 
         let name = entry
             .file_name()
             .to_string_lossy()
             .to_string();
 
-        let path_str = entry_path
-            .to_string_lossy()
-            .to_string();
-
-        let is_directory = metadata.is_dir();
-        let is_markdown = !is_directory && is_markdown_file(&name);
+        if !options.show_hidden && is_hidden_entry(&name, metadata) {
+            continue;
+        }
 
-        files.push(FileItem {
+        files.push(file_item(
+            &entry_path,
             name,
-            path: path_str,
-            is_directory,
-            is_markdown,
-        });
+            &link_metadata,
+            resolved.as_ref(),
+            options,
+        ));
     }
 
-    // Sort: directories first, then alphabetically
-    files.sort_by(|a, b| {
-        match (a.is_directory, b.is_directory) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
-    });
+    files.sort_by(|a, b| compare_items(a, b, options));
 
     Ok(files)
 }
 
+/// Describe a single path as `read_directory` would list it, without
+/// reading its parent directory or, unless `options` asks for a title or
+/// frontmatter check, its contents. A symlink is described along with what
+/// it points to, as in a listing.
+pub fn file_info(path: &str, options: &DirectoryOptions) -> Result<FileItem, FsError> {
+    let file_path = Path::new(path);
+    let link_metadata = fs::symlink_metadata(file_path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => FsError::NotFound {
+            message: format!("Path does not exist: {}", path),
+        },
+        _ => FsError::io("Failed to read metadata", e),
+    })?;
+    let name = file_path.file_name().map_or_else(
+        || path.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let resolved = resolved_metadata(file_path, &link_metadata);
+    Ok(file_item(
+        file_path,
+        name,
+        &link_metadata,
+        resolved.as_ref(),
+        options,
+    ))
+}
+
+/// Read directory contents as `read_directory` does, keeping only files of
+/// the given kinds. Directories are always kept so a picker can navigate.
+pub fn read_directory_filtered(
+    path: &str,
+    kinds: &[FileKind],
+    options: &DirectoryOptions,
+) -> Result<Vec<FileItem>, FsError> {
+    let mut items = read_directory(path, options)?;
+    items.retain(|item| item.is_directory || kinds.contains(&item.kind));
+    Ok(items)
+}
+
+/// A directory's listing with counts, so an empty folder can be told apart
+/// from one still loading or failing to load
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryListing {
+    pub path: String,
+    pub items: Vec<FileItem>,
+    /// Nothing is listed; hidden entries may still be there when
+    /// `show_hidden` is off
+    pub is_empty: bool,
+    pub total: usize,
+}
+
+/// `read_directory` with the listing's counts alongside its items
+pub fn read_directory_listing(
+    path: &str,
+    options: &DirectoryOptions,
+) -> Result<DirectoryListing, FsError> {
+    let items = read_directory(path, options)?;
+    Ok(DirectoryListing {
+        path: path.to_string(),
+        is_empty: items.is_empty(),
+        total: items.len(),
+        items,
+    })
+}
+
+/// A directory entry with its loaded subtree
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileTreeNode {
+    pub item: FileItem,
+    /// Entries of a directory; empty for files, unreadable directories and
+    /// directories past the depth limit
+    pub children: Vec<FileTreeNode>,
+}
+
+/// Read a directory and its subdirectories in one pass, down to `max_depth`
+/// levels (1 lists only the top level). Subdirectories that can't be read are
+/// logged and left empty. Symlinked directories are listed but only expanded
+/// with `follow_symlinks`; see `WalkGuard`.
+pub fn read_directory_tree(
+    path: &str,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+) -> Result<Vec<FileTreeNode>, FsError> {
+    let items = read_directory(path, &DirectoryOptions::default())?;
+    let mut guard = WalkGuard::new(Path::new(path), follow_symlinks);
+    Ok(build_tree(items, 1, max_depth, &mut guard))
+}
+
+fn build_tree(
+    items: Vec<FileItem>,
+    depth: usize,
+    max_depth: Option<usize>,
+    guard: &mut WalkGuard,
+) -> Vec<FileTreeNode> {
+    items
+        .into_iter()
+        .map(|item| {
+            let expand = item.is_directory
+                && max_depth.is_none_or(|max| depth < max)
+                && guard.enter(Path::new(&item.path), item.is_symlink);
+            let children = if expand {
+                read_subtree(&item.path, depth + 1, max_depth, guard)
+            } else {
+                Vec::new()
+            };
+            FileTreeNode { item, children }
+        })
+        .collect()
+}
+
+fn read_subtree(
+    path: &str,
+    depth: usize,
+    max_depth: Option<usize>,
+    guard: &mut WalkGuard,
+) -> Vec<FileTreeNode> {
+    match read_directory(path, &DirectoryOptions::default()) {
+        Ok(items) => build_tree(items, depth, max_depth, guard),
+        Err(e) => {
+            eprintln!("Skipping {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Modification time of `metadata` in unix milliseconds, or `None` where
+/// the platform doesn't record it
+pub(crate) fn modified_millis(metadata: &fs::Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_millis()).ok()
+}
+
+/// Display title of a note, or `None` if it can't be read
+fn read_title(path: &Path) -> Option<String> {
+    let content = read_file(&path.to_string_lossy()).ok()?;
+    Some(derive_title(&content, path))
+}
+
+/// Whether a file's first line is a `---` frontmatter fence, reading only
+/// that line
+fn starts_with_frontmatter(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut first_line = String::new();
+    std::io::BufReader::new(file.take(64))
+        .read_line(&mut first_line)
+        .is_ok()
+        && first_line.trim_end() == "---"
+}
+
 /// Check if a file is a markdown file based on extension
-fn is_markdown_file(filename: &str) -> bool {
+pub(crate) fn is_markdown_file(filename: &str) -> bool {
     let lower = filename.to_lowercase();
     lower.ends_with(".md")
         || lower.ends_with(".markdown")
         || lower.ends_with(".mdown")
 }
 
+/// Classify a file name by its extension. Data formats like `.json` count
+/// as code since they are edited as text; names without an extension are
+/// `Other`.
+pub fn file_kind(filename: &str) -> FileKind {
+    if is_markdown_file(filename) {
+        return FileKind::Markdown;
+    }
+    let Some(extension) = Path::new(filename).extension() else {
+        return FileKind::Other;
+    };
+    match extension.to_string_lossy().to_lowercase().as_str() {
+        "txt" | "text" | "log" | "csv" | "tsv" | "rst" | "org" | "adoc" | "ini" | "cfg" => {
+            FileKind::Text
+        }
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "bmp" | "ico" | "tif" | "tiff"
+        | "avif" | "heic" => FileKind::Image,
+        "pdf" => FileKind::Pdf,
+        "rs" | "js" | "mjs" | "cjs" | "ts" | "jsx" | "tsx" | "py" | "rb" | "go" | "java" | "kt"
+        | "swift" | "c" | "h" | "cpp" | "hpp" | "cc" | "cs" | "php" | "lua" | "sh" | "bash"
+        | "zsh" | "fish" | "ps1" | "sql" | "html" | "htm" | "css" | "scss" | "json" | "yaml"
+        | "yml" | "toml" | "xml" => FileKind::Code,
+        "zip" | "gz" | "tar" | "tgz" | "7z" | "rar" | "exe" | "dll" | "so" | "dylib" | "bin"
+        | "dmg" | "iso" | "wasm" | "class" | "o" | "mp3" | "wav" | "flac" | "ogg" | "mp4"
+        | "mov" | "avi" | "mkv" | "webm" | "docx" | "xlsx" | "pptx" | "ttf" | "otf" | "woff"
+        | "woff2" | "sqlite" | "db" => FileKind::Binary,
+        _ => FileKind::Other,
+    }
+}
+
+/// `kind` of a listed entry, agreeing with its `is_markdown` when the
+/// configured markdown extensions differ from the built-in ones
+fn item_kind(name: &str, is_directory: bool, is_markdown: bool) -> FileKind {
+    match file_kind(name) {
+        _ if is_directory => FileKind::Other,
+        _ if is_markdown => FileKind::Markdown,
+        FileKind::Markdown => FileKind::Text,
+        kind => kind,
+    }
+}
+
+/// Check a file name against a set of markdown extensions, given without
+/// the leading dot
+pub fn has_markdown_extension(filename: &str, extensions: &[String]) -> bool {
+    let Some(extension) = Path::new(filename).extension() else {
+        return false;
+    };
+    let extension = extension.to_string_lossy();
+    extensions
+        .iter()
+        .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension))
+}
+
+/// Whether `filename` counts as markdown: by `extensions` when given, such
+/// as a vault's configured ones, and otherwise by `is_markdown_file`
+pub(crate) fn is_markdown_name(filename: &str, extensions: Option<&[String]>) -> bool {
+    match extensions {
+        Some(extensions) => has_markdown_extension(filename, extensions),
+        None => is_markdown_file(filename),
+    }
+}
+
+/// Recompute `is_markdown` and `kind` for previously listed items, e.g.
+/// after the configured markdown extensions change
+pub fn reclassify_items(items: Vec<FileItem>, extensions: &[String]) -> Vec<FileItem> {
+    items
+        .into_iter()
+        .map(|item| {
+            let is_markdown = !item.is_directory && has_markdown_extension(&item.name, extensions);
+            let kind = item_kind(&item.name, item.is_directory, is_markdown);
+            FileItem {
+                is_markdown,
+                kind,
+                is_text: kind.is_text(),
+                ..item
+            }
+        })
+        .collect()
+}
+
+/// Lexically resolve `.` and `..` components without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the root stays at the root
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Canonical absolute form of `path`, following symlinks and `..`, so two
+/// spellings of the same file compare equal. A path that doesn't exist yet
+/// is resolved through its nearest existing ancestor, and one with no
+/// existing ancestor is only normalized.
+pub fn canonical_path(path: &str) -> PathBuf {
+    let mut existing = Path::new(path);
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            let full = missing
+                .into_iter()
+                .rev()
+                .fold(resolved, |full, name| full.join(name));
+            return normalize_path(&full);
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.as_os_str());
+                existing = parent;
+            }
+            _ => return normalize_path(Path::new(path)),
+        }
+    }
+}
+
+/// Whether two path components name the same thing; Windows paths compare
+/// case-insensitively, drive letters included
+fn same_component(a: Component, b: Component) -> bool {
+    if cfg!(windows) {
+        a.as_os_str().to_string_lossy().to_lowercase()
+            == b.as_os_str().to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+/// `target` relative to `base` with `/` separators, e.g. `notes/sub/file.md`,
+/// for display. Both are resolved with `canonical_path` first. A target
+/// outside `base`, such as one on another drive, comes back as its full
+/// path, and `base` itself as `.`.
+pub fn relative_path(base: &str, target: &str) -> String {
+    let base = canonical_path(base);
+    let target = canonical_path(target);
+    let mut rest = target.components();
+    for base_part in base.components() {
+        match rest.next() {
+            Some(part) if same_component(base_part, part) => {}
+            _ => return target.to_string_lossy().to_string(),
+        }
+    }
+    let parts: Vec<String> = rest
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// The folder containing `path`, after resolving `.` and `..` lexically.
+/// `None` at a root (`/`, `C:\`, `\\server\share\`) and for a lone relative
+/// name.
+pub fn parent_directory(path: &str) -> Option<String> {
+    normalize_path(Path::new(path))
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(|parent| parent.to_string_lossy().to_string())
+}
+
+/// Every folder from the root down to `path`, ending with `path` itself
+/// (normalized like `parent_directory`), for breadcrumbs
+pub fn path_ancestors(path: &str) -> Vec<String> {
+    let normalized = normalize_path(Path::new(path));
+    let mut ancestors: Vec<String> = normalized
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .map(|ancestor| ancestor.to_string_lossy().to_string())
+        .collect();
+    ancestors.reverse();
+    ancestors
+}
+
+/// Longest file name most filesystems accept: 255 bytes on Unix, 255 UTF-16
+/// units on Windows
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Device names Windows reserves in every folder, with or without an
+/// extension
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Whether a file name can be created, and if not, why
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NameValidation {
+    pub valid: bool,
+    pub reason: Option<String>,
+}
+
+fn unix_name_problem(name: &str) -> Option<String> {
+    if name.contains('/') {
+        return Some("Name cannot contain '/'".to_string());
+    }
+    if name.contains('\0') {
+        return Some("Name cannot contain a NUL character".to_string());
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Some(format!(
+            "Name is too long ({} bytes, limit {})",
+            name.len(),
+            MAX_NAME_LENGTH
+        ));
+    }
+    None
+}
+
+fn windows_name_problem(name: &str) -> Option<String> {
+    if let Some(c) = name.chars().find(|c| "<>:\"/\\|?*".contains(*c)) {
+        return Some(format!("Name cannot contain '{}'", c));
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Some(format!(
+            "Name cannot contain control character U+{:04X}",
+            c as u32
+        ));
+    }
+    if name.ends_with(['.', ' ']) {
+        return Some("Name cannot end with a dot or a space".to_string());
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some(format!("{} is a reserved name on Windows", stem));
+    }
+    let units = name.encode_utf16().count();
+    if units > MAX_NAME_LENGTH {
+        return Some(format!(
+            "Name is too long ({} characters, limit {})",
+            units, MAX_NAME_LENGTH
+        ));
+    }
+    None
+}
+
+/// Check that `name` can be used for a new file or folder on this platform,
+/// before trying to create it. With `portable`, the Windows and Unix rules
+/// both apply, so names stay valid in vaults synced between systems.
+pub fn validate_filename(name: &str, portable: bool) -> NameValidation {
+    let problem = if name.is_empty() {
+        Some("Name cannot be empty".to_string())
+    } else if name == "." || name == ".." {
+        Some(format!("{} is not a valid name", name))
+    } else if portable {
+        windows_name_problem(name).or_else(|| unix_name_problem(name))
+    } else if cfg!(windows) {
+        windows_name_problem(name)
+    } else {
+        unix_name_problem(name)
+    };
+    NameValidation {
+        valid: problem.is_none(),
+        reason: problem,
+    }
+}
+
+/// Resolve `path` as the OS would, following symlinks and `..`, and fail
+/// unless it lands inside the canonical `root`. Paths that don't exist yet
+/// are resolved through their nearest existing ancestor.
+pub fn resolve_within(root: &Path, path: &str) -> Result<PathBuf, FsError> {
+    let outside = || FsError::PermissionDenied {
+        message: format!("Path outside workspace: {}", path),
+    };
+    let mut existing = Path::new(path);
+    let mut missing = Vec::new();
+    let resolved = loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            break resolved;
+        }
+        // A dangling symlink would be followed by a write
+        if fs::symlink_metadata(existing).is_ok() {
+            return Err(outside());
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(Component::Normal(name))) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Err(outside()),
+        }
+    };
+
+    let full = missing
+        .into_iter()
+        .rev()
+        .fold(resolved, |full, name| full.join(name));
+    if full.starts_with(root) {
+        Ok(full)
+    } else {
+        Err(outside())
+    }
+}
+
+/// Optional workspace root confining the file commands. Until one is set,
+/// paths are unrestricted.
+pub struct WorkspaceState {
+    root: Mutex<Option<PathBuf>>,
+}
+
+impl WorkspaceState {
+    pub fn new() -> Self {
+        WorkspaceState {
+            root: Mutex::new(None),
+        }
+    }
+
+    /// Confine paths to `path`, or lift the restriction with `None`.
+    /// Returns the canonical root.
+    pub fn set_root(&self, path: Option<&str>) -> Result<Option<String>, FsError> {
+        let root = match path {
+            Some(path) => {
+                let root = fs::canonicalize(path)
+                    .map_err(|e| FsError::io(format!("Failed to resolve path {}", path), e))?;
+                if !root.is_dir() {
+                    return Err(FsError::NotADirectory {
+                        message: format!("Path is not a directory: {}", path),
+                    });
+                }
+                Some(root)
+            }
+            None => None,
+        };
+        let display = root.as_ref().map(|root| root.to_string_lossy().to_string());
+        *self.root.lock().unwrap() = root;
+        Ok(display)
+    }
+
+    /// The canonical workspace root, if one is set
+    pub fn root(&self) -> Option<PathBuf> {
+        self.root.lock().unwrap().clone()
+    }
+
+    /// Fail if a workspace root is set and `path` resolves outside it.
+    ///
+    /// Returns the path to operate on from then on: resolved to its
+    /// canonical location, so a directory swapped for a link after the
+    /// check can't redirect it. A final symlink is kept as named, with only
+    /// its parent resolved, so it is renamed or deleted rather than its
+    /// target; where it points, even if nothing is there any more, must be
+    /// inside too since a write would follow it. Without a root, `path` is
+    /// returned as given.
+    pub fn check(&self, path: &str) -> Result<String, FsError> {
+        let Some(root) = self.root.lock().unwrap().clone() else {
+            return Ok(path.to_string());
+        };
+        let entry = Path::new(path);
+        let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        let (true, Some(parent), Some(name)) = (is_link, entry.parent(), entry.file_name()) else {
+            return Ok(resolve_within(&root, path)?.to_string_lossy().to_string());
+        };
+        let parent = resolve_within(&root, &parent.to_string_lossy())?;
+        let link = parent.join(name);
+        let target = fs::read_link(&link).map_err(|e| FsError::io("Failed to read link", e))?;
+        resolve_within(&root, &parent.join(target).to_string_lossy()).map_err(|_| {
+            FsError::PermissionDenied {
+                message: format!("Path outside workspace: {}", path),
+            }
+        })?;
+        Ok(link.to_string_lossy().to_string())
+    }
+}
+
+impl Default for WorkspaceState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Read file contents as UTF-8 string
-pub fn read_file(path: &str) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))
+pub fn read_file(path: &str) -> Result<String, FsError> {
+    fs::read_to_string(path).map_err(|e| FsError::io("Failed to read file", e))
+}
+
+/// Bytes from the start of a file that `is_binary_file` looks at
+const BINARY_SAMPLE_BYTES: u64 = 8 * 1024;
+
+/// Whether a sample of a file's bytes looks binary: it has a NUL byte, or
+/// more than 30% of it is control characters other than whitespace and
+/// escape. Bytes above ASCII aren't counted, so UTF-8 and legacy 8-bit text
+/// pass.
+fn looks_binary(bytes: &[u8]) -> bool {
+    // UTF-16 text is full of NULs but is still text
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    if bytes.contains(&0) {
+        return true;
+    }
+    let control = bytes
+        .iter()
+        .filter(|&&byte| {
+            (byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)) || byte == 0x7F
+        })
+        .count();
+    control * 10 > bytes.len() * 3
+}
+
+/// Guess from its first few KB whether a file holds binary data rather than
+/// text, reading no further
+pub fn is_binary_file(path: &str) -> Result<bool, FsError> {
+    let file = fs::File::open(path).map_err(|e| FsError::io("Failed to read file", e))?;
+    let mut sample = Vec::new();
+    file.take(BINARY_SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .map_err(|e| FsError::io("Failed to read file", e))?;
+    Ok(looks_binary(&sample))
+}
+
+/// `read_file`, failing with `BinaryFile` for files that look binary
+/// instead of with a UTF-8 decoding error
+pub fn read_text_file(path: &str) -> Result<String, FsError> {
+    if is_binary_file(path)? {
+        return Err(FsError::BinaryFile {
+            message: format!("Binary file, cannot display as text: {}", path),
+        });
+    }
+    read_file(path)
+}
+
+/// A slice of a file read by `read_file_chunk`. Its bounds are snapped to
+/// whole UTF-8 characters, so `start` and `end` may differ slightly from the
+/// requested range; continue from `end` to read the next chunk.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileChunk {
+    pub content: String,
+    /// Byte offset the content starts at, past any partial character at the
+    /// requested offset
+    pub start: u64,
+    /// Byte offset just past the content, extended to finish a character
+    /// cut off by the requested length
+    pub end: u64,
+    /// Whether `end` reached the end of the file
+    pub eof: bool,
+}
+
+/// Size of a file in bytes
+pub fn file_size(path: &str) -> Result<u64, FsError> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| FsError::io("Failed to read metadata", e))
+}
+
+/// Mark a file or directory read-only or writable. On Unix this clears
+/// every write bit, or restores the owner's; on Windows it toggles the
+/// read-only attribute.
+pub fn set_readonly(path: &str, readonly: bool) -> Result<(), FsError> {
+    let mut permissions = fs::metadata(path)
+        .map_err(|e| FsError::io("Failed to read metadata", e))?
+        .permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if readonly {
+            mode & !0o222
+        } else {
+            mode | 0o200
+        });
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(readonly);
+    fs::set_permissions(path, permissions).map_err(|e| FsError::io("Failed to set permissions", e))
+}
+
+/// `permissions` with the permission bits `mode`, e.g. `0o600`. Windows has
+/// no permission bits, so there only the owner-write bit counts, clearing or
+/// setting the read-only attribute.
+fn with_mode(mut permissions: fs::Permissions, mode: u32) -> fs::Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(mode & 0o7777);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(mode & 0o200 == 0);
+    permissions
+}
+
+/// Give a file or directory the permission bits `mode`; see `with_mode`
+pub fn set_permissions(path: &str, mode: u32) -> Result<(), FsError> {
+    let permissions = fs::metadata(path)
+        .map_err(|e| FsError::io("Failed to read metadata", e))?
+        .permissions();
+    fs::set_permissions(path, with_mode(permissions, mode))
+        .map_err(|e| FsError::io("Failed to set permissions", e))
+}
+
+/// Set `mode` on a freshly created file before anything is written to it,
+/// so private content is never readable under the umask's permissions
+fn apply_mode(file: &fs::File, mode: Option<u32>) -> std::io::Result<()> {
+    match mode {
+        Some(mode) => file.set_permissions(with_mode(file.metadata()?.permissions(), mode)),
+        None => Ok(()),
+    }
+}
+
+/// Read about `length` bytes from `offset` without loading the whole file.
+/// Bytes that aren't valid UTF-8 are replaced rather than failing the read.
+pub fn read_file_chunk(path: &str, offset: u64, length: usize) -> Result<FileChunk, FsError> {
+    // A UTF-8 character is at most four bytes, so three spare bytes are
+    // enough to finish one cut off at the end
+    const MAX_CONTINUATION: usize = 3;
+    let is_continuation = |byte: u8| byte & 0b1100_0000 == 0b1000_0000;
+
+    let mut file = fs::File::open(path).map_err(|e| FsError::io("Failed to read file", e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| FsError::io("Failed to read metadata", e))?
+        .len();
+    // A length past the end of the file reads the rest of it
+    let rest = usize::try_from(size.saturating_sub(offset)).unwrap_or(usize::MAX);
+    let length = length.min(rest);
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| FsError::io("Failed to read file", e))?;
+    let mut bytes = Vec::new();
+    file.take(length.saturating_add(MAX_CONTINUATION) as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| FsError::io("Failed to read file", e))?;
+
+    let skip = bytes
+        .iter()
+        .take(MAX_CONTINUATION)
+        .take_while(|byte| is_continuation(**byte))
+        .count();
+    let cut = (length.max(skip)..bytes.len())
+        .take(MAX_CONTINUATION + 1)
+        .find(|&i| !is_continuation(bytes[i]))
+        .unwrap_or(bytes.len());
+
+    let end = offset + cut as u64;
+    Ok(FileChunk {
+        content: String::from_utf8_lossy(&bytes[skip..cut]).into_owned(),
+        start: offset + skip as u64,
+        end,
+        eof: end >= size,
+    })
+}
+
+/// The start of a file, for previews
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileHead {
+    pub content: String,
+    /// Whether the file goes on past `content`
+    pub truncated: bool,
+}
+
+/// Read up to `max_lines` lines from the start of a file, stopping sooner
+/// at `max_bytes`, without loading the rest. Lines end at `\n`, so `\r\n`
+/// counts once; a character cut off by the byte cap is dropped, and other
+/// invalid UTF-8 is replaced.
+pub fn read_file_head(path: &str, max_lines: usize, max_bytes: usize) -> Result<FileHead, FsError> {
+    let file = fs::File::open(path).map_err(|e| FsError::io("Failed to read file", e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| FsError::io("Failed to read metadata", e))?
+        .len();
+    let mut reader = std::io::BufReader::new(file.take(max_bytes as u64));
+    let mut bytes = Vec::new();
+    for _ in 0..max_lines {
+        let read = reader
+            .read_until(b'\n', &mut bytes)
+            .map_err(|e| FsError::io("Failed to read file", e))?;
+        if read == 0 {
+            break;
+        }
+    }
+
+    let truncated = (bytes.len() as u64) < size;
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        // No error length means the bytes end partway through a character
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+    Ok(FileHead {
+        content: String::from_utf8_lossy(&bytes).into_owned(),
+        truncated,
+    })
+}
+
+/// File contents decoded from the encoding they were saved in
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DecodedFile {
+    pub content: String,
+    /// WHATWG name of the detected encoding, e.g. `UTF-8` or `windows-1252`
+    pub encoding: String,
+}
+
+/// Read file contents, detecting their encoding: a byte order mark wins,
+/// then valid UTF-8, and anything else is decoded as Windows-1252 (a
+/// superset of Latin-1) so stray legacy bytes don't make the file unreadable
+pub fn read_file_with_encoding(path: &str) -> Result<DecodedFile, FsError> {
+    let bytes = fs::read(path).map_err(|e| FsError::io("Failed to read file", e))?;
+    let encoding: &'static Encoding = match Encoding::for_bom(&bytes) {
+        Some((encoding, _)) => encoding,
+        None if std::str::from_utf8(&bytes).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+    // `decode` strips the byte order mark itself
+    let (content, _, _) = encoding.decode(&bytes);
+    Ok(DecodedFile {
+        content: content.into_owned(),
+        encoding: encoding.name().to_string(),
+    })
+}
+
+/// Encode `content` in the encoding named by `label`, e.g. the `encoding`
+/// of a `DecodedFile`. UTF-16 gets a byte order mark so it reads back as
+/// UTF-16. A character the encoding has no byte sequence for is an error
+/// naming the character and its line rather than being replaced.
+pub fn encode_content(content: &str, label: &str) -> Result<Vec<u8>, FsError> {
+    let encoding =
+        Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| FsError::Encoding {
+            message: format!("Unknown encoding: {}", label),
+        })?;
+    // encoding_rs only decodes UTF-16; its encoder writes UTF-8 instead
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let little_endian = encoding == UTF_16LE;
+        let mut bytes = Vec::with_capacity(2 + content.len() * 2);
+        for unit in std::iter::once(0xFEFF).chain(content.encode_utf16()) {
+            let pair = if little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            };
+            bytes.extend_from_slice(&pair);
+        }
+        return Ok(bytes);
+    }
+
+    let mut encoder = encoding.new_encoder();
+    let mut bytes = Vec::with_capacity(content.len());
+    let mut read = 0;
+    loop {
+        let (result, consumed) =
+            encoder.encode_from_utf8_to_vec_without_replacement(&content[read..], &mut bytes, true);
+        read += consumed;
+        match result {
+            EncoderResult::InputEmpty => return Ok(bytes),
+            EncoderResult::OutputFull => bytes.reserve(content.len() - read + 16),
+            EncoderResult::Unmappable(c) => {
+                let line = content[..read - c.len_utf8()].matches('\n').count() + 1;
+                return Err(FsError::Encoding {
+                    message: format!(
+                        "Character '{}' (U+{:04X}) on line {} can't be saved as {}",
+                        c,
+                        c as u32,
+                        line,
+                        encoding.name()
+                    ),
+                });
+            }
+        }
+    }
 }
 
-/// Write content to file
-pub fn write_file(path: &str, content: &str) -> Result<(), String> {
+/// Write content to file in the encoding named by `label`, atomically; see
+/// `encode_content`. Nothing is written if the content can't be encoded.
+pub fn write_file_with_encoding(path: &str, content: &str, label: &str) -> Result<(), FsError> {
+    write_file_atomic(path, &encode_content(content, label)?)
+}
+
+/// Write content to file, replacing it atomically so a crash mid-write
+/// leaves the previous contents intact
+pub fn write_file(path: &str, content: &str) -> Result<(), FsError> {
+    write_file_with_mode(path, content, None)
+}
+
+/// `write_file`, giving the written file the permission bits `mode` when
+/// set rather than the defaults for a new file
+pub fn write_file_with_mode(path: &str, content: &str, mode: Option<u32>) -> Result<(), FsError> {
     let file_path = PathBuf::from(path);
 
     // Create parent directories if they don't exist
     if let Some(parent) = file_path.parent() {
         fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directories: {}", e))?;
+            .map_err(|e| FsError::io("Failed to create parent directories", e))?;
     }
 
-    let mut file = fs::File::create(&file_path)
-        .map_err(|e| format!("Failed to create file: {}", e))?;
+    write_file_atomic_with_mode(path, content.as_bytes(), mode)
+}
 
-    file.write_all(content.as_bytes())
-        .map_err(|e| format!("Failed to write to file: {}", e))?;
+/// Append content to a file, creating it and its parent directories if
+/// needed. With `newline_before`, a line break is added first unless the
+/// file is empty or already ends with one.
+pub fn append_to_file(path: &str, content: &str, newline_before: bool) -> Result<(), FsError> {
+    let file_path = PathBuf::from(path);
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| FsError::io("Failed to create parent directories", e))?;
+    }
 
-    Ok(())
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&file_path)
+        .map_err(|e| FsError::io("Failed to open file", e))?;
+
+    let mut text = String::new();
+    if newline_before && !ends_with_newline(&mut file)? {
+        text.push('\n');
+    }
+    text.push_str(content);
+    file.write_all(text.as_bytes())
+        .map_err(|e| FsError::io("Failed to append to file", e))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Whether a file is empty or its last byte is a line break
+fn ends_with_newline(file: &mut fs::File) -> Result<bool, FsError> {
+    let len = file
+        .seek(SeekFrom::End(0))
+        .map_err(|e| FsError::io("Failed to seek", e))?;
+    if len == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1))
+        .and_then(|_| file.read_exact(&mut last))
+        .map_err(|e| FsError::io("Failed to read file", e))?;
+    Ok(last[0] == b'\n')
+}
 
-    #[test]
-    fn test_is_markdown_file() {
-        assert!(is_markdown_file("README.md"));
-        assert!(is_markdown_file("notes.markdown"));
-        assert!(is_markdown_file("doc.mdown"));
-        assert!(is_markdown_file("FILE.MD")); // Case insensitive
-        assert!(!is_markdown_file("notes.txt"));
-        assert!(!is_markdown_file("image.png"));
+/// Why `write_file_checked` refused or failed to save
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SaveError {
+    /// The file changed on disk since it was opened; nothing was written
+    Conflict {
+        message: String,
+        /// What is on disk now, or `None` if the file was deleted
+        current_content: Option<String>,
+        current_modified: Option<u64>,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+/// A save refused before it reached the file, e.g. by an editing lock
+impl From<FsError> for SaveError {
+    fn from(e: FsError) -> Self {
+        SaveError::Failed {
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Write `content` only if the file's modification time still matches
+/// `expected_modified` (unix milliseconds, as in `FileItem::modified`),
+/// returning the new modification time. With no `expected_modified` the
+/// file is new to the caller, so the save creates it and only conflicts
+/// with a file that appeared in the meantime.
+pub fn write_file_checked(
+    path: &str,
+    content: &str,
+    expected_modified: Option<u64>,
+) -> Result<Option<u64>, SaveError> {
+    let current_modified = fs::metadata(path).ok().and_then(|m| modified_millis(&m));
+    let unchanged = match expected_modified {
+        Some(expected) => current_modified == Some(expected),
+        None => !Path::new(path).exists(),
+    };
+    if !unchanged {
+        return Err(SaveError::Conflict {
+            message: format!("Conflict: file modified externally: {}", path),
+            current_content: fs::read_to_string(path).ok(),
+            current_modified,
+        });
+    }
+    write_file(path, content).map_err(|e| SaveError::Failed {
+        message: e.to_string(),
+    })?;
+    Ok(fs::metadata(path).ok().and_then(|m| modified_millis(&m)))
+}
+
+/// A full-content write within an edit transaction
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: String,
+    pub content: String,
+}
+
+/// Outcome of `apply_edits`: either every file was written or none was
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct TransactionResult {
+    pub committed: bool,
+    /// Files written, in edit order; empty when the transaction failed
+    pub written: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Symlinks followed before a write gives up, matching the usual OS limit
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// The file a write to `path` actually replaces: symlinks are followed so
+/// saving through a link updates its target rather than swapping the link
+/// for a regular file. Dangling links resolve to the path they point at.
+fn write_target(path: &Path) -> PathBuf {
+    let mut target = path.to_path_buf();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        match fs::read_link(&target) {
+            Ok(link) => {
+                target = match target.parent() {
+                    Some(parent) => parent.join(link),
+                    None => link,
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    target
+}
+
+/// Create a sibling temporary file for replacing `path`. Each call gets a
+/// fresh name, created exclusively, so concurrent saves never share one.
+fn create_temp_for(path: &Path) -> std::io::Result<(PathBuf, fs::File)> {
+    static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Missing file name"))?
+        .to_string_lossy();
+    loop {
+        let n = NEXT_TEMP.fetch_add(1, AtomicOrdering::Relaxed);
+        let temp = path.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), n));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+        {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Write several files all-or-nothing. Every edit is first written to a
+/// temporary file beside its target; only once all of them succeed are they
+/// renamed into place. Any failure discards the temporary files, and a
+/// failed rename restores the files already replaced.
+pub fn apply_edits(edits: Vec<FileEdit>) -> TransactionResult {
+    let failed = |error: String| TransactionResult {
+        error: Some(error),
+        ..Default::default()
+    };
+
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let discard = |staged: &[(PathBuf, PathBuf)]| {
+        for (_, temp) in staged {
+            let _ = fs::remove_file(temp);
+        }
+    };
+    for edit in &edits {
+        let target = PathBuf::from(&edit.path);
+        if staged.iter().any(|(staged, _)| *staged == target) {
+            discard(&staged);
+            return failed(format!("File edited twice: {}", edit.path));
+        }
+        let (temp, mut file) = match create_temp_for(&target) {
+            Ok(created) => created,
+            Err(e) => {
+                discard(&staged);
+                return failed(format!("Failed to write {}: {}", edit.path, e));
+            }
+        };
+        let written = file
+            .write_all(edit.content.as_bytes())
+            .and_then(|_| file.sync_all());
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            discard(&staged);
+            return failed(format!("Failed to write {}: {}", edit.path, e));
+        }
+        staged.push((target, temp));
+    }
+
+    // Keep the originals so a failed rename can be undone
+    let originals: Vec<Option<Vec<u8>>> = staged
+        .iter()
+        .map(|(target, _)| fs::read(target).ok())
+        .collect();
+    for (i, (target, temp)) in staged.iter().enumerate() {
+        if let Err(e) = fs::rename(temp, target) {
+            for ((target, _), original) in staged[..i].iter().zip(&originals) {
+                let _ = match original {
+                    Some(bytes) => fs::write(target, bytes),
+                    None => fs::remove_file(target),
+                };
+            }
+            discard(&staged[i..]);
+            return failed(format!("Failed to replace {}: {}", target.display(), e));
+        }
+    }
+
+    TransactionResult {
+        committed: true,
+        written: edits.into_iter().map(|edit| edit.path).collect(),
+        error: None,
+    }
+}
+
+/// Line terminator used in a text file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// How `write_file_with_eol` treats line endings
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EolMode {
+    Lf,
+    Crlf,
+    /// Write the content exactly as given
+    #[default]
+    Preserve,
+}
+
+/// File contents along with their dominant line ending
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileWithEol {
+    pub content: String,
+    pub line_ending: LineEnding,
+}
+
+/// The more common line ending in `content`; LF on a tie or with no lines
+pub fn detect_line_ending(content: &str) -> LineEnding {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    if crlf > lf {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+/// Rewrite every line ending in `content`, including mixed ones, as `ending`
+pub fn normalize_line_endings(content: &str, ending: LineEnding) -> String {
+    let unified = content.replace("\r\n", "\n");
+    match ending {
+        LineEnding::Lf => unified,
+        LineEnding::Crlf => unified.replace('\n', "\r\n"),
+    }
+}
+
+/// Read file contents and report their dominant line ending
+pub fn read_file_with_eol(path: &str) -> Result<FileWithEol, FsError> {
+    let content = read_file(path)?;
+    Ok(FileWithEol {
+        line_ending: detect_line_ending(&content),
+        content,
+    })
+}
+
+/// Write content to file, first converting line endings as `eol` asks
+pub fn write_file_with_eol(path: &str, content: &str, eol: EolMode) -> Result<(), FsError> {
+    match eol {
+        EolMode::Lf => write_file(path, &normalize_line_endings(content, LineEnding::Lf)),
+        EolMode::Crlf => write_file(path, &normalize_line_endings(content, LineEnding::Crlf)),
+        EolMode::Preserve => write_file(path, content),
+    }
+}
+
+/// Replace a file's contents by writing a sibling temporary file and renaming
+/// it over the target, so readers never observe a partial write
+pub fn write_file_atomic(path: &str, content: &[u8]) -> Result<(), FsError> {
+    write_file_atomic_with_mode(path, content, None)
+}
+
+/// `write_file_atomic`, with the permission bits `mode` when set. Otherwise
+/// an existing file keeps its permissions; read-only files are refused.
+pub fn write_file_atomic_with_mode(
+    path: &str,
+    content: &[u8],
+    mode: Option<u32>,
+) -> Result<(), FsError> {
+    let file_path = write_target(Path::new(path));
+    if file_path.file_name().is_none() {
+        return Err(FsError::InvalidPath {
+            message: format!("Invalid file path: {}", path),
+        });
+    }
+    let existing = fs::metadata(&file_path).ok();
+    if existing
+        .as_ref()
+        .is_some_and(|m| m.permissions().readonly())
+    {
+        return Err(FsError::PermissionDenied {
+            message: format!("File is read-only: {}", path),
+        });
+    }
+
+    let (temp_path, mut file) = create_temp_for(&file_path)
+        .map_err(|e| FsError::io("Failed to write file atomically", e))?;
+    let result = match (mode, &existing) {
+        (Some(_), _) => apply_mode(&file, mode),
+        (None, Some(existing)) if existing.is_file() => {
+            file.set_permissions(existing.permissions())
+        }
+        (None, _) => Ok(()),
+    }
+    .and_then(|_| file.write_all(content))
+    .and_then(|_| file.sync_all())
+    .and_then(|_| fs::rename(&temp_path, &file_path));
+
+    result.map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        FsError::io("Failed to write file atomically", e)
+    })
+}
+
+/// Which part of a file `truncate_file` keeps
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeepEnd {
+    Head,
+    Tail,
+}
+
+/// Trim a file to at most `max_bytes`, keeping its head or tail.
+///
+/// The cut is moved to a line boundary so only whole lines are kept; a file
+/// without a usable line break is cut at the nearest char boundary instead.
+/// Returns the number of bytes kept.
+pub fn truncate_file(path: &str, max_bytes: usize, keep: KeepEnd) -> Result<usize, FsError> {
+    let content = read_file(path)?;
+    if content.len() <= max_bytes {
+        return Ok(content.len());
+    }
+
+    let kept = match keep {
+        KeepEnd::Head => {
+            let mut end = max_bytes;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            match content[..end].rfind('\n') {
+                Some(newline) => &content[..=newline],
+                None => &content[..end],
+            }
+        }
+        KeepEnd::Tail => {
+            let mut start = content.len() - max_bytes;
+            while !content.is_char_boundary(start) {
+                start += 1;
+            }
+            if content.as_bytes()[start - 1] == b'\n' {
+                &content[start..]
+            } else {
+                match content[start..].find('\n') {
+                    Some(newline) if start + newline + 1 < content.len() => {
+                        &content[start + newline + 1..]
+                    }
+                    _ => &content[start..],
+                }
+            }
+        }
+    };
+
+    write_file_atomic(path, kept.as_bytes())?;
+    Ok(kept.len())
+}
+
+/// Create a new file with `content`, failing if anything already exists at
+/// `path`. The existence check and creation happen in one atomic open.
+pub fn create_file_exclusive(path: &str, content: &str) -> Result<(), FsError> {
+    create_file_exclusive_with_mode(path, content, None)
+}
+
+/// `create_file_exclusive`, with the permission bits `mode` when set
+pub fn create_file_exclusive_with_mode(
+    path: &str,
+    content: &str,
+    mode: Option<u32>,
+) -> Result<(), FsError> {
+    let file_path = PathBuf::from(path);
+
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| FsError::io("Failed to create parent directories", e))?;
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&file_path)
+        .map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => FsError::AlreadyExists {
+                message: format!("File already exists: {}", path),
+            },
+            _ => FsError::io("Failed to create file", e),
+        })?;
+
+    apply_mode(&file, mode).map_err(|e| FsError::io("Failed to set permissions", e))?;
+    file.write_all(content.as_bytes())
+        .map_err(|e| FsError::io("Failed to write to file", e))
+}
+
+/// Candidate paths in `dir` for a new entry: `base.ext`, then `base 1.ext`,
+/// `base 2.ext` and so on. `extension` may be given with or without its dot.
+fn numbered_paths<'a>(
+    dir: &'a str,
+    base_name: &'a str,
+    extension: &'a str,
+) -> Result<impl Iterator<Item = PathBuf> + 'a, FsError> {
+    if !Path::new(dir).is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", dir),
+        });
+    }
+    if base_name.trim().is_empty() || base_name.contains(['/', '\\']) {
+        return Err(FsError::InvalidPath {
+            message: format!("Invalid name: {}", base_name),
+        });
+    }
+    let extension = extension.trim_start_matches('.');
+    Ok((0..).map(move |n| {
+        let name = match n {
+            0 => base_name.to_string(),
+            n => format!("{} {}", base_name, n),
+        };
+        match extension {
+            "" => Path::new(dir).join(name),
+            ext => Path::new(dir).join(format!("{}.{}", name, ext)),
+        }
+    }))
+}
+
+/// Create an empty file at the first free name in `dir` (`Untitled.md`,
+/// `Untitled 1.md`, ...), returning its path. Each candidate is claimed
+/// with `create`, e.g. `create_file_exclusive` under a file lock, whose
+/// exclusive open means concurrent calls never pick the same name; an
+/// `AlreadyExists` error moves on to the next name.
+pub fn create_unique_file(
+    dir: &str,
+    base_name: &str,
+    extension: &str,
+    mut create: impl FnMut(&str) -> Result<(), FsError>,
+) -> Result<String, FsError> {
+    for path in numbered_paths(dir, base_name, extension)? {
+        let path = path.to_string_lossy().to_string();
+        match create(&path) {
+            Ok(()) => return Ok(path),
+            Err(FsError::AlreadyExists { .. }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("numbered_paths never ends")
+}
+
+/// Create a directory at the first free name in `dir` (`Untitled`,
+/// `Untitled 1`, ...), returning its path
+pub fn create_unique_directory(dir: &str, base_name: &str) -> Result<String, FsError> {
+    for path in numbered_paths(dir, base_name, "")? {
+        match fs::create_dir(&path) {
+            Ok(()) => return Ok(path.to_string_lossy().to_string()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(FsError::io("Failed to create directory", e)),
+        }
+    }
+    unreachable!("numbered_paths never ends")
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hex-encoded SHA-256 of a file's contents, read in chunks so large files
+/// aren't loaded into memory
+pub fn file_hash(path: &str) -> Result<String, FsError> {
+    let mut file = fs::File::open(path).map_err(|e| FsError::io("Failed to open file", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| FsError::io("Failed to read file", e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Content hash of one path in a batch, or why it couldn't be hashed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileHashResult {
+    pub path: String,
+    pub hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Hash each path with `hash` (e.g. `file_hash`), carrying on past
+/// failures so each is reported against its own path
+pub fn hash_paths(
+    paths: &[String],
+    mut hash: impl FnMut(&str) -> Result<String, FsError>,
+) -> Vec<FileHashResult> {
+    paths
+        .iter()
+        .map(|path| {
+            let (hash, error) = match hash(path) {
+                Ok(hash) => (Some(hash), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            FileHashResult {
+                path: path.clone(),
+                hash,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Hex-encoded SHA-256 over each entry's name, size and modification time.
+/// Cheap to compute and changes whenever the listing does.
+pub fn directory_signature(path: &str) -> Result<String, FsError> {
+    let entries = fs::read_dir(path).map_err(|e| FsError::io("Failed to read directory", e))?;
+
+    let mut listing: Vec<(String, u64, u128)> = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| FsError::io("Failed to read entry", e))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|e| FsError::io("Failed to read metadata", e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos());
+        listing.push((
+            entry.file_name().to_string_lossy().to_string(),
+            metadata.len(),
+            modified,
+        ));
+    }
+    listing.sort();
+
+    let mut hasher = Sha256::new();
+    for (name, size, modified) in listing {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(size.to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Decides which directories a recursive walk descends into. Symlinked
+/// directories are entered only when following links, and each real
+/// directory at most once, so a link cycle can't make a walk loop forever.
+pub(crate) struct WalkGuard {
+    follow_symlinks: bool,
+    visited: HashSet<PathBuf>,
+}
+
+impl WalkGuard {
+    pub(crate) fn new(root: &Path, follow_symlinks: bool) -> Self {
+        WalkGuard {
+            follow_symlinks,
+            visited: fs::canonicalize(root).into_iter().collect(),
+        }
+    }
+
+    /// Whether to descend into the directory at `path`, which is itself a
+    /// symlink when `is_symlink`
+    pub(crate) fn enter(&mut self, path: &Path, is_symlink: bool) -> bool {
+        if is_symlink && !self.follow_symlinks {
+            return false;
+        }
+        fs::canonicalize(path).is_ok_and(|canonical| self.visited.insert(canonical))
+    }
+}
+
+/// An entry met by `walk_directory`
+pub(crate) struct WalkEntry {
+    pub path: PathBuf,
+    /// Metadata of what the entry points to; a broken link's own metadata
+    pub metadata: fs::Metadata,
+    pub is_symlink: bool,
+}
+
+/// Walk everything below `root` depth-first, passing each entry to `visit`,
+/// which returns whether a directory should be descended into; `WalkGuard`
+/// has the final say for symlinked and already-walked directories. Entries
+/// below the root that can't be read are returned rather than failing the
+/// walk.
+pub(crate) fn walk_directory(
+    root: &Path,
+    follow_symlinks: bool,
+    mut visit: impl FnMut(&WalkEntry) -> bool,
+) -> Result<Vec<PathBuf>, FsError> {
+    let mut guard = WalkGuard::new(root, follow_symlinks);
+    let mut skipped = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(FsError::io("Failed to read directory", e)),
+            Err(_) => {
+                skipped.push(dir);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(link_metadata) = entry.metadata() else {
+                skipped.push(path);
+                continue;
+            };
+            let is_symlink = link_metadata.file_type().is_symlink();
+            let metadata = match is_symlink {
+                true => fs::metadata(&path).unwrap_or(link_metadata),
+                false => link_metadata,
+            };
+            let entry = WalkEntry {
+                path,
+                metadata,
+                is_symlink,
+            };
+            if visit(&entry) && entry.metadata.is_dir() && guard.enter(&entry.path, is_symlink) {
+                pending.push(entry.path);
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+/// Totals for a directory tree, as shown in a folder properties dialog
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct DirectoryStats {
+    pub total_bytes: u64,
+    pub file_count: u64,
+    /// Subdirectories below the root; the root itself isn't counted
+    pub dir_count: u64,
+    /// Entries that couldn't be read and were left out of the totals
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// Walk a directory tree, summing file sizes and counting files and
+/// subdirectories. Symlinked directories are only walked with
+/// `follow_symlinks` and aren't counted themselves; see `walk_directory`.
+/// Unreadable entries below the root are recorded in `skipped` rather than
+/// failing the walk.
+pub fn directory_stats(path: &str, follow_symlinks: bool) -> Result<DirectoryStats, FsError> {
+    let root = fs::canonicalize(path).map_err(|e| FsError::io("Failed to read directory", e))?;
+    if !root.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Not a directory: {}", path),
+        });
+    }
+
+    let mut stats = DirectoryStats::default();
+    let skipped = walk_directory(Path::new(path), follow_symlinks, |entry| {
+        if !entry.metadata.is_dir() {
+            stats.file_count += 1;
+            stats.total_bytes += entry.metadata.len();
+        } else if !entry.is_symlink {
+            stats.dir_count += 1;
+        }
+        true
+    })?;
+    stats.skipped = skipped
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    Ok(stats)
+}
+
+/// What to do when a merged entry already exists at the destination
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    /// Move under a free name like `note (1).md`
+    Rename,
+}
+
+/// What happened to one entry during a merge
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeAction {
+    Moved,
+    Skipped,
+    Overwritten,
+    Renamed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeOutcome {
+    pub source: String,
+    pub destination: String,
+    pub action: MergeAction,
+}
+
+/// First path of the form `stem (n).ext` next to `path` that doesn't exist
+pub(crate) fn unique_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Move `from` to `to`, copying and removing when a rename isn't possible
+/// (e.g. across filesystems)
+fn move_entry(from: &Path, to: &Path) -> Result<(), FsError> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::create_dir_all(to).map_err(|e| FsError::io("Failed to create directory", e))?;
+        let entries =
+            fs::read_dir(from).map_err(|e| FsError::io("Failed to read directory", e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| FsError::io("Failed to read entry", e))?;
+            move_entry(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from).map_err(|e| FsError::io("Failed to remove directory", e))
+    } else {
+        fs::copy(from, to).map_err(|e| FsError::io("Failed to copy file", e))?;
+        fs::remove_file(from).map_err(|e| FsError::io("Failed to remove file", e))
+    }
+}
+
+fn merge_into(
+    source: &Path,
+    dest: &Path,
+    on_conflict: ConflictPolicy,
+    outcomes: &mut Vec<MergeOutcome>,
+) -> Result<(), FsError> {
+    let entries = fs::read_dir(source).map_err(|e| FsError::io("Failed to read directory", e))?;
+    let mut entries: Vec<PathBuf> = entries
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| FsError::io("Failed to read entry", e))?;
+    entries.sort();
+
+    for from in entries {
+        let mut to = dest.join(from.file_name().unwrap_or_default());
+
+        // Directories present on both sides merge rather than conflict
+        if from.is_dir() && to.is_dir() {
+            merge_into(&from, &to, on_conflict, outcomes)?;
+            // Only remove the source directory once everything has left it
+            let _ = fs::remove_dir(&from);
+            continue;
+        }
+
+        let action = if !to.exists() {
+            MergeAction::Moved
+        } else {
+            match on_conflict {
+                ConflictPolicy::Skip => MergeAction::Skipped,
+                ConflictPolicy::Overwrite => {
+                    if to.is_dir() {
+                        fs::remove_dir_all(&to)
+                    } else {
+                        fs::remove_file(&to)
+                    }
+                    .map_err(|e| FsError::io(format!("Failed to replace {}", to.display()), e))?;
+                    MergeAction::Overwritten
+                }
+                ConflictPolicy::Rename => {
+                    to = unique_path(&to);
+                    MergeAction::Renamed
+                }
+            }
+        };
+
+        if action != MergeAction::Skipped {
+            move_entry(&from, &to)?;
+        }
+        outcomes.push(MergeOutcome {
+            source: from.to_string_lossy().to_string(),
+            destination: to.to_string_lossy().to_string(),
+            action,
+        });
+    }
+
+    Ok(())
+}
+
+/// Move everything in `source` into `dest`, merging subdirectories that
+/// exist in both and resolving other name clashes with `on_conflict`.
+/// `source` is removed once empty; skipped entries stay behind in it.
+pub fn merge_directories(
+    source: &str,
+    dest: &str,
+    on_conflict: ConflictPolicy,
+) -> Result<Vec<MergeOutcome>, FsError> {
+    let source_path = fs::canonicalize(source)
+        .map_err(|e| FsError::io(format!("Failed to resolve {}", source), e))?;
+    let dest_path = fs::canonicalize(dest)
+        .map_err(|e| FsError::io(format!("Failed to resolve {}", dest), e))?;
+
+    if !source_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", source),
+        });
+    }
+    if !dest_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", dest),
+        });
+    }
+    if dest_path.starts_with(&source_path) {
+        return Err(FsError::InvalidPath {
+            message: format!("Cannot merge {} into itself or a subdirectory", source),
+        });
+    }
+
+    let mut outcomes = Vec::new();
+    merge_into(&source_path, &dest_path, on_conflict, &mut outcomes)?;
+    let _ = fs::remove_dir(&source_path);
+    Ok(outcomes)
+}
+
+/// Rename a file or directory, refusing to replace anything at `new_path`.
+/// Moves to another filesystem are done by copying and then deleting.
+pub fn rename_path(old_path: &str, new_path: &str) -> Result<(), FsError> {
+    if fs::symlink_metadata(old_path).is_err() {
+        return Err(FsError::NotFound {
+            message: format!("Path does not exist: {}", old_path),
+        });
+    }
+    if fs::symlink_metadata(new_path).is_ok() {
+        return Err(FsError::AlreadyExists {
+            message: format!("Path already exists: {}", new_path),
+        });
+    }
+    rename_or_move(old_path, new_path, |from, to| fs::rename(from, to))
+}
+
+/// Rename with `rename`, falling back to copy-then-delete when the paths
+/// are on different filesystems. The source is only removed once the copy
+/// has fully succeeded; a failed copy has its partial destination removed.
+fn rename_or_move(
+    old_path: &str,
+    new_path: &str,
+    rename: impl FnOnce(&str, &str) -> std::io::Result<()>,
+) -> Result<(), FsError> {
+    match rename(old_path, new_path) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(FsError::io("Failed to rename", e)),
+    }
+
+    if fs::symlink_metadata(new_path).is_ok() {
+        return Err(FsError::AlreadyExists {
+            message: format!("Path already exists: {}", new_path),
+        });
+    }
+    if let Some(parent) = Path::new(new_path).parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| FsError::io("Failed to create parent directories", e))?;
+    }
+    if let Err(e) = copy_for_move(Path::new(old_path), Path::new(new_path)) {
+        let e = FsError::io("Failed to copy", e);
+        if fs::symlink_metadata(new_path).is_ok() {
+            if let Err(cleanup) = delete_path(new_path) {
+                eprintln!("Failed to clean up {}: {}", new_path, cleanup);
+            }
+        }
+        return Err(e);
+    }
+    delete_path(old_path).map_err(|e| FsError::Io {
+        message: format!("Copied but failed to remove {}: {}", old_path, e),
+    })
+}
+
+/// Copy `from` to `to` as part of a move: symlinks are recreated as links
+/// rather than copied through, and directories keep their permissions.
+/// Stops at the first entry that can't be copied.
+fn copy_for_move(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(from)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, to);
+        #[cfg(windows)]
+        return if fs::metadata(from).is_ok_and(|m| m.is_dir()) {
+            std::os::windows::fs::symlink_dir(target, to)
+        } else {
+            std::os::windows::fs::symlink_file(target, to)
+        };
+    }
+    if !metadata.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_for_move(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    fs::set_permissions(to, metadata.permissions())
+}
+
+/// Move a file or directory into `dest_dir`, keeping its name, and return
+/// the new path. A name already taken in `dest_dir` is replaced when
+/// `overwrite` is set and otherwise numbered like `create_unique_file`
+/// (`note 1.md`, `note 2.md`, ...). The move itself is done by `rename`,
+/// e.g. `rename_path`. Moving into the folder it is already in does nothing.
+///
+/// A replaced entry is first set aside under a hidden name and put back if
+/// the move fails. Once the move succeeds it is handed to `discard` with
+/// the path it had and the one it was set aside at, e.g. to trash it.
+pub fn move_into_directory(
+    src: &str,
+    dest_dir: &str,
+    overwrite: bool,
+    rename: impl FnOnce(&str, &str) -> Result<(), FsError>,
+    discard: impl FnOnce(&str, &str) -> Result<(), FsError>,
+) -> Result<String, FsError> {
+    let metadata = fs::symlink_metadata(src).map_err(|_| FsError::NotFound {
+        message: format!("Path does not exist: {}", src),
+    })?;
+    let src_path = Path::new(src);
+    let name = src_path.file_name().ok_or_else(|| FsError::InvalidPath {
+        message: format!("Path has no file name: {}", src),
+    })?;
+    let dest = fs::canonicalize(dest_dir)
+        .ok()
+        .filter(|dest| dest.is_dir())
+        .ok_or_else(|| FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", dest_dir),
+        })?;
+    // Resolve only the parent, so a symlink is moved rather than its target
+    let source = src_path
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok())
+        .map_or_else(|| src_path.to_path_buf(), |parent| parent.join(name));
+    if source.parent() == Some(dest.as_path()) {
+        return Ok(src.to_string());
+    }
+    if metadata.is_dir() && dest.starts_with(&source) {
+        return Err(FsError::InvalidPath {
+            message: format!("Cannot move a directory into itself: {}", src),
+        });
+    }
+
+    let mut target = Path::new(dest_dir).join(name);
+    let mut replaced = None;
+    if fs::symlink_metadata(&target).is_ok() {
+        if overwrite {
+            if source.starts_with(dest.join(name)) {
+                return Err(FsError::InvalidPath {
+                    message: format!("Cannot replace a directory containing {}", src),
+                });
+            }
+            let backup = (0..)
+                .map(|attempt| {
+                    let hidden = format!(".{}.replacing-{}", name.to_string_lossy(), attempt);
+                    Path::new(dest_dir).join(hidden)
+                })
+                .find(|backup| fs::symlink_metadata(backup).is_err())
+                .unwrap_or_default();
+            fs::rename(&target, &backup)
+                .map_err(|e| FsError::io(format!("Failed to set aside {}", target.display()), e))?;
+            replaced = Some(backup);
+        } else {
+            let name = Path::new(name);
+            let (base_name, extension) = match name.file_stem() {
+                Some(stem) if !metadata.is_dir() => (stem, name.extension().unwrap_or_default()),
+                _ => (name.as_os_str(), OsStr::new("")),
+            };
+            target = numbered_paths(
+                dest_dir,
+                &base_name.to_string_lossy(),
+                &extension.to_string_lossy(),
+            )?
+            .find(|path| fs::symlink_metadata(path).is_err())
+            .unwrap_or(target);
+        }
+    }
+
+    let target = target.to_string_lossy().to_string();
+    if let Err(e) = rename(src, &target) {
+        if let Some(backup) = &replaced {
+            if let Err(undo) = fs::rename(backup, &target) {
+                eprintln!("Failed to restore {}: {}", target, undo);
+            }
+        }
+        return Err(e);
+    }
+    if let Some(backup) = replaced {
+        if let Err(e) = discard(&target, &backup.to_string_lossy()) {
+            eprintln!("Failed to discard replaced {}: {}", target, e);
+        }
+    }
+    Ok(target)
+}
+
+/// Entries handled between progress reports of a tracked copy or delete
+const PROGRESS_INTERVAL: usize = 100;
+
+/// How far a tracked copy or delete has got
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct OperationProgress {
+    /// Files and directories handled so far
+    pub processed: usize,
+    pub total: usize,
+    /// The entry handled most recently
+    pub current_path: String,
+}
+
+/// Counts entries through a tracked operation, reporting progress every
+/// `PROGRESS_INTERVAL` entries and at the end, and stopping it once
+/// `cancel` is set
+struct ProgressTracker<'a, F: FnMut(&OperationProgress)> {
+    progress: OperationProgress,
+    cancel: &'a AtomicBool,
+    on_progress: F,
+}
+
+impl<'a, F: FnMut(&OperationProgress)> ProgressTracker<'a, F> {
+    fn new(root: &Path, cancel: &'a AtomicBool, on_progress: F) -> Self {
+        let mut total = 1;
+        let _ = walk_directory(root, false, |_| {
+            total += 1;
+            true
+        });
+        ProgressTracker {
+            progress: OperationProgress {
+                processed: 0,
+                total,
+                current_path: String::new(),
+            },
+            cancel,
+            on_progress,
+        }
+    }
+
+    /// Fail with `Cancelled` if the operation has been cancelled, reporting
+    /// how far it got
+    fn check_cancelled(&mut self) -> Result<(), FsError> {
+        if !self.cancel.load(AtomicOrdering::SeqCst) {
+            return Ok(());
+        }
+        (self.on_progress)(&self.progress);
+        Err(FsError::Cancelled {
+            message: format!(
+                "Cancelled after {} of {} entries",
+                self.progress.processed, self.progress.total
+            ),
+        })
+    }
+
+    fn advance(&mut self, path: &Path) {
+        self.progress.processed += 1;
+        self.progress.current_path = path.to_string_lossy().to_string();
+        let processed = self.progress.processed;
+        if processed.is_multiple_of(PROGRESS_INTERVAL) || processed == self.progress.total {
+            (self.on_progress)(&self.progress);
+        }
+    }
+}
+
+/// Copy a file or directory to `dest`, refusing to replace anything there
+/// and creating `dest`'s parent directories as needed. A directory copy
+/// carries on past entries it can't copy and reports them all together.
+pub fn copy_path(src: &str, dest: &str) -> Result<(), FsError> {
+    copy_path_tracked(src, dest, &AtomicBool::new(false), |_| {})
+}
+
+/// `copy_path`, reporting progress and stopping early with `Cancelled` once
+/// `cancel` is set. Entries already copied are left in place.
+pub fn copy_path_tracked(
+    src: &str,
+    dest: &str,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(&OperationProgress),
+) -> Result<(), FsError> {
+    let metadata = fs::metadata(src).map_err(|_| FsError::NotFound {
+        message: format!("Path does not exist: {}", src),
+    })?;
+    if fs::symlink_metadata(dest).is_ok() {
+        return Err(FsError::AlreadyExists {
+            message: format!("Path already exists: {}", dest),
+        });
+    }
+    let dest_path = Path::new(dest);
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| FsError::io("Failed to create parent directories", e))?;
+    }
+
+    let src_path = Path::new(src);
+    let mut tracker = ProgressTracker::new(src_path, cancel, on_progress);
+    if !metadata.is_dir() {
+        tracker.check_cancelled()?;
+        fs::copy(src, dest).map_err(|e| FsError::io("Failed to copy file", e))?;
+        tracker.advance(src_path);
+        return Ok(());
+    }
+    if canonical_path(dest).starts_with(canonical_path(src)) {
+        return Err(FsError::InvalidPath {
+            message: format!("Cannot copy {} into itself", src),
+        });
+    }
+
+    fn copy_dir<F: FnMut(&OperationProgress)>(
+        from: &Path,
+        to: &Path,
+        errors: &mut Vec<String>,
+        tracker: &mut ProgressTracker<F>,
+    ) -> Result<(), FsError> {
+        if let Err(e) = fs::create_dir(to) {
+            errors.push(format!("{}: {}", to.display(), e));
+            return Ok(());
+        }
+        let entries = match fs::read_dir(from) {
+            Ok(entries) => entries,
+            Err(e) => {
+                errors.push(format!("{}: {}", from.display(), e));
+                return Ok(());
+            }
+        };
+        for entry in entries.flatten() {
+            tracker.check_cancelled()?;
+            let source = entry.path();
+            let target = to.join(entry.file_name());
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                copy_dir(&source, &target, errors, tracker)?;
+            } else if let Err(e) = fs::copy(&source, &target) {
+                errors.push(format!("{}: {}", source.display(), e));
+            }
+            tracker.advance(&source);
+        }
+        Ok(())
+    }
+
+    let mut errors = Vec::new();
+    tracker.check_cancelled()?;
+    copy_dir(src_path, dest_path, &mut errors, &mut tracker)?;
+    tracker.advance(src_path);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(FsError::Io {
+            message: format!("Failed to copy some entries: {}", errors.join("; ")),
+        })
+    }
+}
+
+/// Permanently delete a file
+pub fn delete_file(path: &str) -> Result<(), FsError> {
+    let file_path = PathBuf::from(path);
+
+    if !file_path.is_file() {
+        return Err(FsError::NotAFile {
+            message: format!("Path is not a file: {}", path),
+        });
+    }
+
+    fs::remove_file(&file_path).map_err(|e| FsError::io("Failed to delete file", e))
+}
+
+/// Most paths listed in a `DeletePreview`
+const DELETE_PREVIEW_LIMIT: usize = 100;
+
+/// What deleting a directory would remove
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DeletePreview {
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub total_bytes: u64,
+    /// Paths that would be removed, capped at `DELETE_PREVIEW_LIMIT`
+    pub entries: Vec<String>,
+    pub truncated: bool,
+    /// Entries that couldn't be read and were left out of the totals
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// Summarize everything beneath a directory without deleting anything.
+/// Symlinks are counted as files and not followed, matching `remove_dir_all`.
+/// Unreadable entries are recorded in `skipped` rather than failing the
+/// preview; see `walk_directory`.
+pub fn directory_delete_preview(path: &str) -> Result<DeletePreview, FsError> {
+    let dir_path = PathBuf::from(path);
+
+    if !dir_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", path),
+        });
+    }
+
+    let mut preview = DeletePreview::default();
+    let skipped = walk_directory(&dir_path, false, |entry| {
+        if preview.entries.len() < DELETE_PREVIEW_LIMIT {
+            preview
+                .entries
+                .push(entry.path.to_string_lossy().to_string());
+        } else {
+            preview.truncated = true;
+        }
+
+        if entry.is_symlink {
+            preview.file_count += 1;
+            preview.total_bytes += fs::symlink_metadata(&entry.path).map_or(0, |m| m.len());
+        } else if entry.metadata.is_dir() {
+            preview.directory_count += 1;
+        } else {
+            preview.file_count += 1;
+            preview.total_bytes += entry.metadata.len();
+        }
+        true
+    })?;
+    preview.skipped = skipped
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    Ok(preview)
+}
+
+/// Summarize what deleting a file or directory would remove, without
+/// deleting anything. A file, or a symlink, previews as just itself.
+pub fn delete_preview(path: &str) -> Result<DeletePreview, FsError> {
+    let metadata = fs::symlink_metadata(path).map_err(|_| FsError::NotFound {
+        message: format!("Path does not exist: {}", path),
+    })?;
+    if metadata.is_dir() {
+        return directory_delete_preview(path);
+    }
+
+    Ok(DeletePreview {
+        file_count: 1,
+        directory_count: 0,
+        total_bytes: metadata.len(),
+        entries: vec![path.to_string()],
+        truncated: false,
+        skipped: Vec::new(),
+    })
+}
+
+/// Permanently delete a directory and everything inside it
+pub fn delete_directory(path: &str) -> Result<(), FsError> {
+    let dir_path = PathBuf::from(path);
+
+    if !dir_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", path),
+        });
+    }
+
+    fs::remove_dir_all(&dir_path).map_err(|e| FsError::io("Failed to delete directory", e))
+}
+
+/// `delete_directory`, one entry at a time so it can report progress and
+/// stop early with `Cancelled` once `cancel` is set, leaving whatever
+/// hasn't been deleted yet. Each entry is passed to `on_removed` once it is
+/// gone. Symlinks are removed, not followed.
+pub fn delete_directory_tracked(
+    path: &str,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(&OperationProgress),
+    mut on_removed: impl FnMut(&Path),
+) -> Result<(), FsError> {
+    let dir_path = PathBuf::from(path);
+
+    if !dir_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", path),
+        });
+    }
+
+    fn remove_dir<F: FnMut(&OperationProgress)>(
+        dir: &Path,
+        tracker: &mut ProgressTracker<F>,
+        on_removed: &mut impl FnMut(&Path),
+    ) -> Result<(), FsError> {
+        let entries = fs::read_dir(dir).map_err(|e| FsError::io("Failed to read directory", e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| FsError::io("Failed to read entry", e))?;
+            tracker.check_cancelled()?;
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                remove_dir(&path, tracker, on_removed)?;
+            } else {
+                fs::remove_file(&path).map_err(|e| FsError::io("Failed to delete file", e))?;
+                tracker.advance(&path);
+                on_removed(&path);
+            }
+        }
+        fs::remove_dir(dir).map_err(|e| FsError::io("Failed to delete directory", e))?;
+        tracker.advance(dir);
+        on_removed(dir);
+        Ok(())
+    }
+
+    let mut tracker = ProgressTracker::new(&dir_path, cancel, on_progress);
+    remove_dir(&dir_path, &mut tracker, &mut on_removed)
+}
+
+/// What moving a path to the trash would affect, for a confirmation dialog
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TrashPreview {
+    pub is_directory: bool,
+    /// Size of the file, or of everything beneath the directory
+    pub total_bytes: u64,
+    /// Files and folders beneath a directory; `None` for files
+    pub child_count: Option<usize>,
+    /// Whether the platform looks to have a trash to move it to
+    pub trash_available: bool,
+}
+
+/// Best guess at whether `move_to_trash` can succeed here. Windows and
+/// macOS always have one; elsewhere it needs the freedesktop data directory.
+fn trash_available() -> bool {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return true;
+    }
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    data_home.is_some_and(|dir| dir.is_dir())
+}
+
+/// Summarize a path before trashing it, without changing anything
+pub fn trash_preview(path: &str) -> Result<TrashPreview, FsError> {
+    let metadata = fs::symlink_metadata(path).map_err(|_| FsError::NotFound {
+        message: format!("Path does not exist: {}", path),
+    })?;
+
+    let (total_bytes, child_count) = if metadata.is_dir() {
+        let contents = directory_delete_preview(path)?;
+        (
+            contents.total_bytes,
+            Some(contents.file_count + contents.directory_count),
+        )
+    } else {
+        (metadata.len(), None)
+    };
+
+    Ok(TrashPreview {
+        is_directory: metadata.is_dir(),
+        total_bytes,
+        child_count,
+        trash_available: trash_available(),
+    })
+}
+
+/// Move a file or directory to the platform trash, so it can be restored.
+/// Fails when the platform has no trash (e.g. some headless Linux setups),
+/// leaving the caller to fall back to a permanent delete.
+pub fn move_to_trash(path: &str) -> Result<(), FsError> {
+    if fs::symlink_metadata(path).is_err() {
+        return Err(FsError::NotFound {
+            message: format!("Path does not exist: {}", path),
+        });
+    }
+    trash::delete(path).map_err(|e| FsError::Io {
+        message: format!("Trash is unavailable: {}", e),
+    })
+}
+
+/// An item in the platform trash
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TrashedItem {
+    /// Identifies the item to `restore_from_trash`
+    pub id: String,
+    pub name: String,
+    /// Where it was before it was trashed, and where restoring puts it
+    pub original_path: String,
+    /// Unix timestamp in seconds of when it was trashed
+    pub deleted_at: i64,
+}
+
+/// The trash crate can only list, restore and purge the trash on Windows
+/// and freedesktop.org systems
+#[cfg(any(
+    windows,
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+))]
+mod os_trash {
+    use super::FsError;
+    use trash::os_limited;
+    pub use trash::TrashItem;
+
+    fn trash_error(context: &str, e: trash::Error) -> FsError {
+        let message = format!("{}: {}", context, e);
+        match e {
+            trash::Error::RestoreCollision { .. } => FsError::AlreadyExists { message },
+            _ => FsError::Io { message },
+        }
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, FsError> {
+        os_limited::list().map_err(|e| trash_error("Failed to read the trash", e))
+    }
+
+    pub fn restore(items: Vec<TrashItem>) -> Result<(), FsError> {
+        os_limited::restore_all(items).map_err(|e| trash_error("Failed to restore", e))
+    }
+
+    pub fn purge(items: Vec<TrashItem>) -> Result<(), FsError> {
+        os_limited::purge_all(items).map_err(|e| trash_error("Failed to empty the trash", e))
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+mod os_trash {
+    use super::FsError;
+    pub use trash::TrashItem;
+
+    fn unsupported() -> FsError {
+        FsError::Unsupported {
+            message: "The trash can't be browsed on this platform".to_string(),
+        }
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, FsError> {
+        Err(unsupported())
+    }
+
+    pub fn restore(_items: Vec<TrashItem>) -> Result<(), FsError> {
+        Err(unsupported())
+    }
+
+    pub fn purge(_items: Vec<TrashItem>) -> Result<(), FsError> {
+        Err(unsupported())
+    }
+}
+
+impl From<&os_trash::TrashItem> for TrashedItem {
+    fn from(item: &os_trash::TrashItem) -> Self {
+        TrashedItem {
+            id: item.id.to_string_lossy().to_string(),
+            name: item.name.to_string_lossy().to_string(),
+            original_path: item.original_path().to_string_lossy().to_string(),
+            deleted_at: item.time_deleted,
+        }
+    }
+}
+
+/// A trash that can be browsed, restored from and emptied: `OsTrash`, or a
+/// stand-in in tests
+pub trait TrashProvider {
+    /// Everything in the trash, in no particular order
+    fn list(&self) -> Result<Vec<TrashedItem>, FsError>;
+    /// Put the items with these ids back where they were
+    fn restore(&self, ids: &[String]) -> Result<(), FsError>;
+    /// Permanently delete the items with these ids
+    fn purge(&self, ids: &[String]) -> Result<(), FsError>;
+}
+
+/// The platform trash. Every call fails with `Unsupported` where it can't
+/// be browsed, e.g. on macOS.
+pub struct OsTrash;
+
+impl OsTrash {
+    fn items(ids: &[String]) -> Result<Vec<os_trash::TrashItem>, FsError> {
+        Ok(os_trash::list()?
+            .into_iter()
+            .filter(|item| ids.iter().any(|id| item.id.to_string_lossy() == *id))
+            .collect())
+    }
+}
+
+impl TrashProvider for OsTrash {
+    fn list(&self) -> Result<Vec<TrashedItem>, FsError> {
+        Ok(os_trash::list()?.iter().map(TrashedItem::from).collect())
+    }
+
+    fn restore(&self, ids: &[String]) -> Result<(), FsError> {
+        os_trash::restore(Self::items(ids)?)
+    }
+
+    fn purge(&self, ids: &[String]) -> Result<(), FsError> {
+        os_trash::purge(Self::items(ids)?)
+    }
+}
+
+/// What the trash holds from inside `scope`, e.g. the vault, most recently
+/// trashed first. Items trashed from anywhere else are never exposed.
+pub fn list_trash(trash: &impl TrashProvider, scope: &Path) -> Result<Vec<TrashedItem>, FsError> {
+    let scope = canonical_path(&scope.to_string_lossy());
+    let mut items: Vec<TrashedItem> = trash
+        .list()?
+        .into_iter()
+        .filter(|item| canonical_path(&item.original_path).starts_with(&scope))
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+    Ok(items)
+}
+
+/// The item with the given `id` trashed from inside `scope`
+pub fn trashed_item(
+    trash: &impl TrashProvider,
+    id: &str,
+    scope: &Path,
+) -> Result<TrashedItem, FsError> {
+    list_trash(trash, scope)?
+        .into_iter()
+        .find(|item| item.id == id)
+        .ok_or_else(|| FsError::NotFound {
+            message: format!("Not in the trash: {}", id),
+        })
+}
+
+/// Put an item trashed from inside `scope` back where it was, refusing to
+/// replace anything there since. Returns the restored path.
+pub fn restore_from_trash(
+    trash: &impl TrashProvider,
+    id: &str,
+    scope: &Path,
+) -> Result<String, FsError> {
+    let item = trashed_item(trash, id, scope)?;
+    if fs::symlink_metadata(&item.original_path).is_ok() {
+        return Err(FsError::AlreadyExists {
+            message: format!("Path already exists: {}", item.original_path),
+        });
+    }
+    trash.restore(&[item.id])?;
+    Ok(item.original_path)
+}
+
+/// Permanently delete everything trashed from inside `scope`, returning the
+/// original paths of the purged items
+pub fn empty_trash(trash: &impl TrashProvider, scope: &Path) -> Result<Vec<String>, FsError> {
+    let items = list_trash(trash, scope)?;
+    if !items.is_empty() {
+        let ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        trash.purge(&ids)?;
+    }
+    Ok(items.into_iter().map(|item| item.original_path).collect())
+}
+
+/// Outcome for one path of a batch operation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PathResult {
+    pub path: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl PathResult {
+    fn new(path: &str, result: Result<(), FsError>) -> Self {
+        PathResult {
+            path: path.to_string(),
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Permanently delete a file, or a directory and everything inside it,
+/// without following symlinks: a link, even a dangling one, is removed
+/// itself, and a directory goes with its contents but nothing a link inside
+/// it points to
+pub fn delete_path(path: &str) -> Result<(), FsError> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| FsError::io("Failed to delete", e))?;
+    if metadata.is_dir() {
+        return delete_directory(path);
+    }
+    // Links to directories are directories themselves on Windows
+    fs::remove_file(path)
+        .or_else(|e| {
+            if metadata.is_symlink() && cfg!(windows) {
+                fs::remove_dir(path)
+            } else {
+                Err(e)
+            }
+        })
+        .map_err(|e| FsError::io("Failed to delete file", e))
+}
+
+/// Delete each path with `delete` (e.g. `delete_path` or `move_to_trash`),
+/// carrying on past failures so each is reported against its own path
+pub fn delete_paths(
+    paths: &[String],
+    mut delete: impl FnMut(&str) -> Result<(), FsError>,
+) -> Vec<PathResult> {
+    paths
+        .iter()
+        .map(|path| PathResult::new(path, delete(path)))
+        .collect()
+}
+
+/// Delete everything inside the directory at `path` with `delete` (e.g.
+/// `delete_path` or `move_to_trash`) but keep the directory itself, so its
+/// permissions and any watch on it stay as they were. Carries on past
+/// failures, reporting each entry in name order.
+pub fn empty_directory(
+    path: &str,
+    mut delete: impl FnMut(&str) -> Result<(), FsError>,
+) -> Result<Vec<PathResult>, FsError> {
+    let dir_path = Path::new(path);
+    if !dir_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", path),
+        });
+    }
+    let entries = fs::read_dir(dir_path).map_err(|e| FsError::io("Failed to read directory", e))?;
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let entry = entry.to_string_lossy();
+            PathResult::new(&entry, delete(&entry))
+        })
+        .collect())
+}
+
+/// Rename each `(old, new)` pair with `rename` (e.g. `rename_path`),
+/// carrying on past failures; results are reported against the old paths
+pub fn rename_paths(
+    pairs: &[(String, String)],
+    mut rename: impl FnMut(&str, &str) -> Result<(), FsError>,
+) -> Vec<PathResult> {
+    pairs
+        .iter()
+        .map(|(old_path, new_path)| PathResult::new(old_path, rename(old_path, new_path)))
+        .collect()
+}
+
+/// Sequential numbers substituted for `{index}` by a `RenameRule`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Numbering {
+    /// Number of the first file in name order
+    pub start: u64,
+    /// Minimum digits, padded with zeros, e.g. 3 for `007`
+    pub padding: usize,
+}
+
+impl Default for Numbering {
+    fn default() -> Self {
+        Numbering {
+            start: 1,
+            padding: 0,
+        }
+    }
+}
+
+/// How `bulk_rename` derives each file's new name. The steps apply in
+/// order: `template`, `find` and `replace`, `prefix` and `suffix`, and
+/// finally any `{index}` is replaced with the file's number.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RenameRule {
+    /// New name built from `{name}` (the old name without its extension),
+    /// `{ext}` (the extension with its dot) and `{index}`, e.g.
+    /// `note-{index}{ext}`; the old name is kept when unset
+    pub template: Option<String>,
+    pub find: Option<String>,
+    pub replace: String,
+    /// Treat `find` as a regular expression; `replace` may refer to its
+    /// groups as `$1`
+    pub regex: bool,
+    pub prefix: String,
+    /// Added before the extension
+    pub suffix: String,
+    pub numbering: Numbering,
+}
+
+/// A file renamed by `bulk_rename`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RenameMapping {
+    pub old_path: String,
+    pub new_path: String,
+}
+
+/// `name` split before its extension's dot; dotfiles and names without a
+/// dot have no extension
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    }
+}
+
+/// Key under which two names refer to the same file
+fn name_key(name: &str) -> String {
+    if cfg!(windows) {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// The new name `rule` gives the `index`th file, `name`
+fn apply_rename_rule(name: &str, index: u64, rule: &RenameRule, find: Option<&Regex>) -> String {
+    let (stem, extension) = split_extension(name);
+    let mut new_name = match &rule.template {
+        Some(template) => template.replace("{name}", stem).replace("{ext}", extension),
+        None => name.to_string(),
+    };
+    match (find, rule.find.as_deref()) {
+        (Some(pattern), _) => {
+            new_name = pattern
+                .replace_all(&new_name, rule.replace.as_str())
+                .to_string()
+        }
+        (None, Some(find)) if !find.is_empty() => new_name = new_name.replace(find, &rule.replace),
+        _ => {}
+    }
+    let (stem, extension) = split_extension(&new_name);
+    let new_name = format!("{}{}{}{}", rule.prefix, stem, rule.suffix, extension);
+    let number = format!("{:0width$}", index, width = rule.numbering.padding);
+    new_name.replace("{index}", &number)
+}
+
+/// Rename the files directly inside `dir`, dotfiles aside, by `rule`,
+/// numbering them in name order. Every new name is worked out and checked
+/// first: an invalid name, two files given the same name, or a name already
+/// used by something else in `dir` aborts the whole batch with each problem
+/// listed, before anything is renamed. Names may be shuffled among the
+/// files themselves (`1.md` to `2.md` and `2.md` to `3.md`). A rename that
+/// fails midway puts back the ones already done, and any that can't be put
+/// back are listed in the error with the name each was left under. Returns
+/// the files whose names changed.
+pub fn bulk_rename(dir: &str, rule: &RenameRule) -> Result<Vec<RenameMapping>, FsError> {
+    let dir_path = Path::new(dir);
+    if !dir_path.is_dir() {
+        return Err(FsError::NotADirectory {
+            message: format!("Path is not a directory: {}", dir),
+        });
+    }
+    let find = match rule.find.as_deref() {
+        Some(find) if rule.regex => Some(
+            RegexBuilder::new(find)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .dfa_size_limit(REGEX_SIZE_LIMIT)
+                .build()
+                .map_err(|e| FsError::InvalidPattern {
+                    message: format!("Invalid rename pattern: {}", e),
+                })?,
+        ),
+        _ => None,
+    };
+
+    let entries = fs::read_dir(dir_path).map_err(|e| FsError::io("Failed to read directory", e))?;
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort_by(|a, b| {
+        a.to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b))
+    });
+    let renames: Vec<(String, String)> = names
+        .iter()
+        .zip(rule.numbering.start..)
+        .map(|(name, index)| {
+            (
+                name.clone(),
+                apply_rename_rule(name, index, rule, find.as_ref()),
+            )
+        })
+        .collect();
+
+    let invalid: Vec<String> = renames
+        .iter()
+        .filter(|(old, new)| old != new)
+        .filter_map(|(old, new)| {
+            let reason = validate_filename(new, false).reason?;
+            Some(format!("{} -> {}: {}", old, new, reason))
+        })
+        .collect();
+    if !invalid.is_empty() {
+        return Err(FsError::InvalidPath {
+            message: format!("Rename aborted: {}", invalid.join("; ")),
+        });
+    }
+
+    let sources: HashSet<String> = names.iter().map(|name| name_key(name)).collect();
+    let mut claimed: HashMap<String, &str> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for (old, new) in &renames {
+        if let Some(other) = claimed.insert(name_key(new), old) {
+            conflicts.push(format!("{} and {} would both be named {}", other, old, new));
+        } else if !sources.contains(&name_key(new))
+            && fs::symlink_metadata(dir_path.join(new)).is_ok()
+        {
+            conflicts.push(format!("{} would replace existing {}", old, new));
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(FsError::AlreadyExists {
+            message: format!("Rename aborted: {}", conflicts.join("; ")),
+        });
+    }
+
+    // Move every file aside first, so a new name can be one another file is
+    // about to give up
+    let changed: Vec<&(String, String)> = renames.iter().filter(|(old, new)| old != new).collect();
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut result = Ok(());
+    let mut temps = Vec::with_capacity(changed.len());
+    for (n, (old, _)) in changed.iter().enumerate() {
+        let temp = (0..)
+            .map(|attempt| dir_path.join(format!(".{}.renaming-{}-{}", old, n, attempt)))
+            .find(|temp| fs::symlink_metadata(temp).is_err())
+            .unwrap_or_default();
+        let from = dir_path.join(old);
+        if let Err(e) = fs::rename(&from, &temp) {
+            result = Err(FsError::io(format!("Failed to rename {}", old), e));
+            break;
+        }
+        done.push((from, temp.clone()));
+        temps.push(temp);
+    }
+    if result.is_ok() {
+        for (temp, (old, new)) in temps.iter().zip(&changed) {
+            let to = dir_path.join(new);
+            if let Err(e) = fs::rename(temp, &to) {
+                result = Err(FsError::io(format!("Failed to rename {}", old), e));
+                break;
+            }
+            done.push((temp.clone(), to));
+        }
+    }
+    if let Err(e) = result {
+        let mut stranded = Vec::new();
+        for (from, to) in done.into_iter().rev() {
+            if let Err(undo) = fs::rename(&to, &from) {
+                eprintln!("Failed to restore {}: {}", from.display(), undo);
+                stranded.push(format!("{} (left as {})", from.display(), to.display()));
+            }
+        }
+        if stranded.is_empty() {
+            return Err(e);
+        }
+        return Err(FsError::Io {
+            message: format!("{}; could not restore {}", e, stranded.join(", ")),
+        });
+    }
+
+    Ok(changed
+        .into_iter()
+        .map(|(old, new)| RenameMapping {
+            old_path: dir_path.join(old).to_string_lossy().to_string(),
+            new_path: dir_path.join(new).to_string_lossy().to_string(),
+        })
+        .collect())
+}
+
+/// A file to write as part of a `save_files` batch
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileSave {
+    pub path: String,
+    pub content: String,
+}
+
+/// Outcome of one file of a `save_files` batch, keeping the error's code
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct SaveResult {
+    pub path: String,
+    pub ok: bool,
+    pub error: Option<FsError>,
+}
+
+/// Save each file with `save` (e.g. `write_file_atomic`), carrying on past
+/// failures so each is reported against its own path
+pub fn save_files(
+    files: &[FileSave],
+    mut save: impl FnMut(&str, &str) -> Result<(), FsError>,
+) -> Vec<SaveResult> {
+    files
+        .iter()
+        .map(|file| {
+            let error = save(&file.path, &file.content).err();
+            SaveResult {
+                path: file.path.clone(),
+                ok: error.is_none(),
+                error,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_markdown_file() {
+        assert!(is_markdown_file("README.md"));
+        assert!(is_markdown_file("notes.markdown"));
+        assert!(is_markdown_file("doc.mdown"));
+        assert!(is_markdown_file("FILE.MD")); // Case insensitive
+        assert!(!is_markdown_file("notes.txt"));
+        assert!(!is_markdown_file("image.png"));
+    }
+
+    #[test]
+    fn test_binary_files_are_refused_as_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("note.md"), "# Café\n\tindented\r\n").unwrap();
+        fs::write(path("image.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(path("noise.bin"), [1u8, 2, 3, b'a', 4, 5, b'b']).unwrap();
+        fs::write(path("empty.md"), "").unwrap();
+
+        assert_eq!(is_binary_file(&path("note.md")), Ok(false));
+        assert_eq!(is_binary_file(&path("image.png")), Ok(true));
+        assert_eq!(is_binary_file(&path("noise.bin")), Ok(true));
+        assert_eq!(is_binary_file(&path("empty.md")), Ok(false));
+        assert!(looks_binary(&[0xEF, 0xBB, 0xBF, 0, 0]));
+        assert!(!looks_binary(&[0xFF, 0xFE, b'h', 0, b'i', 0]));
+
+        assert_eq!(
+            read_text_file(&path("note.md")).unwrap(),
+            "# Café\n\tindented\r\n"
+        );
+        assert!(matches!(
+            read_text_file(&path("image.png")),
+            Err(FsError::BinaryFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_errors_carry_a_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("note.md"), "").unwrap();
+        fs::create_dir(path("folder")).unwrap();
+
+        assert!(matches!(
+            read_file(&path("gone.md")),
+            Err(FsError::NotFound { .. })
+        ));
+        assert!(matches!(
+            read_directory(&path("note.md"), &DirectoryOptions::default()),
+            Err(FsError::NotADirectory { .. })
+        ));
+        assert!(matches!(
+            delete_file(&path("folder")),
+            Err(FsError::NotAFile { .. })
+        ));
+        assert!(matches!(
+            rename_path(&path("note.md"), &path("folder")),
+            Err(FsError::AlreadyExists { .. })
+        ));
+        assert!(matches!(
+            copy_path(&path("folder"), &path("folder/inside")),
+            Err(FsError::InvalidPath { .. })
+        ));
+
+        let error = read_file(&path("gone.md")).unwrap_err();
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["message"], error.message());
+        assert!(error.message().starts_with("Failed to read file: "));
+    }
+
+    #[test]
+    fn test_append_to_file_accumulates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal/today.md");
+        let path = path.to_string_lossy();
+
+        append_to_file(&path, "- first", false).unwrap();
+        append_to_file(&path, "- second", true).unwrap();
+        append_to_file(&path, "\n", false).unwrap();
+        append_to_file(&path, "- third", true).unwrap();
+
+        assert_eq!(read_file(&path).unwrap(), "- first\n- second\n- third");
+    }
+
+    #[test]
+    fn test_create_file_exclusive_refuses_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.md");
+        let path = path.to_string_lossy().to_string();
+
+        create_file_exclusive(&path, "first").unwrap();
+        let error = create_file_exclusive(&path, "second").unwrap_err();
+
+        assert_eq!(
+            error,
+            FsError::AlreadyExists {
+                message: format!("File already exists: {}", path)
+            }
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+    }
+
+    #[test]
+    fn test_move_into_directory_numbers_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("archive");
+        fs::create_dir(&archive).unwrap();
+        fs::write(archive.join("note.md"), "old").unwrap();
+        fs::write(dir.path().join("note.md"), "new").unwrap();
+        let src = dir.path().join("note.md").to_string_lossy().to_string();
+        let dest = archive.to_string_lossy().to_string();
+
+        let discard = |_: &str, backup: &str| delete_path(backup);
+        let moved = move_into_directory(&src, &dest, false, rename_path, discard).unwrap();
+        assert_eq!(moved, archive.join("note 1.md").to_string_lossy());
+        assert_eq!(fs::read_to_string(archive.join("note.md")).unwrap(), "old");
+        assert!(!Path::new(&src).exists());
+
+        fs::write(&src, "newer").unwrap();
+        let moved = move_into_directory(&src, &dest, true, rename_path, discard).unwrap();
+        assert_eq!(moved, archive.join("note.md").to_string_lossy());
+        assert_eq!(
+            fs::read_to_string(archive.join("note.md")).unwrap(),
+            "newer"
+        );
+        assert_eq!(fs::read_dir(&archive).unwrap().count(), 2);
+
+        // Already there, and a folder can't go inside itself
+        assert_eq!(
+            move_into_directory(&moved, &dest, false, rename_path, discard).unwrap(),
+            moved
+        );
+        assert!(matches!(
+            move_into_directory(&dest, &dest, false, rename_path, discard),
+            Err(FsError::InvalidPath { .. })
+        ));
+    }
+
+    #[test]
+    fn test_move_into_directory_restores_replaced_entry_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("archive");
+        fs::create_dir(&archive).unwrap();
+        fs::write(archive.join("note.md"), "old").unwrap();
+        fs::write(dir.path().join("note.md"), "new").unwrap();
+        let src = dir.path().join("note.md").to_string_lossy().to_string();
+        let dest = archive.to_string_lossy().to_string();
+
+        let failing = |_: &str, _: &str| Err(FsError::from("disk full".to_string()));
+        let discard = |_: &str, _: &str| panic!("nothing was replaced");
+        assert!(move_into_directory(&src, &dest, true, failing, discard).is_err());
+        assert_eq!(fs::read_to_string(archive.join("note.md")).unwrap(), "old");
+        assert_eq!(fs::read_dir(&archive).unwrap().count(), 1);
+
+        let mut discarded = Vec::new();
+        let discard = |target: &str, backup: &str| {
+            discarded.push((target.to_string(), fs::read_to_string(backup).unwrap()));
+            delete_path(backup)
+        };
+        move_into_directory(&src, &dest, true, rename_path, discard).unwrap();
+        let target = archive.join("note.md").to_string_lossy().to_string();
+        assert_eq!(discarded, vec![(target, "old".to_string())]);
+        assert_eq!(fs::read_dir(&archive).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_empty_directory_keeps_directory_and_link_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir_all(target.join("nested")).unwrap();
+        fs::write(target.join("note.md"), "").unwrap();
+        fs::write(target.join("keep.md"), "").unwrap();
+        fs::write(target.join("nested/deep.md"), "").unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("safe.md"), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::os::unix::fs::symlink(&outside, target.join("link")).unwrap();
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o750)).unwrap();
+        }
+        let permissions = fs::metadata(&target).unwrap().permissions();
+
+        let results = empty_directory(&target.to_string_lossy(), |entry| {
+            if entry.ends_with("keep.md") {
+                return Err(FsError::PermissionDenied {
+                    message: "Refused".to_string(),
+                });
+            }
+            delete_path(entry)
+        })
+        .unwrap();
+        let failed: Vec<&PathResult> = results.iter().filter(|result| !result.ok).collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].path.ends_with("keep.md"));
+        assert_eq!(results.len(), if cfg!(unix) { 4 } else { 3 });
+
+        let left: Vec<_> = fs::read_dir(&target).unwrap().flatten().collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(fs::metadata(&target).unwrap().permissions(), permissions);
+        assert!(outside.join("safe.md").exists());
+        assert!(matches!(
+            empty_directory(&target.join("keep.md").to_string_lossy(), delete_path),
+            Err(FsError::NotADirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bulk_rename_numbers_and_aborts_on_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        for name in ["b.png", "a.png", "c.txt", ".hidden"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        fs::create_dir(dir.path().join("folder")).unwrap();
+        let names = || {
+            let mut names: Vec<String> = fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let rule = RenameRule {
+            template: Some("shot-{index}{ext}".to_string()),
+            numbering: Numbering {
+                start: 1,
+                padding: 2,
+            },
+            ..Default::default()
+        };
+        let renamed = bulk_rename(&root, &rule).unwrap();
+        assert_eq!(renamed.len(), 3);
+        assert_eq!(
+            renamed[0].old_path,
+            dir.path().join("a.png").to_string_lossy()
+        );
+        assert_eq!(
+            names(),
+            vec![
+                ".hidden",
+                "folder",
+                "shot-01.png",
+                "shot-02.png",
+                "shot-03.txt"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("shot-02.png")).unwrap(),
+            "b.png"
+        );
+
+        // Names can pass along the files being renamed
+        let shift = RenameRule {
+            find: Some(r"-(\d+)".to_string()),
+            replace: "-0{index}".to_string(),
+            regex: true,
+            numbering: Numbering {
+                start: 2,
+                padding: 0,
+            },
+            ..Default::default()
+        };
+        bulk_rename(&root, &shift).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("shot-02.png")).unwrap(),
+            "a.png"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("shot-04.txt")).unwrap(),
+            "c.txt"
+        );
+
+        // Two files given one name, or a name taken by a folder, rename nothing
+        let before = names();
+        let same = RenameRule {
+            template: Some("same{ext}".to_string()),
+            ..Default::default()
+        };
+        let error = bulk_rename(&root, &same).unwrap_err();
+        assert!(matches!(error, FsError::AlreadyExists { .. }));
+        assert!(error.message().contains("would both be named same.png"));
+        let taken = RenameRule {
+            find: Some("shot-02.png".to_string()),
+            replace: "folder".to_string(),
+            ..Default::default()
+        };
+        assert!(bulk_rename(&root, &taken)
+            .unwrap_err()
+            .message()
+            .contains("would replace existing folder"));
+        let invalid = RenameRule {
+            prefix: "a/".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            bulk_rename(&root, &invalid),
+            Err(FsError::InvalidPath { .. })
+        ));
+        let unclosed = RenameRule {
+            find: Some("(shot".to_string()),
+            regex: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            bulk_rename(&root, &unclosed),
+            Err(FsError::InvalidPattern { .. })
+        ));
+        assert_eq!(names(), before);
+    }
+
+    #[test]
+    fn test_rename_falls_back_to_copy_across_devices() {
+        let dir = tempfile::tempdir().unwrap();
+        let cross_device = |_: &str, _: &str| Err(std::io::Error::from(ErrorKind::CrossesDevices));
+        let src = dir.path().join("notes");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.md"), "# A").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("sub/a.md", src.join("alias.md")).unwrap();
+            std::os::unix::fs::symlink("missing.md", src.join("broken.md")).unwrap();
+        }
+        let src = src.to_string_lossy().to_string();
+        let dest = dir.path().join("usb/notes").to_string_lossy().to_string();
+
+        rename_or_move(&src, &dest, cross_device).unwrap();
+        assert!(!Path::new(&src).exists());
+        assert_eq!(
+            fs::read_to_string(Path::new(&dest).join("sub/a.md")).unwrap(),
+            "# A"
+        );
+
+        // Links are moved as links, broken or not, and a copy that fails
+        // part way leaves the source alone and no partial destination behind
+        #[cfg(unix)]
+        {
+            let dest = Path::new(&dest);
+            assert_eq!(
+                fs::read_link(dest.join("alias.md")).unwrap(),
+                Path::new("sub/a.md")
+            );
+            assert_eq!(
+                fs::read_link(dest.join("broken.md")).unwrap(),
+                Path::new("missing.md")
+            );
+            // A socket has no contents to copy
+            let _socket = std::os::unix::net::UnixListener::bind(dest.join("sub/z.sock")).unwrap();
+            let dest = dest.to_string_lossy().to_string();
+            let back = dir.path().join("back").to_string_lossy().to_string();
+            assert!(rename_or_move(&dest, &back, cross_device).is_err());
+            assert!(Path::new(&dest).join("sub/a.md").exists());
+            assert!(!Path::new(&back).exists());
+        }
+    }
+
+    #[test]
+    fn test_create_unique_file_and_directory_pick_free_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        fs::write(dir.path().join("Untitled 1.md"), "taken").unwrap();
+        let claim = |path: &str| create_file_exclusive(path, "");
+
+        let first = create_unique_file(&root, "Untitled", ".md", claim).unwrap();
+        let second = create_unique_file(&root, "Untitled", "md", claim).unwrap();
+        assert_eq!(first, dir.path().join("Untitled.md").to_string_lossy());
+        assert_eq!(second, dir.path().join("Untitled 2.md").to_string_lossy());
+        assert_eq!(fs::read_to_string(&second).unwrap(), "");
+
+        create_unique_directory(&root, "Untitled").unwrap();
+        let folder = create_unique_directory(&root, "Untitled").unwrap();
+        assert_eq!(folder, dir.path().join("Untitled 1").to_string_lossy());
+        assert!(Path::new(&folder).is_dir());
+        assert!(create_unique_file(&root, "../Untitled", "md", claim).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_created_with_requested_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("journal.md");
+        let created = dir.path().join("created.md");
+        let path = journal.to_string_lossy().to_string();
+        let mode = |file: &Path| fs::metadata(file).unwrap().permissions().mode() & 0o777;
+
+        write_file_with_mode(&path, "private", Some(0o600)).unwrap();
+        assert_eq!(mode(&journal), 0o600);
+        assert_eq!(fs::read_to_string(&journal).unwrap(), "private");
+        create_file_exclusive_with_mode(&created.to_string_lossy(), "", Some(0o640)).unwrap();
+        assert_eq!(mode(&created), 0o640);
+
+        set_permissions(&path, 0o644).unwrap();
+        assert_eq!(mode(&journal), 0o644);
+        assert!(set_permissions(&dir.path().join("missing").to_string_lossy(), 0o600).is_err());
+    }
+
+    #[test]
+    fn test_read_directory_natural_sort() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["item2.md", "item10.md", "item1.md"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let options = DirectoryOptions {
+            sort: SortBy::Natural,
+            ..Default::default()
+        };
+
+        let files = read_directory(&dir.path().to_string_lossy(), &options).unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+
+        assert_eq!(names, vec!["item1.md", "item2.md", "item10.md"]);
+    }
+
+    #[test]
+    fn test_read_directory_sort_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("folder")).unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, content, age) in [
+            ("b.md", "12", 30),
+            ("A.md", "1", 10),
+            ("c.md", "12345", 20),
+            ("d.md", "12", 20),
+        ] {
+            let mut file = fs::File::create(dir.path().join(name)).unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            let modified = now - std::time::Duration::from_secs(age);
+            file.set_modified(modified).unwrap();
+        }
+        let root = dir.path().to_string_lossy().to_string();
+        let names = |sort: SortBy, descending: bool, dirs_first: bool| -> Vec<String> {
+            let options = DirectoryOptions {
+                sort,
+                descending,
+                dirs_first,
+                ..Default::default()
+            };
+            read_directory(&root, &options)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+
+        assert_eq!(
+            names(SortBy::Name, false, true),
+            ["folder", "A.md", "b.md", "c.md", "d.md"]
+        );
+        assert_eq!(
+            names(SortBy::Name, true, true),
+            ["folder", "d.md", "c.md", "b.md", "A.md"]
+        );
+        assert_eq!(
+            names(SortBy::Name, false, false),
+            ["A.md", "b.md", "c.md", "d.md", "folder"]
+        );
+        // Equal sizes and times fall back to ascending name either way
+        assert_eq!(
+            names(SortBy::Size, false, true),
+            ["folder", "A.md", "b.md", "d.md", "c.md"]
+        );
+        assert_eq!(
+            names(SortBy::Size, true, false),
+            ["c.md", "b.md", "d.md", "A.md", "folder"]
+        );
+        assert_eq!(
+            names(SortBy::Modified, false, true),
+            ["folder", "b.md", "c.md", "d.md", "A.md"]
+        );
+        assert_eq!(
+            names(SortBy::Modified, true, true),
+            ["folder", "A.md", "c.md", "d.md", "b.md"]
+        );
+    }
+
+    #[test]
+    fn test_read_directory_listing_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+        let options = DirectoryOptions::default();
+
+        let listing = read_directory_listing(&path, &options).unwrap();
+        assert!(listing.is_empty);
+        assert_eq!(listing.total, 0);
+        assert_eq!(listing.path, path);
+
+        fs::write(dir.path().join("a.md"), "").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let listing = read_directory_listing(&path, &options).unwrap();
+        assert!(!listing.is_empty);
+        assert_eq!(listing.total, 2);
+        assert_eq!(listing.items[0].name, "sub");
+
+        let missing = dir.path().join("missing").to_string_lossy().to_string();
+        assert!(matches!(
+            read_directory_listing(&missing, &options),
+            Err(FsError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_file_info_describes_one_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "---\ntitle: Plan\n---\nbody").unwrap();
+        let options = DirectoryOptions {
+            with_titles: true,
+            ..Default::default()
+        };
+
+        let info = file_info(&note.to_string_lossy(), &options).unwrap();
+        assert_eq!(info.name, "note.md");
+        assert!(info.is_markdown && !info.is_directory && !info.is_symlink);
+        assert_eq!(info.size, fs::metadata(&note).unwrap().len());
+        assert_eq!(info.title.as_deref(), Some("Plan"));
+        assert!(info.modified.is_some());
+        let folder = file_info(&dir.path().to_string_lossy(), &options).unwrap();
+        assert!(folder.is_directory);
+        assert_eq!(folder.size, 0);
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.md");
+            std::os::unix::fs::symlink(&note, &link).unwrap();
+            let info = file_info(&link.to_string_lossy(), &options).unwrap();
+            assert!(info.is_symlink && info.is_markdown);
+            assert_eq!(
+                info.symlink_target,
+                Some(note.to_string_lossy().to_string())
+            );
+        }
+
+        let missing = dir.path().join("missing.md").to_string_lossy().to_string();
+        assert_eq!(
+            file_info(&missing, &options).unwrap_err(),
+            FsError::NotFound {
+                message: format!("Path does not exist: {}", missing)
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_directory_filtered_keeps_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("a.md"), "").unwrap();
+        fs::write(dir.path().join("b.txt"), "").unwrap();
+        fs::write(dir.path().join("c.png"), "").unwrap();
+        let root = dir.path().to_string_lossy();
+        let names = |kinds: &[FileKind]| -> Vec<String> {
+            read_directory_filtered(&root, kinds, &DirectoryOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+
+        assert_eq!(names(&[FileKind::Markdown]), ["notes", "a.md"]);
+        assert_eq!(
+            names(&[FileKind::Markdown, FileKind::Image]),
+            ["notes", "a.md", "c.png"]
+        );
+        assert_eq!(names(&[]), ["notes"]);
+    }
+
+    #[test]
+    fn test_read_directory_flags_frontmatter() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("meta.md"), "---\ntitle: Meta\n---\nbody").unwrap();
+        fs::write(dir.path().join("plain.md"), "# Plain\n---\n").unwrap();
+        fs::write(dir.path().join("data.yml"), "---\n").unwrap();
+        let root = dir.path().to_string_lossy();
+        let flags = |options: &DirectoryOptions| -> Vec<Option<bool>> {
+            read_directory(&root, options)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.has_frontmatter)
+                .collect()
+        };
+
+        assert_eq!(flags(&DirectoryOptions::default()), vec![None, None, None]);
+        let options = DirectoryOptions {
+            check_frontmatter: true,
+            ..Default::default()
+        };
+        assert_eq!(flags(&options), vec![None, Some(true), Some(false)]);
+    }
+
+    #[test]
+    fn test_read_directory_with_titles() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("2024-01-05.md"), "# Kickoff Meeting\n").unwrap();
+        fs::write(dir.path().join("image.png"), "").unwrap();
+        let root = dir.path().to_string_lossy();
+        let titles = |options: &DirectoryOptions| -> Vec<Option<String>> {
+            read_directory(&root, options)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.title)
+                .collect()
+        };
+
+        assert_eq!(titles(&DirectoryOptions::default()), vec![None, None]);
+        let options = DirectoryOptions {
+            with_titles: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            titles(&options),
+            vec![Some("Kickoff Meeting".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn test_read_directory_reports_size_and_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("folder")).unwrap();
+        fs::write(dir.path().join("note.md"), "12345").unwrap();
+        let before = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let items = read_directory(&dir.path().to_string_lossy(), &DirectoryOptions::default())
+            .unwrap();
+
+        assert_eq!(items[0].size, 0);
+        assert_eq!(items[1].size, 5);
+        let modified = items[1].modified.unwrap();
+        // Allow for coarse filesystem timestamps
+        assert!(modified.abs_diff(before) < 10_000);
+    }
+
+    #[test]
+    fn test_read_directory_hides_dotfiles_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/HEAD"), "ref").unwrap();
+        fs::write(dir.path().join(".DS_Store"), "").unwrap();
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("b.md"), "").unwrap();
+        fs::write(dir.path().join("a.md"), "").unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let names = |options: &DirectoryOptions| -> Vec<String> {
+            read_directory(&root, options)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+
+        assert_eq!(
+            names(&DirectoryOptions::default()),
+            ["notes", "a.md", "b.md"]
+        );
+        let options = DirectoryOptions {
+            show_hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&options),
+            [".git", "notes", ".DS_Store", "a.md", "b.md"]
+        );
+        // A hidden directory opened directly still lists its contents
+        let git = dir.path().join(".git").to_string_lossy().to_string();
+        let items = read_directory(&git, &DirectoryOptions::default()).unwrap();
+        assert_eq!(items[0].name, "HEAD");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_directory_reports_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("a.md"), "hello").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("notes"), dir.path().join("linked")).unwrap();
+        std::os::unix::fs::symlink("gone.md", dir.path().join("broken.md")).unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+
+        let items = read_directory(&root, &DirectoryOptions::default()).unwrap();
+        let item = |name: &str| items.iter().find(|item| item.name == name).unwrap();
+
+        let linked = item("linked");
+        assert!(linked.is_symlink && linked.is_directory && !linked.is_broken_symlink);
+        assert_eq!(
+            linked.symlink_target.as_deref(),
+            Some(dir.path().join("notes").to_string_lossy().as_ref())
+        );
+        let broken = item("broken.md");
+        assert!(broken.is_symlink && broken.is_broken_symlink);
+        assert!(!broken.is_directory && !broken.is_markdown);
+        assert_eq!(broken.symlink_target.as_deref(), Some("gone.md"));
+        assert!(!item("a.md").is_symlink);
+    }
+
+    #[test]
+    fn test_read_directory_tree_respects_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/deep.md"), "").unwrap();
+        fs::write(dir.path().join("top.md"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a/loop")).unwrap();
+        let root = dir.path().to_string_lossy();
+
+        let tree = read_directory_tree(&root, None, false).unwrap();
+        assert_eq!(tree.len(), 2);
+        let a = &tree[0];
+        let b = a.children.iter().find(|n| n.item.name == "b").unwrap();
+        assert_eq!(b.children[0].item.name, "deep.md");
+
+        let shallow = read_directory_tree(&root, Some(2), false).unwrap();
+        let b = shallow[0].children.iter().find(|n| n.item.name == "b").unwrap();
+        assert!(b.children.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walks_follow_symlinks_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("notes/a.md"), "1234").unwrap();
+        // A link back to the root: following it must not loop
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("notes/loop")).unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        fs::write(shared.path().join("b.md"), "12").unwrap();
+        std::os::unix::fs::symlink(shared.path(), dir.path().join("shared")).unwrap();
+        let root = dir.path().to_string_lossy();
+
+        let stats = directory_stats(&root, false).unwrap();
+        assert_eq!((stats.file_count, stats.total_bytes), (1, 4));
+        let stats = directory_stats(&root, true).unwrap();
+        assert_eq!((stats.file_count, stats.total_bytes), (2, 6));
+        assert_eq!(stats.dir_count, 1);
+
+        let tree = read_directory_tree(&root, None, false).unwrap();
+        let node = |tree: &[FileTreeNode], name: &str| {
+            tree.iter().find(|n| n.item.name == name).unwrap().clone()
+        };
+        assert!(node(&tree, "shared").item.is_symlink);
+        assert!(node(&tree, "shared").children.is_empty());
+        let notes = node(&tree, "notes");
+        assert!(node(&notes.children, "loop").children.is_empty());
+        let tree = read_directory_tree(&root, None, true).unwrap();
+        assert_eq!(node(&tree, "shared").children[0].item.name, "b.md");
+        let notes = node(&tree, "notes");
+        assert!(node(&notes.children, "loop").children.is_empty());
+    }
+
+    #[test]
+    fn test_directory_stats_totals_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/deep.md"), "12345").unwrap();
+        fs::write(dir.path().join("top.md"), "123").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a/loop")).unwrap();
+
+        let stats = directory_stats(&dir.path().to_string_lossy(), false).unwrap();
+        assert_eq!(stats.total_bytes, 8);
+        assert_eq!(stats.file_count, 2);
+        assert_eq!(stats.dir_count, 2);
+        assert!(stats.skipped.is_empty());
+
+        let file = dir.path().join("top.md");
+        assert!(matches!(
+            directory_stats(&file.to_string_lossy(), false),
+            Err(FsError::NotADirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn test_read_file_with_encoding_falls_back_from_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let latin1 = dir.path().join("latin1.md");
+        let utf8 = dir.path().join("utf8.md");
+        fs::write(&latin1, b"caf\xe9").unwrap();
+        fs::write(&utf8, "café").unwrap();
+
+        let decoded = read_file_with_encoding(&latin1.to_string_lossy()).unwrap();
+        assert_eq!(decoded.content, "café");
+        assert_eq!(decoded.encoding, "windows-1252");
+        // The strict reader still refuses it
+        assert!(read_file(&latin1.to_string_lossy()).is_err());
+
+        let decoded = read_file_with_encoding(&utf8.to_string_lossy()).unwrap();
+        assert_eq!(decoded.content, "café");
+        assert_eq!(decoded.encoding, "UTF-8");
+    }
+
+    #[test]
+    fn test_write_file_with_encoding_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let latin1 = dir.path().join("latin1.md");
+        let path = latin1.to_string_lossy().to_string();
+        fs::write(&latin1, b"caf\xe9").unwrap();
+
+        let decoded = read_file_with_encoding(&path).unwrap();
+        let edited = format!("{} cr\u{e8}me\n", decoded.content);
+        write_file_with_encoding(&path, &edited, &decoded.encoding).unwrap();
+        assert_eq!(fs::read(&latin1).unwrap(), b"caf\xe9 cr\xe8me\n");
+        assert_eq!(read_file_with_encoding(&path).unwrap().content, edited);
+
+        let error = write_file_with_encoding(&path, "ok\n\u{3b1}", "latin1").unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Character '\u{3b1}' (U+03B1) on line 2 can't be saved as windows-1252"
+        );
+        assert_eq!(fs::read(&latin1).unwrap(), b"caf\xe9 cr\xe8me\n");
+        assert!(matches!(
+            write_file_with_encoding(&path, "x", "klingon"),
+            Err(FsError::Encoding { .. })
+        ));
+
+        write_file_with_encoding(&path, "\u{3b1}", "utf-16le").unwrap();
+        let decoded = read_file_with_encoding(&path).unwrap();
+        assert_eq!(decoded.content, "\u{3b1}");
+        assert_eq!(decoded.encoding, "UTF-16LE");
+    }
+
+    #[test]
+    fn test_relative_path_within_and_outside_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("vault");
+        fs::create_dir_all(root.join("notes/sub")).unwrap();
+        fs::write(root.join("notes/sub/file.md"), "").unwrap();
+        let base = root.to_string_lossy().to_string();
+        let path = |relative: &str| dir.path().join(relative).to_string_lossy().to_string();
+
+        assert_eq!(
+            relative_path(&base, &path("vault/notes/sub/file.md")),
+            "notes/sub/file.md"
+        );
+        // Targets needn't exist, and `..` is resolved first
+        assert_eq!(
+            relative_path(&base, &path("vault/notes/sub/../new.md")),
+            "notes/new.md"
+        );
+        assert_eq!(relative_path(&base, &base), ".");
+        let outside = canonical_path(&path("other.md"));
+        assert_eq!(
+            relative_path(&base, &path("vault/../other.md")),
+            outside.to_string_lossy()
+        );
+        // A sibling sharing the base's name as a prefix is still outside
+        let sibling = canonical_path(&path("vault-old/a.md"));
+        assert_eq!(
+            relative_path(&base, &path("vault-old/a.md")),
+            sibling.to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn test_parent_directory_and_ancestors() {
+        assert_eq!(
+            parent_directory("/vault/notes/today.md").unwrap(),
+            "/vault/notes"
+        );
+        assert_eq!(parent_directory("/vault/notes/").unwrap(), "/vault");
+        assert_eq!(parent_directory("/vault/notes/../inbox").unwrap(), "/vault");
+        assert_eq!(parent_directory("/vault").unwrap(), "/");
+        assert_eq!(parent_directory("/"), None);
+        assert_eq!(parent_directory("notes/today.md").unwrap(), "notes");
+        assert_eq!(parent_directory("today.md"), None);
+
+        assert_eq!(
+            path_ancestors("/vault/notes/./today.md"),
+            vec!["/", "/vault", "/vault/notes", "/vault/notes/today.md"]
+        );
+        assert_eq!(path_ancestors("/"), vec!["/"]);
+        assert_eq!(path_ancestors("notes/sub"), vec!["notes", "notes/sub"]);
+
+        #[cfg(windows)]
+        {
+            assert_eq!(parent_directory(r"C:\vault").unwrap(), r"C:\");
+            assert_eq!(parent_directory(r"C:\"), None);
+            assert_eq!(
+                parent_directory(r"\\server\share\notes").unwrap(),
+                r"\\server\share\"
+            );
+            assert_eq!(parent_directory(r"\\server\share\"), None);
+            assert_eq!(
+                path_ancestors(r"C:\vault\notes"),
+                vec![r"C:\", r"C:\vault", r"C:\vault\notes"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_filename_rules() {
+        let reason = |name: &str, portable: bool| validate_filename(name, portable).reason;
+
+        assert_eq!(
+            validate_filename("Meeting notes.md", true),
+            NameValidation {
+                valid: true,
+                reason: None
+            }
+        );
+        assert!(reason("", false).is_some());
+        assert!(reason("..", false).is_some());
+        assert_eq!(reason("a/b.md", false).unwrap(), "Name cannot contain '/'");
+        assert!(reason(&"x".repeat(MAX_NAME_LENGTH + 1), false).is_some());
+        // Multi-byte names hit the Unix byte limit before the Windows one
+        let accents = "é".repeat(200);
+        assert!(windows_name_problem(&accents).is_none());
+        assert!(reason(&accents, true).is_some());
+
+        // Portable names follow the Windows rules on every platform
+        assert_eq!(reason("what?.md", true).unwrap(), "Name cannot contain '?'");
+        assert_eq!(
+            reason("con.txt", true).unwrap(),
+            "con is a reserved name on Windows"
+        );
+        assert!(reason("console.md", true).is_none());
+        assert!(reason("notes.", true).is_some());
+        assert!(reason("tab\there", true).is_some());
+        assert!(reason("line\nbreak", true).is_some());
+        if !cfg!(windows) {
+            assert!(reason("what?.md", false).is_none());
+            assert!(reason("con.txt", false).is_none());
+        }
+    }
+
+    #[test]
+    fn test_resolve_within_rejects_parent_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(dir.path()).unwrap();
+        let root = base.join("vault");
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        let path = |relative: &str| root.join(relative).to_string_lossy().to_string();
+
+        assert_eq!(
+            resolve_within(&root, &path("notes/../notes/new.md")),
+            Ok(root.join("notes/new.md"))
+        );
+        assert!(resolve_within(&root, &path("../secret.txt")).is_err());
+        assert!(resolve_within(&root, &path("notes/../../secret.txt")).is_err());
+        // Not-yet-existing paths can't climb out either
+        assert!(resolve_within(&root, &path("missing/../../escape.md")).is_err());
+
+        let workspace = WorkspaceState::new();
+        assert_eq!(
+            workspace.check(&path("../secret.txt")),
+            Ok(path("../secret.txt"))
+        );
+        workspace.set_root(Some(&root.to_string_lossy())).unwrap();
+        assert_eq!(
+            workspace.check(&path("notes/../notes/new.md")),
+            Ok(root.join("notes/new.md").to_string_lossy().to_string())
+        );
+        assert_eq!(
+            workspace.check(&path("../secret.txt")),
+            Err(FsError::PermissionDenied {
+                message: format!("Path outside workspace: {}", path("../secret.txt"))
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_within_rejects_symlink_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(dir.path()).unwrap();
+        let root = base.join("vault");
+        fs::create_dir_all(base.join("outside")).unwrap();
+        fs::create_dir(&root).unwrap();
+        fs::write(base.join("outside/secret.md"), "secret").unwrap();
+        std::os::unix::fs::symlink(base.join("outside"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(base.join("outside/gone.md"), root.join("dangling.md")).unwrap();
+        let path = |relative: &str| root.join(relative).to_string_lossy().to_string();
+
+        assert!(resolve_within(&root, &path("link/secret.md")).is_err());
+        assert!(resolve_within(&root, &path("link/new.md")).is_err());
+        assert!(resolve_within(&root, &path("dangling.md")).is_err());
+
+        // A link inside the workspace is checked against its target but
+        // handed back as the link itself
+        fs::write(root.join("note.md"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("note.md"), root.join("alias.md")).unwrap();
+        let workspace = WorkspaceState::new();
+        workspace.set_root(Some(&root.to_string_lossy())).unwrap();
+        assert_eq!(workspace.check(&path("alias.md")), Ok(path("alias.md")));
+        assert!(workspace.check(&path("link/secret.md")).is_err());
+        assert!(workspace.check(&path("dangling.md")).is_err());
+
+        // A link left dangling by deleting its note can still be deleted
+        fs::remove_file(root.join("note.md")).unwrap();
+        let alias = workspace.check(&path("alias.md")).unwrap();
+        assert_eq!(alias, path("alias.md"));
+        delete_path(&alias).unwrap();
+        assert!(fs::symlink_metadata(root.join("alias.md")).is_err());
+    }
+
+    #[test]
+    fn test_read_file_chunk_snaps_to_char_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.md");
+        // "é" is two bytes, at offsets 2 and 3
+        fs::write(&path, "abé cd").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        assert_eq!(file_size(&path), Ok(7));
+        let first = read_file_chunk(&path, 0, 3).unwrap();
+        assert_eq!(first.content, "abé");
+        assert_eq!((first.start, first.end, first.eof), (0, 4, false));
+
+        let rest = read_file_chunk(&path, first.end, 100).unwrap();
+        assert_eq!(rest.content, " cd");
+        assert!(rest.eof);
+
+        // Starting mid-character skips to the next whole one
+        let mid = read_file_chunk(&path, 3, 3).unwrap();
+        assert_eq!(mid.content, " c");
+        assert_eq!((mid.start, mid.end), (4, 6));
+
+        // A length past the end reads the rest without overflowing
+        let all = read_file_chunk(&path, 0, usize::MAX).unwrap();
+        assert_eq!(all.content, "abé cd");
+        assert_eq!((all.end, all.eof), (7, true));
+        let past = read_file_chunk(&path, 10, usize::MAX).unwrap();
+        assert_eq!(past.content, "");
+        assert!(past.eof);
+    }
+
+    #[test]
+    fn test_read_file_head_stops_at_lines_or_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(&path, "one\r\ntwo\nthré\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let head = read_file_head(&path, 2, 1024).unwrap();
+        assert_eq!(head.content, "one\r\ntwo\n");
+        assert!(head.truncated);
+        let whole = read_file_head(&path, 10, 1024).unwrap();
+        assert_eq!(whole.content, "one\r\ntwo\nthré\n");
+        assert!(!whole.truncated);
+
+        // The cap falls inside "é", which is left out rather than mangled
+        let capped = read_file_head(&path, 10, 13).unwrap();
+        assert_eq!(capped.content, "one\r\ntwo\nthr");
+        assert!(capped.truncated);
+    }
+
+    #[test]
+    fn test_write_file_checked_detects_external_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "opened").unwrap();
+        let path = note.to_string_lossy().to_string();
+        let opened = modified_millis(&fs::metadata(&note).unwrap()).unwrap();
+
+        let saved = write_file_checked(&path, "saved", Some(opened))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "saved");
+
+        // Another program rewrites the file with a later timestamp
+        fs::write(&note, "external").unwrap();
+        let later = std::time::UNIX_EPOCH + std::time::Duration::from_millis(saved + 5_000);
+        fs::File::options()
+            .write(true)
+            .open(&note)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        match write_file_checked(&path, "clobber", Some(saved)) {
+            Err(SaveError::Conflict {
+                current_content, ..
+            }) => assert_eq!(current_content.as_deref(), Some("external")),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(&note).unwrap(), "external");
+    }
+
+    #[test]
+    fn test_write_file_checked_creates_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.md").to_string_lossy().to_string();
+
+        assert!(write_file_checked(&path, "first", None).unwrap().is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+        // Once it exists, saving without a known mtime is a conflict
+        assert!(matches!(
+            write_file_checked(&path, "second", None),
+            Err(SaveError::Conflict { .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+    }
+
+    #[test]
+    fn test_apply_edits_aborts_on_invalid_edit() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "old a").unwrap();
+        fs::write(&b, "old b").unwrap();
+        let edit = |path: &Path, content: &str| FileEdit {
+            path: path.to_string_lossy().to_string(),
+            content: content.to_string(),
+        };
+
+        let result = apply_edits(vec![
+            edit(&a, "new a"),
+            edit(&b, "new b"),
+            edit(&dir.path().join("missing/c.md"), "new c"),
+        ]);
+
+        assert!(!result.committed);
+        assert!(result.error.is_some());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "old b");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let result = apply_edits(vec![edit(&a, "new a"), edit(&b, "new b")]);
+        assert!(result.committed);
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+    }
+
+    #[test]
+    fn test_line_endings_with_mixed_input() {
+        let mixed = "one\r\ntwo\r\nthree\nfour";
+        assert_eq!(detect_line_ending(mixed), LineEnding::Crlf);
+        assert_eq!(detect_line_ending("a\nb\r\n"), LineEnding::Lf);
+        assert_eq!(
+            normalize_line_endings(mixed, LineEnding::Lf),
+            "one\ntwo\nthree\nfour"
+        );
+        assert_eq!(
+            normalize_line_endings(mixed, LineEnding::Crlf),
+            "one\r\ntwo\r\nthree\r\nfour"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md").to_string_lossy().to_string();
+        write_file_with_eol(&path, mixed, EolMode::Preserve).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), mixed);
+        write_file_with_eol(&path, mixed, EolMode::Crlf).unwrap();
+        let read = read_file_with_eol(&path).unwrap();
+        assert_eq!(read.content, "one\r\ntwo\r\nthree\r\nfour");
+        assert_eq!(read.line_ending, LineEnding::Crlf);
+    }
+
+    #[test]
+    fn test_write_file_keeps_original_when_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(&path, "original").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        write_file(&path, "updated").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "updated");
+
+        // Every save stages into its own exclusively created temp file
+        let (first, _) = create_temp_for(Path::new(&path)).unwrap();
+        let (second, _) = create_temp_for(Path::new(&path)).unwrap();
+        assert_ne!(first, second);
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+
+        // A failed rename removes the temporary file it wrote
+        let blocked = dir.path().join("folder");
+        fs::create_dir(&blocked).unwrap();
+        fs::write(blocked.join("child.md"), "").unwrap();
+        assert!(write_file(&blocked.to_string_lossy(), "lost").is_err());
+        let names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert!(names.iter().all(|name| !name.ends_with(".tmp")));
+    }
+
+    #[test]
+    fn test_write_file_keeps_permissions_and_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(&path, "original").unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+            write_file(&path_str, "updated").unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+
+            // Saving through a link rewrites the target and keeps the link
+            let link = dir.path().join("link.md");
+            std::os::unix::fs::symlink("note.md", &link).unwrap();
+            write_file(&link.to_string_lossy(), "through link").unwrap();
+            let link_type = fs::symlink_metadata(&link).unwrap().file_type();
+            assert!(link_type.is_symlink());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "through link");
+        }
+
+        set_readonly(&path_str, true).unwrap();
+        assert!(matches!(
+            write_file(&path_str, "refused"),
+            Err(FsError::PermissionDenied { .. })
+        ));
+        set_readonly(&path_str, false).unwrap();
+        assert_ne!(fs::read_to_string(&path).unwrap(), "refused");
+    }
+
+    #[test]
+    fn test_truncate_file_keeps_whole_tail_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.md");
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "first line\nsecond line\nthird\n").unwrap();
+
+        // 14 bytes from the end lands mid-way through "second line"
+        let kept = truncate_file(&path, 14, KeepEnd::Tail).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        assert_eq!(kept, 6);
+    }
+
+    #[test]
+    fn test_directory_delete_preview_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub/nested")).unwrap();
+        fs::write(dir.path().join("a.md"), "12345").unwrap();
+        fs::write(dir.path().join("sub/b.md"), "123").unwrap();
+        fs::write(dir.path().join("sub/nested/c.png"), "12").unwrap();
+        // A linked folder is one entry, not walked into
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("link")).unwrap();
+        let links = if cfg!(unix) { 1 } else { 0 };
+        let link_bytes = fs::symlink_metadata(dir.path().join("link")).map_or(0, |m| m.len());
+
+        let preview = directory_delete_preview(&dir.path().to_string_lossy()).unwrap();
+
+        assert_eq!(preview.file_count, 3 + links);
+        assert_eq!(preview.directory_count, 2);
+        assert_eq!(preview.total_bytes, 10 + link_bytes);
+        assert_eq!(preview.entries.len(), 5 + links);
+        assert!(preview.skipped.is_empty());
+        assert!(!preview.truncated);
+        assert!(dir.path().join("a.md").exists());
+
+        let note = dir.path().join("a.md").to_string_lossy().to_string();
+        let preview = delete_preview(&note).unwrap();
+        assert_eq!((preview.file_count, preview.total_bytes), (1, 5));
+        assert_eq!(preview.entries, vec![note]);
+        let missing = dir.path().join("gone.md").to_string_lossy().to_string();
+        assert!(matches!(
+            delete_preview(&missing),
+            Err(FsError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_hash_paths_is_content_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("a.md"), "abc").unwrap();
+        fs::write(path("b.md"), "abc").unwrap();
+
+        let results = hash_paths(&[path("a.md"), path("b.md"), path("gone.md")], file_hash);
+
+        assert_eq!(
+            results[0].hash.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(results[1].hash, results[0].hash);
+        assert!(results[2].hash.is_none() && results[2].error.is_some());
+    }
+
+    #[test]
+    fn test_directory_signature_changes_with_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+        fs::write(dir.path().join("a.md"), "a").unwrap();
+
+        let first = directory_signature(&path).unwrap();
+        assert_eq!(directory_signature(&path).unwrap(), first);
+
+        fs::write(dir.path().join("b.md"), "b").unwrap();
+        assert_ne!(directory_signature(&path).unwrap(), first);
+    }
+
+    #[test]
+    fn test_merge_directories_renames_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let dest = dir.path().join("dest");
+        fs::create_dir_all(source.join("shared")).unwrap();
+        fs::create_dir_all(dest.join("shared")).unwrap();
+        fs::write(source.join("note.md"), "from source").unwrap();
+        fs::write(source.join("shared/only-source.md"), "").unwrap();
+        fs::write(dest.join("note.md"), "from dest").unwrap();
+
+        let outcomes = merge_directories(
+            &source.to_string_lossy(),
+            &dest.to_string_lossy(),
+            ConflictPolicy::Rename,
+        )
+        .unwrap();
+
+        let actions: Vec<MergeAction> = outcomes.iter().map(|o| o.action).collect();
+        assert_eq!(actions, vec![MergeAction::Renamed, MergeAction::Moved]);
+        assert_eq!(fs::read_to_string(dest.join("note.md")).unwrap(), "from dest");
+        assert_eq!(
+            fs::read_to_string(dest.join("note (1).md")).unwrap(),
+            "from source"
+        );
+        assert!(dest.join("shared/only-source.md").exists());
+        assert!(!source.exists());
+
+        // Merging into a descendant is refused
+        let nested = dest.join("shared");
+        assert!(merge_directories(
+            &dest.to_string_lossy(),
+            &nested.to_string_lossy(),
+            ConflictPolicy::Skip
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_directory_uses_configured_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("page.mdx"), "# Page").unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let state = crate::config::VaultState::new();
+        let page_is_markdown = |state: &crate::config::VaultState| {
+            let options = DirectoryOptions {
+                markdown_extensions: Some(state.config().markdown_extensions),
+                ..Default::default()
+            };
+            read_directory(&root, &options).unwrap()[0].is_markdown
+        };
+
+        assert!(!page_is_markdown(&state));
+        state
+            .set_markdown_extensions(vec![".MDX".to_string(), "md".to_string()])
+            .unwrap();
+        assert!(page_is_markdown(&state));
+        assert_eq!(state.config().markdown_extensions, ["mdx", "md"]);
+    }
+
+    #[test]
+    fn test_set_readonly_is_reported_in_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("locked.md");
+        fs::write(&note, "# Locked").unwrap();
+        let root = dir.path().to_string_lossy();
+        let readonly = || read_directory(&root, &DirectoryOptions::default()).unwrap()[0].readonly;
+        assert!(!readonly());
+
+        set_readonly(&note.to_string_lossy(), true).unwrap();
+        assert!(readonly());
+        set_readonly(&note.to_string_lossy(), false).unwrap();
+        assert!(!readonly());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&note).unwrap().permissions().mode();
+            assert_eq!(mode & 0o200, 0o200);
+        }
+    }
+
+    #[test]
+    fn test_file_kind_by_extension() {
+        let cases = [
+            ("note.md", FileKind::Markdown),
+            ("README.MARKDOWN", FileKind::Markdown),
+            ("todo.txt", FileKind::Text),
+            ("data.csv", FileKind::Text),
+            ("photo.JPG", FileKind::Image),
+            ("diagram.svg", FileKind::Image),
+            ("paper.pdf", FileKind::Pdf),
+            ("main.rs", FileKind::Code),
+            ("config.json", FileKind::Code),
+            ("archive.tar.gz", FileKind::Binary),
+            ("Makefile", FileKind::Other),
+            (".gitignore", FileKind::Other),
+            ("notes.xyz", FileKind::Other),
+        ];
+        for (name, kind) in cases {
+            assert_eq!(file_kind(name), kind, "{}", name);
+        }
+        assert!(FileKind::Code.is_text());
+        assert!(!FileKind::Image.is_text());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("assets.md")).unwrap();
+        fs::write(dir.path().join("config.json"), "{}").unwrap();
+        let items =
+            read_directory(&dir.path().to_string_lossy(), &DirectoryOptions::default()).unwrap();
+        assert_eq!((items[0].kind, items[0].is_text), (FileKind::Other, false));
+        assert_eq!((items[1].kind, items[1].is_text), (FileKind::Code, true));
+    }
+
+    #[test]
+    fn test_reclassify_after_adding_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = crate::config::VaultState::new();
+        state.open(&dir.path().to_string_lossy()).unwrap();
+        let items = vec![FileItem {
+            name: "log.txt".to_string(),
+            path: dir.path().join("log.txt").to_string_lossy().to_string(),
+            is_directory: false,
+            is_markdown: false,
+            kind: FileKind::Text,
+            is_text: true,
+            size: 0,
+            modified: None,
+            has_frontmatter: None,
+            title: None,
+            is_symlink: false,
+            symlink_target: None,
+            is_broken_symlink: false,
+            readonly: false,
+        }];
+
+        let extensions = state.config().markdown_extensions;
+        assert!(!reclassify_items(items.clone(), &extensions)[0].is_markdown);
+
+        let mut extensions = extensions;
+        extensions.push("txt".to_string());
+        state
+            .update(serde_json::json!({ "markdown_extensions": extensions }))
+            .unwrap();
+        let items = reclassify_items(items, &state.config().markdown_extensions);
+        assert!(items[0].is_markdown);
+        assert_eq!(items[0].kind, FileKind::Markdown);
+    }
+
+    #[test]
+    fn test_trash_preview_reports_type_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("folder");
+        fs::create_dir_all(folder.join("nested")).unwrap();
+        fs::write(folder.join("a.md"), "12345").unwrap();
+        fs::write(folder.join("nested/b.md"), "123").unwrap();
+
+        let preview = trash_preview(&folder.to_string_lossy()).unwrap();
+        assert!(preview.is_directory);
+        assert_eq!(preview.total_bytes, 8);
+        assert_eq!(preview.child_count, Some(3));
+
+        let preview = trash_preview(&folder.join("a.md").to_string_lossy()).unwrap();
+        assert!(!preview.is_directory);
+        assert_eq!(preview.total_bytes, 5);
+        assert_eq!(preview.child_count, None);
+
+        assert!(trash_preview(&dir.path().join("missing").to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn test_canonical_path_resolves_spellings_of_one_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let base = dir.path().to_string_lossy();
+
+        let messy = format!("{}/notes/./../notes//draft.md", base);
+        assert_eq!(canonical_path(&messy), root.join("notes/draft.md"));
+        let missing = format!("{}/new/../new/sub/file.md", base);
+        assert_eq!(canonical_path(&missing), root.join("new/sub/file.md"));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("notes"), root.join("link")).unwrap();
+            let linked = format!("{}/link/draft.md", base);
+            assert_eq!(canonical_path(&linked), root.join("notes/draft.md"));
+        }
+
+        // A directory can't be copied into itself however it is spelled
+        let src = format!("{}/notes", base);
+        let dest = format!("{}/notes/../notes/inner", base);
+        assert!(matches!(
+            copy_path(&src, &dest),
+            Err(FsError::InvalidPath { .. })
+        ));
+    }
+
+    #[test]
+    fn test_copy_path_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("templates");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("daily.md"), "# Daily").unwrap();
+        fs::write(src.join("nested/weekly.md"), "# Weekly").unwrap();
+        let src = src.to_string_lossy().to_string();
+
+        let file_copy = dir.path().join("copies/today.md");
+        copy_path(&format!("{}/daily.md", src), &file_copy.to_string_lossy()).unwrap();
+        assert_eq!(fs::read_to_string(&file_copy).unwrap(), "# Daily");
+
+        let dir_copy = dir.path().join("backup").to_string_lossy().to_string();
+        copy_path(&src, &dir_copy).unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/nested/weekly.md", dir_copy)).unwrap(),
+            "# Weekly"
+        );
+
+        let error = copy_path(&src, &dir_copy).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Path already exists: {}", dir_copy)
+        );
+    }
+
+    #[test]
+    fn test_tracked_copy_and_delete_report_progress_and_cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("big");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        for i in 0..150 {
+            fs::write(src.join(format!("sub/{}.md", i)), "x").unwrap();
+        }
+        let src = src.to_string_lossy().to_string();
+        let dest = dir.path().join("copy").to_string_lossy().to_string();
+
+        let mut reports = Vec::new();
+        let never = AtomicBool::new(false);
+        copy_path_tracked(&src, &dest, &never, |p| reports.push(p.clone())).unwrap();
+        let counts: Vec<_> = reports.iter().map(|p| (p.processed, p.total)).collect();
+        assert_eq!(counts, vec![(100, 152), (152, 152)]);
+        assert_eq!(reports[1].current_path, src);
+
+        // Cancelling from the first report stops the delete part-way
+        let cancel = AtomicBool::new(false);
+        let mut removed = Vec::new();
+        let result = delete_directory_tracked(
+            &dest,
+            &cancel,
+            |_| cancel.store(true, AtomicOrdering::SeqCst),
+            |path| removed.push(path.to_path_buf()),
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cancelled after 100 of 152 entries"
+        );
+        assert_eq!(fs::read_dir(format!("{}/sub", dest)).unwrap().count(), 50);
+        assert_eq!(removed.len(), 100);
+        assert!(removed.iter().all(|path| !path.exists()));
+
+        delete_directory_tracked(&dest, &never, |_| {}, |_| {}).unwrap();
+        assert!(!Path::new(&dest).exists());
+    }
+
+    #[test]
+    fn test_move_to_trash_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gone.md");
+        let path = path.to_string_lossy().to_string();
+
+        assert_eq!(
+            move_to_trash(&path).unwrap_err(),
+            FsError::NotFound {
+                message: format!("Path does not exist: {}", path)
+            }
+        );
+    }
+
+    /// Trash stand-in holding items in memory
+    struct StubTrash(Mutex<Vec<TrashedItem>>);
+
+    impl TrashProvider for StubTrash {
+        fn list(&self) -> Result<Vec<TrashedItem>, FsError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn restore(&self, ids: &[String]) -> Result<(), FsError> {
+            let mut items = self.0.lock().unwrap();
+            for item in items.iter().filter(|item| ids.contains(&item.id)) {
+                fs::write(&item.original_path, "restored").unwrap();
+            }
+            items.retain(|item| !ids.contains(&item.id));
+            Ok(())
+        }
+
+        fn purge(&self, ids: &[String]) -> Result<(), FsError> {
+            let mut items = self.0.lock().unwrap();
+            items.retain(|item| !ids.contains(&item.id));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trash_is_scoped_to_the_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir(&vault).unwrap();
+        let item = |id: &str, path: &Path, deleted_at: i64| TrashedItem {
+            id: id.to_string(),
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            original_path: path.to_string_lossy().to_string(),
+            deleted_at,
+        };
+        let trash = StubTrash(Mutex::new(vec![
+            item("old", &vault.join("old.md"), 1),
+            item("elsewhere", &dir.path().join("private.md"), 2),
+            item("new", &vault.join("new.md"), 3),
+            item("taken", &vault.join("taken.md"), 4),
+        ]));
+        fs::write(vault.join("taken.md"), "").unwrap();
+
+        let listed = list_trash(&trash, &vault).unwrap();
+        let ids: Vec<&str> = listed.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["taken", "new", "old"]);
+
+        // Items from outside the vault can't be restored through it
+        assert!(matches!(
+            restore_from_trash(&trash, "elsewhere", &vault),
+            Err(FsError::NotFound { .. })
+        ));
+        assert!(matches!(
+            restore_from_trash(&trash, "taken", &vault),
+            Err(FsError::AlreadyExists { .. })
+        ));
+        let restored = restore_from_trash(&trash, "new", &vault).unwrap();
+        assert_eq!(restored, vault.join("new.md").to_string_lossy());
+        assert!(vault.join("new.md").is_file());
+
+        let purged = empty_trash(&trash, &vault).unwrap();
+        assert_eq!(purged.len(), 2);
+        let left = trash.list().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, "elsewhere");
+    }
+
+    #[test]
+    fn test_batch_operations_report_each_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("a.md"), "a").unwrap();
+        fs::write(path("b.md"), "b").unwrap();
+        fs::create_dir(path("folder")).unwrap();
+        fs::write(path("folder/c.md"), "c").unwrap();
+
+        let renamed = rename_paths(
+            &[
+                (path("a.md"), path("renamed.md")),
+                (path("missing.md"), path("other.md")),
+            ],
+            rename_path,
+        );
+        assert_eq!(
+            renamed.iter().map(|r| r.ok).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert!(renamed[1].error.is_some());
+        assert!(Path::new(&path("renamed.md")).is_file());
+
+        let deleted = delete_paths(
+            &[path("renamed.md"), path("missing.md"), path("folder")],
+            delete_path,
+        );
+        assert_eq!(
+            deleted.iter().map(|r| r.ok).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert_eq!(deleted[1].path, path("missing.md"));
+        assert!(!Path::new(&path("folder")).exists());
+        assert!(Path::new(&path("b.md")).is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_delete_path_removes_links_not_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("safe.md"), "").unwrap();
+        let dir_link = dir.path().join("dir-link");
+        let dangling = dir.path().join("dangling");
+        std::os::unix::fs::symlink(&outside, &dir_link).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("gone.md"), &dangling).unwrap();
+
+        delete_path(&dir_link.to_string_lossy()).unwrap();
+        delete_path(&dangling.to_string_lossy()).unwrap();
+
+        assert!(fs::symlink_metadata(&dir_link).is_err());
+        assert!(fs::symlink_metadata(&dangling).is_err());
+        assert!(outside.join("safe.md").is_file());
+    }
+
+    #[test]
+    fn test_save_files_reports_each_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("a.md"), "a").unwrap();
+        fs::write(path("locked.md"), "locked").unwrap();
+        set_readonly(&path("locked.md"), true).unwrap();
+        let file = |name: &str, content: &str| FileSave {
+            path: path(name),
+            content: content.to_string(),
+        };
+
+        let saved = save_files(
+            &[
+                file("a.md", "saved"),
+                file("gone/c.md", "lost"),
+                file("locked.md", "changed"),
+                file("new.md", "new"),
+            ],
+            |path, content| write_file_atomic(path, content.as_bytes()),
+        );
+        assert_eq!(
+            saved.iter().map(|r| r.ok).collect::<Vec<_>>(),
+            vec![true, false, false, true]
+        );
+        assert!(matches!(saved[1].error, Some(FsError::NotFound { .. })));
+        assert_eq!(saved[2].path, path("locked.md"));
+        assert!(matches!(
+            saved[2].error,
+            Some(FsError::PermissionDenied { .. })
+        ));
+        assert_eq!(fs::read_to_string(path("a.md")).unwrap(), "saved");
+        assert_eq!(fs::read_to_string(path("locked.md")).unwrap(), "locked");
+        assert_eq!(fs::read_to_string(path("new.md")).unwrap(), "new");
     }
 }
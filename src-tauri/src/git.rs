@@ -0,0 +1,343 @@
+use git2::{Delta, DiffOptions, Repository, Sort, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{is_markdown_file, write_file_atomic};
+
+/// How many commits touched a file within a time window
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileChangeFreq {
+    /// Path relative to the repository root, with forward slashes
+    pub path: String,
+    pub commits: usize,
+}
+
+/// An author and how many lines of a file they last touched
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuthorStat {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+}
+
+fn open_repository(repo_path: &str) -> Result<Repository, String> {
+    Repository::open(repo_path).map_err(|e| format!("Failed to open repository: {}", e))
+}
+
+fn repo_relative(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// `file_path` relative to the repository's working tree; absolute paths
+/// must exist inside it, relative ones are taken as already relative
+fn workdir_relative(repo: &Repository, file_path: &str) -> Option<PathBuf> {
+    let workdir = std::fs::canonicalize(repo.workdir()?).ok()?;
+    let file = Path::new(file_path);
+    match std::fs::canonicalize(file) {
+        Ok(absolute) => Some(absolute.strip_prefix(&workdir).ok()?.to_path_buf()),
+        Err(_) if file.is_relative() => Some(file.to_path_buf()),
+        Err(_) => None,
+    }
+}
+
+/// Count the commits from the last `since_days` days touching each file,
+/// most frequently changed first. Merge commits are compared against their
+/// first parent. With `include_unchanged`, every file in `HEAD` is reported,
+/// the untouched ones with a count of 0.
+pub fn change_frequency(
+    repo_path: &str,
+    since_days: u32,
+    include_unchanged: bool,
+) -> Result<Vec<FileChangeFreq>, String> {
+    let repo = open_repository(repo_path)?;
+    let cutoff = chrono::Utc::now().timestamp() - i64::from(since_days) * 24 * 60 * 60;
+
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to walk history: {}", e))?;
+    revwalk
+        .push_head()
+        .map_err(|e| format!("Failed to read HEAD: {}", e))?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|e| format!("Failed to walk history: {}", e))?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for oid in revwalk {
+        let oid = oid.map_err(|e| format!("Failed to walk history: {}", e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to read commit: {}", e))?;
+        if commit.time().seconds() < cutoff {
+            continue;
+        }
+
+        let tree = commit
+            .tree()
+            .map_err(|e| format!("Failed to read commit tree: {}", e))?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(
+                parent
+                    .tree()
+                    .map_err(|e| format!("Failed to read commit tree: {}", e))?,
+            ),
+            Err(_) => None,
+        };
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .map_err(|e| format!("Failed to diff commit: {}", e))?;
+
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path().or(delta.old_file().path()) {
+                *counts.entry(repo_relative(path)).or_default() += 1;
+            }
+        }
+    }
+
+    if include_unchanged {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .and_then(|commit| commit.tree())
+            .map_err(|e| format!("Failed to read HEAD: {}", e))?;
+        head.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let path = format!("{}{}", dir, entry.name().unwrap_or_default());
+                counts.entry(path).or_default();
+            }
+            TreeWalkResult::Ok
+        })
+        .map_err(|e| format!("Failed to read HEAD: {}", e))?;
+    }
+
+    let mut frequencies: Vec<FileChangeFreq> = counts
+        .into_iter()
+        .map(|(path, commits)| FileChangeFreq { path, commits })
+        .collect();
+    frequencies.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.path.cmp(&b.path)));
+    Ok(frequencies)
+}
+
+/// Markdown files added or modified in the working tree since `commit`,
+/// including new untracked notes, as repository-relative paths
+pub fn notes_changed_since(repo_path: &str, commit: &str) -> Result<Vec<String>, String> {
+    let repo = open_repository(repo_path)?;
+    let base = repo
+        .revparse_single(commit)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| format!("Invalid commit reference {}: {}", commit, e))?;
+    let base_tree = base
+        .tree()
+        .map_err(|e| format!("Failed to read commit tree: {}", e))?;
+
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(Some(&base_tree), Some(&mut options))
+        .map_err(|e| format!("Failed to diff against {}: {}", commit, e))?;
+
+    let mut changed: Vec<String> = diff
+        .deltas()
+        .filter(|delta| {
+            matches!(
+                delta.status(),
+                Delta::Added | Delta::Modified | Delta::Renamed | Delta::Untracked
+            )
+        })
+        .filter_map(|delta| delta.new_file().path().map(repo_relative))
+        .filter(|path| is_markdown_file(path))
+        .collect();
+    changed.sort();
+    changed.dedup();
+    Ok(changed)
+}
+
+/// The author owning the most lines of `file_path` as of `HEAD`, by
+/// line-level blame. `None` outside a repository or for untracked files.
+pub fn note_primary_author(repo_path: &str, file_path: &str) -> Option<AuthorStat> {
+    let repo = open_repository(repo_path).ok()?;
+    let relative = workdir_relative(&repo, file_path)?;
+    let blame = repo.blame_file(&relative, None).ok()?;
+
+    let mut authors: Vec<AuthorStat> = Vec::new();
+    for hunk in blame.iter() {
+        let signature = hunk.final_signature();
+        let name = signature.name().unwrap_or_default();
+        let email = signature.email().unwrap_or_default();
+        match authors
+            .iter_mut()
+            .find(|a| a.email == email && a.name == name)
+        {
+            Some(author) => author.lines += hunk.lines_in_hunk(),
+            None => authors.push(AuthorStat {
+                name: name.to_string(),
+                email: email.to_string(),
+                lines: hunk.lines_in_hunk(),
+            }),
+        }
+    }
+    // Ties go to whoever owns the earliest lines
+    authors.into_iter().reduce(|best, author| {
+        if author.lines > best.lines {
+            author
+        } else {
+            best
+        }
+    })
+}
+
+/// Contents of `file_path` as committed at `rev`
+pub fn read_file_at_revision(
+    repo_path: &str,
+    file_path: &str,
+    rev: &str,
+) -> Result<Vec<u8>, String> {
+    let repo = open_repository(repo_path)?;
+    let relative = workdir_relative(&repo, file_path)
+        .ok_or_else(|| format!("File is not in the repository: {}", file_path))?;
+    let tree = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .and_then(|commit| commit.tree())
+        .map_err(|e| format!("Invalid commit reference {}: {}", rev, e))?;
+    let blob = tree
+        .get_path(&relative)
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|e| format!("File not found at {}: {}", rev, e))?;
+    Ok(blob.content().to_vec())
+}
+
+/// Overwrite the working copy of `file_path` with its contents at `rev`,
+/// leaving the change uncommitted for review
+pub fn restore_file_revision(repo_path: &str, file_path: &str, rev: &str) -> Result<(), String> {
+    let content = read_file_at_revision(repo_path, file_path, rev)?;
+    let repo = open_repository(repo_path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "Repository has no working tree".to_string())?;
+    let relative = workdir_relative(&repo, file_path)
+        .ok_or_else(|| format!("File is not in the repository: {}", file_path))?;
+    write_file_atomic(&workdir.join(relative).to_string_lossy(), &content).map_err(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+
+    /// Write `content` to `name` and commit it on top of `HEAD`
+    fn commit_file(repo: &Repository, name: &str, content: &str) {
+        commit_file_as(repo, "Test", name, content);
+    }
+
+    /// `commit_file` with `author` as the commit's author and committer
+    fn commit_file_as(repo: &Repository, author: &str, name: &str, content: &str) {
+        let workdir = repo.workdir().unwrap();
+        fs::write(workdir.join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let email = format!("{}@example.com", author.to_lowercase());
+        let signature = Signature::now(author, &email).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &format!("Update {}", name),
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_change_frequency_ranks_hot_file_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "hot.md", "1");
+        commit_file(&repo, "cold.md", "1");
+        commit_file(&repo, "hot.md", "2");
+        commit_file(&repo, "hot.md", "3");
+
+        let frequencies = change_frequency(&dir.path().to_string_lossy(), 30, false).unwrap();
+
+        assert_eq!(
+            frequencies,
+            vec![
+                FileChangeFreq {
+                    path: "hot.md".to_string(),
+                    commits: 3,
+                },
+                FileChangeFreq {
+                    path: "cold.md".to_string(),
+                    commits: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notes_changed_since_base_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "unchanged.md", "same");
+        commit_file(&repo, "edited.md", "v1");
+        let base = repo.head().unwrap().target().unwrap().to_string();
+        commit_file(&repo, "edited.md", "v2");
+        commit_file(&repo, "image.png", "binary");
+        fs::write(dir.path().join("draft.md"), "untracked").unwrap();
+
+        let root = dir.path().to_string_lossy();
+        let changed = notes_changed_since(&root, &base).unwrap();
+
+        assert_eq!(changed, vec!["draft.md", "edited.md"]);
+        assert!(notes_changed_since(&root, "not-a-commit")
+            .unwrap_err()
+            .starts_with("Invalid commit reference not-a-commit"));
+    }
+
+    #[test]
+    fn test_restore_file_revision_rewrites_working_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "note.md", "first draft");
+        let first = repo.head().unwrap().target().unwrap().to_string();
+        commit_file(&repo, "note.md", "second draft");
+        fs::write(dir.path().join("note.md"), "unsaved edits").unwrap();
+
+        let root = dir.path().to_string_lossy();
+        let note = dir.path().join("note.md");
+        restore_file_revision(&root, &note.to_string_lossy(), &first).unwrap();
+
+        assert_eq!(fs::read_to_string(&note).unwrap(), "first draft");
+        // Restoring doesn't commit
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_ne!(head.id().to_string(), first);
+        assert!(restore_file_revision(&root, "missing.md", &first).is_err());
+    }
+
+    #[test]
+    fn test_primary_author_owns_most_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file_as(&repo, "Ada", "note.md", "one\ntwo\nthree\n");
+        let extended = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        commit_file_as(&repo, "Grace", "note.md", extended);
+        commit_file_as(&repo, "Ada", "note.md", &extended.replace("one", "ONE"));
+
+        let root = dir.path().to_string_lossy();
+        let note = dir.path().join("note.md");
+        let author = note_primary_author(&root, &note.to_string_lossy()).unwrap();
+
+        assert_eq!(author.name, "Grace");
+        assert_eq!(author.lines, 4);
+        fs::write(dir.path().join("draft.md"), "untracked").unwrap();
+        assert_eq!(note_primary_author(&root, "draft.md"), None);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::markdown::extract_code_blocks;
+
+/// A run of code sharing one style
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HighlightToken {
+    pub text: String,
+    /// Foreground color as `#rrggbb`
+    pub color: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+/// A fenced code block split into styled tokens, one list per line
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HighlightedBlock {
+    /// Language as written on the fence
+    pub language: Option<String>,
+    /// Name of the syntax used, `Plain Text` when the language is unknown
+    pub syntax: String,
+    /// 1-based line number of the opening fence
+    pub line: usize,
+    pub lines: Vec<Vec<HighlightToken>>,
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Highlight every fenced code block in `content` with the named theme
+pub fn highlight_code_blocks(content: &str, theme: &str) -> Result<Vec<HighlightedBlock>, String> {
+    let syntaxes = syntax_set();
+    let theme = theme_set()
+        .themes
+        .get(theme)
+        .ok_or_else(|| format!("Unknown theme: {}", theme))?;
+
+    extract_code_blocks(content)
+        .into_iter()
+        .map(|block| {
+            let syntax = block
+                .language
+                .as_deref()
+                .and_then(|language| syntaxes.find_syntax_by_token(language))
+                .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+            let mut highlighter = HighlightLines::new(syntax, theme);
+
+            let lines = LinesWithEndings::from(&block.code)
+                .map(|line| {
+                    let ranges = highlighter
+                        .highlight_line(line, syntaxes)
+                        .map_err(|e| format!("Failed to highlight code: {}", e))?;
+                    Ok(ranges
+                        .into_iter()
+                        .map(|(style, text)| HighlightToken {
+                            text: text.to_string(),
+                            color: format!(
+                                "#{:02x}{:02x}{:02x}",
+                                style.foreground.r, style.foreground.g, style.foreground.b
+                            ),
+                            bold: style.font_style.contains(FontStyle::BOLD),
+                            italic: style.font_style.contains(FontStyle::ITALIC),
+                        })
+                        .collect())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+
+            Ok(HighlightedBlock {
+                language: block.language,
+                syntax: syntax.name.clone(),
+                line: block.line,
+                lines,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_rust_fence() {
+        let content =
+            "Intro\n\n```rust\nfn main() {\n    let x = 1;\n}\n```\n\n```nope\nplain\n```\n";
+
+        let blocks = highlight_code_blocks(content, "base16-ocean.dark").unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].syntax, "Rust");
+        assert_eq!(blocks[0].line, 3);
+        assert_eq!(blocks[0].lines.len(), 3);
+        // The keyword and the rest of the line get separate, differently colored tokens
+        let first_line = &blocks[0].lines[0];
+        assert_eq!(first_line[0].text, "fn");
+        assert!(first_line
+            .iter()
+            .any(|token| token.color != first_line[0].color));
+        assert_eq!(blocks[1].syntax, "Plain Text");
+    }
+}
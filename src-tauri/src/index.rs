@@ -0,0 +1,154 @@
+use notify::Event;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{is_markdown_file, read_file};
+use crate::markdown::{derive_title, extract_links, extract_tags};
+use crate::vault::{markdown_files_with, IgnoreRules, NoteResolver};
+
+/// What the index knows about one note
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub title: String,
+    pub tags: Vec<String>,
+    /// Notes this note links to
+    pub links: Vec<PathBuf>,
+    /// Notes linking to this note
+    pub backlinks: Vec<PathBuf>,
+}
+
+/// Titles, tags and links for every note in a vault, kept current by
+/// re-parsing only the files that change
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct VaultIndex {
+    pub root: PathBuf,
+    pub notes: BTreeMap<PathBuf, IndexEntry>,
+    /// Notes left out, as in vault-wide scans
+    #[serde(skip)]
+    ignore: IgnoreRules,
+}
+
+impl VaultIndex {
+    /// Parse every note under `root` that `ignore` doesn't skip
+    pub fn build(root: &Path, ignore: &IgnoreRules) -> Self {
+        let mut index = VaultIndex {
+            root: root.to_path_buf(),
+            notes: BTreeMap::new(),
+            ignore: ignore.clone(),
+        };
+        let notes = markdown_files_with(root, ignore);
+        let resolver = NoteResolver::new(root, &notes);
+        for note in &notes {
+            if let Some(entry) = parse_entry(&resolver, note) {
+                index.notes.insert(note.clone(), entry);
+            }
+        }
+        index.link_backlinks();
+        index
+    }
+
+    /// Bring the index up to date with a filesystem event
+    pub fn apply(&mut self, event: &Event) {
+        for path in &event.paths {
+            if path.is_dir() {
+                // Checked against the rules from the root in `refresh`
+                for note in markdown_files_with(path, &IgnoreRules::default()) {
+                    self.refresh(&note);
+                }
+            } else if path.exists() {
+                self.refresh(path);
+            } else {
+                // A removed directory takes every note beneath it along
+                let removed: Vec<PathBuf> = self
+                    .notes
+                    .keys()
+                    .filter(|note| note.starts_with(path))
+                    .cloned()
+                    .collect();
+                for note in removed {
+                    self.remove(&note);
+                }
+            }
+        }
+    }
+
+    /// Re-parse a single note, replacing its entry and the backlinks it
+    /// contributes. Links from other notes are not re-resolved, so a new
+    /// note only gains backlinks as the notes pointing at it are edited.
+    fn refresh(&mut self, path: &Path) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let ignored = path
+            .strip_prefix(&self.root)
+            .is_ok_and(|relative| self.ignore.is_ignored(relative));
+        if !is_markdown_file(&name) || ignored {
+            return;
+        }
+
+        let backlinks = self.remove(path);
+        let mut notes: Vec<PathBuf> = self.notes.keys().cloned().collect();
+        notes.push(path.to_path_buf());
+        let resolver = NoteResolver::new(&self.root, &notes);
+        let Some(mut entry) = parse_entry(&resolver, path) else {
+            return;
+        };
+
+        entry.backlinks = backlinks;
+        for target in &entry.links {
+            if let Some(target) = self.notes.get_mut(target) {
+                target.backlinks.push(path.to_path_buf());
+            }
+        }
+        self.notes.insert(path.to_path_buf(), entry);
+    }
+
+    /// Drop a note and the backlinks it contributed, returning the
+    /// backlinks pointing at it
+    fn remove(&mut self, path: &Path) -> Vec<PathBuf> {
+        let Some(entry) = self.notes.remove(path) else {
+            return Vec::new();
+        };
+        for target in &entry.links {
+            if let Some(target) = self.notes.get_mut(target) {
+                target.backlinks.retain(|source| source != path);
+            }
+        }
+        entry.backlinks
+    }
+
+    fn link_backlinks(&mut self) {
+        let links: Vec<(PathBuf, PathBuf)> = self
+            .notes
+            .iter()
+            .flat_map(|(source, entry)| {
+                entry
+                    .links
+                    .iter()
+                    .map(move |target| (source.clone(), target.clone()))
+            })
+            .collect();
+        for (source, target) in links {
+            if let Some(target) = self.notes.get_mut(&target) {
+                target.backlinks.push(source);
+            }
+        }
+    }
+}
+
+fn parse_entry(resolver: &NoteResolver, note: &Path) -> Option<IndexEntry> {
+    let content = read_file(&note.to_string_lossy()).ok()?;
+    let mut links: Vec<PathBuf> = Vec::new();
+    for link in extract_links(&content) {
+        if let Some(target) = resolver.resolve(note, &link) {
+            if target != note && !links.contains(&target) {
+                links.push(target);
+            }
+        }
+    }
+    Some(IndexEntry {
+        title: derive_title(&content, note),
+        tags: extract_tags(&content),
+        links,
+        backlinks: Vec::new(),
+    })
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+use std::process::Command;
+
+fn ensure_exists(path: &str) -> Result<(), String> {
+    if std::fs::symlink_metadata(path).is_err() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+    Ok(())
+}
+
+/// Open a file or folder with the app the OS associates with it
+pub fn open_with_default_app(path: &str) -> Result<(), String> {
+    ensure_exists(path)?;
+    open::that(path).map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+/// Show a file or folder in the system file manager. On macOS and Windows
+/// it is selected in its parent folder; elsewhere there is no common way to
+/// select an entry, so the folder containing it is opened instead.
+pub fn reveal_in_file_manager(path: &str) -> Result<(), String> {
+    ensure_exists(path)?;
+    let launched = if cfg!(target_os = "macos") {
+        Command::new("open").arg("-R").arg(path).spawn().map(|_| ())
+    } else if cfg!(windows) {
+        // Explorer exits non-zero even on success, so only the launch counts
+        Command::new("explorer")
+            .arg(format!("/select,{}", path))
+            .spawn()
+            .map(|_| ())
+    } else {
+        let target = Path::new(path);
+        let folder = match target.parent() {
+            Some(parent) if !target.is_dir() => parent,
+            _ => target,
+        };
+        open::that(folder)
+    };
+    launched.map_err(|e| format!("Failed to reveal {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_path_is_not_launched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gone.md").to_string_lossy().to_string();
+        let expected = format!("Path does not exist: {}", path);
+
+        assert_eq!(open_with_default_app(&path), Err(expected.clone()));
+        assert_eq!(reveal_in_file_manager(&path), Err(expected));
+    }
+}
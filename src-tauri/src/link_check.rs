@@ -0,0 +1,222 @@
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+/// Hosts checked at the same time
+const MAX_CONCURRENT_HOSTS: usize = 8;
+
+/// Pause between requests to the same host
+const PER_HOST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Outcome of checking one URL: its final status after redirects, or why
+/// no response came back
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LinkCheckResult {
+    pub url: String,
+    pub status: Option<u16>,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Host part of a URL, used to group requests for rate limiting
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    authority.rsplit('@').next().unwrap_or(authority)
+}
+
+fn check_url(client: &Client, url: &str) -> LinkCheckResult {
+    // Some servers refuse HEAD; ask again with GET before calling it broken
+    let response = client
+        .head(url)
+        .send()
+        .and_then(|response| match response.status().as_u16() {
+            405 | 501 => client.get(url).send(),
+            _ => Ok(response),
+        });
+    match response {
+        Ok(response) => LinkCheckResult {
+            url: url.to_string(),
+            status: Some(response.status().as_u16()),
+            ok: response.status().is_success(),
+            error: None,
+        },
+        Err(e) => LinkCheckResult {
+            url: url.to_string(),
+            status: None,
+            ok: false,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Results from the worker that checked `urls`; if it panicked, each URL
+/// is reported as failed with the panic message instead of going missing
+fn worker_results(
+    urls: &[&str],
+    joined: thread::Result<Vec<LinkCheckResult>>,
+) -> Vec<LinkCheckResult> {
+    joined.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        urls.iter()
+            .map(|url| LinkCheckResult {
+                url: url.to_string(),
+                status: None,
+                ok: false,
+                error: Some(format!("Link check failed: {}", message)),
+            })
+            .collect()
+    })
+}
+
+/// Request each distinct URL and report its status, in first-seen order.
+/// Up to `MAX_CONCURRENT_HOSTS` hosts are checked in parallel, with the
+/// requests to any one host spaced `PER_HOST_INTERVAL` apart.
+pub fn check_external_links(urls: Vec<String>, timeout_ms: u64) -> Vec<LinkCheckResult> {
+    let mut unique: Vec<String> = Vec::new();
+    for url in urls {
+        if !unique.contains(&url) {
+            unique.push(url);
+        }
+    }
+
+    let client = match Client::builder()
+        .timeout(Duration::from_millis(timeout_ms))
+        .redirect(Policy::limited(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            let error = format!("Failed to create HTTP client: {}", e);
+            return unique
+                .into_iter()
+                .map(|url| LinkCheckResult {
+                    url,
+                    status: None,
+                    ok: false,
+                    error: Some(error.clone()),
+                })
+                .collect();
+        }
+    };
+
+    let mut by_host: Vec<(&str, Vec<&str>)> = Vec::new();
+    for url in &unique {
+        let host = host_of(url);
+        match by_host.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, urls)) => urls.push(url),
+            None => by_host.push((host, vec![url])),
+        }
+    }
+
+    let mut results: HashMap<String, LinkCheckResult> = HashMap::new();
+    for batch in by_host.chunks(MAX_CONCURRENT_HOSTS) {
+        thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .iter()
+                .map(|(_, urls)| {
+                    let client = &client;
+                    scope.spawn(move || {
+                        let mut checked = Vec::new();
+                        for (i, url) in urls.iter().enumerate() {
+                            if i > 0 {
+                                thread::sleep(PER_HOST_INTERVAL);
+                            }
+                            checked.push(check_url(client, url));
+                        }
+                        checked
+                    })
+                })
+                .collect();
+            for ((_, urls), worker) in batch.iter().zip(workers) {
+                for result in worker_results(urls, worker.join()) {
+                    results.insert(result.url.clone(), result);
+                }
+            }
+        });
+    }
+
+    unique
+        .into_iter()
+        .filter_map(|url| results.remove(&url))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve `requests` requests, answering 200 for `/ok` and 404 otherwise
+    fn mock_server(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Drain the headers before answering
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+                let status = if request_line.contains(" /ok ") {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn test_check_reports_status_per_url() {
+        let base = mock_server(2);
+        let ok = format!("{}/ok", base);
+        let missing = format!("{}/missing", base);
+
+        let results = check_external_links(vec![ok.clone(), missing.clone(), ok.clone()], 2000);
+
+        assert_eq!(
+            results,
+            vec![
+                LinkCheckResult {
+                    url: ok,
+                    status: Some(200),
+                    ok: true,
+                    error: None,
+                },
+                LinkCheckResult {
+                    url: missing,
+                    status: Some(404),
+                    ok: false,
+                    error: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panicked_worker_reports_its_urls() {
+        let urls = ["https://example.com/a", "https://example.com/b"];
+
+        let results = worker_results(&urls, Err(Box::new("boom")));
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].url, urls[1]);
+        assert!(!results[1].ok);
+        assert_eq!(results[1].error.as_deref(), Some("Link check failed: boom"));
+    }
+}
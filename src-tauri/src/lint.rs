@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::markdown::{
+    extract_headings, parse_frontmatter, scan_lines, split_frontmatter, LineInfo,
+};
+
+/// How much a lint finding matters
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+}
+
+/// A problem found in a note
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    /// Name of the rule that produced the finding
+    pub rule: &'static str,
+    /// 1-based line number
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The note being linted, pre-split so rules don't each re-scan it
+struct LintContext<'a> {
+    content: &'a str,
+    /// Body lines, excluding frontmatter
+    lines: Vec<LineInfo<'a>>,
+}
+
+/// A lint rule: its name and the check producing its findings
+type LintRule = (
+    &'static str,
+    fn(&LintContext) -> Vec<(usize, Severity, String)>,
+);
+
+/// Rules run by `note_lint`, in reporting order
+const RULES: &[LintRule] = &[
+    ("missing-title", missing_title),
+    ("empty-heading", empty_heading),
+    ("todo-marker", todo_marker),
+    ("unclosed-link", unclosed_link),
+    ("trailing-whitespace", trailing_whitespace),
+];
+
+fn missing_title(context: &LintContext) -> Vec<(usize, Severity, String)> {
+    let has_frontmatter_title = parse_frontmatter(context.content)
+        .frontmatter
+        .and_then(|fm| fm.get("title").and_then(Value::as_str).map(str::to_string))
+        .is_some_and(|title| !title.trim().is_empty());
+    let has_h1 = extract_headings(context.content)
+        .iter()
+        .any(|heading| heading.level == 1);
+
+    if has_frontmatter_title || has_h1 {
+        return Vec::new();
+    }
+    vec![(
+        1,
+        Severity::Info,
+        "Note has no H1 heading or frontmatter title".to_string(),
+    )]
+}
+
+fn empty_heading(context: &LintContext) -> Vec<(usize, Severity, String)> {
+    extract_headings(context.content)
+        .into_iter()
+        .filter(|heading| heading.text.trim().is_empty())
+        .map(|heading| {
+            (
+                heading.line,
+                Severity::Warning,
+                "Heading is empty".to_string(),
+            )
+        })
+        .collect()
+}
+
+fn todo_marker(context: &LintContext) -> Vec<(usize, Severity, String)> {
+    context
+        .lines
+        .iter()
+        .filter(|line| !line.in_code)
+        .filter_map(|line| {
+            let marker = ["TODO", "FIXME"].into_iter().find(|marker| {
+                line.text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|word| word == *marker)
+            })?;
+            Some((line.number, Severity::Info, format!("{} marker", marker)))
+        })
+        .collect()
+}
+
+fn unclosed_link(context: &LintContext) -> Vec<(usize, Severity, String)> {
+    context
+        .lines
+        .iter()
+        .filter(|line| !line.in_code)
+        .filter_map(|line| {
+            let opened = line.text.matches("[[").count();
+            let closed = line.text.matches("]]").count();
+            if opened > closed {
+                return Some((
+                    line.number,
+                    Severity::Warning,
+                    "Wiki link is missing its closing `]]`".to_string(),
+                ));
+            }
+            let unclosed_target = line
+                .text
+                .match_indices("](")
+                .any(|(start, _)| !line.text[start..].contains(')'));
+            unclosed_target.then(|| {
+                (
+                    line.number,
+                    Severity::Warning,
+                    "Link target is missing its closing `)`".to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn trailing_whitespace(context: &LintContext) -> Vec<(usize, Severity, String)> {
+    context
+        .lines
+        .iter()
+        .filter(|line| !line.in_code)
+        .filter_map(|line| {
+            let trailing = &line.text[line.text.trim_end().len()..];
+            // Exactly two spaces is a deliberate markdown hard line break
+            if trailing.is_empty() || trailing == "  " || line.text.trim().is_empty() {
+                return None;
+            }
+            Some((
+                line.number,
+                Severity::Info,
+                "Trailing whitespace".to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Check a note for common writing and formatting issues, ordered by line
+pub fn note_lint(content: &str) -> Vec<LintFinding> {
+    let body_start = split_frontmatter(content).map_or(0, |(_, start)| start);
+    let context = LintContext {
+        content,
+        lines: scan_lines(content)
+            .into_iter()
+            .filter(|line| line.start >= body_start)
+            .collect(),
+    };
+
+    let mut findings: Vec<LintFinding> = RULES
+        .iter()
+        .flat_map(|(rule, check)| {
+            check(&context)
+                .into_iter()
+                .map(|(line, severity, message)| LintFinding {
+                    rule,
+                    line,
+                    severity,
+                    message,
+                })
+        })
+        .collect();
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_lint_reports_distinct_findings() {
+        let content = "##\n\nTODO: write this \nSee [[broken link\n";
+
+        let findings = note_lint(content);
+        let rules: Vec<(&str, usize)> = findings.iter().map(|f| (f.rule, f.line)).collect();
+
+        assert_eq!(
+            rules,
+            vec![
+                ("missing-title", 1),
+                ("empty-heading", 1),
+                ("todo-marker", 3),
+                ("trailing-whitespace", 3),
+                ("unclosed-link", 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_note_lint_clean_note() {
+        let content = "---\ntitle: Clean\n---\nA line with a hard break  \n[ok](other.md)\n";
+
+        assert!(note_lint(content).is_empty());
+    }
+}
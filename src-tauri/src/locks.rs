@@ -0,0 +1,271 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, TryLockError};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::fs::{canonical_path, to_hex, FsError};
+
+/// How long a write waits for another writer of the same file to finish
+const WRITE_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+const WRITE_LOCK_RETRY: Duration = Duration::from_millis(10);
+
+/// The file locked on behalf of `path`: one per canonical path in a shared
+/// temp folder, rather than the note itself, since an atomic save replaces
+/// the note's file and would take any lock on it along. Lock files are left
+/// in place; removing one could split two lockers onto different files.
+fn lock_file_path(path: &str) -> PathBuf {
+    let key = to_hex(&Sha256::digest(
+        canonical_path(path).to_string_lossy().as_bytes(),
+    ));
+    std::env::temp_dir()
+        .join("manza-locks")
+        .join(format!("{}.lock", key))
+}
+
+fn open_lock_file(path: &str) -> Result<File, FsError> {
+    let lock = lock_file_path(path);
+    if let Some(dir) = lock.parent() {
+        fs::create_dir_all(dir).map_err(|e| FsError::io("Failed to create lock folder", e))?;
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock)
+        .map_err(|e| FsError::io("Failed to open lock file", e))
+}
+
+fn locked(path: &str) -> FsError {
+    FsError::Locked {
+        message: format!("File is locked by another editor: {}", path),
+    }
+}
+
+/// Run `write` holding an exclusive OS lock for `path`, so writes to one
+/// file from separate windows or app instances take turns. Waits up to
+/// `WRITE_LOCK_TIMEOUT` for another writer; a file held for editing
+/// elsewhere fails with `Locked` once that runs out.
+pub fn with_write_lock<T, E: From<FsError>>(
+    path: &str,
+    write: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let file = open_lock_file(path)?;
+    let deadline = Instant::now() + WRITE_LOCK_TIMEOUT;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                thread::sleep(WRITE_LOCK_RETRY)
+            }
+            Err(TryLockError::WouldBlock) => return Err(locked(path).into()),
+            Err(TryLockError::Error(e)) => return Err(FsError::io("Failed to lock file", e).into()),
+        }
+    }
+    // The lock goes with the handle, so it is released on return and when
+    // `write` panics
+    write()
+}
+
+/// Editing locks this process holds, by canonical path, each with the
+/// window holding it. The OS drops the locks with their handles, so they
+/// are released when the app exits however it exits.
+#[derive(Default)]
+pub struct FileLocks {
+    held: Mutex<HashMap<PathBuf, (String, File)>>,
+}
+
+impl FileLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take an editing lock on `path` for `owner`, e.g. a window label.
+    /// Taking it again as the same owner does nothing; while another window
+    /// or app instance holds it this fails with `Locked`.
+    pub fn try_lock(&self, path: &str, owner: &str) -> Result<(), FsError> {
+        let key = canonical_path(path);
+        let mut held = self.held.lock().unwrap();
+        if let Some((holder, _)) = held.get(&key) {
+            return if holder == owner {
+                Ok(())
+            } else {
+                Err(locked(path))
+            };
+        }
+        let file = open_lock_file(path)?;
+        match file.try_lock() {
+            Ok(()) => {
+                held.insert(key, (owner.to_string(), file));
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(locked(path)),
+            Err(TryLockError::Error(e)) => Err(FsError::io("Failed to lock file", e)),
+        }
+    }
+
+    /// Release `owner`'s editing lock on `path`, returning whether it held
+    /// one
+    pub fn unlock(&self, path: &str, owner: &str) -> bool {
+        let key = canonical_path(path);
+        let mut held = self.held.lock().unwrap();
+        match held.get(&key) {
+            Some((holder, _)) if holder == owner => {
+                held.remove(&key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Release every editing lock `owner` holds, e.g. when its window closes
+    pub fn unlock_all(&self, owner: &str) {
+        self.held
+            .lock()
+            .unwrap()
+            .retain(|_, (holder, _)| holder != owner);
+    }
+
+    /// Run `write` to `path` on behalf of `owner`: directly if it holds the
+    /// editing lock, refused if another window does, and otherwise under
+    /// `with_write_lock`
+    pub fn write<T, E: From<FsError>>(
+        &self,
+        path: &str,
+        owner: &str,
+        write: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let holder = self
+            .held
+            .lock()
+            .unwrap()
+            .get(&canonical_path(path))
+            .map(|(holder, _)| holder.clone());
+        match holder {
+            Some(holder) if holder == owner => write(),
+            Some(_) => Err(locked(path).into()),
+            None => with_write_lock(path, write),
+        }
+    }
+
+    /// Run `write` to several files at once, as `write` would for each.
+    /// The locks are taken in a fixed order, each file once, so two writers
+    /// of overlapping sets can't each wait on the other.
+    pub fn write_all<T, E: From<FsError>>(
+        &self,
+        paths: &[String],
+        owner: &str,
+        write: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut paths: Vec<(PathBuf, &str)> = paths
+            .iter()
+            .map(|path| (canonical_path(path), path.as_str()))
+            .collect();
+        paths.sort();
+        paths.dedup_by(|a, b| a.0 == b.0);
+        self.write_in_order(&paths, owner, write)
+    }
+
+    fn write_in_order<T, E: From<FsError>>(
+        &self,
+        paths: &[(PathBuf, &str)],
+        owner: &str,
+        write: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        match paths.split_first() {
+            Some(((_, path), rest)) => {
+                self.write(path, owner, || self.write_in_order(rest, owner, write))
+            }
+            None => write(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{create_file_exclusive, create_unique_file};
+
+    #[test]
+    fn test_editing_lock_blocks_other_editors() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        let path = note.to_string_lossy().to_string();
+        let locks = FileLocks::new();
+        // A second app instance has its own locks but shares the OS ones
+        let other_app = FileLocks::new();
+        let save = |locks: &FileLocks, owner: &str, content: &str| {
+            locks.write(&path, owner, || {
+                fs::write(&note, content).map_err(|e| FsError::io("Failed", e))
+            })
+        };
+
+        locks.try_lock(&path, "main").unwrap();
+        locks.try_lock(&path, "main").unwrap();
+        assert!(matches!(
+            locks.try_lock(&path, "second"),
+            Err(FsError::Locked { .. })
+        ));
+        assert!(matches!(
+            other_app.try_lock(&path, "main"),
+            Err(FsError::Locked { .. })
+        ));
+
+        save(&locks, "main", "mine").unwrap();
+        assert!(matches!(
+            save(&locks, "second", "theirs"),
+            Err(FsError::Locked { .. })
+        ));
+        assert_eq!(fs::read_to_string(&note).unwrap(), "mine");
+
+        assert!(!locks.unlock(&path, "second"));
+        assert!(locks.unlock(&path, "main"));
+        save(&other_app, "main", "theirs").unwrap();
+        other_app.try_lock(&path, "main").unwrap();
+        other_app.unlock_all("main");
+        locks.try_lock(&path, "second").unwrap();
+    }
+
+    #[test]
+    fn test_write_all_locks_each_file_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let locks = FileLocks::new();
+        let other_app = FileLocks::new();
+        let paths = vec![path("b.md"), path("a.md"), path("b.md")];
+
+        // Listing a file twice doesn't wait on its own lock
+        let wrote: Result<(), FsError> = locks.write_all(&paths, "main", || Ok(()));
+        wrote.unwrap();
+
+        other_app.try_lock(&path("a.md"), "main").unwrap();
+        let mut ran = false;
+        let blocked: Result<(), FsError> = locks.write_all(&paths, "main", || {
+            ran = true;
+            Ok(())
+        });
+        assert!(matches!(blocked, Err(FsError::Locked { .. })));
+        assert!(!ran);
+    }
+
+    #[test]
+    fn test_locked_path_blocks_creating_a_note_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let note = dir.path().join("Untitled.md");
+        let locks = FileLocks::new();
+        let other_app = FileLocks::new();
+        other_app.try_lock(&note.to_string_lossy(), "main").unwrap();
+
+        // As the `create_unique_file` command claims each name
+        let created = create_unique_file(&root, "Untitled", "md", |path| {
+            locks.write(path, "main", || create_file_exclusive(path, ""))
+        });
+
+        assert!(matches!(created, Err(FsError::Locked { .. })));
+        assert!(!note.exists());
+    }
+}
@@ -1,15 +1,206 @@
 // Prevents additional console window on Windows in release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{Manager, State};
 
+mod activity;
+mod attachments;
+mod backup;
+mod config;
+mod diff;
+mod drafts;
+mod export;
 mod fs;
-use fs::{read_directory, read_file, write_file, FileItem};
+mod git;
+mod highlight;
+mod index;
+mod launch;
+mod locks;
+mod link_check;
+mod lint;
+mod markdown;
+mod operations;
+mod recent;
+mod render;
+mod search;
+mod template;
+mod vault;
+mod watcher;
+use activity::{logged_delete, read_activity_log, record_activity, ActivityAction, ActivityEntry};
+use attachments::{save_optimized_attachment, ImageOptimizeOptions, SavedAttachment};
+use backup::{
+    list_file_backups, prune_snapshots, restore_file_backup, snapshot_vault,
+    write_file_with_backup, FileBackup,
+};
+use config::{VaultConfig, VaultState};
+use diff::{diff_text, DiffHunk, DEFAULT_CONTEXT_LINES};
+use drafts::Draft;
+use export::{export_markdown_to_html, export_pdf};
+use fs::{
+    append_to_file, apply_edits, canonical_path, copy_path, copy_path_tracked, delete_directory,
+    delete_directory_tracked, delete_file, delete_path, directory_delete_preview,
+    directory_signature, file_hash, file_info, file_size, merge_directories, move_to_trash,
+    read_directory, read_directory_filtered, read_directory_listing, read_directory_tree,
+    read_file, read_file_with_encoding, read_file_with_eol, trash_preview, truncate_file,
+    write_file_atomic, write_file_checked, write_file_with_encoding, write_file_with_eol,
+    write_file_with_mode, ConflictPolicy, DecodedFile, DeletePreview, DirectoryListing,
+    DirectoryOptions, DirectoryStats, EolMode, FileChunk, FileEdit, FileHashResult, FileHead,
+    FileItem, FileKind, FileSave, FileTreeNode, FileWithEol, FsError, KeepEnd, MergeOutcome,
+    NameValidation, OperationProgress, OsTrash, PathResult, RenameMapping, RenameRule, SaveError,
+    SaveResult, TransactionResult, TrashPreview, TrashedItem, WorkspaceState,
+};
+use git::{
+    change_frequency, note_primary_author, notes_changed_since, restore_file_revision, AuthorStat,
+    FileChangeFreq,
+};
+use highlight::{highlight_code_blocks, HighlightedBlock};
+use index::VaultIndex;
+use link_check::{check_external_links, LinkCheckResult};
+use locks::FileLocks;
+use lint::{note_lint, LintFinding};
+use markdown::{
+    clean_whitespace, collapse_blank_lines, document_sections, extract_toc,
+    find_duplicate_headings, heading_structure_issues, locate_heading, markdown_stats,
+    markdown_to_plaintext, read_frontmatter_batch, CleanupOptions, DuplicateHeading,
+    FrontmatterResult, MarkdownStats, NoteBundle, NoteParts, ParsedNote, RenderOptions, Section,
+    StructureIssue, TocEntry, DEFAULT_WORDS_PER_MINUTE,
+};
+use operations::{OperationEvent, OperationState};
+use recent::{RecentEntry, RecentKind};
+use render::RenderCache;
+use search::{
+    glob_files, scan_notes, search_files, FuzzyMatch, IndexedHit, SearchIndex, SearchMatch,
+    SearchOptions, QUICK_OPEN_SKIP,
+};
+use vault::{
+    autocomplete_data, code_block_languages, convert_links, creation_histogram, deduplicate_notes,
+    find_external_links, find_markers, find_orphan_notes, folder_word_counts,
+    frontmatter_key_report, generate_index_note, link_path_between, link_reference,
+    markdown_files_with, move_note_with_assets, order_by_frontmatter, organize_by_tag,
+    query_by_date_range, reading_order, render_with_backlinks, repair_asset_links,
+    tag_cooccurrence, title_mismatches, validate_vault, vault_files, AutocompleteData, Bucket,
+    ConvertResult, DedupAction, DedupGroup, ExternalLinkRef, FolderWordCount, Grouping,
+    HistogramBucket, IgnoreRules, KeyStat, LangCount, LinkStyle, MarkerHit, NoteMoveReport,
+    OrphanMode, RepairReport, ResolvedAsset, TagPair, TitleMismatch, VaultValidation,
+    DEFAULT_MARKERS,
+};
+use watcher::{DirectoryWatcher, WatchOptions, WatchedPath};
 
 /// Tauri command to read directory contents
 #[tauri::command]
-fn get_directory_contents(path: String) -> Result<Vec<FileItem>, String> {
-    read_directory(&path)
+fn get_directory_contents(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    options: Option<DirectoryOptions>,
+) -> Result<Vec<FileItem>, FsError> {
+    let path = workspace.check(&path)?;
+    let mut options = options.unwrap_or_default();
+    options
+        .markdown_extensions
+        .get_or_insert_with(|| vault.config().markdown_extensions);
+    read_directory(&path, &options)
+}
+
+/// Tauri command to read directory contents along with whether the listing
+/// is empty and how many items it has
+#[tauri::command]
+fn get_directory_listing(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    options: Option<DirectoryOptions>,
+) -> Result<DirectoryListing, FsError> {
+    let path = workspace.check(&path)?;
+    let mut options = options.unwrap_or_default();
+    options
+        .markdown_extensions
+        .get_or_insert_with(|| vault.config().markdown_extensions);
+    read_directory_listing(&path, &options)
+}
+
+/// Tauri command to describe a single file or folder, e.g. to decide
+/// whether to offer a reload, without listing its parent or reading it
+#[tauri::command]
+fn get_file_info(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    options: Option<DirectoryOptions>,
+) -> Result<FileItem, FsError> {
+    let path = workspace.check(&path)?;
+    let mut options = options.unwrap_or_default();
+    options
+        .markdown_extensions
+        .get_or_insert_with(|| vault.config().markdown_extensions);
+    file_info(&path, &options)
+}
+
+/// Tauri command to read a directory's subdirectories and only the files of
+/// the given kinds, e.g. for an image picker
+#[tauri::command]
+fn get_directory_contents_filtered(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    kinds: Vec<FileKind>,
+    options: Option<DirectoryOptions>,
+) -> Result<Vec<FileItem>, FsError> {
+    let path = workspace.check(&path)?;
+    let mut options = options.unwrap_or_default();
+    options
+        .markdown_extensions
+        .get_or_insert_with(|| vault.config().markdown_extensions);
+    read_directory_filtered(&path, &kinds, &options)
+}
+
+/// Tauri command to read a directory and its subdirectories in one call
+#[tauri::command]
+fn get_directory_tree(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    max_depth: Option<usize>,
+    follow_symlinks: Option<bool>,
+) -> Result<Vec<FileTreeNode>, FsError> {
+    let path = workspace.check(&path)?;
+    read_directory_tree(&path, max_depth, follow_symlinks.unwrap_or(false))
+}
+
+/// Tauri command to total a directory tree's size and entry counts, off the
+/// main thread
+#[tauri::command]
+async fn directory_stats(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    follow_symlinks: Option<bool>,
+) -> Result<DirectoryStats, FsError> {
+    let path = workspace.check(&path)?;
+    let follow_symlinks = follow_symlinks.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || fs::directory_stats(&path, follow_symlinks))
+        .await
+        .map_err(|e| FsError::from(format!("Failed to compute directory stats: {}", e)))?
+}
+
+/// Tauri command to fingerprint a directory listing for change detection
+#[tauri::command]
+fn get_directory_signature(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
+    directory_signature(&path)
+}
+
+/// Tauri command to confine the file commands to a workspace root, or lift
+/// the restriction when `path` is omitted. Returns the canonical root.
+#[tauri::command]
+fn set_workspace_root(
+    workspace: State<'_, WorkspaceState>,
+    path: Option<String>,
+) -> Result<Option<String>, FsError> {
+    workspace.set_root(path.as_deref())
 }
 
 /// Tauri command to open a directory dialog and return selected path
@@ -25,24 +216,2349 @@ async fn select_directory() -> Result<Option<String>, String> {
 
 /// Tauri command to read file contents
 #[tauri::command]
-fn read_file_contents(path: String) -> Result<String, String> {
+fn read_file_contents(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
     read_file(&path)
 }
 
-/// Tauri command to write file contents
+/// Tauri command to read a file as text, failing with a `binary_file` code
+/// for files that aren't text
+#[tauri::command]
+fn read_text_file(workspace: State<'_, WorkspaceState>, path: String) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
+    fs::read_text_file(&path)
+}
+
+/// Tauri command to guess whether a file holds binary data, before trying
+/// to open it as text
+#[tauri::command]
+fn is_binary_file(workspace: State<'_, WorkspaceState>, path: String) -> Result<bool, FsError> {
+    let path = workspace.check(&path)?;
+    fs::is_binary_file(&path)
+}
+
+/// Tauri command to read part of a large file without loading all of it
+#[tauri::command]
+fn read_file_chunk(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    offset: u64,
+    length: usize,
+) -> Result<FileChunk, FsError> {
+    let path = workspace.check(&path)?;
+    fs::read_file_chunk(&path, offset, length)
+}
+
+/// Tauri command to read the first lines of a file for a preview
+#[tauri::command]
+fn read_file_head(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    max_lines: usize,
+    max_bytes: usize,
+) -> Result<FileHead, FsError> {
+    let path = workspace.check(&path)?;
+    fs::read_file_head(&path, max_lines, max_bytes)
+}
+
+/// Tauri command to get a file's size in bytes
+#[tauri::command]
+fn get_file_size(workspace: State<'_, WorkspaceState>, path: String) -> Result<u64, FsError> {
+    let path = workspace.check(&path)?;
+    file_size(&path)
+}
+
+/// Tauri command to read file contents in whatever encoding they were saved
+/// in, reporting the encoding
+#[tauri::command]
+fn read_file_contents_with_encoding(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<DecodedFile, FsError> {
+    let path = workspace.check(&path)?;
+    read_file_with_encoding(&path)
+}
+
+/// Tauri command to write file contents in a given encoding, typically the
+/// one `read_file_contents_with_encoding` detected, so legacy files keep it
+#[tauri::command]
+fn save_file_contents_with_encoding(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    encoding: String,
+) -> Result<(), FsError> {
+    let target = workspace.check(&path)?;
+    locks.write(&target, window.label(), || {
+        write_file_with_encoding(&target, &content, &encoding)
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(())
+}
+
+/// Tauri command to hold an editing lock on a file while it is open, so
+/// other windows and app instances can't save over it
+#[tauri::command]
+fn try_lock_file(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    locks.try_lock(&path, window.label())
+}
+
+/// Tauri command to release an editing lock taken with `try_lock_file`,
+/// returning whether this window held it
+#[tauri::command]
+fn unlock_file(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> bool {
+    let path = workspace.check(&path).unwrap_or(path);
+    locks.unlock(&path, window.label())
+}
+
+/// Tauri command to write file contents, optionally with Unix permission
+/// bits such as `0o600` for a private note
+#[tauri::command]
+fn save_file_contents(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    mode: Option<u32>,
+) -> Result<(), FsError> {
+    let target = workspace.check(&path)?;
+    locks.write(&target, window.label(), || {
+        write_file_with_mode(&target, &content, mode)
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(())
+}
+
+/// Tauri command to append to a file without rewriting it; `newline_before`
+/// starts the appended content on a fresh line
+#[tauri::command]
+fn append_file_contents(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    newline_before: Option<bool>,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    locks.write(&path, window.label(), || {
+        append_to_file(&path, &content, newline_before.unwrap_or(false))
+    })
+}
+
+/// Tauri command to write file contents unless the file changed on disk
+/// since it was read at `expected_mtime`; without one the file is created
+/// and must not exist yet. A conflict error carries the current content so
+/// the editor can offer a diff
+#[tauri::command]
+fn save_file_contents_checked(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    expected_mtime: Option<u64>,
+) -> Result<Option<u64>, SaveError> {
+    let target = workspace.check(&path).map_err(|e| SaveError::Failed {
+        message: e.to_string(),
+    })?;
+    let mtime = locks.write(&target, window.label(), || {
+        write_file_checked(&target, &content, expected_mtime)
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(mtime)
+}
+
+/// Tauri command to read file contents with their dominant line ending
+#[tauri::command]
+fn read_file_contents_with_eol(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<FileWithEol, FsError> {
+    let path = workspace.check(&path)?;
+    read_file_with_eol(&path)
+}
+
+/// Tauri command to write file contents, converting line endings to LF or
+/// CRLF, or by default leaving them as given
+#[tauri::command]
+fn save_file_contents_with_eol(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    eol: Option<EolMode>,
+) -> Result<(), FsError> {
+    let target = workspace.check(&path)?;
+    locks.write(&target, window.label(), || {
+        write_file_with_eol(&target, &content, eol.unwrap_or_default())
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(())
+}
+
+/// Tauri command to write several files all-or-nothing
+#[tauri::command]
+fn apply_transaction(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    edits: Vec<FileEdit>,
+) -> TransactionResult {
+    let checked: Result<Vec<FileEdit>, FsError> = edits
+        .into_iter()
+        .map(|edit| {
+            Ok(FileEdit {
+                path: workspace.check(&edit.path)?,
+                content: edit.content,
+            })
+        })
+        .collect();
+    let applied = checked.and_then(|edits| {
+        let paths: Vec<String> = edits.iter().map(|edit| edit.path.clone()).collect();
+        locks.write_all(&paths, window.label(), || Ok(apply_edits(edits)))
+    });
+    match applied {
+        Ok(result) => result,
+        Err(error) => TransactionResult {
+            error: Some(error.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Tauri command to save a note with runs of blank lines collapsed, returning
+/// the tidied content
+#[tauri::command]
+fn tidy_blank_lines(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    max_consecutive: Option<usize>,
+) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
+    let tidied = collapse_blank_lines(&content, max_consecutive.unwrap_or(1));
+    locks.write(&path, window.label(), || {
+        write_file_atomic(&path, tidied.as_bytes())
+    })?;
+    Ok(tidied)
+}
+
+/// Tauri command to save a note with trailing whitespace and the final
+/// line break cleaned up, returning the cleaned content
+#[tauri::command]
+fn save_file_clean(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    options: Option<CleanupOptions>,
+) -> Result<String, FsError> {
+    let target = workspace.check(&path)?;
+    let cleaned = clean_whitespace(&content, &options.unwrap_or_default());
+    locks.write(&target, window.label(), || {
+        write_file_atomic(&target, cleaned.as_bytes())
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(cleaned)
+}
+
+/// Tauri command to create a file, failing if anything already exists at the path
+#[tauri::command]
+fn create_file_exclusive(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+    mode: Option<u32>,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    locks.write(&path, window.label(), || {
+        fs::create_file_exclusive_with_mode(&path, &content, mode)
+    })
+}
+
+/// Tauri command to create a file from a template, filling `{{key}}`
+/// placeholders; fails if anything already exists at the path
+#[tauri::command]
+fn create_file_from_template(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    template: String,
+    variables: Option<HashMap<String, String>>,
+) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
+    locks.write(&path, window.label(), || {
+        template::create_file_from_template(&path, &template, variables.unwrap_or_default())
+    })
+}
+
+/// Tauri command to create an empty file with a name nothing else in the
+/// directory has, e.g. `Untitled 2.md`, returning its path
+#[tauri::command]
+fn create_unique_file(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    dir: String,
+    base_name: Option<String>,
+    extension: Option<String>,
+) -> Result<String, FsError> {
+    let dir = workspace.check(&dir)?;
+    fs::create_unique_file(
+        &dir,
+        base_name.as_deref().unwrap_or("Untitled"),
+        extension.as_deref().unwrap_or("md"),
+        |path| locks.write(path, window.label(), || fs::create_file_exclusive(path, "")),
+    )
+}
+
+/// Tauri command to create a directory with a name nothing else in the
+/// directory has, returning its path
+#[tauri::command]
+fn create_unique_directory(
+    workspace: State<'_, WorkspaceState>,
+    dir: String,
+    base_name: Option<String>,
+) -> Result<String, FsError> {
+    let dir = workspace.check(&dir)?;
+    fs::create_unique_directory(&dir, base_name.as_deref().unwrap_or("Untitled"))
+}
+
+/// Tauri command to hash a file's content as a cache key; the hash ignores
+/// timestamps, so it is stable across runs and machines
+#[tauri::command]
+fn get_file_hash(workspace: State<'_, WorkspaceState>, path: String) -> Result<String, FsError> {
+    let path = workspace.check(&path)?;
+    file_hash(&path)
+}
+
+/// Tauri command to hash several files in one call, reporting each failure
+/// against its path
+#[tauri::command]
+fn hash_paths(workspace: State<'_, WorkspaceState>, paths: Vec<String>) -> Vec<FileHashResult> {
+    fs::hash_paths(&paths, |path| file_hash(&workspace.check(path)?))
+}
+
+/// Tauri command to refresh `is_markdown` on listed items against the
+/// vault's configured markdown extensions
+#[tauri::command]
+fn reclassify_items(vault: State<'_, VaultState>, items: Vec<FileItem>) -> Vec<FileItem> {
+    fs::reclassify_items(items, &vault.config().markdown_extensions)
+}
+
+/// Tauri command to trim a file to a maximum size, keeping its head or tail
+#[tauri::command]
+fn trim_file(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    max_bytes: usize,
+    keep: KeepEnd,
+) -> Result<usize, FsError> {
+    let path = workspace.check(&path)?;
+    locks.write(&path, window.label(), || {
+        truncate_file(&path, max_bytes, keep)
+    })
+}
+
+/// Tauri command to save a pasted attachment next to a note, shrinking images
+#[tauri::command]
+fn save_optimized_image(
+    workspace: State<'_, WorkspaceState>,
+    note_path: String,
+    file_name: String,
+    bytes: Vec<u8>,
+    options: Option<ImageOptimizeOptions>,
+) -> Result<SavedAttachment, String> {
+    let note_path = workspace.check(&note_path)?;
+    save_optimized_attachment(&note_path, &file_name, &bytes, &options.unwrap_or_default())
+}
+
+/// Vault to log activity in: the one given, or else the open vault
+/// The notes under `root` a vault-wide rewrite may write, for taking their
+/// file locks up front
+fn note_paths(root: &str, rules: &IgnoreRules) -> Vec<String> {
+    markdown_files_with(Path::new(root), rules)
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// The files under each of `dirs`, for locking everything a folder-wide
+/// operation may rename or replace
+fn file_paths(dirs: &[&str]) -> Vec<String> {
+    dirs.iter()
+        .flat_map(|dir| vault_files(Path::new(dir), &IgnoreRules::default()))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+fn activity_root(vault: &VaultState, vault_root: Option<String>) -> Option<String> {
+    vault_root.or_else(|| vault.root().map(|root| root.to_string_lossy().to_string()))
+}
+
+/// Tauri command to permanently delete a file, logging it in the vault
+#[tauri::command]
+fn delete_file_at_path(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    vault_root: Option<String>,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    let root = activity_root(&vault, vault_root);
+    logged_delete(root.as_deref(), &path, ActivityAction::Delete, delete_file)
+}
+
+/// Tauri command to permanently delete a directory off the main thread,
+/// logging it in the vault. With an `operation_id` it emits
+/// `operation-progress` and can be stopped with `cancel_operation`, in which
+/// case whatever was already deleted is logged.
+#[tauri::command]
+async fn delete_directory_at_path(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationState>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    vault_root: Option<String>,
+    operation_id: Option<String>,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    let root = activity_root(&vault, vault_root);
+    let Some(id) = operation_id else {
+        return tauri::async_runtime::spawn_blocking(move || {
+            logged_delete(
+                root.as_deref(),
+                &path,
+                ActivityAction::Delete,
+                delete_directory,
+            )
+        })
+        .await
+        .map_err(|e| FsError::from(format!("Failed to delete directory: {}", e)))?;
+    };
+    let operation = operations.begin(&id)?;
+    let cancel = operation.cancelled();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut removed = Vec::new();
+        let result = logged_delete(root.as_deref(), &path, ActivityAction::Delete, |path| {
+            delete_directory_tracked(
+                path,
+                &cancel,
+                |progress| emit_operation_progress(&app_handle, &id, progress),
+                |entry| removed.push(entry.to_path_buf()),
+            )
+        });
+        if let Err(FsError::Cancelled { .. }) = result {
+            // Log the outermost entries removed; their contents went with them
+            let outermost = removed
+                .iter()
+                .filter(|entry| entry.parent().is_some_and(Path::exists));
+            for entry in outermost {
+                let entry = entry.to_string_lossy();
+                record_activity(root.as_deref(), &entry, ActivityAction::Delete);
+            }
+        }
+        result
+    })
+    .await
+    .map_err(|e| FsError::from(format!("Failed to delete directory: {}", e)))?
+}
+
+/// Tauri command to move a file or directory to the platform trash, logging it
+/// in the vault. This is the default delete; the permanent ones remain for
+/// when the trash is unavailable.
+#[tauri::command]
+fn move_to_trash_at_path(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    vault_root: Option<String>,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    let root = activity_root(&vault, vault_root);
+    logged_delete(root.as_deref(), &path, ActivityAction::Trash, move_to_trash)
+}
+
+/// Folder whose trashed items the trash commands may touch: the workspace
+/// when one is set, or else the vault
+fn trash_scope(
+    workspace: &WorkspaceState,
+    vault: &VaultState,
+    vault_root: Option<String>,
+) -> Result<PathBuf, FsError> {
+    workspace
+        .root()
+        .or_else(|| activity_root(vault, vault_root).map(PathBuf::from))
+        .ok_or_else(|| FsError::from("No vault is open".to_string()))
+}
+
+/// Tauri command to list what the platform trash holds from the workspace
+/// or vault, newest first. Fails with code `unsupported` where the trash
+/// can't be browsed.
+#[tauri::command]
+fn list_trash(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    vault_root: Option<String>,
+) -> Result<Vec<TrashedItem>, FsError> {
+    fs::list_trash(&OsTrash, &trash_scope(&workspace, &vault, vault_root)?)
+}
+
+/// Tauri command to put an item trashed from the workspace or vault back
+/// where it was, logging it in the vault. Returns the restored path.
+#[tauri::command]
+fn restore_from_trash(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    id: String,
+    vault_root: Option<String>,
+) -> Result<String, FsError> {
+    let scope = trash_scope(&workspace, &vault, vault_root.clone())?;
+    let restored = fs::restore_from_trash(&OsTrash, &id, &scope)?;
+    let root = activity_root(&vault, vault_root);
+    record_activity(root.as_deref(), &restored, ActivityAction::Restore);
+    Ok(restored)
+}
+
+/// Tauri command to permanently delete what the platform trash holds from
+/// the workspace or vault, logging each item in the vault. Returns how many
+/// items were purged.
+#[tauri::command]
+fn empty_trash(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    vault_root: Option<String>,
+) -> Result<usize, FsError> {
+    let scope = trash_scope(&workspace, &vault, vault_root.clone())?;
+    let purged = fs::empty_trash(&OsTrash, &scope)?;
+    let root = activity_root(&vault, vault_root);
+    for path in &purged {
+        record_activity(root.as_deref(), path, ActivityAction::Purge);
+    }
+    Ok(purged.len())
+}
+
+/// Tauri command to delete several paths in one call, reporting each
+/// failure against its path. Paths go to the trash unless `permanent`.
+#[tauri::command]
+fn delete_paths(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    paths: Vec<String>,
+    permanent: Option<bool>,
+    vault_root: Option<String>,
+) -> Vec<PathResult> {
+    let root = activity_root(&vault, vault_root);
+    let permanent = permanent.unwrap_or(false);
+    fs::delete_paths(&paths, |path| {
+        let path = workspace.check(path)?;
+        if permanent {
+            logged_delete(root.as_deref(), &path, ActivityAction::Delete, delete_path)
+        } else {
+            logged_delete(root.as_deref(), &path, ActivityAction::Trash, move_to_trash)
+        }
+    })
+}
+
+/// Tauri command to delete everything inside a folder but keep the folder,
+/// along with its permissions and any watch on it. Entries go to the trash
+/// unless `permanent`, and each failure is reported against its entry.
+#[tauri::command]
+fn empty_directory(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    permanent: Option<bool>,
+    vault_root: Option<String>,
+) -> Result<Vec<PathResult>, FsError> {
+    let path = workspace.check(&path)?;
+    let root = activity_root(&vault, vault_root);
+    let permanent = permanent.unwrap_or(false);
+    fs::empty_directory(&path, |entry| {
+        if permanent {
+            logged_delete(root.as_deref(), entry, ActivityAction::Delete, delete_path)
+        } else {
+            logged_delete(root.as_deref(), entry, ActivityAction::Trash, move_to_trash)
+        }
+    })
+}
+
+/// Tauri command to save several open documents in one call, each
+/// atomically, reporting each failure against its path
+#[tauri::command]
+fn save_files(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    files: Vec<FileSave>,
+) -> Vec<SaveResult> {
+    fs::save_files(&files, |path, content| {
+        let target = workspace.check(path)?;
+        locks.write(&target, window.label(), || {
+            write_file_atomic(&target, content.as_bytes())
+        })?;
+        clear_draft(&app_handle, path);
+        Ok(())
+    })
+}
+
+/// Tauri command to rename several paths in one call, reporting each
+/// failure against its old path
+#[tauri::command]
+fn rename_paths(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    pairs: Vec<(String, String)>,
+) -> Vec<PathResult> {
+    fs::rename_paths(&pairs, |old_path, new_path| {
+        let old_path = workspace.check(old_path)?;
+        let new_path = workspace.check(new_path)?;
+        watcher
+            .rename(app_handle.clone(), &old_path, &new_path)
+            .map_err(FsError::from)
+    })
+}
+
+/// Tauri command to rename the files in a folder by a pattern, all or
+/// nothing, returning the old and new path of each renamed file
+#[tauri::command]
+fn bulk_rename(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    dir: String,
+    rule: RenameRule,
+) -> Result<Vec<RenameMapping>, FsError> {
+    let dir = workspace.check(&dir)?;
+    locks.write_all(&file_paths(&[&dir]), window.label(), || {
+        fs::bulk_rename(&dir, &rule)
+    })
+}
+
+/// Tauri command to rename a file or directory, moving a single-file watch
+/// along with it
+#[tauri::command]
+fn rename_file_or_directory(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    old_path: String,
+    new_path: String,
+) -> Result<(), String> {
+    let old_path = workspace.check(&old_path)?;
+    let new_path = workspace.check(&new_path)?;
+    watcher.rename(app_handle, &old_path, &new_path)
+}
+
+/// Tauri command to move a file or folder into a directory, as on drag and
+/// drop, returning its new path; a taken name is numbered unless
+/// `overwrite` is set, in which case the entry it replaces is trashed
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn move_into_directory(
+    app_handle: tauri::AppHandle,
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    src: String,
+    dest_dir: String,
+    overwrite: Option<bool>,
+    vault_root: Option<String>,
+) -> Result<String, FsError> {
+    let src = workspace.check(&src)?;
+    let dest_dir = workspace.check(&dest_dir)?;
+    let root = activity_root(&vault, vault_root);
+    fs::move_into_directory(
+        &src,
+        &dest_dir,
+        overwrite.unwrap_or(false),
+        |old_path, new_path| {
+            watcher
+                .rename(app_handle, old_path, new_path)
+                .map_err(FsError::from)
+        },
+        |replaced, backup| {
+            logged_delete(root.as_deref(), replaced, ActivityAction::Trash, |_| {
+                move_to_trash(backup)
+            })
+        },
+    )
+}
+
+/// Tauri command to mark a file or folder read-only or writable
+#[tauri::command]
+fn set_readonly(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    readonly: bool,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    fs::set_readonly(&path, readonly)
+}
+
+/// Tauri command to set a file or directory's Unix permission bits; on
+/// Windows only the owner-write bit applies, as the read-only attribute
+#[tauri::command]
+fn set_permissions(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    mode: u32,
+) -> Result<(), FsError> {
+    let path = workspace.check(&path)?;
+    fs::set_permissions(&path, mode)
+}
+
+/// Tauri command to resolve a path to its canonical absolute form,
+/// following symlinks and `..`; paths that don't exist yet are resolved
+/// through their nearest existing ancestor
+#[tauri::command]
+fn canonicalize_path(path: String) -> String {
+    canonical_path(&path).to_string_lossy().to_string()
+}
+
+/// Tauri command to express a path relative to a base folder, e.g. the
+/// workspace root, for tidy labels; paths outside it stay absolute
+#[tauri::command]
+fn relative_path(base: String, target: String) -> String {
+    fs::relative_path(&base, &target)
+}
+
+/// Resolve an asset reference from a note and check it stays inside the
+/// workspace
+fn resolve_asset(
+    workspace: &WorkspaceState,
+    markdown_path: &str,
+    asset_ref: &str,
+) -> Result<String, String> {
+    let markdown_path = workspace.check(markdown_path)?;
+    let path = vault::resolve_asset_path(&markdown_path, asset_ref)?;
+    Ok(workspace.check(&path)?)
+}
+
+/// Tauri command to turn an image or file reference in a note, such as
+/// `./images/diagram.png`, into an absolute path for `convertFileSrc`
+#[tauri::command]
+fn resolve_asset_path(
+    workspace: State<'_, WorkspaceState>,
+    markdown_path: String,
+    asset_ref: String,
+) -> Result<String, String> {
+    resolve_asset(&workspace, &markdown_path, &asset_ref)
+}
+
+/// Tauri command to resolve several asset references from one note,
+/// reporting each failure against its reference
+#[tauri::command]
+fn resolve_asset_paths(
+    workspace: State<'_, WorkspaceState>,
+    markdown_path: String,
+    refs: Vec<String>,
+) -> Vec<ResolvedAsset> {
+    refs.iter()
+        .map(|asset_ref| {
+            ResolvedAsset::new(asset_ref, resolve_asset(&workspace, &markdown_path, asset_ref))
+        })
+        .collect()
+}
+
+/// Tauri command to get the folder containing a path, or `null` at a root,
+/// for "go to parent folder"
+#[tauri::command]
+fn get_parent_directory(path: String) -> Option<String> {
+    fs::parent_directory(&path)
+}
+
+/// Tauri command to list the folders from the root down to a path, ending
+/// with the path itself, for breadcrumbs
+#[tauri::command]
+fn get_path_ancestors(path: String) -> Vec<String> {
+    fs::path_ancestors(&path)
+}
+
+/// Tauri command to check a name typed for a new file or folder before
+/// creating it. `portable` applies the rules of every platform, not just
+/// this one.
+#[tauri::command]
+fn validate_filename(name: String, portable: Option<bool>) -> NameValidation {
+    fs::validate_filename(&name, portable.unwrap_or(false))
+}
+
+/// Tauri command to clean up `.`, `..` and redundant separators in a path
+/// without touching the filesystem
+#[tauri::command]
+fn normalize_path(path: String) -> String {
+    fs::normalize_path(Path::new(&path))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Emit `operation-progress` for a tracked copy or delete
+fn emit_operation_progress(
+    app_handle: &tauri::AppHandle,
+    operation_id: &str,
+    progress: &OperationProgress,
+) {
+    let event = OperationEvent {
+        operation_id,
+        progress,
+    };
+    if let Err(e) = app_handle.emit_all("operation-progress", event) {
+        eprintln!("Failed to emit operation-progress: {}", e);
+    }
+}
+
+/// Tauri command to copy a file or directory to a new path off the main
+/// thread. With an `operation_id` it emits `operation-progress` and can be
+/// stopped with `cancel_operation`.
+#[tauri::command]
+async fn copy_file_or_directory(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    operations: State<'_, OperationState>,
+    workspace: State<'_, WorkspaceState>,
+    source: String,
+    dest: String,
+    operation_id: Option<String>,
+) -> Result<(), FsError> {
+    let source = workspace.check(&source)?;
+    let dest = workspace.check(&dest)?;
+    let owner = window.label().to_string();
+    let Some(id) = operation_id else {
+        return tauri::async_runtime::spawn_blocking(move || {
+            let locks = app_handle.state::<FileLocks>();
+            locks.write(&dest, &owner, || copy_path(&source, &dest))
+        })
+        .await
+        .map_err(|e| FsError::from(format!("Failed to copy: {}", e)))?;
+    };
+    let operation = operations.begin(&id)?;
+    let cancel = operation.cancelled();
+    tauri::async_runtime::spawn_blocking(move || {
+        let locks = app_handle.state::<FileLocks>();
+        locks.write(&dest, &owner, || {
+            copy_path_tracked(&source, &dest, &cancel, |progress| {
+                emit_operation_progress(&app_handle, &id, progress)
+            })
+        })
+    })
+    .await
+    .map_err(|e| FsError::from(format!("Failed to copy: {}", e)))?
+}
+
+/// Tauri command to stop a tracked copy or delete, returning whether it was
+/// still running
+#[tauri::command]
+fn cancel_operation(operations: State<'_, OperationState>, operation_id: String) -> bool {
+    operations.cancel(&operation_id)
+}
+
+/// Tauri command to summarize what moving a path to the trash would affect
+#[tauri::command]
+fn preview_trash(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<TrashPreview, FsError> {
+    let path = workspace.check(&path)?;
+    trash_preview(&path)
+}
+
+/// Tauri command to summarize what deleting a directory would remove
+#[tauri::command]
+fn preview_directory_delete(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<DeletePreview, FsError> {
+    let path = workspace.check(&path)?;
+    directory_delete_preview(&path)
+}
+
+/// Tauri command to summarize what deleting a file or directory would
+/// remove, for a confirmation dialog
+#[tauri::command]
+fn preview_delete(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<DeletePreview, FsError> {
+    let path = workspace.check(&path)?;
+    fs::delete_preview(&path)
+}
+
+/// Tauri command to merge one folder into another
+#[tauri::command]
+fn merge_folders(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    source: String,
+    dest: String,
+    on_conflict: ConflictPolicy,
+) -> Result<Vec<MergeOutcome>, FsError> {
+    let source = workspace.check(&source)?;
+    let dest = workspace.check(&dest)?;
+    locks.write_all(&file_paths(&[&source, &dest]), window.label(), || {
+        merge_directories(&source, &dest, on_conflict)
+    })
+}
+
+/// Tauri command to read recent entries from the vault activity log
+#[tauri::command]
+fn get_activity_log(
+    workspace: State<'_, WorkspaceState>,
+    vault_root: String,
+    limit: usize,
+) -> Result<Vec<ActivityEntry>, String> {
+    let vault_root = workspace.check(&vault_root)?;
+    read_activity_log(&vault_root, limit)
+}
+
+/// Tauri command to read a note's parsed frontmatter and the body after it
+#[tauri::command]
+fn read_file_with_frontmatter(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<ParsedNote, String> {
+    let path = workspace.check(&path)?;
+    markdown::read_file_with_frontmatter(&path)
+}
+
+/// Tauri command to parse frontmatter for several files in one call
+#[tauri::command]
+fn batch_frontmatter(
+    workspace: State<'_, WorkspaceState>,
+    paths: Vec<String>,
+) -> Vec<FrontmatterResult> {
+    read_frontmatter_batch(paths, |path| read_file(&workspace.check(path)?))
+}
+
+/// Tauri command to read a note with its outline, tasks, tags and word count
+#[tauri::command]
+fn open_note(workspace: State<'_, WorkspaceState>, path: String) -> Result<NoteBundle, String> {
+    let path = workspace.check(&path)?;
+    markdown::open_note(&path)
+}
+
+/// Tauri command to read a note split into frontmatter and body, with the
+/// body's offset in the file
+#[tauri::command]
+fn load_note_parts(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<NoteParts, String> {
+    let path = workspace.check(&path)?;
+    markdown::load_note_parts(&path)
+}
+
+/// Tauri command to move a note along with its exclusive attachments
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn move_note_bundle(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    note_path: String,
+    target_dir: String,
+    ignore: Option<Vec<String>>,
+) -> Result<NoteMoveReport, String> {
+    let root = workspace.check(&root)?;
+    let note_path = workspace.check(&note_path)?;
+    let target_dir = workspace.check(&target_dir)?;
+    let rules = vault.ignore_rules(ignore)?;
+    locks.write(&note_path, window.label(), || {
+        move_note_with_assets(&root, &note_path, &target_dir, &rules)
+    })
+}
+
+/// Tauri command to move a note into a folder named after its first tag,
+/// under the vault's configured organize root
+#[tauri::command]
+fn organize_note_by_tag(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    note_path: String,
+) -> Result<Option<String>, String> {
+    let root = workspace.check(&root)?;
+    let note_path = workspace.check(&note_path)?;
+    let organize_root = vault.config().organize_root.unwrap_or_default();
+    locks.write(&note_path, window.label(), || {
+        organize_by_tag(&root, &note_path, &organize_root)
+    })
+}
+
+/// Tauri command to report headings whose anchors collide
+#[tauri::command]
+fn check_duplicate_headings(content: String) -> Vec<DuplicateHeading> {
+    find_duplicate_headings(&content)
+}
+
+/// Tauri command to flag skipped heading levels and repeated H1s
+#[tauri::command]
+fn check_heading_structure(content: String) -> Vec<StructureIssue> {
+    heading_structure_issues(&content)
+}
+
+/// Tauri command to list a note's headings for the outline panel
+#[tauri::command]
+fn get_document_outline(content: String) -> Vec<TocEntry> {
+    extract_toc(&content)
+}
+
+/// Tauri command to split a note into heading sections with byte ranges
+#[tauri::command]
+fn get_sections(content: String) -> Vec<Section> {
+    document_sections(&content)
+}
+
+/// Tauri command to find the line of a heading for deep links to `note#heading`
+#[tauri::command]
+fn find_heading_line(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    anchor: String,
+) -> Result<Option<usize>, String> {
+    let path = workspace.check(&path)?;
+    locate_heading(&path, &anchor)
+}
+
+/// Tauri command to check a note for common writing and formatting issues
+#[tauri::command]
+fn lint_note(content: String) -> Vec<LintFinding> {
+    note_lint(&content)
+}
+
+/// Tauri command to count words and characters and estimate reading time,
+/// at 200 words per minute unless given
+#[tauri::command]
+fn get_markdown_stats(content: String, words_per_minute: Option<u32>) -> MarkdownStats {
+    markdown_stats(&content, words_per_minute.unwrap_or(DEFAULT_WORDS_PER_MINUTE))
+}
+
+/// Tauri command to render markdown as plain text
+#[tauri::command]
+fn to_plaintext(content: String) -> String {
+    markdown_to_plaintext(&content)
+}
+
+/// Tauri command to render markdown to HTML, reusing cached renders; the
+/// vault's configured cache size applies
+#[tauri::command]
+fn render_markdown(
+    vault: State<'_, VaultState>,
+    cache: State<'_, RenderCache>,
+    content: String,
+) -> String {
+    cache.set_capacity(vault.config().render_cache_entries);
+    cache.render_markdown_cached(&content)
+}
+
+/// Tauri command to render markdown to HTML, optionally pointing relative
+/// links at `asset://` URLs and sanitizing raw HTML
+#[tauri::command]
+fn render_markdown_to_html(content: String, options: Option<RenderOptions>) -> String {
+    markdown::render_markdown(&content, &options.unwrap_or_default())
+}
+
+/// Tauri command to export a note to PDF
+#[tauri::command]
+fn export_to_pdf(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    output_path: String,
+) -> Result<(), String> {
+    let path = workspace.check(&path)?;
+    let output_path = workspace.check(&output_path)?;
+    export_pdf(&path, &output_path)
+}
+
+/// Tauri command to export a note to a self-contained HTML file, embedding
+/// only images from within the vault, and return the path written
+#[tauri::command]
+fn export_to_html(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    src_path: String,
+    dest_path: String,
+    theme: Option<String>,
+    vault_root: Option<String>,
+) -> Result<String, String> {
+    let src_path = workspace.check(&src_path)?;
+    let dest_path = workspace.check(&dest_path)?;
+    let root = activity_root(&vault, vault_root);
+    export_markdown_to_html(&src_path, &dest_path, theme.as_deref(), root.as_deref())
+}
+
+/// Tauri command to show a file or folder in the system file manager
+#[tauri::command]
+fn reveal_in_file_manager(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<(), String> {
+    let path = workspace.check(&path)?;
+    launch::reveal_in_file_manager(&path)
+}
+
+/// Tauri command to open a file or folder with the OS default app
+#[tauri::command]
+fn open_with_default_app(
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<(), String> {
+    let path = workspace.check(&path)?;
+    launch::open_with_default_app(&path)
+}
+
+/// Location of the recent-files list in the app config directory
+fn recent_store(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_config_dir()
+        .map(|dir| dir.join("recent.json"))
+        .ok_or_else(|| "Failed to resolve app config directory".to_string())
+}
+
+/// Tauri command to record a file or folder as recently opened
+#[tauri::command]
+fn add_recent(app_handle: tauri::AppHandle, path: String, kind: RecentKind) -> Result<(), String> {
+    recent::add_recent(&recent_store(&app_handle)?, &path, kind)
+}
+
+/// Tauri command to list recently opened paths, newest first; pass the
+/// folder kind with a limit of 1 to find the workspace to reopen
+#[tauri::command]
+fn get_recent(
+    app_handle: tauri::AppHandle,
+    kind: Option<RecentKind>,
+    limit: Option<usize>,
+) -> Result<Vec<RecentEntry>, String> {
+    recent::get_recent(&recent_store(&app_handle)?, kind, limit.unwrap_or(recent::MAX_RECENT))
+}
+
+/// Tauri command to forget every recently opened path
+#[tauri::command]
+fn clear_recent(app_handle: tauri::AppHandle) -> Result<(), String> {
+    recent::clear_recent(&recent_store(&app_handle)?)
+}
+
+/// Folder in the app data directory holding crash-recovery drafts
+fn drafts_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join("drafts"))
+        .ok_or_else(|| "Failed to resolve app data directory".to_string())
+}
+
+/// Drop the draft of a note once it has been saved to its real location. A
+/// leftover draft only means a stale recovery offer, so failures are logged
+/// rather than failing the save.
+fn clear_draft(app_handle: &tauri::AppHandle, path: &str) {
+    let cleared = drafts_dir(app_handle).and_then(|dir| drafts::discard_draft(&dir, path));
+    if let Err(e) = cleared {
+        eprintln!("Failed to clear draft of {}: {}", path, e);
+    }
+}
+
+/// Tauri command to keep a note's unsaved content so it survives a crash
+#[tauri::command]
+fn stash_draft(app_handle: tauri::AppHandle, path: String, content: String) -> Result<(), String> {
+    drafts::stash_draft(&drafts_dir(&app_handle)?, &path, &content)
+}
+
+/// Tauri command to list notes with unsaved drafts, newest first
+#[tauri::command]
+fn list_drafts(app_handle: tauri::AppHandle) -> Result<Vec<Draft>, String> {
+    drafts::list_drafts(&drafts_dir(&app_handle)?)
+}
+
+/// Tauri command to read the unsaved draft of a note, if there is one
+#[tauri::command]
+fn recover_draft(app_handle: tauri::AppHandle, path: String) -> Result<Option<String>, String> {
+    drafts::recover_draft(&drafts_dir(&app_handle)?, &path)
+}
+
+/// Tauri command to throw away the unsaved draft of a note
+#[tauri::command]
+fn discard_draft(app_handle: tauri::AppHandle, path: String) -> Result<bool, String> {
+    drafts::discard_draft(&drafts_dir(&app_handle)?, &path)
+}
+
+/// Tauri command to diff two versions of a note's content into hunks
+#[tauri::command]
+fn diff_contents(
+    old: String,
+    new: String,
+    context_lines: Option<u32>,
+) -> Result<Vec<DiffHunk>, String> {
+    diff_text(&old, &new, context_lines.unwrap_or(DEFAULT_CONTEXT_LINES))
+}
+
+/// Tauri command to diff two files, e.g. a note and one of its backups
+#[tauri::command]
+fn diff_files(
+    workspace: State<'_, WorkspaceState>,
+    path_a: String,
+    path_b: String,
+    context_lines: Option<u32>,
+) -> Result<Vec<DiffHunk>, String> {
+    let path_a = workspace.check(&path_a)?;
+    let path_b = workspace.check(&path_b)?;
+    diff::diff_files(&path_a, &path_b, context_lines.unwrap_or(DEFAULT_CONTEXT_LINES))
+}
+
+/// Tauri command to highlight a note's fenced code blocks
+#[tauri::command]
+fn get_highlighted_code(
+    content: String,
+    theme: Option<String>,
+) -> Result<Vec<HighlightedBlock>, String> {
+    highlight_code_blocks(&content, theme.as_deref().unwrap_or("base16-ocean.dark"))
+}
+
+/// Tauri command to build link markup from one note to another
+#[tauri::command]
+fn build_link_reference(
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    from_note: String,
+    to_note: String,
+    style: LinkStyle,
+) -> Result<String, String> {
+    let root = workspace.check(&root)?;
+    let from_note = workspace.check(&from_note)?;
+    let to_note = workspace.check(&to_note)?;
+    link_reference(&root, &from_note, &to_note, style)
+}
+
+/// Tauri command to convert links across the vault to one style
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn convert_link_style(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    to_style: LinkStyle,
+    dry_run: bool,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<ConvertResult>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    if dry_run {
+        return convert_links(&root, to_style, dry_run, &rules);
+    }
+    locks.write_all(&note_paths(&root, &rules), window.label(), || {
+        convert_links(&root, to_style, dry_run, &rules)
+    })
+}
+
+/// Tauri command to list markdown files over a size threshold
+#[tauri::command]
+fn find_large_notes(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    threshold_bytes: u64,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<FileItem>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    vault::find_large_notes(&root, threshold_bytes, &rules)
+}
+
+/// Tauri command to find notes with identical content and report, trash or
+/// link the extra copies, logging each trashed copy in the vault
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn dedupe_notes(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    action: DedupAction,
+    ignore: Option<Vec<String>>,
+    vault_root: Option<String>,
+) -> Result<Vec<DedupGroup>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    let log_root = activity_root(&vault, vault_root);
+    let dedupe = || {
+        deduplicate_notes(&root, action, &rules, |path| {
+            logged_delete(
+                log_root.as_deref(),
+                path,
+                ActivityAction::Trash,
+                move_to_trash,
+            )
+            .map_err(String::from)
+        })
+    };
+    match action {
+        DedupAction::Report => dedupe(),
+        _ => locks.write_all(&note_paths(&root, &rules), window.label(), dedupe),
+    }
+}
+
+/// Tauri command to read a note with a generated "Linked mentions" section
+/// appended; the section is delimited and must not be saved back
+#[tauri::command]
+fn note_with_backlinks(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    note_path: String,
+    ignore: Option<Vec<String>>,
+) -> Result<String, String> {
+    let root = workspace.check(&root)?;
+    let note_path = workspace.check(&note_path)?;
+    let rules = vault.ignore_rules(ignore)?;
+    render_with_backlinks(&root, &note_path, &rules)
+}
+
+/// Tauri command to list lines carrying follow-up markers like `TODO`,
+/// skipping code blocks unless `include_code` is set
+#[tauri::command]
+fn find_todo_markers(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    markers: Option<Vec<String>>,
+    include_code: Option<bool>,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<MarkerHit>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    let markers = markers
+        .unwrap_or_else(|| DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect());
+    find_markers(&root, &markers, !include_code.unwrap_or(false), &rules)
+}
+
+/// Tauri command to total word counts per folder for a treemap
+#[tauri::command]
+fn get_folder_word_counts(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<FolderWordCount>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    folder_word_counts(&root, &rules)
+}
+
+/// Tauri command to list notes whose file name disagrees with their title
+#[tauri::command]
+fn find_title_mismatches(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<TitleMismatch>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    title_mismatches(&root, &rules)
+}
+
+/// Tauri command to write an index note linking every note in the vault
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn build_index_note(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    output_path: String,
+    grouping: Grouping,
+    style: Option<LinkStyle>,
+    ignore: Option<Vec<String>>,
+) -> Result<String, String> {
+    let root = workspace.check(&root)?;
+    let output_path = workspace.check(&output_path)?;
+    let rules = vault.ignore_rules(ignore)?;
+    locks.write(&output_path, window.label(), || {
+        generate_index_note(
+            &root,
+            &output_path,
+            grouping,
+            style.unwrap_or(LinkStyle::Wiki),
+            &rules,
+        )
+    })
+}
+
+/// Tauri command to list the tag pairs that most often appear together
+#[tauri::command]
+fn get_tag_cooccurrence(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    limit: Option<usize>,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<TagPair>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    tag_cooccurrence(&root, limit.unwrap_or(50), &rules)
+}
+
+/// Tauri command to list notes without backlinks
+#[tauri::command]
+fn get_orphan_notes(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    mode: Option<OrphanMode>,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    find_orphan_notes(&root, mode.unwrap_or_default(), &rules)
+}
+
+/// Tauri command to repair a note's broken asset links
+#[tauri::command]
+fn repair_links(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    note_path: String,
+    ignore: Option<Vec<String>>,
+) -> Result<RepairReport, String> {
+    let root = workspace.check(&root)?;
+    let note_path = workspace.check(&note_path)?;
+    let rules = vault.ignore_rules(ignore)?;
+    locks.write(&note_path, window.label(), || {
+        repair_asset_links(&root, &note_path, &rules)
+    })
+}
+
+/// Tauri command to find notes whose frontmatter date falls within a range
+#[tauri::command]
+fn find_by_date_range(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    field: String,
+    start: String,
+    end: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    query_by_date_range(&root, &field, &start, &end, &rules)
+}
+
+/// Tauri command to count notes created per day, week or month
+#[tauri::command]
+fn get_creation_histogram(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    bucket: Bucket,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<HistogramBucket>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    creation_histogram(&root, bucket, &rules)
+}
+
+/// Tauri command to back up the vault into a timestamped folder, emitting
+/// `snapshot-progress` as large vaults are copied
+#[tauri::command]
+fn create_snapshot(
+    app_handle: tauri::AppHandle,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    backup_root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<String, String> {
+    let root = workspace.check(&root)?;
+    let backup_root = workspace.check(&backup_root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    snapshot_vault(&root, &backup_root, &rules, |progress| {
+        if let Err(e) = app_handle.emit_all("snapshot-progress", progress) {
+            eprintln!("Failed to emit snapshot-progress: {}", e);
+        }
+    })
+}
+
+/// Tauri command to save a file, first backing up what was on disk into the
+/// vault's configured backup folder. Returns the backup's path, if one was
+/// made.
+#[tauri::command]
+fn save_file_with_backup(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+) -> Result<Option<String>, String> {
+    let target = workspace.check(&path)?;
+    let config = vault.config();
+    let backup = locks.write(&target, window.label(), || {
+        write_file_with_backup(&target, &content, &config.backup_dir, config.backups_per_file)
+    })?;
+    clear_draft(&app_handle, &path);
+    Ok(backup)
+}
+
+/// Tauri command to list a file's save backups, newest first
+#[tauri::command]
+fn list_backups(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<Vec<FileBackup>, String> {
+    let path = workspace.check(&path)?;
+    Ok(list_file_backups(&path, &vault.config().backup_dir))
+}
+
+/// Tauri command to overwrite a file with one of its backups
+#[tauri::command]
+fn restore_backup(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    backup_path: String,
+    target: String,
+) -> Result<(), String> {
+    let backup_path = workspace.check(&backup_path)?;
+    let target = workspace.check(&target)?;
+    locks.write(&target, window.label(), || {
+        restore_file_backup(&backup_path, &target)
+    })
+}
+
+/// Tauri command to delete all but the most recent `keep` snapshots
+#[tauri::command]
+fn prune_backups(
+    workspace: State<'_, WorkspaceState>,
+    backup_root: String,
+    keep: usize,
+) -> Result<Vec<String>, String> {
+    let backup_root = workspace.check(&backup_root)?;
+    prune_snapshots(&backup_root, keep)
+}
+
+/// Tauri command to find the shortest chain of links between two notes
+#[tauri::command]
+fn find_link_path(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    from_note: String,
+    to_note: String,
+    directed: Option<bool>,
+    ignore: Option<Vec<String>>,
+) -> Result<Option<Vec<String>>, String> {
+    let root = workspace.check(&root)?;
+    let from_note = workspace.check(&from_note)?;
+    let to_note = workspace.check(&to_note)?;
+    let rules = vault.ignore_rules(ignore)?;
+    link_path_between(&root, &from_note, &to_note, directed.unwrap_or(false), &rules)
+}
+
+/// Tauri command to list the notes linked from an index note in reading order
+#[tauri::command]
+fn get_reading_order(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    index_note: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let root = workspace.check(&root)?;
+    let index_note = workspace.check(&index_note)?;
+    let rules = vault.ignore_rules(ignore)?;
+    reading_order(&root, &index_note, &rules)
+}
+
+/// Tauri command to search the vault's notes for a phrase, case-sensitive by
+/// default, optionally as whole words or a regular expression
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn search_directory(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    query: String,
+    context_lines: Option<usize>,
+    case_sensitive: Option<bool>,
+    whole_word: Option<bool>,
+    regex: Option<bool>,
+    max_results: Option<usize>,
+    max_file_bytes: Option<u64>,
+    ignore: Option<Vec<String>>,
+    follow_symlinks: Option<bool>,
+) -> Result<Vec<SearchMatch>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    let defaults = SearchOptions::default();
+    let options = SearchOptions {
+        context_lines: context_lines.unwrap_or(defaults.context_lines),
+        case_sensitive: case_sensitive.unwrap_or(defaults.case_sensitive),
+        whole_word: whole_word.unwrap_or(defaults.whole_word),
+        regex: regex.unwrap_or(defaults.regex),
+        max_results: max_results.unwrap_or(defaults.max_results),
+        max_file_bytes: max_file_bytes.unwrap_or(defaults.max_file_bytes),
+        follow_symlinks: follow_symlinks.unwrap_or(defaults.follow_symlinks),
+    };
+    search_files(&root, &query, &options, &rules)
+}
+
+/// Tauri command to quick-open files by fuzzy-matching their names,
+/// skipping `node_modules` as well as the ignore rules
+#[tauri::command]
+fn find_files_fuzzy(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    query: String,
+    limit: Option<usize>,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<FuzzyMatch>, String> {
+    let root = workspace.check(&root)?;
+    let mut patterns = ignore.unwrap_or_else(|| vault.config().ignore_patterns);
+    patterns.extend(QUICK_OPEN_SKIP.iter().map(|s| s.to_string()));
+    let rules = vault.ignore_rules(Some(patterns))?;
+    search::find_files_fuzzy(&root, &query, limit.unwrap_or(50), &rules)
+}
+
+/// Tauri command to list files under `root` matching any of several glob
+/// patterns, e.g. `**/*.md`, skipping those matched by `exclude` as well as
+/// the vault's ignore patterns
+#[tauri::command]
+fn find_files_by_glob(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    patterns: Vec<String>,
+    exclude: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let root = workspace.check(&root)?;
+    let mut ignored = vault.config().ignore_patterns;
+    ignored.extend(exclude.unwrap_or_default());
+    let rules = vault.ignore_rules(Some(ignored))?;
+    glob_files(&root, &patterns, &rules)
+}
+
+/// Tauri command to tally frontmatter keys across the vault
+#[tauri::command]
+fn analyze_frontmatter_keys(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<KeyStat>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    frontmatter_key_report(&root, &rules)
+}
+
+/// Tauri command to gather note names, tags and headings for autocomplete
+/// in one scan
+#[tauri::command]
+fn get_autocomplete_data(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<AutocompleteData, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    autocomplete_data(&root, &rules)
+}
+
+/// Tauri command to count the languages of code blocks across the vault
+#[tauri::command]
+fn get_code_languages(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<LangCount>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    code_block_languages(&root, &rules)
+}
+
+/// Tauri command to sort notes by a frontmatter field, `order` by default
+#[tauri::command]
+fn sort_by_frontmatter(
+    workspace: State<'_, WorkspaceState>,
+    paths: Vec<String>,
+    field: Option<String>,
+) -> Result<Vec<String>, String> {
+    let paths = paths
+        .iter()
+        .map(|path| workspace.check(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let field = field.as_deref().unwrap_or("order");
+    Ok(order_by_frontmatter(paths, field))
+}
+
+/// Tauri command to list every link from the vault's notes to a web page
+#[tauri::command]
+fn get_external_links(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<Vec<ExternalLinkRef>, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    find_external_links(&root, &rules)
+}
+
+/// Tauri command to check that web links respond, off the main thread
+#[tauri::command]
+async fn verify_external_links(
+    urls: Vec<String>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<LinkCheckResult>, String> {
+    let timeout_ms = timeout_ms.unwrap_or(10_000);
+    tauri::async_runtime::spawn_blocking(move || check_external_links(urls, timeout_ms))
+        .await
+        .map_err(|e| format!("Failed to check links: {}", e))
+}
+
+/// Tauri command to check the vault for broken links, encoding problems,
+/// ambiguous names and unused attachments
+#[tauri::command]
+fn run_vault_validation(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+    ignore: Option<Vec<String>>,
+) -> Result<VaultValidation, String> {
+    let root = workspace.check(&root)?;
+    let rules = vault.ignore_rules(ignore)?;
+    validate_vault(&root, &rules)
+}
+
+/// Tauri command to count recent commits touching each file in a repository
+#[tauri::command]
+fn get_change_frequency(
+    workspace: State<'_, WorkspaceState>,
+    repo_path: String,
+    since_days: u32,
+    include_unchanged: Option<bool>,
+) -> Result<Vec<FileChangeFreq>, String> {
+    let repo_path = workspace.check(&repo_path)?;
+    change_frequency(&repo_path, since_days, include_unchanged.unwrap_or(false))
+}
+
+/// Tauri command to list notes added or modified since a commit
+#[tauri::command]
+fn get_changes_since_commit(
+    workspace: State<'_, WorkspaceState>,
+    repo_path: String,
+    commit: String,
+) -> Result<Vec<String>, String> {
+    let repo_path = workspace.check(&repo_path)?;
+    notes_changed_since(&repo_path, &commit)
+}
+
+/// Tauri command to find who wrote most of a note's current lines
+#[tauri::command]
+fn get_primary_author(
+    workspace: State<'_, WorkspaceState>,
+    repo_path: String,
+    file_path: String,
+) -> Option<AuthorStat> {
+    let repo_path = workspace.check(&repo_path).ok()?;
+    let file_path = workspace.check(&file_path).ok()?;
+    note_primary_author(&repo_path, &file_path)
+}
+
+/// Tauri command to restore a file's working copy to an earlier revision
+/// without committing
+#[tauri::command]
+fn restore_revision(
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    workspace: State<'_, WorkspaceState>,
+    repo_path: String,
+    file_path: String,
+    rev: String,
+) -> Result<(), String> {
+    let repo_path = workspace.check(&repo_path)?;
+    let file_path = workspace.check(&file_path)?;
+    locks.write(&file_path, window.label(), || {
+        restore_file_revision(&repo_path, &file_path, &rev)
+    })
+}
+
+/// Tauri command to open a vault and load its configuration
+#[tauri::command]
+fn open_vault(
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<VaultConfig, String> {
+    let path = workspace.check(&path)?;
+    vault.open(&path)
+}
+
+/// Tauri command to read the open vault's configuration
+#[tauri::command]
+fn get_vault_config(vault: State<'_, VaultState>) -> VaultConfig {
+    vault.config()
+}
+
+/// Tauri command to set the file extensions listed as markdown, e.g. adding
+/// `mdx`; an empty list restores `md`, `markdown` and `mdown`
+#[tauri::command]
+fn set_markdown_extensions(
+    vault: State<'_, VaultState>,
+    extensions: Vec<String>,
+) -> Result<VaultConfig, String> {
+    vault.set_markdown_extensions(extensions)
+}
+
+/// Tauri command to merge and persist changes to the vault configuration
+#[tauri::command]
+fn update_vault_config(
+    vault: State<'_, VaultState>,
+    partial: serde_json::Value,
+) -> Result<VaultConfig, String> {
+    vault.update(partial)
+}
+
+/// Tauri command to start watching a directory for changes; `watch-ready`
+/// follows once the watch is established. The vault's configured debounce
+/// interval and markdown extensions apply unless the options give them, and
+/// its ignore patterns are added to the options' own.
+#[tauri::command]
+fn watch_directory(
+    app_handle: tauri::AppHandle,
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    options: Option<WatchOptions>,
+) -> Result<(), String> {
+    let path = workspace.check(&path)?;
+    let config = vault.config();
+    let mut options = options.unwrap_or_default();
+    options.debounce_ms.get_or_insert(config.debounce_ms);
+    options.ignore.extend(config.ignore_patterns);
+    options
+        .markdown_extensions
+        .get_or_insert(config.markdown_extensions);
+    watcher.watch_directory(app_handle, &path, options)
+}
+
+/// Tauri command to start watching a directory and return its contents,
+/// giving the frontend a baseline that later change events build on. The
+/// vault's debounce interval and markdown extensions apply unless given,
+/// and its ignore patterns are added to the options' own.
+#[tauri::command]
+fn watch_directory_with_snapshot(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    vault: State<'_, VaultState>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    options: Option<WatchOptions>,
+    directory_options: Option<DirectoryOptions>,
+) -> Result<Vec<FileItem>, String> {
+    let path = workspace.check(&path)?;
+    let config = vault.config();
+    let mut options = options.unwrap_or_default();
+    options.debounce_ms.get_or_insert(config.debounce_ms);
+    options.ignore.extend(config.ignore_patterns);
+    options
+        .markdown_extensions
+        .get_or_insert_with(|| config.markdown_extensions.clone());
+    let mut directory_options = directory_options.unwrap_or_default();
+    directory_options
+        .markdown_extensions
+        .get_or_insert(config.markdown_extensions);
+    watcher.watch_directory_with_snapshot(app_handle, &path, options, &directory_options)
+}
+
+/// Tauri command to create a note and immediately watch it for changes
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn create_and_watch(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    locks: State<'_, FileLocks>,
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+    content: String,
+) -> Result<(), String> {
+    let path = workspace.check(&path)?;
+    let extensions = vault.config().markdown_extensions;
+    locks.write(&path, window.label(), || {
+        watcher.create_and_watch(app_handle, &path, &content, Some(extensions))
+    })
+}
+
+/// Tauri command to watch a single file, which may be outside the
+/// workspace, for live reload; replaces any previous single-file watch
+#[tauri::command]
+fn watch_file(
+    app_handle: tauri::AppHandle,
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    path: String,
+) -> Result<(), String> {
+    let extensions = vault.config().markdown_extensions;
+    watcher.watch_file(app_handle, &path, Some(extensions))
+}
+
+/// Tauri command to follow a growing file such as a log, emitting appended
+/// text as `file-tail` events; returns the offset reading starts from
+#[tauri::command]
+fn tail_file(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    path: String,
+) -> Result<u64, String> {
+    let path = workspace.check(&path)?;
+    watcher.tail_file(app_handle, &path)
+}
+
+/// Tauri command to stop following a file, returning whether it was
+#[tauri::command]
+fn stop_tail(watcher: State<'_, DirectoryWatcher>, path: String) -> bool {
+    watcher.stop_tail(&path)
+}
+
+/// Tauri command to stop the single-file watch, returning whether there
+/// was one
+#[tauri::command]
+fn unwatch_file(watcher: State<'_, DirectoryWatcher>) -> bool {
+    watcher.unwatch_file()
+}
+
+/// Tauri command to report the single file currently being watched
+#[tauri::command]
+fn get_watched_file(watcher: State<'_, DirectoryWatcher>) -> Option<String> {
+    watcher.watched_file()
+}
+
+/// Tauri command to get the vault index kept current by the watch on the
+/// vault
+#[tauri::command]
+fn get_cached_index(
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    vault_root: Option<String>,
+) -> Result<VaultIndex, String> {
+    let root = activity_root(&vault, vault_root).ok_or("No vault is open")?;
+    watcher
+        .cached_index(Path::new(&root))
+        .ok_or_else(|| "No vault index; watch a directory with track_index".to_string())
+}
+
+/// Tauri command to query the search index kept current by the watch on the
+/// vault
+#[tauri::command]
+fn search_indexed(
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    query: String,
+    vault_root: Option<String>,
+) -> Result<Vec<IndexedHit>, String> {
+    let root = activity_root(&vault, vault_root).ok_or("No vault is open")?;
+    watcher
+        .search_indexed(Path::new(&root), &query)
+        .ok_or_else(|| "No search index; watch a directory with track_search_index".to_string())
+}
+
+/// Where search indexes are saved between runs, one file per vault
+fn search_index_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join("search-index"))
+        .ok_or_else(|| "Failed to resolve app data directory".to_string())
+}
+
+/// Load the saved search index of `root` and catch it up, or build one,
+/// off the main thread, then save it and hand it to the watch of its tree,
+/// if any, to keep current and save as it changes. Returns how many notes
+/// it holds.
+async fn install_search_index(
+    app_handle: &tauri::AppHandle,
+    watcher: &DirectoryWatcher,
+    root: PathBuf,
+) -> Result<usize, String> {
+    let dir = search_index_dir(app_handle)?;
+    let index = tauri::async_runtime::spawn_blocking(move || {
+        let mut index = SearchIndex::load_or_build(&dir, &root);
+        index.save_changes();
+        index
+    })
+    .await
+    .map_err(|e| format!("Failed to build search index: {}", e))?;
+    let notes = index.note_count();
+    watcher.set_search_index(index);
+    Ok(notes)
+}
+
+/// Tauri command to build the search index of a vault off the main thread,
+/// reusing the copy saved by an earlier run so only changed notes are read
+/// again. Returns how many notes are indexed.
+#[tauri::command]
+async fn build_search_index(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    root: String,
+) -> Result<usize, String> {
+    let root = workspace.check(&root)?;
+    if !Path::new(&root).is_dir() {
+        return Err(format!("Path is not a directory: {}", root));
+    }
+    install_search_index(&app_handle, &watcher, fs::normalize_path(Path::new(&root))).await
+}
+
+/// Tauri command to find the notes containing every word of a query using
+/// the search index of the vault, building it off the main thread on first
+/// use. While no watch would keep the index current, notes are scanned
+/// directly instead.
+#[tauri::command]
+async fn search_index_query(
+    app_handle: tauri::AppHandle,
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    workspace: State<'_, WorkspaceState>,
+    query: String,
+    vault_root: Option<String>,
+) -> Result<Vec<IndexedHit>, String> {
+    let root = activity_root(&vault, vault_root).ok_or("No vault is open")?;
+    let root = workspace.check(&root)?;
+    let root = fs::normalize_path(Path::new(&root));
+    if let Some(hits) = watcher.query_search_index(&root, &query) {
+        return Ok(hits);
+    }
+    if !watcher.watches_tree(&root) {
+        return tauri::async_runtime::spawn_blocking(move || scan_notes(&root, &query))
+            .await
+            .map_err(|e| format!("Failed to search notes: {}", e));
+    }
+    install_search_index(&app_handle, &watcher, root.clone()).await?;
+    Ok(watcher
+        .query_search_index(&root, &query)
+        .unwrap_or_default())
+}
+
+/// Tauri command to stop watching one directory, leaving the others running
+#[tauri::command]
+fn unwatch_directory(watcher: State<'_, DirectoryWatcher>, path: String) -> Result<(), String> {
+    watcher.unwatch_directory(&path)
+}
+
+/// Tauri command to list the directories currently being watched
+#[tauri::command]
+fn get_watched_directories(watcher: State<'_, DirectoryWatcher>) -> Vec<String> {
+    watcher.watched_directories()
+}
+
+/// Tauri command to list every running watch with its mode and debounce
+/// interval, so the frontend can reconcile its state after a reload
+#[tauri::command]
+fn get_watched_paths(watcher: State<'_, DirectoryWatcher>) -> Vec<WatchedPath> {
+    watcher.watched_paths()
+}
+
+/// Tauri command to check whether a directory or file is being watched
+#[tauri::command]
+fn is_watching(watcher: State<'_, DirectoryWatcher>, path: String) -> bool {
+    watcher.is_watching(&path)
+}
+
+/// Tauri command to hold back watcher events during a bulk operation
 #[tauri::command]
-fn save_file_contents(path: String, content: String) -> Result<(), String> {
-    write_file(&path, &content)
+fn pause_watching(watcher: State<'_, DirectoryWatcher>) {
+    watcher.pause_watching();
+}
+
+/// Tauri command to resume watcher events, by default emitting a single
+/// `refresh` if any were dropped while paused
+#[tauri::command]
+fn resume_watching(
+    app_handle: tauri::AppHandle,
+    watcher: State<'_, DirectoryWatcher>,
+    refresh: Option<bool>,
+) -> bool {
+    watcher.resume_watching(app_handle, refresh.unwrap_or(true))
+}
+
+/// Tauri command to check whether watcher events are being held back
+#[tauri::command]
+fn is_watching_paused(watcher: State<'_, DirectoryWatcher>) -> bool {
+    watcher.is_paused()
+}
+
+/// Tauri command to stop watching every directory
+#[tauri::command]
+fn stop_watching(watcher: State<'_, DirectoryWatcher>) {
+    watcher.stop_watching();
+}
+
+/// Tauri command to mark a file as open in the editor
+#[tauri::command]
+fn register_open_file(watcher: State<'_, DirectoryWatcher>, path: String) {
+    watcher.register_open_file(&path);
+}
+
+/// Tauri command to forget a file that was closed in the editor
+#[tauri::command]
+fn unregister_open_file(watcher: State<'_, DirectoryWatcher>, path: String) {
+    watcher.unregister_open_file(&path);
+}
+
+/// Tauri command to check whether the vault changed since it was marked clean
+#[tauri::command]
+fn vault_dirty_status(
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    vault_root: Option<String>,
+) -> bool {
+    activity_root(&vault, vault_root).is_some_and(|root| watcher.is_dirty(Path::new(&root)))
+}
+
+/// Tauri command to acknowledge all vault changes so far
+#[tauri::command]
+fn mark_vault_clean(
+    vault: State<'_, VaultState>,
+    watcher: State<'_, DirectoryWatcher>,
+    vault_root: Option<String>,
+) {
+    if let Some(root) = activity_root(&vault, vault_root) {
+        watcher.mark_clean(Path::new(&root));
+    }
 }
 
 fn main() {
     tauri::Builder::default()
+        .manage(DirectoryWatcher::new())
+        .manage(VaultState::new())
+        .manage(RenderCache::default())
+        .manage(WorkspaceState::new())
+        .manage(OperationState::new())
+        .manage(FileLocks::new())
         .invoke_handler(tauri::generate_handler![
             get_directory_contents,
+            get_directory_listing,
+            get_file_info,
+            get_directory_contents_filtered,
+            get_directory_tree,
+            directory_stats,
+            get_directory_signature,
             select_directory,
+            set_workspace_root,
             read_file_contents,
-            save_file_contents
+            read_text_file,
+            is_binary_file,
+            read_file_chunk,
+            read_file_head,
+            get_file_size,
+            read_file_contents_with_encoding,
+            save_file_contents_with_encoding,
+            try_lock_file,
+            unlock_file,
+            save_file_contents,
+            append_file_contents,
+            save_file_contents_checked,
+            read_file_contents_with_eol,
+            save_file_contents_with_eol,
+            apply_transaction,
+            tidy_blank_lines,
+            save_file_clean,
+            create_file_exclusive,
+            create_file_from_template,
+            create_unique_file,
+            create_unique_directory,
+            get_file_hash,
+            hash_paths,
+            trim_file,
+            reclassify_items,
+            save_optimized_image,
+            delete_file_at_path,
+            delete_directory_at_path,
+            move_to_trash_at_path,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            rename_file_or_directory,
+            move_into_directory,
+            delete_paths,
+            empty_directory,
+            save_files,
+            rename_paths,
+            bulk_rename,
+            copy_file_or_directory,
+            cancel_operation,
+            set_readonly,
+            set_permissions,
+            canonicalize_path,
+            normalize_path,
+            relative_path,
+            get_parent_directory,
+            get_path_ancestors,
+            resolve_asset_path,
+            resolve_asset_paths,
+            validate_filename,
+            preview_trash,
+            preview_directory_delete,
+            preview_delete,
+            merge_folders,
+            get_activity_log,
+            read_file_with_frontmatter,
+            batch_frontmatter,
+            move_note_bundle,
+            organize_note_by_tag,
+            check_duplicate_headings,
+            check_heading_structure,
+            get_document_outline,
+            find_heading_line,
+            get_sections,
+            to_plaintext,
+            get_markdown_stats,
+            render_markdown,
+            render_markdown_to_html,
+            export_to_pdf,
+            export_to_html,
+            reveal_in_file_manager,
+            open_with_default_app,
+            add_recent,
+            get_recent,
+            clear_recent,
+            lint_note,
+            diff_contents,
+            diff_files,
+            get_highlighted_code,
+            open_note,
+            load_note_parts,
+            build_link_reference,
+            convert_link_style,
+            find_large_notes,
+            find_title_mismatches,
+            get_folder_word_counts,
+            find_todo_markers,
+            dedupe_notes,
+            note_with_backlinks,
+            build_index_note,
+            get_tag_cooccurrence,
+            get_orphan_notes,
+            repair_links,
+            find_by_date_range,
+            get_creation_histogram,
+            find_link_path,
+            create_snapshot,
+            prune_backups,
+            save_file_with_backup,
+            list_backups,
+            restore_backup,
+            get_reading_order,
+            search_directory,
+            find_files_fuzzy,
+            find_files_by_glob,
+            analyze_frontmatter_keys,
+            get_code_languages,
+            get_autocomplete_data,
+            sort_by_frontmatter,
+            run_vault_validation,
+            get_external_links,
+            verify_external_links,
+            get_change_frequency,
+            get_changes_since_commit,
+            get_primary_author,
+            restore_revision,
+            open_vault,
+            get_vault_config,
+            update_vault_config,
+            set_markdown_extensions,
+            watch_directory,
+            watch_directory_with_snapshot,
+            create_and_watch,
+            watch_file,
+            tail_file,
+            stop_tail,
+            unwatch_file,
+            get_watched_file,
+            get_cached_index,
+            search_indexed,
+            build_search_index,
+            search_index_query,
+            stop_watching,
+            pause_watching,
+            resume_watching,
+            is_watching_paused,
+            unwatch_directory,
+            get_watched_directories,
+            get_watched_paths,
+            is_watching,
+            register_open_file,
+            unregister_open_file,
+            vault_dirty_status,
+            mark_vault_clean,
+            stash_draft,
+            list_drafts,
+            recover_draft,
+            discard_draft
         ])
+        .on_window_event(|event| {
+            // A closed window can no longer release its editing locks
+            if let tauri::WindowEvent::Destroyed = event.event() {
+                let window = event.window();
+                window.state::<FileLocks>().unlock_all(window.label());
+            }
+        })
         .setup(|app| {
             #[cfg(debug_assertions)]
             {
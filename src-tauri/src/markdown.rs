@@ -0,0 +1,1383 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+use std::path::Path;
+
+use crate::fs::{normalize_path, read_file, FsError};
+
+/// A markdown document split into its frontmatter and body
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ParsedNote {
+    pub frontmatter: Option<Value>,
+    pub body: String,
+}
+
+/// A heading found in markdown content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// 1-based line number
+    pub line: usize,
+    /// GitHub-style slug of the heading text
+    pub anchor: String,
+}
+
+/// An entry in a document outline; anchors are unique within the document
+pub type TocEntry = Heading;
+
+/// Headings whose anchors collide, making all but the first unreachable
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateHeading {
+    pub anchor: String,
+    pub occurrences: Vec<Heading>,
+}
+
+/// Kind of problem in a note's heading outline
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StructureIssueKind {
+    /// A heading more than one level below the one before it
+    SkippedLevel,
+    /// An H1 after the first
+    MultipleH1,
+}
+
+/// A heading that breaks the note's outline
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StructureIssue {
+    /// 1-based line number of the offending heading
+    pub line: usize,
+    pub kind: StructureIssueKind,
+    pub message: String,
+}
+
+/// A span of a note running from one heading to the next heading of the
+/// same or higher level, so subsections nest inside their parent
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Section {
+    /// Heading text, or `None` for the preamble before the first heading
+    pub heading: Option<String>,
+    /// Heading level; 0 for the preamble
+    pub level: u8,
+    /// Byte range of the section within the content
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A checkbox task list item
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Task {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub checked: bool,
+}
+
+/// Everything the editor needs when opening a note, from a single read
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteBundle {
+    pub path: String,
+    pub content: String,
+    pub title: String,
+    pub frontmatter: Option<Value>,
+    pub headings: Vec<Heading>,
+    pub tasks: Vec<Task>,
+    pub tags: Vec<String>,
+    pub word_count: usize,
+}
+
+/// A note split at the end of its frontmatter, so editor positions in the
+/// body map back to offsets in the file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NoteParts {
+    /// YAML between the `---` fences, as written
+    pub raw_frontmatter: Option<String>,
+    /// Parsed frontmatter; `None` when absent or not valid YAML
+    pub frontmatter: Option<Value>,
+    pub body: String,
+    /// Byte offset in the file where `body` starts
+    pub body_start: usize,
+}
+
+/// A fenced code block
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// First word of the fence's info string, if any
+    pub language: Option<String>,
+    pub code: String,
+    /// 1-based line number of the opening fence
+    pub line: usize,
+}
+
+/// Per-file result of a batch frontmatter read
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FrontmatterResult {
+    pub path: String,
+    pub frontmatter: Option<Value>,
+    pub error: Option<String>,
+}
+
+/// Syntax a link was written in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Wiki,
+    Markdown,
+}
+
+/// A link found in markdown content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// Whether the link is an image or embed (`![...]`)
+    pub embed: bool,
+    /// Display text, or the target itself when a wiki link has no alias
+    pub text: String,
+    /// Link target without any `#anchor`
+    pub target: String,
+    pub anchor: Option<String>,
+    /// 1-based line number
+    pub line: usize,
+    /// Byte range of the target within the content
+    pub target_start: usize,
+    pub target_end: usize,
+    /// Byte range of the whole link syntax within the content
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A line of content with its position and code-block state
+pub(crate) struct LineInfo<'a> {
+    /// 1-based line number
+    pub number: usize,
+    /// Byte offset of the line start within the content
+    pub start: usize,
+    pub text: &'a str,
+    /// Whether the line is a fence delimiter or inside a fenced code block
+    pub in_code: bool,
+}
+
+/// Split content into lines, tracking fenced code blocks
+pub(crate) fn scan_lines(content: &str) -> Vec<LineInfo<'_>> {
+    let mut lines = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut start = 0;
+
+    for (index, raw) in content.split_inclusive('\n').enumerate() {
+        let text = raw.trim_end_matches(['\n', '\r']);
+        let trimmed = text.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let run = marker.map_or(0, |m| trimmed.chars().take_while(|c| *c == m).count());
+
+        let in_code = match (fence, marker) {
+            (Some((open, len)), Some(m)) if m == open && run >= len => {
+                fence = None;
+                true
+            }
+            (Some(_), _) => true,
+            (None, Some(m)) if run >= 3 => {
+                fence = Some((m, run));
+                true
+            }
+            (None, _) => false,
+        };
+
+        lines.push(LineInfo {
+            number: index + 1,
+            start,
+            text,
+            in_code,
+        });
+        start += raw.len();
+    }
+
+    lines
+}
+
+/// Byte ranges of inline code spans within a line
+fn inline_code_ranges(line: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut open: Option<usize> = None;
+    for (i, c) in line.char_indices() {
+        if c == '`' {
+            match open.take() {
+                Some(start) => ranges.push((start, i + 1)),
+                None => open = Some(i),
+            }
+        }
+    }
+    ranges
+}
+
+fn wiki_link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(!?)\[\[([^\[\]|#]*)(?:#([^\[\]|]*))?(?:\|([^\[\]]*))?\]\]").unwrap()
+    })
+}
+
+fn markdown_link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r#"(!?)\[([^\[\]]*)\]\(\s*(?:<([^>]*)>|([^()\s]+))(?:\s+(?:"[^"]*"|'[^']*'))?\s*\)"#,
+        )
+        .unwrap()
+    })
+}
+
+/// Extract wiki (`[[Note]]`) and markdown (`[text](target)`) links, skipping
+/// code blocks and inline code
+pub fn extract_links(content: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+
+    for line in scan_lines(content).into_iter().filter(|l| !l.in_code) {
+        let code = inline_code_ranges(line.text);
+        let in_code = |pos: usize| code.iter().any(|(s, e)| pos >= *s && pos < *e);
+
+        for caps in wiki_link_regex().captures_iter(line.text) {
+            let whole = caps.get(0).unwrap();
+            if in_code(whole.start()) {
+                continue;
+            }
+            let target = caps.get(2).unwrap();
+            let name = target.as_str().trim();
+            if name.is_empty() {
+                continue;
+            }
+            links.push(Link {
+                kind: LinkKind::Wiki,
+                embed: !caps[1].is_empty(),
+                text: caps
+                    .get(4)
+                    .map(|m| m.as_str().trim().to_string())
+                    .unwrap_or_else(|| name.to_string()),
+                target: name.to_string(),
+                anchor: caps.get(3).map(|m| m.as_str().trim().to_string()),
+                line: line.number,
+                target_start: line.start + target.start(),
+                target_end: line.start + target.end(),
+                start: line.start + whole.start(),
+                end: line.start + whole.end(),
+            });
+        }
+
+        for caps in markdown_link_regex().captures_iter(line.text) {
+            let whole = caps.get(0).unwrap();
+            if in_code(whole.start()) {
+                continue;
+            }
+            let raw = caps.get(3).or_else(|| caps.get(4)).unwrap();
+            let (target, anchor) = match raw.as_str().split_once('#') {
+                Some((path, anchor)) => (path, Some(anchor.to_string())),
+                None => (raw.as_str(), None),
+            };
+            links.push(Link {
+                kind: LinkKind::Markdown,
+                embed: !caps[1].is_empty(),
+                text: caps[2].to_string(),
+                target: target.to_string(),
+                anchor,
+                line: line.number,
+                target_start: line.start + raw.start(),
+                target_end: line.start + raw.start() + target.len(),
+                start: line.start + whole.start(),
+                end: line.start + whole.end(),
+            });
+        }
+    }
+
+    links.sort_by_key(|link| link.start);
+    links
+}
+
+/// Whether a link target points outside the local filesystem
+pub fn is_external_link(target: &str) -> bool {
+    target.contains("://") || target.starts_with("mailto:") || target.starts_with("data:")
+}
+
+/// Decode `%XX` escapes in a link target
+pub fn decode_link_target(target: &str) -> String {
+    let bytes = target.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            if let Ok(byte) = u8::from_str_radix(&target[i + 1..i + 3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Locate a leading `---` delimited block, returning the raw YAML and the
+/// byte offset where the body starts
+pub(crate) fn split_frontmatter(content: &str) -> Option<(&str, usize)> {
+    let first_line_end = content.find('\n')?;
+    if content[..first_line_end].trim_end() != "---" {
+        return None;
+    }
+
+    let yaml_start = first_line_end + 1;
+    let mut line_start = yaml_start;
+    while line_start <= content.len() {
+        let line_end = content[line_start..]
+            .find('\n')
+            .map(|i| line_start + i)
+            .unwrap_or(content.len());
+        let line = content[line_start..line_end].trim_end();
+        if line == "---" || line == "..." {
+            let body_start = (line_end + 1).min(content.len());
+            return Some((&content[yaml_start..line_start], body_start));
+        }
+        line_start = line_end + 1;
+    }
+
+    None
+}
+
+/// Parse YAML frontmatter from markdown content.
+///
+/// Content without a terminated frontmatter block, or whose block is not
+/// valid YAML, is returned whole as the body with no frontmatter.
+pub fn parse_frontmatter(content: &str) -> ParsedNote {
+    let Some((yaml, body_start)) = split_frontmatter(content) else {
+        return ParsedNote {
+            frontmatter: None,
+            body: content.to_string(),
+        };
+    };
+
+    match serde_yaml::from_str::<Value>(yaml) {
+        Ok(value) => ParsedNote {
+            // An empty block parses as null; treat it as an empty mapping
+            frontmatter: Some(if value.is_null() {
+                Value::Object(Default::default())
+            } else {
+                value
+            }),
+            body: content[body_start..].to_string(),
+        },
+        Err(_) => ParsedNote {
+            frontmatter: None,
+            body: content.to_string(),
+        },
+    }
+}
+
+/// Convert heading text into a GitHub-style anchor slug
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse an ATX heading line (`## Title`) into its level and text
+fn parse_atx_heading(line: &str) -> Option<(u8, String)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let trimmed = &line[indent..];
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // Drop an optional closing sequence of `#`s
+    let rest = rest.trim();
+    let without_closing = rest.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        rest
+    };
+
+    Some((level as u8, text.to_string()))
+}
+
+/// Setext underline level: `===` is level 1, `---` is level 2
+fn setext_level(line: &str) -> Option<u8> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || line.len() - line.trim_start().len() > 3 {
+        return None;
+    }
+    if trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Extract ATX and setext headings, ignoring code blocks and frontmatter
+pub fn extract_headings(content: &str) -> Vec<Heading> {
+    let body_start = split_frontmatter(content).map_or(0, |(_, start)| start);
+    let lines: Vec<_> = scan_lines(content)
+        .into_iter()
+        .filter(|l| l.start >= body_start)
+        .collect();
+
+    let mut headings = Vec::new();
+    let mut previous: Option<&LineInfo> = None;
+    for line in &lines {
+        if line.in_code {
+            previous = None;
+            continue;
+        }
+
+        if let Some((level, text)) = parse_atx_heading(line.text) {
+            headings.push(Heading {
+                level,
+                anchor: slugify(&text),
+                text,
+                line: line.number,
+            });
+            previous = None;
+            continue;
+        }
+
+        if let (Some(level), Some(prev)) = (setext_level(line.text), previous) {
+            let text = prev.text.trim().to_string();
+            headings.push(Heading {
+                level,
+                anchor: slugify(&text),
+                text,
+                line: prev.number,
+            });
+            previous = None;
+            continue;
+        }
+
+        let is_paragraph = !line.text.trim().is_empty() && !line.text.starts_with("    ");
+        previous = is_paragraph.then_some(line);
+    }
+
+    headings
+}
+
+/// 1-based line of the first heading in a note matching `anchor`, given
+/// either as a slug (with or without `#`) or as the heading text
+pub fn locate_heading(path: &str, anchor: &str) -> Result<Option<usize>, String> {
+    let content = read_file(path)?;
+    let anchor = anchor.trim_start_matches('#');
+    let slug = slugify(anchor);
+    Ok(extract_headings(&content)
+        .into_iter()
+        .find(|heading| heading.anchor == anchor || heading.anchor == slug)
+        .map(|heading| heading.line))
+}
+
+/// Outline of a note's headings, with colliding anchors suffixed `-1`,
+/// `-2`, ... in document order the way GitHub links them
+pub fn extract_toc(content: &str) -> Vec<TocEntry> {
+    let mut used = std::collections::HashSet::new();
+    extract_headings(content)
+        .into_iter()
+        .map(|mut entry| {
+            let base = entry.anchor.clone();
+            let mut suffix = 0;
+            while !used.insert(entry.anchor.clone()) {
+                suffix += 1;
+                entry.anchor = format!("{}-{}", base, suffix);
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Find headings whose anchors collide with another heading in the note
+pub fn find_duplicate_headings(content: &str) -> Vec<DuplicateHeading> {
+    let mut groups: Vec<DuplicateHeading> = Vec::new();
+    for heading in extract_headings(content) {
+        match groups.iter_mut().find(|g| g.anchor == heading.anchor) {
+            Some(group) => group.occurrences.push(heading),
+            None => groups.push(DuplicateHeading {
+                anchor: heading.anchor.clone(),
+                occurrences: vec![heading],
+            }),
+        }
+    }
+    groups.retain(|g| g.occurrences.len() > 1);
+    groups
+}
+
+/// Flag headings that jump more than one level deeper than the previous
+/// heading, and every H1 after the first
+pub fn heading_structure_issues(content: &str) -> Vec<StructureIssue> {
+    let mut issues = Vec::new();
+    let mut previous: Option<u8> = None;
+    let mut seen_h1 = false;
+    for heading in extract_headings(content) {
+        if let Some(previous) = previous {
+            if heading.level > previous + 1 {
+                issues.push(StructureIssue {
+                    line: heading.line,
+                    kind: StructureIssueKind::SkippedLevel,
+                    message: format!("H{} follows H{}, skipping a level", heading.level, previous),
+                });
+            }
+        }
+        if heading.level == 1 {
+            if seen_h1 {
+                issues.push(StructureIssue {
+                    line: heading.line,
+                    kind: StructureIssueKind::MultipleH1,
+                    message: "Note has more than one H1".to_string(),
+                });
+            }
+            seen_h1 = true;
+        }
+        previous = Some(heading.level);
+    }
+    issues
+}
+
+/// Split a note into heading sections with byte ranges, plus a preamble
+/// section for any content before the first heading
+pub fn document_sections(content: &str) -> Vec<Section> {
+    let line_starts: Vec<usize> = scan_lines(content).iter().map(|l| l.start).collect();
+    let headings: Vec<(u8, String, usize)> = extract_headings(content)
+        .into_iter()
+        .map(|h| (h.level, h.text, line_starts[h.line - 1]))
+        .collect();
+
+    let mut sections = Vec::new();
+    let first_start = headings.first().map_or(content.len(), |h| h.2);
+    if first_start > 0 {
+        sections.push(Section {
+            heading: None,
+            level: 0,
+            start: 0,
+            end: first_start,
+        });
+    }
+
+    for (i, (level, text, start)) in headings.iter().enumerate() {
+        let end = headings[i + 1..]
+            .iter()
+            .find(|next| next.0 <= *level)
+            .map_or(content.len(), |next| next.2);
+        sections.push(Section {
+            heading: Some(text.clone()),
+            level: *level,
+            start: *start,
+            end,
+        });
+    }
+
+    sections
+}
+
+/// Markdown extensions enabled when parsing with pulldown-cmark
+pub(crate) fn parser_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+}
+
+/// Strip markdown formatting, keeping the readable text.
+///
+/// Links and images reduce to their display text, code blocks keep their
+/// content, and frontmatter is dropped.
+pub fn markdown_to_plaintext(content: &str) -> String {
+    let body = parse_frontmatter(content).body;
+    let mut text = String::new();
+
+    for event in Parser::new_ext(&body, parser_options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::TableCell) => text.push('\t'),
+            Event::End(TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow) => text.push('\n'),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::List(_)
+                | TagEnd::Table,
+            )
+            | Event::Rule => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+
+    let mut result = String::new();
+    let mut blank_run = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result.trim().to_string()
+}
+
+/// How `render_markdown` treats links and raw HTML
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RenderOptions {
+    /// Directory that relative link and image targets resolve against; when
+    /// set they are rewritten to `asset://` URLs the webview can load, and
+    /// otherwise left as written
+    pub asset_base: Option<String>,
+    /// Escape raw HTML and neutralize script URLs, and `data:` URLs that
+    /// aren't images, in links and images
+    pub sanitize: bool,
+    /// Directory that relative image targets are read from and embedded as
+    /// `data:` URLs, so the HTML stands alone; images that can't be read
+    /// are left to `asset_base`
+    pub embed_images_from: Option<String>,
+    /// Folder embedded images must lie in once symlinks are resolved, e.g.
+    /// the vault; images outside it are left to `asset_base`. Any readable
+    /// image is embedded when unset.
+    pub embed_images_within: Option<String>,
+}
+
+/// URL scheme of a link target, lowercased, if it has one
+fn url_scheme(target: &str) -> Option<String> {
+    let (scheme, _) = target.split_once(':')?;
+    let valid = !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Whether sanitizing must neutralize `target`: script URLs, and `data:`
+/// URLs other than images
+fn is_unsafe_url(target: &str) -> bool {
+    let target = target.trim();
+    match url_scheme(target).as_deref() {
+        Some("javascript" | "vbscript") => true,
+        Some("data") => !target[5..]
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("image/"),
+        _ => false,
+    }
+}
+
+/// `asset://` URL for a relative target resolved against `base`
+fn asset_url(base: &Path, target: &str) -> Option<String> {
+    if target.is_empty()
+        || target.starts_with('#')
+        || target.starts_with('/')
+        || url_scheme(target).is_some()
+    {
+        return None;
+    }
+    let (path, fragment) = match target.split_once('#') {
+        Some((path, anchor)) => (path, format!("#{}", anchor)),
+        None => (target, String::new()),
+    };
+    let resolved = normalize_path(&base.join(decode_link_target(path)));
+    let encoded: String = resolved
+        .to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches('/')
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    Some(format!("asset://localhost/{}{}", encoded, fragment))
+}
+
+/// `data:` URL holding the image at `target`, resolved against `base`,
+/// provided it lies within `within`
+fn embedded_image_url(base: &Path, target: &str, within: Option<&Path>) -> Option<String> {
+    if target.starts_with('/') || url_scheme(target).is_some() {
+        return None;
+    }
+    let path = base.join(decode_link_target(target));
+    if let Some(within) = within {
+        let resolved = std::fs::canonicalize(&path).ok()?;
+        if !resolved.starts_with(std::fs::canonicalize(within).ok()?) {
+            return None;
+        }
+    }
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    let bytes = std::fs::read(path).ok()?;
+    Some(format!("data:{};base64,{}", mime, BASE64.encode(bytes)))
+}
+
+/// Render a note's markdown body to an HTML fragment, dropping frontmatter.
+/// Tables, strikethrough, task lists and footnotes are enabled.
+pub fn render_markdown(content: &str, options: &RenderOptions) -> String {
+    let body = parse_frontmatter(content).body;
+    let base = options.asset_base.as_deref().map(Path::new);
+    let rewrite = |dest_url: pulldown_cmark::CowStr<'static>| {
+        if options.sanitize && is_unsafe_url(&dest_url) {
+            return "#".into();
+        }
+        match base.and_then(|base| asset_url(base, &dest_url)) {
+            Some(url) => url.into(),
+            None => dest_url,
+        }
+    };
+    let embed_base = options.embed_images_from.as_deref().map(Path::new);
+    let embed_within = options.embed_images_within.as_deref().map(Path::new);
+    let rewrite_image = |dest_url: pulldown_cmark::CowStr<'static>| {
+        let embedded =
+            embed_base.and_then(|base| embedded_image_url(base, &dest_url, embed_within));
+        match embedded {
+            Some(url) => url.into(),
+            None => rewrite(dest_url),
+        }
+    };
+
+    let events = Parser::new_ext(&body, parser_options()).map(|event| match event.into_static() {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: rewrite(dest_url),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: rewrite_image(dest_url),
+            title,
+            id,
+        }),
+        // Emitted as text, raw HTML is escaped rather than interpreted
+        Event::Html(html) | Event::InlineHtml(html) if options.sanitize => Event::Text(html),
+        event => event,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+/// Render a note's markdown body to an HTML fragment, dropping frontmatter
+pub fn render_markdown_to_html(content: &str) -> String {
+    render_markdown(content, &RenderOptions::default())
+}
+
+/// Collapse runs of blank lines to at most `max_consecutive`, leaving blank
+/// lines inside fenced code blocks untouched
+pub fn collapse_blank_lines(content: &str, max_consecutive: usize) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut blank_run = 0;
+    for (raw, line) in content.split_inclusive('\n').zip(scan_lines(content)) {
+        if !line.in_code && line.text.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > max_consecutive {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        result.push_str(raw);
+    }
+    result
+}
+
+/// Whitespace cleanups applied by `clean_whitespace`; all on by default
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct CleanupOptions {
+    /// Strip spaces and tabs from the end of each line
+    pub trim_trailing_whitespace: bool,
+    /// End the content with exactly one line break
+    pub ensure_final_newline: bool,
+    /// Leave lines inside fenced code blocks as they are
+    pub preserve_code_blocks: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        CleanupOptions {
+            trim_trailing_whitespace: true,
+            ensure_final_newline: true,
+            preserve_code_blocks: true,
+        }
+    }
+}
+
+/// Clean up trailing whitespace and the final line break as `options`
+/// asks, keeping each line's own line ending. Note that trimming also
+/// removes trailing-space hard breaks in prose.
+pub fn clean_whitespace(content: &str, options: &CleanupOptions) -> String {
+    let mut result = String::with_capacity(content.len() + 1);
+    for (raw, line) in content.split_inclusive('\n').zip(scan_lines(content)) {
+        let keep =
+            !options.trim_trailing_whitespace || (options.preserve_code_blocks && line.in_code);
+        if keep {
+            result.push_str(raw);
+        } else {
+            result.push_str(line.text.trim_end_matches([' ', '\t']));
+            result.push_str(&raw[line.text.len()..]);
+        }
+    }
+
+    if options.ensure_final_newline && !result.is_empty() {
+        let ending = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        result.truncate(result.trim_end_matches(['\n', '\r']).len());
+        result.push_str(ending);
+    }
+    result
+}
+
+/// Extract fenced code blocks in document order
+pub fn extract_code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+
+    for (event, range) in Parser::new_ext(content, parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let language = info
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .next()
+                    .filter(|language| !language.is_empty())
+                    .map(str::to_string);
+                current = Some(CodeBlock {
+                    language,
+                    code: String::new(),
+                    line: content[..range.start].matches('\n').count() + 1,
+                });
+            }
+            Event::Text(text) => {
+                if let Some(block) = current.as_mut() {
+                    block.code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Count words of prose, ignoring markup and code blocks
+pub fn count_words(content: &str) -> usize {
+    let body = parse_frontmatter(content).body;
+    let mut in_code_block = false;
+    let mut words = 0;
+
+    for event in Parser::new_ext(&body, parser_options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) | Event::Code(text) if !in_code_block => {
+                words += text.split_whitespace().count();
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// Reading speed assumed by `markdown_stats` unless the caller gives one
+pub const DEFAULT_WORDS_PER_MINUTE: u32 = 200;
+
+/// Status bar figures for a document
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MarkdownStats {
+    /// Words of prose, as counted by `count_words`
+    pub words: usize,
+    /// Every character of the source, code blocks included
+    pub characters: usize,
+    pub reading_minutes: f64,
+}
+
+/// Word and character counts plus an estimated reading time at
+/// `words_per_minute`
+pub fn markdown_stats(content: &str, words_per_minute: u32) -> MarkdownStats {
+    let words = count_words(content);
+    MarkdownStats {
+        words,
+        characters: content.chars().count(),
+        reading_minutes: words as f64 / f64::from(words_per_minute.max(1)),
+    }
+}
+
+/// Extract `- [ ]` / `- [x]` task list items outside code blocks
+pub fn extract_tasks(content: &str) -> Vec<Task> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re =
+        RE.get_or_init(|| Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+\[([ xX])\](?:\s+(.*))?$").unwrap());
+
+    scan_lines(content)
+        .into_iter()
+        .filter(|line| !line.in_code)
+        .filter_map(|line| {
+            let caps = re.captures(line.text)?;
+            Some(Task {
+                line: line.number,
+                text: caps.get(2).map_or("", |m| m.as_str()).trim().to_string(),
+                checked: &caps[1] != " ",
+            })
+        })
+        .collect()
+}
+
+/// Tags listed under a frontmatter key, as a YAML list or a comma or
+/// space separated string
+fn frontmatter_tags(frontmatter: &Value, key: &str) -> Vec<String> {
+    let raw: Vec<String> = match frontmatter.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(text)) => text.split([',', ' ']).map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    raw.iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Extract tags from frontmatter (`tags`/`tag`) and inline `#tags`,
+/// deduplicated case-insensitively in order of appearance
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let parsed = parse_frontmatter(content);
+    tags_from_parts(parsed.frontmatter.as_ref(), &parsed.body)
+}
+
+/// `extract_tags` for a note already split into frontmatter and body
+fn tags_from_parts(frontmatter: Option<&Value>, body: &str) -> Vec<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(?:^|\s)#([\p{L}\p{N}_][\p{L}\p{N}_/-]*)").unwrap());
+
+    let mut tags: Vec<String> = Vec::new();
+    if let Some(frontmatter) = frontmatter {
+        tags.extend(frontmatter_tags(frontmatter, "tags"));
+        tags.extend(frontmatter_tags(frontmatter, "tag"));
+    }
+
+    for line in scan_lines(body).into_iter().filter(|l| !l.in_code) {
+        let code = inline_code_ranges(line.text);
+        for caps in re.captures_iter(line.text) {
+            let tag = caps.get(1).unwrap();
+            let in_code = code
+                .iter()
+                .any(|(s, e)| tag.start() >= *s && tag.start() < *e);
+            // Purely numeric `#123` is an issue reference, not a tag
+            if !in_code && !tag.as_str().chars().all(|c| c.is_ascii_digit()) {
+                tags.push(tag.as_str().to_string());
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    tags.retain(|tag| seen.insert(tag.to_lowercase()));
+    tags
+}
+
+/// Derive a note's title: frontmatter `title`, then the first H1, then the
+/// file name without its extension
+pub fn derive_title(content: &str, path: &Path) -> String {
+    title_from_parts(
+        parse_frontmatter(content).frontmatter.as_ref(),
+        content,
+        path,
+    )
+}
+
+/// `derive_title` with the note's frontmatter already parsed
+fn title_from_parts(frontmatter: Option<&Value>, content: &str, path: &Path) -> String {
+    let frontmatter_title = frontmatter
+        .and_then(|fm| fm.get("title").and_then(Value::as_str).map(str::to_string))
+        .filter(|title| !title.trim().is_empty());
+
+    frontmatter_title
+        .or_else(|| {
+            extract_headings(content)
+                .into_iter()
+                .find(|h| h.level == 1)
+                .map(|h| h.text)
+        })
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        })
+}
+
+/// Read a note and compute everything the editor shows alongside it
+pub fn open_note(path: &str) -> Result<NoteBundle, String> {
+    let content = read_file(path)?;
+    let parts = note_parts(&content);
+
+    Ok(NoteBundle {
+        path: path.to_string(),
+        title: title_from_parts(parts.frontmatter.as_ref(), &content, Path::new(path)),
+        headings: extract_headings(&content),
+        tasks: extract_tasks(&content),
+        tags: tags_from_parts(parts.frontmatter.as_ref(), &parts.body),
+        word_count: count_words(&content),
+        frontmatter: parts.frontmatter,
+        content,
+    })
+}
+
+/// Read a note and split it into its frontmatter and body. A block that
+/// isn't valid YAML is still split off, with `frontmatter` left empty.
+pub fn load_note_parts(path: &str) -> Result<NoteParts, String> {
+    Ok(note_parts(&read_file(path)?))
+}
+
+/// Split note content as `load_note_parts` does
+fn note_parts(content: &str) -> NoteParts {
+    let Some((yaml, body_start)) = split_frontmatter(content) else {
+        return NoteParts {
+            raw_frontmatter: None,
+            frontmatter: None,
+            body: content.to_string(),
+            body_start: 0,
+        };
+    };
+
+    NoteParts {
+        raw_frontmatter: Some(yaml.to_string()),
+        frontmatter: parse_frontmatter(content).frontmatter,
+        body: content[body_start..].to_string(),
+        body_start,
+    }
+}
+
+/// Read a note and split it into parsed frontmatter and body
+pub fn read_file_with_frontmatter(path: &str) -> Result<ParsedNote, String> {
+    Ok(parse_frontmatter(&read_file(path)?))
+}
+
+/// Read each file with `read` (e.g. `read_file`) and parse its frontmatter,
+/// carrying on past failures so each is reported against its own path
+pub fn read_frontmatter_batch(
+    paths: Vec<String>,
+    mut read: impl FnMut(&str) -> Result<String, FsError>,
+) -> Vec<FrontmatterResult> {
+    paths
+        .into_iter()
+        .map(|path| match read(&path) {
+            Ok(content) => FrontmatterResult {
+                frontmatter: parse_frontmatter(&content).frontmatter,
+                path,
+                error: None,
+            },
+            Err(e) => FrontmatterResult {
+                path,
+                frontmatter: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_read_frontmatter_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "---\ntitle: First\n---\nBody").unwrap();
+        fs::write(&b, "---\ntags: [x, y]\n---\n").unwrap();
+        let missing = dir.path().join("missing.md");
+
+        let results = read_frontmatter_batch(
+            vec![
+                a.to_string_lossy().to_string(),
+                b.to_string_lossy().to_string(),
+                missing.to_string_lossy().to_string(),
+            ],
+            read_file,
+        );
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].frontmatter.as_ref().unwrap()["title"], "First");
+        assert_eq!(results[1].frontmatter.as_ref().unwrap()["tags"][1], "y");
+        assert!(results[2].frontmatter.is_none());
+        assert!(results[2].error.is_some());
+    }
+
+    #[test]
+    fn test_parse_frontmatter_edge_cases() {
+        let parsed = parse_frontmatter("---\ntitle: Note\ntags: [a, b]\n---\nBody\n");
+        let frontmatter = parsed.frontmatter.unwrap();
+        assert_eq!(frontmatter["title"], "Note");
+        assert_eq!(frontmatter["tags"][1], "b");
+        assert_eq!(parsed.body, "Body\n");
+
+        for content in [
+            "# No frontmatter\n",
+            "---\ntitle: Unterminated\nBody\n",
+            "---\ntitle: [unclosed\n---\nBody\n",
+        ] {
+            let parsed = parse_frontmatter(content);
+            assert_eq!(parsed.frontmatter, None);
+            assert_eq!(parsed.body, content);
+        }
+    }
+
+    #[test]
+    fn test_find_duplicate_headings() {
+        let content = "# Notes\n\n## Setup\ntext\n\n## Usage\n\n## Setup\n";
+        let duplicates = find_duplicate_headings(content);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].anchor, "setup");
+        let lines: Vec<usize> = duplicates[0].occurrences.iter().map(|h| h.line).collect();
+        assert_eq!(lines, vec![3, 8]);
+    }
+
+    #[test]
+    fn test_extract_toc_dedupes_anchors() {
+        let content =
+            "Setup\n=====\n\n```sh\n# not a heading\n```\n\n## Setup\n\n## Setup-1\n\n### Setup\n";
+        let toc = extract_toc(content);
+
+        let entries: Vec<(u8, usize, &str)> = toc
+            .iter()
+            .map(|entry| (entry.level, entry.line, entry.anchor.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (1, 1, "setup"),
+                (2, 8, "setup-1"),
+                (2, 10, "setup-1-1"),
+                (3, 12, "setup-2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_heading_structure_issues() {
+        let content = "# Title\n\n### Too deep\n\n## Fine\n\n# Second title\n";
+        let issues = heading_structure_issues(content);
+
+        let found: Vec<(usize, StructureIssueKind)> = issues
+            .iter()
+            .map(|issue| (issue.line, issue.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (3, StructureIssueKind::SkippedLevel),
+                (7, StructureIssueKind::MultipleH1),
+            ]
+        );
+        assert_eq!(issues[0].message, "H3 follows H1, skipping a level");
+    }
+
+    #[test]
+    fn test_document_sections() {
+        let content = "Intro\n# One\ntext\n## Sub\nmore\n# Two\nend\n";
+        let sections = document_sections(content);
+
+        let spans: Vec<(Option<&str>, u8, &str)> = sections
+            .iter()
+            .map(|s| (s.heading.as_deref(), s.level, &content[s.start..s.end]))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (None, 0, "Intro\n"),
+                (Some("One"), 1, "# One\ntext\n## Sub\nmore\n"),
+                (Some("Sub"), 2, "## Sub\nmore\n"),
+                (Some("Two"), 1, "# Two\nend\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locate_heading() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(
+            &path,
+            "---\ntitle: T\n---\n# Guide\n\nIntro\n\n## Getting Started\n",
+        )
+        .unwrap();
+        let path = path.to_string_lossy();
+
+        assert_eq!(locate_heading(&path, "#getting-started").unwrap(), Some(8));
+        assert_eq!(locate_heading(&path, "Getting Started").unwrap(), Some(8));
+        assert_eq!(locate_heading(&path, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_markdown_to_plaintext() {
+        let content = "# Title\n\nSee [the docs](https://example.com) and **bold** text.\n";
+        let text = markdown_to_plaintext(content);
+
+        assert_eq!(text, "Title\n\nSee the docs and bold text.");
+    }
+
+    #[test]
+    fn test_render_markdown_sanitizes_and_rewrites_assets() {
+        let content = "![chart](img/my%20chart.png) [site](https://example.com)\n\n\
+                       <script>alert(1)</script>\n\n[x](javascript:alert(1))\n\n\
+                       [page](data:text/html,hi) ![dot](data:image/png;base64,iVBORw==)\n";
+
+        let raw = render_markdown(content, &RenderOptions::default());
+        assert!(raw.contains("<script>"));
+        assert!(raw.contains("src=\"img/my%20chart.png\""));
+
+        let options = RenderOptions {
+            asset_base: Some("/vault/notes".to_string()),
+            sanitize: true,
+            ..Default::default()
+        };
+        let html = render_markdown(content, &options);
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("src=\"asset://localhost/vault/notes/img/my%20chart.png\""));
+        assert!(html.contains("href=\"https://example.com\""));
+        assert!(html.contains("href=\"#\""));
+        // Only image data URLs survive
+        assert!(!html.contains("data:text/html"));
+        assert!(html.contains("src=\"data:image/png;base64,iVBORw==\""));
+    }
+
+    #[test]
+    fn test_markdown_stats_ignores_markup_and_code() {
+        let content = "# Title\n\n**bold** and _quiet_ words\n\n```\nlet x = 1;\n```\n";
+
+        let stats = markdown_stats(content, DEFAULT_WORDS_PER_MINUTE);
+
+        assert_eq!(stats.words, 5);
+        assert_eq!(stats.characters, content.chars().count());
+        assert_eq!(stats.reading_minutes, 5.0 / 200.0);
+        assert_eq!(markdown_stats(content, 5).reading_minutes, 1.0);
+    }
+
+    #[test]
+    fn test_open_note_populates_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(
+            &path,
+            "---\ntags: [work]\n---\n# Weekly Plan\n\n- [ ] Ship it #urgent\n- [x] Review\n",
+        )
+        .unwrap();
+
+        let bundle = open_note(&path.to_string_lossy()).unwrap();
+
+        assert_eq!(bundle.title, "Weekly Plan");
+        assert!(bundle.content.starts_with("---"));
+        assert!(bundle.frontmatter.is_some());
+        assert_eq!(bundle.headings.len(), 1);
+        assert_eq!(bundle.tasks.len(), 2);
+        assert!(bundle.tasks[1].checked);
+        assert_eq!(bundle.tags, vec!["work", "urgent"]);
+        assert_eq!(bundle.word_count, 6);
+    }
+
+    #[test]
+    fn test_load_note_parts_splits_at_body() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        let content = "---\ntitle: Plan\n---\n# Plan\n";
+        fs::write(&path, content).unwrap();
+
+        let parts = load_note_parts(&path.to_string_lossy()).unwrap();
+
+        assert_eq!(parts.raw_frontmatter.as_deref(), Some("title: Plan\n"));
+        assert_eq!(parts.frontmatter.unwrap()["title"], "Plan");
+        assert_eq!(parts.body, "# Plan\n");
+        assert_eq!(parts.body_start, 20);
+        assert_eq!(&content[parts.body_start..], parts.body);
+    }
+
+    #[test]
+    fn test_clean_whitespace_respects_code_blocks() {
+        let content = "# Title  \nText\t\n```\nindented   \n```\n\n\n";
+
+        let cleaned = clean_whitespace(content, &CleanupOptions::default());
+        assert_eq!(cleaned, "# Title\nText\n```\nindented   \n```\n");
+
+        let everywhere = CleanupOptions {
+            preserve_code_blocks: false,
+            ..Default::default()
+        };
+        assert!(clean_whitespace(content, &everywhere).contains("indented\n"));
+        let newline_only = CleanupOptions {
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        assert_eq!(clean_whitespace("a  \r\nb", &newline_only), "a  \r\nb\r\n");
+        assert_eq!(clean_whitespace("", &CleanupOptions::default()), "");
+    }
+
+    #[test]
+    fn test_collapse_blank_lines_skips_code_fences() {
+        let content = "# Title\n\n\n\nIntro\n```\nfn a() {}\n\n\n\nfn b() {}\n```\n\n\n\nEnd\n";
+
+        assert_eq!(
+            collapse_blank_lines(content, 1),
+            "# Title\n\nIntro\n```\nfn a() {}\n\n\n\nfn b() {}\n```\n\nEnd\n"
+        );
+    }
+}
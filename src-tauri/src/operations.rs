@@ -0,0 +1,95 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::fs::OperationProgress;
+
+/// Payload of `operation-progress` events
+#[derive(Debug, Serialize, Clone)]
+pub struct OperationEvent<'a> {
+    pub operation_id: &'a str,
+    #[serde(flatten)]
+    pub progress: &'a OperationProgress,
+}
+
+/// Cancellation flags of the tracked copies and deletes now running, keyed
+/// by the id the frontend gave each
+#[derive(Default)]
+pub struct OperationState {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// A registered operation; dropping it unregisters the id
+pub struct RunningOperation<'a> {
+    state: &'a OperationState,
+    id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RunningOperation<'_> {
+    /// Flag set once the operation is asked to stop, shared so the thread
+    /// doing the work can watch it
+    pub fn cancelled(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+}
+
+impl Drop for RunningOperation<'_> {
+    fn drop(&mut self) {
+        self.state.running.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl OperationState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an operation under `id`, failing if one is already running
+    /// with it
+    pub fn begin(&self, id: &str) -> Result<RunningOperation<'_>, String> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(id) {
+            return Err(format!("Operation is already running: {}", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(id.to_string(), cancelled.clone());
+        Ok(RunningOperation {
+            state: self,
+            id: id.to_string(),
+            cancelled,
+        })
+    }
+
+    /// Ask the operation with `id` to stop, returning whether one was running
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.running.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_cancel_by_id_until_finished() {
+        let state = OperationState::new();
+        let operation = state.begin("copy-1").unwrap();
+        assert!(state.begin("copy-1").is_err());
+
+        assert!(state.cancel("copy-1"));
+        assert!(operation.cancelled().load(Ordering::SeqCst));
+        assert!(!state.cancel("copy-2"));
+
+        drop(operation);
+        assert!(!state.cancel("copy-1"));
+        assert!(state.begin("copy-1").is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::activity::now_millis;
+use crate::fs::write_file;
+
+/// Most entries of each kind kept in the recent list; older ones are
+/// dropped, so opening many notes doesn't push out the folders
+pub const MAX_RECENT: usize = 30;
+
+/// Whether a recent entry is a note or a workspace folder
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecentKind {
+    File,
+    Folder,
+}
+
+/// A recently opened path
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecentEntry {
+    pub path: String,
+    pub kind: RecentKind,
+    /// Unix timestamp in milliseconds of the last time it was opened
+    pub opened_at: u64,
+}
+
+fn load(store: &Path) -> Result<Vec<RecentEntry>, String> {
+    if !store.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(store).map_err(|e| format!("Failed to read recent list: {}", e))?;
+    // A damaged list only costs the history, so start over rather than fail
+    Ok(serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid recent list {}: {}", store.display(), e);
+        Vec::new()
+    }))
+}
+
+fn save(store: &Path, entries: &[RecentEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize recent list: {}", e))?;
+    write_file(&store.to_string_lossy(), &content).map_err(String::from)
+}
+
+/// Record that a path was opened, moving it to the front if it was already
+/// listed and dropping the oldest entries of its kind past `MAX_RECENT`
+pub fn add_recent(store: &Path, path: &str, kind: RecentKind) -> Result<(), String> {
+    let mut entries = load(store)?;
+    entries.retain(|entry| !(entry.path == path && entry.kind == kind));
+    entries.insert(
+        0,
+        RecentEntry {
+            path: path.to_string(),
+            kind,
+            opened_at: now_millis(),
+        },
+    );
+    let mut same_kind = 0;
+    entries.retain(|entry| {
+        if entry.kind != kind {
+            return true;
+        }
+        same_kind += 1;
+        same_kind <= MAX_RECENT
+    });
+    save(store, &entries)
+}
+
+/// Read recent entries, newest first, optionally only those of one kind.
+/// Entries whose paths no longer exist are pruned from the store.
+pub fn get_recent(
+    store: &Path,
+    kind: Option<RecentKind>,
+    limit: usize,
+) -> Result<Vec<RecentEntry>, String> {
+    let mut entries = load(store)?;
+    let before = entries.len();
+    entries.retain(|entry| Path::new(&entry.path).exists());
+    if entries.len() != before {
+        save(store, &entries)?;
+    }
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| kind.is_none() || kind == Some(entry.kind))
+        .take(limit)
+        .collect())
+}
+
+/// Forget every recent entry
+pub fn clear_recent(store: &Path) -> Result<(), String> {
+    if store.exists() {
+        fs::remove_file(store).map_err(|e| format!("Failed to clear recent list: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_list_dedupes_and_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("config").join("recent.json");
+        let note = dir.path().join("note.md");
+        let other = dir.path().join("other.md");
+        fs::write(&note, "# Note").unwrap();
+        fs::write(&other, "# Other").unwrap();
+        let note = note.to_string_lossy().to_string();
+        let other = other.to_string_lossy().to_string();
+        let folder = dir.path().to_string_lossy().to_string();
+
+        add_recent(&store, &note, RecentKind::File).unwrap();
+        add_recent(&store, &folder, RecentKind::Folder).unwrap();
+        add_recent(&store, &other, RecentKind::File).unwrap();
+        add_recent(&store, &note, RecentKind::File).unwrap();
+
+        let paths = |entries: Vec<RecentEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.path).collect()
+        };
+        let files = get_recent(&store, Some(RecentKind::File), 10).unwrap();
+        assert_eq!(paths(files), vec![note.clone(), other.clone()]);
+        let all = get_recent(&store, None, 2).unwrap();
+        assert_eq!(paths(all), vec![note.clone(), other.clone()]);
+
+        fs::remove_file(&other).unwrap();
+        let all = get_recent(&store, None, 10).unwrap();
+        assert_eq!(paths(all), vec![note, folder]);
+        assert_eq!(load(&store).unwrap().len(), 2);
+
+        clear_recent(&store).unwrap();
+        assert!(get_recent(&store, None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recent_list_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("recent.json");
+        let folder = dir.path().to_string_lossy().to_string();
+
+        for i in 0..MAX_RECENT + 5 {
+            let note = dir.path().join(format!("{}.md", i));
+            fs::write(&note, "").unwrap();
+            add_recent(&store, &note.to_string_lossy(), RecentKind::File).unwrap();
+        }
+        add_recent(&store, &folder, RecentKind::Folder).unwrap();
+        for i in 0..5 {
+            let note = dir.path().join(format!("{}.md", i));
+            add_recent(&store, &note.to_string_lossy(), RecentKind::File).unwrap();
+        }
+
+        let files = get_recent(&store, Some(RecentKind::File), usize::MAX).unwrap();
+        assert_eq!(files.len(), MAX_RECENT);
+        // Files opened since don't push the folder out
+        let folders = get_recent(&store, Some(RecentKind::Folder), usize::MAX).unwrap();
+        assert_eq!(folders[0].path, folder);
+    }
+
+    #[test]
+    fn test_invalid_recent_list_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("recent.json");
+        let note = dir.path().join("note.md");
+        fs::write(&note, "").unwrap();
+        fs::write(&store, "{ not json").unwrap();
+
+        assert!(get_recent(&store, None, 10).unwrap().is_empty());
+        add_recent(&store, &note.to_string_lossy(), RecentKind::File).unwrap();
+        assert_eq!(get_recent(&store, None, 10).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,122 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::fs::to_hex;
+use crate::markdown::render_markdown_to_html;
+
+/// Renders kept by the cache managed in app state, until a vault's
+/// `render_cache_entries` says otherwise
+pub const DEFAULT_RENDER_CACHE_ENTRIES: usize = 64;
+
+/// Content-addressed key for a markdown render: the hex SHA-256 of the content
+pub fn render_cache_key(content: &str) -> String {
+    to_hex(&Sha256::digest(content.as_bytes()))
+}
+
+struct LruRenders {
+    capacity: usize,
+    html: HashMap<String, String>,
+    /// Keys from least to most recently used
+    order: VecDeque<String>,
+    /// Renders performed, i.e. cache misses
+    renders: usize,
+}
+
+/// Bounded least-recently-used cache of rendered HTML, so re-previewing
+/// unchanged content skips the markdown parse
+pub struct RenderCache {
+    inner: Mutex<LruRenders>,
+}
+
+impl RenderCache {
+    pub fn new(capacity: usize) -> Self {
+        RenderCache {
+            inner: Mutex::new(LruRenders {
+                capacity: capacity.max(1),
+                html: HashMap::new(),
+                order: VecDeque::new(),
+                renders: 0,
+            }),
+        }
+    }
+
+    /// Change how many renders are kept, dropping the least recently used
+    /// ones that no longer fit
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut cache = self.inner.lock().unwrap();
+        cache.capacity = capacity.max(1);
+        while cache.html.len() > cache.capacity {
+            let Some(oldest) = cache.order.pop_front() else {
+                break;
+            };
+            cache.html.remove(&oldest);
+        }
+    }
+
+    /// Render `content` to HTML, reusing the cached render of identical content
+    pub fn render_markdown_cached(&self, content: &str) -> String {
+        let key = render_cache_key(content);
+        let mut cache = self.inner.lock().unwrap();
+
+        if let Some(html) = cache.html.get(&key).cloned() {
+            cache.order.retain(|k| *k != key);
+            cache.order.push_back(key);
+            return html;
+        }
+
+        let html = render_markdown_to_html(content);
+        cache.renders += 1;
+        if cache.html.len() >= cache.capacity {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.html.remove(&oldest);
+            }
+        }
+        cache.html.insert(key.clone(), html.clone());
+        cache.order.push_back(key);
+        html
+    }
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RENDER_CACHE_ENTRIES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_content_renders_once() {
+        let cache = RenderCache::new(2);
+        let renders = || cache.inner.lock().unwrap().renders;
+
+        let first = cache.render_markdown_cached("# Same");
+        assert_eq!(cache.render_markdown_cached("# Same"), first);
+        assert_eq!(renders(), 1);
+
+        cache.render_markdown_cached("two");
+        cache.render_markdown_cached("three");
+        // "# Same" was least recently used and got evicted
+        cache.render_markdown_cached("# Same");
+        assert_eq!(renders(), 4);
+    }
+
+    #[test]
+    fn test_shrinking_capacity_evicts_oldest() {
+        let cache = RenderCache::new(3);
+        let renders = || cache.inner.lock().unwrap().renders;
+        for content in ["one", "two", "three"] {
+            cache.render_markdown_cached(content);
+        }
+
+        cache.set_capacity(1);
+
+        cache.render_markdown_cached("three");
+        assert_eq!(renders(), 3);
+        cache.render_markdown_cached("two");
+        assert_eq!(renders(), 4);
+    }
+}
@@ -0,0 +1,930 @@
+use globset::{GlobBuilder, GlobSetBuilder};
+use notify::Event;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{
+    is_markdown_file, modified_millis, read_file, to_hex, write_file_atomic, REGEX_SIZE_LIMIT,
+};
+use crate::vault::{markdown_files, vault_files, vault_files_with, IgnoreRules};
+use crate::watcher::is_hidden;
+
+/// Where one occurrence of the query sits within a line, as byte offsets
+/// for slicing and char offsets for the editor
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+/// A line of a note with every occurrence of the query in it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchLine {
+    pub line: usize,
+    pub text: String,
+    pub ranges: Vec<MatchRange>,
+}
+
+/// A matching line and the lines around it. Context lines carry ranges too,
+/// since they may contain occurrences of their own.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub path: String,
+    #[serde(flatten)]
+    pub matched: SearchLine,
+    pub before: Vec<SearchLine>,
+    pub after: Vec<SearchLine>,
+}
+
+/// How a vault search matches and how much it reads
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Lines of context on either side of each match
+    pub context_lines: usize,
+    pub case_sensitive: bool,
+    /// Only match the query where it starts and ends on a word boundary
+    pub whole_word: bool,
+    /// Treat the query as a regular expression rather than literal text
+    pub regex: bool,
+    /// Stop searching once this many matches are found
+    pub max_results: usize,
+    /// Skip files larger than this, e.g. huge logs saved with a `.md` name
+    pub max_file_bytes: u64,
+    /// Descend into symlinked directories; each real directory is still
+    /// searched once
+    pub follow_symlinks: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            context_lines: 2,
+            case_sensitive: true,
+            whole_word: false,
+            regex: false,
+            max_results: 1000,
+            max_file_bytes: 2 * 1024 * 1024,
+            follow_symlinks: false,
+        }
+    }
+}
+
+/// The pattern `search_files` matches lines against. With `whole_word`, a
+/// match must sit between word boundaries, so a query ending in punctuation
+/// only matches where a word character follows it.
+fn query_regex(query: &str, options: &SearchOptions) -> Result<Regex, String> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid search query: {}", e))
+}
+
+/// Non-overlapping occurrences of `query` in `text`
+fn match_ranges(text: &str, query: &Regex) -> Vec<MatchRange> {
+    let mut ranges = Vec::new();
+    let mut chars_before = 0;
+    let mut counted_to = 0;
+    for found in query.find_iter(text) {
+        let start = found.start();
+        chars_before += text[counted_to..start].chars().count();
+        let length = found.as_str().chars().count();
+        ranges.push(MatchRange {
+            start,
+            end: found.end(),
+            char_start: chars_before,
+            char_end: chars_before + length,
+        });
+        chars_before += length;
+        counted_to = found.end();
+    }
+    ranges
+}
+
+fn search_line(lines: &[&str], index: usize, query: &Regex) -> SearchLine {
+    SearchLine {
+        line: index + 1,
+        text: lines[index].to_string(),
+        ranges: match_ranges(lines[index], query),
+    }
+}
+
+fn search_note(path: &Path, query: &Regex, options: &SearchOptions) -> Vec<SearchMatch> {
+    let too_large = fs::metadata(path).is_ok_and(|m| m.len() > options.max_file_bytes);
+    if too_large {
+        return Vec::new();
+    }
+    let Ok(content) = read_file(&path.to_string_lossy()) else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = content.lines().collect();
+
+    let mut matches = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if !query.is_match(line) {
+            continue;
+        }
+        let first = index.saturating_sub(options.context_lines);
+        let last = (index + options.context_lines).min(lines.len() - 1);
+        matches.push(SearchMatch {
+            path: path.to_string_lossy().to_string(),
+            matched: search_line(&lines, index, query),
+            before: (first..index)
+                .map(|i| search_line(&lines, i, query))
+                .collect(),
+            after: (index + 1..=last)
+                .map(|i| search_line(&lines, i, query))
+                .collect(),
+        });
+    }
+    matches
+}
+
+/// Find every line of every note under `root` containing `query`, with up
+/// to `context_lines` lines on either side. The query is literal text unless
+/// `options.regex` is set; an invalid pattern is an error. Notes are read one
+/// at a time and the walk stops once `max_results` matches are found.
+pub fn search_files(
+    root: &str,
+    query: &str,
+    options: &SearchOptions,
+    ignore: &IgnoreRules,
+) -> Result<Vec<SearchMatch>, String> {
+    let root = Path::new(root);
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let query = query_regex(query, options)?;
+
+    Ok(vault_files_with(root, ignore, options.follow_symlinks)
+        .iter()
+        .filter(|path| is_markdown_file(&path.file_name().unwrap_or_default().to_string_lossy()))
+        .flat_map(|note| search_note(note, &query, options))
+        .take(options.max_results)
+        .collect())
+}
+
+/// Folders quick-open never descends into, on top of the ignore rules;
+/// hidden folders such as `.git` are always skipped
+pub const QUICK_OPEN_SKIP: [&str; 1] = ["node_modules"];
+
+/// A file whose name fuzzily matches a quick-open query
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub path: String,
+    pub score: i64,
+    /// Char indices into the file name of the matched query characters
+    pub indices: Vec<usize>,
+}
+
+/// Score `candidate` against `query` as a case-insensitive subsequence,
+/// returning the score and matched char indices, or `None` when the query
+/// characters don't all appear in order. Runs of consecutive matches and
+/// matches at the start of a word score higher and skipped characters cost;
+/// the best-scoring alignment is chosen, not just the leftmost.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let chars: Vec<char> = candidate.chars().collect();
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lower)
+        .collect();
+    let is_word_start = |i: usize| {
+        i == 0
+            || matches!(chars[i - 1], ' ' | '-' | '_' | '.' | '/')
+            || (chars[i - 1].is_lowercase() && chars[i].is_uppercase())
+    };
+    let gain = |i: usize| if is_word_start(i) { 40 } else { 16 };
+
+    // best[q][i]: top score with query char `q` matched at `i`, plus the
+    // position the previous query char was matched at
+    let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; chars.len()]; query.len()];
+    for (q, wanted) in query.iter().enumerate() {
+        for i in (0..chars.len()).filter(|&i| lower(chars[i]) == *wanted) {
+            best[q][i] = if q == 0 {
+                Some((gain(i) - i.min(8) as i64, 0))
+            } else {
+                (0..i)
+                    .filter_map(|prev| {
+                        let (score, _) = best[q - 1][prev]?;
+                        let step = if prev + 1 == i {
+                            16
+                        } else {
+                            -((i - prev - 1) as i64)
+                        };
+                        Some((score + gain(i) + step, prev))
+                    })
+                    .max_by_key(|(score, _)| *score)
+            };
+        }
+    }
+
+    let last = query.len().checked_sub(1)?;
+    let (end, (score, _)) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, cell)| Some((i, (*cell)?)))
+        .max_by_key(|(_, (score, _))| *score)?;
+    let mut indices = vec![end];
+    for q in (1..=last).rev() {
+        let (_, prev) = best[q][indices[0]]?;
+        indices.insert(0, prev);
+    }
+    Some((score, indices))
+}
+
+/// Files under `root` whose names fuzzily match `query`, best first, at
+/// most `limit` of them. Ties go to the shorter name, then the path.
+pub fn find_files_fuzzy(
+    root: &str,
+    query: &str,
+    limit: usize,
+    ignore: &IgnoreRules,
+) -> Result<Vec<FuzzyMatch>, String> {
+    let root = Path::new(root);
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut matches: Vec<(usize, FuzzyMatch)> = vault_files(root, ignore)
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let (score, indices) = fuzzy_score(query, &name)?;
+            Some((
+                name.chars().count(),
+                FuzzyMatch {
+                    path: path.to_string_lossy().to_string(),
+                    score,
+                    indices,
+                },
+            ))
+        })
+        .collect();
+    matches.sort_by(|(a_len, a), (b_len, b)| {
+        b.score
+            .cmp(&a.score)
+            .then(a_len.cmp(b_len))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, found)| found)
+        .collect())
+}
+
+/// Files under `root` whose path relative to it matches any of `patterns`,
+/// sorted, as paths joined onto `root`. Patterns use `/` separators and `*`
+/// stays within one folder, so `*.md` matches only top-level notes and
+/// `**/*.md` matches them at any depth. Only files are returned, never
+/// directories, and ignored or hidden entries are skipped.
+pub fn glob_files(
+    root: &str,
+    patterns: &[String],
+    ignore: &IgnoreRules,
+) -> Result<Vec<String>, String> {
+    let root = Path::new(root);
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern.trim_start_matches("./"))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid glob pattern {}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    let set = builder
+        .build()
+        .map_err(|e| format!("Failed to compile glob patterns: {}", e))?;
+
+    Ok(vault_files(root, ignore)
+        .into_iter()
+        .filter(|path| set.is_match(path.strip_prefix(root).unwrap_or(path)))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
+
+/// A note containing every word of an indexed query, scored by how often
+/// those words occur in it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct IndexedHit {
+    pub path: String,
+    pub score: usize,
+}
+
+/// Lowercased words of `text`, split on anything that isn't alphanumeric
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// What the index holds for one note
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IndexedNote {
+    /// SHA-256 of the content the words were taken from
+    hash: String,
+    /// Modification time in milliseconds and size when last read, so
+    /// unchanged notes can be skipped without reading them
+    modified: Option<u64>,
+    size: u64,
+    /// Occurrences of each word
+    counts: HashMap<String, usize>,
+}
+
+/// Inverted index from words to the notes containing them, kept current by
+/// re-tokenizing only the files whose content changes. It can be saved and
+/// loaded again, with the postings rebuilt from each note's word counts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    root: PathBuf,
+    /// Occurrences of each word per note
+    #[serde(skip)]
+    postings: HashMap<String, HashMap<PathBuf, usize>>,
+    notes: HashMap<PathBuf, IndexedNote>,
+    /// Folder `save_changes` saves the index in, set by `load_or_build`
+    #[serde(skip)]
+    save_dir: Option<PathBuf>,
+    /// Whether any note changed since the index was last saved
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+impl SearchIndex {
+    /// Tokenize every note under `root`
+    pub fn build(root: &Path) -> Self {
+        let mut index = SearchIndex {
+            root: root.to_path_buf(),
+            ..Default::default()
+        };
+        for note in markdown_files(root) {
+            index.refresh(&note);
+        }
+        index
+    }
+
+    /// The copy of `root`'s index saved in `dir` by `save`, brought up to
+    /// date with `revalidate`, or a fresh build when there is none. Either
+    /// way `save_changes` saves it back to `dir`.
+    pub fn load_or_build(dir: &Path, root: &Path) -> Self {
+        let mut index = match Self::load(dir, root) {
+            Ok(Some(mut index)) => {
+                index.revalidate();
+                index
+            }
+            Ok(None) => Self::build(root),
+            Err(e) => {
+                eprintln!("{}", e);
+                Self::build(root)
+            }
+        };
+        index.save_dir = Some(dir.to_path_buf());
+        index
+    }
+
+    /// The copy of `root`'s index saved in `dir`, as it was when saved
+    pub fn load(dir: &Path, root: &Path) -> Result<Option<Self>, String> {
+        let file = index_file(dir, root);
+        if !file.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&file).map_err(|e| format!("Failed to read search index: {}", e))?;
+        let mut index: SearchIndex =
+            serde_json::from_str(&content).map_err(|e| format!("Invalid search index: {}", e))?;
+        if index.root != root {
+            return Ok(None);
+        }
+        for (note, indexed) in &index.notes {
+            for (word, count) in &indexed.counts {
+                index
+                    .postings
+                    .entry(word.clone())
+                    .or_default()
+                    .insert(note.clone(), *count);
+            }
+        }
+        Ok(Some(index))
+    }
+
+    /// Save the index in `dir`, one file per vault root
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create index folder: {}", e))?;
+        let content = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize search index: {}", e))?;
+        write_file_atomic(
+            &index_file(dir, &self.root).to_string_lossy(),
+            content.as_bytes(),
+        )
+        .map_err(String::from)
+    }
+
+    /// Save the index in the folder `load_or_build` found it in, if any
+    /// note changed since it was last saved. A failure is logged and the
+    /// save is tried again next time.
+    pub fn save_changes(&mut self) {
+        let Some(dir) = self.save_dir.as_deref().filter(|_| self.unsaved) else {
+            return;
+        };
+        match self.save(dir) {
+            Ok(()) => self.unsaved = false,
+            Err(e) => eprintln!(
+                "Failed to save search index of {}: {}",
+                self.root.display(),
+                e
+            ),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Catch up with changes made while nothing kept the index current:
+    /// notes that are gone are evicted and those whose modification time or
+    /// size changed are re-read, re-tokenized only if their hash differs
+    pub fn revalidate(&mut self) {
+        let current: HashSet<PathBuf> = markdown_files(&self.root).into_iter().collect();
+        let gone: Vec<PathBuf> = self
+            .notes
+            .keys()
+            .filter(|note| !current.contains(*note))
+            .cloned()
+            .collect();
+        for note in gone {
+            self.remove(&note);
+        }
+        for note in current {
+            let unchanged = match (self.notes.get(&note), fs::metadata(&note)) {
+                (Some(indexed), Ok(metadata)) => {
+                    indexed.modified == modified_millis(&metadata) && indexed.size == metadata.len()
+                }
+                _ => false,
+            };
+            if !unchanged {
+                self.refresh(&note);
+            }
+        }
+    }
+
+    /// Bring the index up to date with a filesystem event. Paths outside
+    /// the index's root are ignored.
+    pub fn apply(&mut self, event: &Event) {
+        for path in &event.paths {
+            if !path.starts_with(&self.root) {
+                continue;
+            }
+            if path.is_dir() {
+                for note in markdown_files(path) {
+                    self.refresh(&note);
+                }
+            } else if path.exists() {
+                self.refresh(path);
+            } else {
+                // A removed directory takes every note beneath it along
+                let removed: Vec<PathBuf> = self
+                    .notes
+                    .keys()
+                    .filter(|note| note.starts_with(path))
+                    .cloned()
+                    .collect();
+                for note in removed {
+                    self.remove(&note);
+                }
+            }
+        }
+    }
+
+    /// Notes containing every word of `query`, best matches first
+    pub fn query(&self, query: &str) -> Vec<IndexedHit> {
+        let terms = query_terms(query);
+
+        let mut scores: Option<HashMap<&PathBuf, usize>> = None;
+        for term in &terms {
+            let Some(notes) = self.postings.get(term) else {
+                return Vec::new();
+            };
+            scores = Some(match scores {
+                None => notes.iter().map(|(note, count)| (note, *count)).collect(),
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(note, score)| notes.get(note).map(|count| (note, score + count)))
+                    .collect(),
+            });
+        }
+
+        ranked_hits(
+            scores
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(note, score)| IndexedHit {
+                    path: note.to_string_lossy().to_string(),
+                    score,
+                })
+                .collect(),
+        )
+    }
+
+    /// Re-read a single note, replacing its postings if its content changed
+    fn refresh(&mut self, path: &Path) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !is_markdown_file(&name) || is_hidden(&self.root, path) {
+            return;
+        }
+
+        let (Ok(metadata), Ok(content)) = (fs::metadata(path), read_file(&path.to_string_lossy()))
+        else {
+            self.remove(path);
+            return;
+        };
+        let hash = to_hex(&Sha256::digest(content.as_bytes()));
+        if let Some(indexed) = self.notes.get_mut(path) {
+            if indexed.hash == hash {
+                indexed.modified = modified_millis(&metadata);
+                indexed.size = metadata.len();
+                self.unsaved = true;
+                return;
+            }
+        }
+
+        self.remove(path);
+        self.unsaved = true;
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for word in tokenize(&content) {
+            *counts.entry(word).or_default() += 1;
+        }
+        for (word, count) in &counts {
+            self.postings
+                .entry(word.clone())
+                .or_default()
+                .insert(path.to_path_buf(), *count);
+        }
+        self.notes.insert(
+            path.to_path_buf(),
+            IndexedNote {
+                hash,
+                modified: modified_millis(&metadata),
+                size: metadata.len(),
+                counts,
+            },
+        );
+    }
+
+    /// Drop a note's postings
+    fn remove(&mut self, path: &Path) {
+        let Some(indexed) = self.notes.remove(path) else {
+            return;
+        };
+        self.unsaved = true;
+        for word in indexed.counts.into_keys() {
+            if let Some(notes) = self.postings.get_mut(&word) {
+                notes.remove(path);
+                if notes.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+}
+
+/// Where `SearchIndex::save` keeps the index of `root`, named by a hash of
+/// the root so any path maps to a safe file name
+fn index_file(dir: &Path, root: &Path) -> PathBuf {
+    let key = to_hex(&Sha256::digest(root.to_string_lossy().as_bytes()));
+    dir.join(format!("{}.json", key))
+}
+
+/// Distinct words of a query
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = tokenize(query).collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn ranked_hits(mut hits: Vec<IndexedHit>) -> Vec<IndexedHit> {
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    hits
+}
+
+/// What `SearchIndex::query` would find under `root`, worked out by reading
+/// every note instead, for when no current index is at hand
+pub fn scan_notes(root: &Path, query: &str) -> Vec<IndexedHit> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let hits = markdown_files(root)
+        .into_iter()
+        .filter(|note| !is_hidden(root, note))
+        .filter_map(|note| {
+            let content = read_file(&note.to_string_lossy()).ok()?;
+            let mut counts = vec![0; terms.len()];
+            for word in tokenize(&content) {
+                if let Ok(i) = terms.binary_search(&word) {
+                    counts[i] += 1;
+                }
+            }
+            if counts.contains(&0) {
+                return None;
+            }
+            Some(IndexedHit {
+                path: note.to_string_lossy().to_string(),
+                score: counts.iter().sum(),
+            })
+        })
+        .collect();
+    ranked_hits(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_two_occurrences_on_one_line() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("note.md"),
+            "intro\nrésumé: cat and cat\noutro cat\n",
+        )
+        .unwrap();
+
+        let options = SearchOptions {
+            context_lines: 1,
+            ..Default::default()
+        };
+        let matches = search_files(
+            &dir.path().to_string_lossy(),
+            "cat",
+            &options,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(matches.len(), 2);
+        let first = &matches[0];
+        assert_eq!(first.matched.line, 2);
+        assert_eq!(
+            first.matched.ranges,
+            vec![
+                MatchRange {
+                    start: 10,
+                    end: 13,
+                    char_start: 8,
+                    char_end: 11,
+                },
+                MatchRange {
+                    start: 18,
+                    end: 21,
+                    char_start: 16,
+                    char_end: 19,
+                },
+            ]
+        );
+        assert_eq!(first.before[0].text, "intro");
+        assert!(first.before[0].ranges.is_empty());
+        // The context line holds a match of its own
+        assert_eq!(first.after[0].ranges.len(), 1);
+    }
+
+    #[test]
+    fn test_glob_files_matches_any_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("notes/drafts")).unwrap();
+        fs::write(dir.path().join("index.md"), "").unwrap();
+        fs::write(dir.path().join("notes/a.md"), "").unwrap();
+        fs::write(dir.path().join("notes/drafts/b.md"), "").unwrap();
+        fs::write(dir.path().join("notes/cover.png"), "").unwrap();
+        let root = dir.path().to_string_lossy();
+        let patterns =
+            |list: &[&str]| -> Vec<String> { list.iter().map(|p| p.to_string()).collect() };
+        let relative = |paths: Vec<String>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|p| {
+                    let p = Path::new(p).strip_prefix(dir.path()).unwrap();
+                    p.to_string_lossy().replace('\\', "/")
+                })
+                .collect()
+        };
+        let none = IgnoreRules::default();
+
+        let top = glob_files(&root, &patterns(&["*.md"]), &none).unwrap();
+        assert_eq!(relative(top), vec!["index.md"]);
+
+        // Overlapping patterns list each file once; "notes" is a directory
+        // and so never listed itself
+        let all = glob_files(&root, &patterns(&["**/*.md", "notes/**", "notes"]), &none).unwrap();
+        assert_eq!(
+            relative(all),
+            vec![
+                "index.md",
+                "notes/a.md",
+                "notes/cover.png",
+                "notes/drafts/b.md"
+            ]
+        );
+
+        let exclude = IgnoreRules::new(&patterns(&["drafts"])).unwrap();
+        let kept = glob_files(&root, &patterns(&["**/*.md"]), &exclude).unwrap();
+        assert_eq!(relative(kept), vec!["index.md", "notes/a.md"]);
+        assert!(glob_files(&root, &patterns(&["[oops"]), &none).is_err());
+    }
+
+    #[test]
+    fn test_find_files_fuzzy_ranks_word_starts() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(dir.path().join("node_modules/pkg/meeting-notes.md"), "").unwrap();
+        fs::write(dir.path().join("meeting-notes.md"), "").unwrap();
+        fs::write(dir.path().join("my-tangent-notes.md"), "").unwrap();
+        fs::write(dir.path().join("budget.md"), "").unwrap();
+        let root = dir.path().to_string_lossy();
+        let skip: Vec<String> = QUICK_OPEN_SKIP.iter().map(|s| s.to_string()).collect();
+        let rules = IgnoreRules::new(&skip).unwrap();
+
+        let matches = find_files_fuzzy(&root, "meno", 10, &rules).unwrap();
+
+        let names: Vec<String> = matches
+            .iter()
+            .map(|m| {
+                Path::new(&m.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, vec!["meeting-notes.md", "my-tangent-notes.md"]);
+        assert_eq!(matches[0].indices, vec![0, 1, 8, 9]);
+        assert!(matches[0].score > matches[1].score);
+        assert_eq!(find_files_fuzzy(&root, "meno", 1, &rules).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_case_insensitive_search_with_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "Rust\nrust\nRUST\n").unwrap();
+        fs::write(dir.path().join("big.md"), "rust ".repeat(100)).unwrap();
+        let root = dir.path().to_string_lossy();
+        let options = SearchOptions {
+            case_sensitive: false,
+            max_file_bytes: 100,
+            ..Default::default()
+        };
+
+        let matches = search_files(&root, "rust", &options, &IgnoreRules::default()).unwrap();
+        assert_eq!(matches.len(), 3);
+        assert!(matches.iter().all(|m| m.path.ends_with("a.md")));
+
+        let limited = SearchOptions {
+            max_results: 2,
+            ..options
+        };
+        let matches = search_files(&root, "rust", &limited, &IgnoreRules::default()).unwrap();
+        assert_eq!(matches.len(), 2);
+    }
+
+    #[test]
+    fn test_whole_word_and_regex_search() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("note.md"),
+            "cat catalog
+concat
+TODO-42 and todo-7
+",
+        )
+        .unwrap();
+        let root = dir.path().to_string_lossy();
+        let search = |query: &str, options: SearchOptions| {
+            search_files(&root, query, &options, &IgnoreRules::default())
+        };
+        let lines = |matches: Vec<SearchMatch>| -> Vec<usize> {
+            matches.iter().map(|m| m.matched.line).collect()
+        };
+
+        assert_eq!(
+            lines(search("cat", SearchOptions::default()).unwrap()),
+            [1, 2]
+        );
+        let whole_word = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let matches = search("cat", whole_word).unwrap();
+        assert_eq!(lines(matches.clone()), [1]);
+        assert_eq!(matches[0].matched.ranges.len(), 1);
+        assert_eq!(matches[0].matched.ranges[0].end, 3);
+
+        let regex = SearchOptions {
+            regex: true,
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let matches = search(r"todo-\d+", regex.clone()).unwrap();
+        let found: Vec<(usize, usize)> = matches[0]
+            .matched
+            .ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect();
+        assert_eq!(found, [(0, 7), (12, 18)]);
+        // Literal mode leaves regex syntax alone
+        assert!(search(r"todo-\d+", SearchOptions::default())
+            .unwrap()
+            .is_empty());
+
+        let error = search("(unclosed", regex.clone()).unwrap_err();
+        assert!(error.starts_with("Invalid search query"));
+        assert!(search("a{1000}{1000}", regex).is_err());
+    }
+
+    #[test]
+    fn test_search_index_saves_and_catches_up_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("vault");
+        let store = dir.path().join("index");
+        fs::create_dir(&root).unwrap();
+        let note = root.join("note.md");
+        let gone = root.join("gone.md");
+        fs::write(&note, "Planning the garden").unwrap();
+        fs::write(&gone, "garden shed").unwrap();
+        let paths = |hits: Vec<IndexedHit>| -> Vec<String> {
+            hits.into_iter().map(|hit| hit.path).collect()
+        };
+
+        let index = SearchIndex::build(&root);
+        assert_eq!(index.note_count(), 2);
+        index.save(&store).unwrap();
+        assert!(SearchIndex::load(&store, &dir.path().join("other"))
+            .unwrap()
+            .is_none());
+        let loaded = SearchIndex::load(&store, &root).unwrap().unwrap();
+        assert_eq!(paths(loaded.query("garden")), paths(index.query("garden")));
+
+        // Changes made while nothing kept the index current
+        fs::remove_file(&gone).unwrap();
+        fs::write(&note, "Harvest the garden, then the other garden").unwrap();
+        fs::write(root.join("new.md"), "harvest").unwrap();
+        let index = SearchIndex::load_or_build(&store, &root);
+        assert_eq!(index.note_count(), 2);
+        assert!(index.query("shed").is_empty());
+        assert!(index.query("planning").is_empty());
+        assert_eq!(
+            index.query("garden"),
+            vec![IndexedHit {
+                path: note.to_string_lossy().to_string(),
+                score: 2
+            }]
+        );
+        assert_eq!(index.query("harvest"), scan_notes(&root, "harvest"));
+        assert_eq!(scan_notes(&root, "harvest garden")[0].score, 3);
+
+        // Changes kept current from events are saved back where it was found
+        let mut index = index;
+        index.save_changes();
+        fs::write(&note, "compost").unwrap();
+        index.apply(&Event::new(notify::EventKind::Any).add_path(note.clone()));
+        index.save_changes();
+        let loaded = SearchIndex::load(&store, &root).unwrap().unwrap();
+        assert_eq!(
+            paths(loaded.query("compost")),
+            vec![note.to_string_lossy().to_string()]
+        );
+        assert!(loaded.query("garden").is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use chrono::NaiveDateTime;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::fs::{create_file_exclusive, FsError};
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{\s*([\w.-]+)\s*\}\}").unwrap())
+}
+
+/// Variables every template can use: `date` (`2024-05-01`), `time`
+/// (`09:30`), `datetime` and `title`, the new file's name without its
+/// extension
+pub fn builtin_variables(path: &Path, now: NaiveDateTime) -> HashMap<String, String> {
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    HashMap::from([
+        ("date".to_string(), now.format("%Y-%m-%d").to_string()),
+        ("time".to_string(), now.format("%H:%M").to_string()),
+        (
+            "datetime".to_string(),
+            now.format("%Y-%m-%d %H:%M").to_string(),
+        ),
+        ("title".to_string(), title),
+    ])
+}
+
+/// Replace each `{{key}}` in `template` (spaces inside the braces allowed)
+/// with its value. Placeholders with no value are left as written, so a
+/// typo shows up in the note rather than silently vanishing.
+pub fn fill_placeholders(template: &str, variables: &HashMap<String, String>) -> String {
+    placeholder_regex()
+        .replace_all(template, |caps: &regex::Captures| {
+            match variables.get(&caps[1]) {
+                Some(value) => value.clone(),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Create a file from a template, filling its placeholders from the
+/// built-in variables and `variables`, which take precedence. Fails if
+/// anything already exists at `path`. Returns the written content.
+pub fn create_file_from_template(
+    path: &str,
+    template: &str,
+    variables: HashMap<String, String>,
+) -> Result<String, FsError> {
+    let mut all = builtin_variables(Path::new(path), chrono::Local::now().naive_local());
+    all.extend(variables);
+    let content = fill_placeholders(template, &all);
+    create_file_exclusive(path, &content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_fill_placeholders_leaves_unknown_keys() {
+        let now = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        let mut variables = builtin_variables(Path::new("/vault/Standup.md"), now);
+        variables.insert("attendees".to_string(), "Ana, Bo".to_string());
+
+        let filled = fill_placeholders(
+            "# {{title}}\n{{ date }} {{time}}\nWith: {{attendees}}\n{{agenda}} {{}}\n",
+            &variables,
+        );
+        assert_eq!(
+            filled,
+            "# Standup\n2024-05-01 09:30\nWith: Ana, Bo\n{{agenda}} {{}}\n"
+        );
+    }
+
+    #[test]
+    fn test_create_file_from_template_refuses_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meeting.md").to_string_lossy().to_string();
+        let variables = HashMap::from([("title".to_string(), "Sync".to_string())]);
+
+        let content = create_file_from_template(&path, "# {{title}}\n", variables).unwrap();
+        assert_eq!(content, "# Sync\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Sync\n");
+        assert!(matches!(
+            create_file_from_template(&path, "again", HashMap::new()),
+            Err(FsError::AlreadyExists { .. })
+        ));
+    }
+}
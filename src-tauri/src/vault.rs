@@ -0,0 +1,2664 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use pathdiff::diff_paths;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{
+    file_hash, is_markdown_file, modified_millis, normalize_path, read_file, to_hex, unique_path,
+    walk_directory, write_file, write_file_atomic, FileItem, FileKind,
+};
+use crate::markdown::{
+    count_words, decode_link_target, derive_title, extract_code_blocks, extract_headings,
+    extract_links, extract_tags, is_external_link, parse_frontmatter, scan_lines, slugify, Link,
+    LinkKind,
+};
+
+/// A file that was moved from one location to another
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MovedFile {
+    pub from: String,
+    pub to: String,
+}
+
+/// Outcome of moving a note together with its attachments
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteMoveReport {
+    pub note: MovedFile,
+    /// Attachments referenced only by the moved note, moved alongside it
+    pub assets: Vec<MovedFile>,
+    /// Attachments left in place because other notes reference them
+    pub shared_assets: Vec<String>,
+}
+
+/// A link whose target was rewritten
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LinkRepair {
+    /// 1-based line number
+    pub line: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// A broken link that couldn't be repaired automatically
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UnresolvedLink {
+    /// 1-based line number
+    pub line: usize,
+    pub target: String,
+    /// Files sharing the target's name; empty when nothing matched
+    pub candidates: Vec<String>,
+}
+
+/// Outcome of repairing a note's broken asset links
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RepairReport {
+    pub repaired: Vec<LinkRepair>,
+    pub ambiguous: Vec<UnresolvedLink>,
+    pub missing: Vec<UnresolvedLink>,
+}
+
+/// How many notes use a given frontmatter key
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct KeyStat {
+    pub key: String,
+    pub count: usize,
+}
+
+/// How many fenced code blocks use a given language
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LangCount {
+    /// Lowercased language identifier, or `UNLABELED_LANGUAGE`
+    pub language: String,
+    pub count: usize,
+}
+
+/// Language reported for code blocks without an info string
+pub const UNLABELED_LANGUAGE: &str = "(none)";
+
+/// What `deduplicate_notes` does with the extra copies of a note
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupAction {
+    /// Only list the duplicates
+    Report,
+    /// Trash every copy but the kept one
+    Trash,
+    /// Replace every copy but the kept one with a link to it
+    Link,
+}
+
+/// Notes with identical content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DedupGroup {
+    pub hash: String,
+    /// The copy left untouched: the first by path
+    pub kept: String,
+    pub duplicates: Vec<String>,
+    /// Duplicates the action failed on, which are left as they were
+    pub errors: Vec<DedupError>,
+}
+
+/// A duplicate `deduplicate_notes` couldn't trash or link
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DedupError {
+    pub path: String,
+    pub error: String,
+}
+
+/// Markers `find_markers` looks for when none are given
+pub const DEFAULT_MARKERS: [&str; 3] = ["TODO", "FIXME", "XXX"];
+
+/// A line carrying a follow-up marker such as `TODO`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MarkerHit {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    pub marker: String,
+    /// The whole line, trimmed
+    pub text: String,
+}
+
+/// Total words in the markdown notes beneath a folder
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FolderWordCount {
+    pub path: String,
+    pub words: usize,
+}
+
+/// A note whose file name and title disagree once slugified
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TitleMismatch {
+    pub path: String,
+    /// File name without its extension
+    pub filename: String,
+    /// Title from frontmatter or the first H1
+    pub title: String,
+}
+
+/// Everything the editor's `[[`, `#` and heading autocomplete offers,
+/// gathered in one vault walk
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct AutocompleteData {
+    /// Wiki link targets: note names, or root-relative paths where names
+    /// collide
+    pub notes: Vec<String>,
+    pub tags: Vec<String>,
+    pub headings: Vec<String>,
+    /// Changes whenever a note is added, removed or modified, so the
+    /// frontend can keep its copy until the vault changes
+    pub generation: String,
+}
+
+/// Two tags and how many notes carry both
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TagPair {
+    pub first: String,
+    pub second: String,
+    pub count: usize,
+}
+
+/// Period a creation histogram counts notes over
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+}
+
+/// How many notes were created in one period
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HistogramBucket {
+    /// First day of the period, as `YYYY-MM-DD`
+    pub start: String,
+    pub count: usize,
+}
+
+/// How an index note groups the notes it lists
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    Folder,
+    Tag,
+}
+
+/// Outcome of converting one note's links
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConvertResult {
+    pub path: String,
+    /// Links rewritten (or that would be, in a dry run)
+    pub converted: usize,
+    /// Links in the source style left alone because no note matched
+    pub unresolved: usize,
+}
+
+/// A link whose target doesn't exist
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub note: String,
+    /// 1-based line number
+    pub line: usize,
+    pub target: String,
+}
+
+/// A link from a note to a web page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ExternalLinkRef {
+    pub note: String,
+    /// 1-based line number
+    pub line: usize,
+    pub url: String,
+}
+
+/// Notes sharing a name, which makes `[[name]]` links ambiguous
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DuplicateName {
+    pub name: String,
+    pub paths: Vec<String>,
+}
+
+/// Consolidated result of checking a vault for problems
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VaultValidation {
+    pub broken_links: Vec<BrokenLink>,
+    /// Broken embeds (`![[...]]` and `![...](...)`)
+    pub broken_transclusions: Vec<BrokenLink>,
+    /// Notes that aren't valid UTF-8 and so can't be checked further
+    pub non_utf8: Vec<String>,
+    pub duplicate_names: Vec<DuplicateName>,
+    /// Non-markdown files no note links to or embeds
+    pub orphan_attachments: Vec<String>,
+}
+
+/// How a link between notes is written
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// `[[Note]]`, using the note's base name
+    Wiki,
+    /// `[Note](relative/path.md)`
+    Relative,
+}
+
+/// Gitignore-style patterns for skipping paths during vault walks.
+///
+/// Patterns without a `/` match a file or directory name at any depth;
+/// other patterns match against the path relative to the vault root. Hidden
+/// entries are always skipped.
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    set: GlobSet,
+}
+
+impl IgnoreRules {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.trim().trim_start_matches('/').trim_end_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            let base = if pattern.contains('/') {
+                pattern.to_string()
+            } else {
+                format!("**/{}", pattern)
+            };
+            for glob in [base.clone(), format!("{}/**", base)] {
+                builder.add(
+                    Glob::new(&glob)
+                        .map_err(|e| format!("Invalid ignore pattern {}: {}", pattern, e))?,
+                );
+            }
+        }
+        let set = builder
+            .build()
+            .map_err(|e| format!("Failed to compile ignore patterns: {}", e))?;
+        Ok(IgnoreRules { set })
+    }
+
+    /// Whether `relative` (a path relative to the vault root) is ignored
+    pub fn is_ignored(&self, relative: &Path) -> bool {
+        relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            || self.matches_pattern(relative)
+    }
+
+    /// Whether `relative` matches one of the patterns, without the implicit
+    /// skipping of hidden entries
+    pub fn matches_pattern(&self, relative: &Path) -> bool {
+        self.set.is_match(relative)
+    }
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        IgnoreRules {
+            set: GlobSet::empty(),
+        }
+    }
+}
+
+/// Collect all markdown files under `root`, skipping hidden directories
+pub fn markdown_files(root: &Path) -> Vec<PathBuf> {
+    markdown_files_with(root, &IgnoreRules::default())
+}
+
+/// Collect all markdown files under `root` that aren't ignored
+pub fn markdown_files_with(root: &Path, ignore: &IgnoreRules) -> Vec<PathBuf> {
+    vault_files(root, ignore)
+        .into_iter()
+        .filter(|path| is_markdown_file(&path.file_name().unwrap_or_default().to_string_lossy()))
+        .collect()
+}
+
+/// Collect every file under `root` that isn't ignored, sorted by path.
+/// Symlinked directories aren't descended into.
+pub fn vault_files(root: &Path, ignore: &IgnoreRules) -> Vec<PathBuf> {
+    vault_files_with(root, ignore, false)
+}
+
+/// `vault_files`, descending into symlinked directories when
+/// `follow_symlinks` is set; see `walk_directory`
+pub fn vault_files_with(root: &Path, ignore: &IgnoreRules, follow_symlinks: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    // An unreadable root just has no files, like any unreadable directory
+    let _ = walk_directory(root, follow_symlinks, |entry| {
+        let relative = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+        if ignore.is_ignored(relative) {
+            return false;
+        }
+        if !entry.metadata.is_dir() {
+            files.push(entry.path.clone());
+        }
+        true
+    });
+
+    files.sort();
+    files
+}
+
+/// Resolves link targets to notes within a vault
+pub struct NoteResolver {
+    root: PathBuf,
+    notes: HashSet<PathBuf>,
+    by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+impl NoteResolver {
+    pub fn new(root: &Path, notes: &[PathBuf]) -> Self {
+        let mut by_name: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for note in notes {
+            by_name
+                .entry(note_name(note).to_lowercase())
+                .or_default()
+                .push(note.clone());
+        }
+        NoteResolver {
+            root: root.to_path_buf(),
+            notes: notes.iter().cloned().collect(),
+            by_name,
+        }
+    }
+
+    /// Resolve a link in `from` to the note it points at, if any
+    pub fn resolve(&self, from: &Path, link: &Link) -> Option<PathBuf> {
+        match link.kind {
+            LinkKind::Markdown => {
+                let target = resolve_link_target(from, &link.target)?;
+                self.notes.contains(&target).then_some(target)
+            }
+            LinkKind::Wiki => self.resolve_wiki(from, &link.target),
+        }
+    }
+
+    /// Resolve a wiki target by name, or by root-relative path when it
+    /// contains a `/`. Ambiguous names prefer a note in the same folder.
+    pub fn resolve_wiki(&self, from: &Path, target: &str) -> Option<PathBuf> {
+        if target.contains('/') {
+            let base = normalize_path(&self.root.join(target));
+            let with_extension = PathBuf::from(format!("{}.md", base.to_string_lossy()));
+            return [base, with_extension]
+                .into_iter()
+                .find(|candidate| self.notes.contains(candidate));
+        }
+
+        let name = target.strip_suffix(".md").unwrap_or(target).to_lowercase();
+        let candidates = self.by_name.get(&name)?;
+        candidates
+            .iter()
+            .find(|candidate| candidate.parent() == from.parent())
+            .or_else(|| candidates.first())
+            .cloned()
+    }
+}
+
+/// Directed links between the notes of a vault
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    pub notes: Vec<PathBuf>,
+    pub outbound: HashMap<PathBuf, Vec<PathBuf>>,
+    pub inbound: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+/// Build the note-to-note link graph for a vault. Self-links are ignored.
+pub fn build_link_graph(root: &Path, ignore: &IgnoreRules) -> LinkGraph {
+    let notes = markdown_files_with(root, ignore);
+    let resolver = NoteResolver::new(root, &notes);
+    let mut graph = LinkGraph::default();
+
+    for note in &notes {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        for link in extract_links(&content) {
+            let Some(target) = resolver.resolve(note, &link) else {
+                continue;
+            };
+            if &target == note {
+                continue;
+            }
+            let outbound = graph.outbound.entry(note.clone()).or_default();
+            if !outbound.contains(&target) {
+                outbound.push(target.clone());
+                graph.inbound.entry(target).or_default().push(note.clone());
+            }
+        }
+    }
+
+    graph.notes = notes;
+    graph
+}
+
+/// Opens the section `render_with_backlinks` appends. Everything from here
+/// to `BACKLINKS_END` is generated and must be stripped before saving.
+pub const BACKLINKS_START: &str = "<!-- manza:linked-mentions -->";
+/// Closes the section `render_with_backlinks` appends
+pub const BACKLINKS_END: &str = "<!-- /manza:linked-mentions -->";
+
+/// A note's content followed by a "Linked mentions" section listing, as
+/// wiki links, every note that links to it. The section is wrapped in
+/// `BACKLINKS_START`/`BACKLINKS_END` and left out when nothing links here.
+pub fn render_with_backlinks(
+    root: &str,
+    note_path: &str,
+    ignore: &IgnoreRules,
+) -> Result<String, String> {
+    let root = canonical(root)?;
+    let note = canonical(note_path)?;
+    let mut rendered = read_file(&note.to_string_lossy())?;
+    let graph = build_link_graph(&root, ignore);
+    let Some(sources) = graph.inbound.get(&note) else {
+        return Ok(rendered);
+    };
+    let mut sources = sources.clone();
+    sources.sort();
+
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+    rendered.push_str(&format!("\n{}\n## Linked mentions\n\n", BACKLINKS_START));
+    for source in &sources {
+        rendered.push_str(&format!(
+            "- [[{}]]\n",
+            wiki_target(&root, &graph.notes, source)
+        ));
+    }
+    rendered.push_str(BACKLINKS_END);
+    rendered.push('\n');
+    Ok(rendered)
+}
+
+/// Which notes count as orphans
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanMode {
+    /// No other note links to it
+    #[default]
+    NoBacklinks,
+    /// No inbound and no outbound links
+    Isolated,
+}
+
+/// Find notes that nothing links to (and, for `Isolated`, that link nowhere)
+pub fn find_orphan_notes(
+    root: &str,
+    mode: OrphanMode,
+    ignore: &IgnoreRules,
+) -> Result<Vec<String>, String> {
+    let root = canonical(root)?;
+    let graph = build_link_graph(&root, ignore);
+
+    Ok(graph
+        .notes
+        .iter()
+        .filter(|note| !graph.inbound.contains_key(*note))
+        .filter(|note| mode == OrphanMode::NoBacklinks || !graph.outbound.contains_key(*note))
+        .map(|note| note.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Shortest chain of links from `from_note` to `to_note`, inclusive of both
+/// ends. With `directed` unset, backlinks can be followed as well.
+pub fn link_path_between(
+    root: &str,
+    from_note: &str,
+    to_note: &str,
+    directed: bool,
+    ignore: &IgnoreRules,
+) -> Result<Option<Vec<String>>, String> {
+    let root = canonical(root)?;
+    let from = canonical(from_note)?;
+    let to = canonical(to_note)?;
+    let graph = build_link_graph(&root, ignore);
+
+    let mut previous: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut queue = VecDeque::from([from.clone()]);
+    let mut visited = HashSet::from([from.clone()]);
+
+    while let Some(note) = queue.pop_front() {
+        if note == to {
+            let mut path = vec![note.to_string_lossy().to_string()];
+            let mut current = &note;
+            while let Some(prev) = previous.get(current) {
+                path.push(prev.to_string_lossy().to_string());
+                current = prev;
+            }
+            path.reverse();
+            return Ok(Some(path));
+        }
+
+        let outbound = graph.outbound.get(&note).into_iter().flatten();
+        let inbound = graph
+            .inbound
+            .get(&note)
+            .into_iter()
+            .flatten()
+            .filter(|_| !directed);
+        for next in outbound.chain(inbound) {
+            if visited.insert(next.clone()) {
+                previous.insert(next.clone(), note.clone());
+                queue.push_back(next.clone());
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// How many levels of sub-indexes `reading_order` expands
+const READING_ORDER_MAX_DEPTH: usize = 4;
+
+/// Notes linked from an index note, top to bottom. Linked notes whose name
+/// contains "index" are sub-indexes and are expanded in place, up to
+/// `READING_ORDER_MAX_DEPTH` levels; each note appears at most once.
+pub fn reading_order(
+    root: &str,
+    index_note: &str,
+    ignore: &IgnoreRules,
+) -> Result<Vec<String>, String> {
+    let root = canonical(root)?;
+    let index = canonical(index_note)?;
+    let notes = markdown_files_with(&root, ignore);
+    let resolver = NoteResolver::new(&root, &notes);
+
+    let mut visited = HashSet::from([index.clone()]);
+    let mut order = Vec::new();
+    collect_reading_order(&resolver, &index, 1, &mut visited, &mut order);
+    Ok(order
+        .into_iter()
+        .map(|note| note.to_string_lossy().to_string())
+        .collect())
+}
+
+fn collect_reading_order(
+    resolver: &NoteResolver,
+    index: &Path,
+    depth: usize,
+    visited: &mut HashSet<PathBuf>,
+    order: &mut Vec<PathBuf>,
+) {
+    let Ok(content) = read_file(&index.to_string_lossy()) else {
+        return;
+    };
+    let mut links = extract_links(&content);
+    links.retain(|link| !link.embed);
+    links.sort_by_key(|link| link.start);
+
+    for link in links {
+        let Some(target) = resolver.resolve(index, &link) else {
+            continue;
+        };
+        if !visited.insert(target.clone()) {
+            continue;
+        }
+        let is_index = note_name(&target).to_lowercase().contains("index");
+        if is_index && depth < READING_ORDER_MAX_DEPTH {
+            collect_reading_order(resolver, &target, depth + 1, visited, order);
+        } else {
+            order.push(target);
+        }
+    }
+}
+
+/// Resolve a markdown link target relative to the note containing it.
+///
+/// Returns `None` for external URLs and pure `#anchor` links.
+pub fn resolve_link_target(note: &Path, target: &str) -> Option<PathBuf> {
+    if target.is_empty() || is_external_link(target) {
+        return None;
+    }
+    let base = note.parent()?;
+    Some(normalize_path(&base.join(decode_link_target(target))))
+}
+
+/// Outcome of resolving one reference with `resolve_asset_path`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ResolvedAsset {
+    pub reference: String,
+    /// Absolute path of the asset, when it resolved to an existing file
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ResolvedAsset {
+    pub fn new(reference: &str, result: Result<String, String>) -> Self {
+        let (path, error) = match result {
+            Ok(path) => (Some(path), None),
+            Err(e) => (None, Some(e)),
+        };
+        ResolvedAsset {
+            reference: reference.to_string(),
+            path,
+            error,
+        }
+    }
+}
+
+/// Absolute path of a file a note refers to, e.g. in `![](./images/a.png)`,
+/// for the preview to load. Relative references are resolved against the
+/// note's folder and absolute ones kept; `%XX` escapes are decoded and any
+/// `#fragment` or `?query` dropped. Fails for URLs and for references to
+/// files that don't exist.
+pub fn resolve_asset_path(note: &str, reference: &str) -> Result<String, String> {
+    let target = reference.trim();
+    let target = target
+        .strip_prefix('<')
+        .and_then(|target| target.strip_suffix('>'))
+        .unwrap_or(target);
+    let target = target.split(['#', '?']).next().unwrap_or_default();
+    if target.is_empty() || is_external_link(target) {
+        return Err(format!("Not a local asset: {}", reference));
+    }
+
+    let target = PathBuf::from(decode_link_target(target));
+    let resolved = if target.is_absolute() {
+        normalize_path(&target)
+    } else {
+        let base = Path::new(note)
+            .parent()
+            .ok_or_else(|| format!("Invalid note path: {}", note))?;
+        normalize_path(&base.join(target))
+    };
+    if !resolved.is_file() {
+        return Err(format!("Asset not found: {}", resolved.display()));
+    }
+    Ok(resolved.to_string_lossy().to_string())
+}
+
+/// Format `to` as a markdown link target relative to the directory `from_dir`
+pub fn relative_link_target(from_dir: &Path, to: &Path) -> String {
+    diff_paths(to, from_dir)
+        .unwrap_or_else(|| to.to_path_buf())
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(' ', "%20")
+}
+
+/// Name a note is referred to by in wiki links: its file stem
+pub fn note_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// What to write inside `[[...]]` to reach `note`: its name, or its
+/// root-relative path when another of `notes` shares that name
+fn wiki_target(root: &Path, notes: &[PathBuf], note: &Path) -> String {
+    let name = note_name(note);
+    let collides = notes.iter().any(|other| {
+        note_name(other).eq_ignore_ascii_case(&name)
+            && fs::canonicalize(other).is_ok_and(|other| other != note)
+    });
+    if !collides {
+        return name;
+    }
+    let relative = note.strip_prefix(root).unwrap_or(note);
+    relative
+        .with_extension("")
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Build the markup for a link from `from_note` to `to_note`.
+///
+/// Wiki links fall back to a root-relative path when another note in the
+/// vault shares the target's base name.
+pub fn link_reference(
+    root: &str,
+    from_note: &str,
+    to_note: &str,
+    style: LinkStyle,
+) -> Result<String, String> {
+    let root = canonical(root)?;
+    let from_note = canonical(from_note)?;
+    let to_note = canonical(to_note)?;
+    let name = note_name(&to_note);
+
+    match style {
+        LinkStyle::Wiki => Ok(format!(
+            "[[{}]]",
+            wiki_target(&root, &markdown_files(&root), &to_note)
+        )),
+        LinkStyle::Relative => {
+            let from_dir = from_note
+                .parent()
+                .ok_or_else(|| "Note has no parent directory".to_string())?;
+            Ok(format!(
+                "[{}]({})",
+                name,
+                relative_link_target(from_dir, &to_note)
+            ))
+        }
+    }
+}
+
+fn canonical(path: &str) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|e| format!("Failed to resolve path {}: {}", path, e))
+}
+
+/// Resolved local markdown-link targets in a note, paired with the byte range
+/// of each target in the content
+fn local_link_targets(note: &Path, content: &str) -> Vec<(usize, usize, PathBuf)> {
+    extract_links(content)
+        .into_iter()
+        .filter(|link| link.kind == LinkKind::Markdown)
+        .filter_map(|link| {
+            let resolved = resolve_link_target(note, &link.target)?;
+            let resolved = fs::canonicalize(resolved).ok()?;
+            Some((link.target_start, link.target_end, resolved))
+        })
+        .collect()
+}
+
+/// Rename each `(from, to)` pair in order, creating missing parent folders.
+/// If one fails, the ones already moved are put back.
+fn move_all(moves: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    for (i, (from, to)) in moves.iter().enumerate() {
+        let moved = match to.parent() {
+            Some(parent) => {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))
+            }
+            None => Ok(()),
+        }
+        .and_then(|_| {
+            fs::rename(from, to).map_err(|e| format!("Failed to move {}: {}", from.display(), e))
+        });
+        if let Err(e) = moved {
+            return Err(undo_moves(&moves[..i], e));
+        }
+    }
+    Ok(())
+}
+
+/// Put back the completed `moves`, latest first, returning `error` along
+/// with any file that could not be restored
+fn undo_moves(moves: &[(PathBuf, PathBuf)], error: String) -> String {
+    let stranded: Vec<String> = moves
+        .iter()
+        .rev()
+        .filter(|(from, to)| fs::rename(to, from).is_err())
+        .map(|(from, to)| format!("{} (left as {})", from.display(), to.display()))
+        .collect();
+    if stranded.is_empty() {
+        error
+    } else {
+        format!("{}; could not restore {}", error, stranded.join(", "))
+    }
+}
+
+/// Move a note into `target_dir` along with any attachments no other note
+/// references, rewriting the note's local links for its new location.
+/// Other notes are found with `ignore` applied, and a move that fails
+/// partway is undone.
+pub fn move_note_with_assets(
+    root: &str,
+    note_path: &str,
+    target_dir: &str,
+    ignore: &IgnoreRules,
+) -> Result<NoteMoveReport, String> {
+    let root = canonical(root)?;
+    let note = canonical(note_path)?;
+    let target_dir = canonical(target_dir)?;
+
+    if !target_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", target_dir.display()));
+    }
+
+    let note_dir = note
+        .parent()
+        .ok_or_else(|| format!("Note has no parent directory: {}", note_path))?
+        .to_path_buf();
+    let file_name = note
+        .file_name()
+        .ok_or_else(|| format!("Invalid note path: {}", note_path))?;
+    let new_note = target_dir.join(file_name);
+    if new_note.exists() {
+        return Err(format!(
+            "Destination already exists: {}",
+            new_note.display()
+        ));
+    }
+
+    let content = read_file(&note.to_string_lossy())?;
+    let targets = local_link_targets(&note, &content);
+
+    let mut assets: Vec<PathBuf> = Vec::new();
+    for (_, _, path) in &targets {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_file() && !is_markdown_file(&name) && !assets.contains(path) {
+            assets.push(path.clone());
+        }
+    }
+
+    let mut referenced_elsewhere: HashSet<PathBuf> = HashSet::new();
+    for other in markdown_files_with(&root, ignore) {
+        let Ok(other) = fs::canonicalize(&other) else {
+            continue;
+        };
+        if other == note {
+            continue;
+        }
+        if let Ok(other_content) = read_file(&other.to_string_lossy()) {
+            for (_, _, path) in local_link_targets(&other, &other_content) {
+                referenced_elsewhere.insert(path);
+            }
+        }
+    }
+
+    // Exclusive assets keep their position relative to the note
+    let mut planned: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut shared_assets = Vec::new();
+    for asset in &assets {
+        if referenced_elsewhere.contains(asset) {
+            shared_assets.push(asset.to_string_lossy().to_string());
+            continue;
+        }
+        let relative = asset
+            .strip_prefix(&note_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| PathBuf::from(asset.file_name().unwrap_or_default()));
+        let destination = target_dir.join(relative);
+        if destination.exists() {
+            return Err(format!(
+                "Destination already exists: {}",
+                destination.display()
+            ));
+        }
+        planned.insert(asset.clone(), destination);
+    }
+
+    let mut moves: Vec<(PathBuf, PathBuf)> = assets
+        .iter()
+        .filter_map(|asset| Some((asset.clone(), planned.get(asset)?.clone())))
+        .collect();
+    let moved_assets = moves
+        .iter()
+        .map(|(from, to)| MovedFile {
+            from: from.to_string_lossy().to_string(),
+            to: to.to_string_lossy().to_string(),
+        })
+        .collect();
+    moves.push((note.clone(), new_note.clone()));
+    move_all(&moves)?;
+
+    let mut rewritten = content.clone();
+    for (start, end, path) in targets.iter().rev() {
+        let location = planned.get(path).unwrap_or(path);
+        rewritten.replace_range(*start..*end, &relative_link_target(&target_dir, location));
+    }
+    if rewritten != content {
+        if let Err(e) = write_file(&new_note.to_string_lossy(), &rewritten) {
+            return Err(undo_moves(&moves, e.into()));
+        }
+    }
+
+    Ok(NoteMoveReport {
+        note: MovedFile {
+            from: note.to_string_lossy().to_string(),
+            to: new_note.to_string_lossy().to_string(),
+        },
+        assets: moved_assets,
+        shared_assets,
+    })
+}
+
+/// Repair a note's broken local asset links by finding files with the same
+/// name elsewhere in the vault. Only unique matches are rewritten.
+pub fn repair_asset_links(
+    root: &str,
+    note_path: &str,
+    ignore: &IgnoreRules,
+) -> Result<RepairReport, String> {
+    let root = canonical(root)?;
+    let note = canonical(note_path)?;
+    let note_dir = note
+        .parent()
+        .ok_or_else(|| format!("Note has no parent directory: {}", note_path))?;
+    let content = read_file(&note.to_string_lossy())?;
+
+    let mut by_name: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for file in vault_files(&root, ignore) {
+        let name = file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        by_name.entry(name).or_default().push(file);
+    }
+
+    let mut report = RepairReport::default();
+    let mut rewritten = content.clone();
+    let links = extract_links(&content);
+    for link in links.iter().rev() {
+        if link.kind != LinkKind::Markdown {
+            continue;
+        }
+        let Some(resolved) = resolve_link_target(&note, &link.target) else {
+            continue;
+        };
+        let name = resolved
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if resolved.exists() || is_markdown_file(&name) {
+            continue;
+        }
+
+        let candidates = by_name.get(&name).cloned().unwrap_or_default();
+        match candidates.as_slice() {
+            [found] => {
+                let target = relative_link_target(note_dir, found);
+                rewritten.replace_range(link.target_start..link.target_end, &target);
+                report.repaired.push(LinkRepair {
+                    line: link.line,
+                    from: link.target.clone(),
+                    to: target,
+                });
+            }
+            found => {
+                let unresolved = UnresolvedLink {
+                    line: link.line,
+                    target: link.target.clone(),
+                    candidates: found
+                        .iter()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect(),
+                };
+                if found.is_empty() {
+                    report.missing.push(unresolved);
+                } else {
+                    report.ambiguous.push(unresolved);
+                }
+            }
+        }
+    }
+
+    if rewritten != content {
+        write_file(&note.to_string_lossy(), &rewritten)?;
+    }
+
+    // Links were visited last to first so replacements kept offsets valid
+    report.repaired.reverse();
+    report.ambiguous.reverse();
+    report.missing.reverse();
+    Ok(report)
+}
+
+/// Parse a date in one of the common frontmatter formats, discarding any
+/// time of day
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.date_naive());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(datetime.date());
+        }
+    }
+    ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%B %d, %Y", "%b %d, %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+}
+
+/// Notes whose frontmatter `field` holds a date within `[start, end]`,
+/// ordered by that date. Notes without a parseable date are skipped.
+pub fn query_by_date_range(
+    root: &str,
+    field: &str,
+    start: &str,
+    end: &str,
+    ignore: &IgnoreRules,
+) -> Result<Vec<String>, String> {
+    let start = parse_date(start).ok_or_else(|| format!("Invalid start date: {}", start))?;
+    let end = parse_date(end).ok_or_else(|| format!("Invalid end date: {}", end))?;
+    let root = canonical(root)?;
+
+    let mut matches: Vec<(NaiveDate, PathBuf)> = markdown_files_with(&root, ignore)
+        .into_iter()
+        .filter_map(|note| {
+            let content = read_file(&note.to_string_lossy()).ok()?;
+            let frontmatter = parse_frontmatter(&content).frontmatter?;
+            let date = parse_date(frontmatter.get(field)?.as_str()?)?;
+            (start <= date && date <= end).then_some((date, note))
+        })
+        .collect();
+
+    matches.sort();
+    Ok(matches
+        .into_iter()
+        .map(|(_, note)| note.to_string_lossy().to_string())
+        .collect())
+}
+
+/// When a note was created: its frontmatter `created` date, else the file's
+/// creation time, else its modification time on platforms without one
+fn creation_date(note: &Path) -> Option<NaiveDate> {
+    let from_frontmatter = read_file(&note.to_string_lossy())
+        .ok()
+        .and_then(|content| parse_frontmatter(&content).frontmatter)
+        .and_then(|frontmatter| parse_date(frontmatter.get("created")?.as_str()?));
+    if from_frontmatter.is_some() {
+        return from_frontmatter;
+    }
+    let metadata = fs::metadata(note).ok()?;
+    let time = metadata.created().or_else(|_| metadata.modified()).ok()?;
+    Some(DateTime::<Local>::from(time).date_naive())
+}
+
+/// Count notes created in each day, week or month, oldest first. Periods
+/// without notes are left out.
+pub fn creation_histogram(
+    root: &str,
+    bucket: Bucket,
+    ignore: &IgnoreRules,
+) -> Result<Vec<HistogramBucket>, String> {
+    let root = canonical(root)?;
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+
+    for note in markdown_files_with(&root, ignore) {
+        let Some(date) = creation_date(&note) else {
+            continue;
+        };
+        let start = match bucket {
+            Bucket::Day => date,
+            Bucket::Week => date.week(chrono::Weekday::Mon).first_day(),
+            Bucket::Month => date.with_day(1).unwrap_or(date),
+        };
+        *counts.entry(start).or_default() += 1;
+    }
+
+    Ok(counts
+        .into_iter()
+        .map(|(start, count)| HistogramBucket {
+            start: start.format("%Y-%m-%d").to_string(),
+            count,
+        })
+        .collect())
+}
+
+/// Tally frontmatter keys across the vault, most common first, so rare
+/// variants like `tag` beside `tags` stand out
+pub fn frontmatter_key_report(root: &str, ignore: &IgnoreRules) -> Result<Vec<KeyStat>, String> {
+    let root = canonical(root)?;
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let Some(serde_json::Value::Object(keys)) = parse_frontmatter(&content).frontmatter else {
+            continue;
+        };
+        for key in keys.keys() {
+            *counts.entry(key.clone()).or_default() += 1;
+        }
+    }
+
+    let mut stats: Vec<KeyStat> = counts
+        .into_iter()
+        .map(|(key, count)| KeyStat { key, count })
+        .collect();
+    stats.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    Ok(stats)
+}
+
+/// Move a note into a folder named after its first tag, under
+/// `organize_root` (relative to the vault root), returning its new path.
+/// Nested tags like `project/alpha` become nested folders, and a name clash
+/// in the folder gets a numbered name. Notes without tags stay put.
+pub fn organize_by_tag(
+    root: &str,
+    note_path: &str,
+    organize_root: &str,
+) -> Result<Option<String>, String> {
+    let root = canonical(root)?;
+    let note = canonical(note_path)?;
+    let content = read_file(&note.to_string_lossy())?;
+    let Some(tag) = extract_tags(&content).into_iter().next() else {
+        return Ok(None);
+    };
+
+    let folder = normalize_path(&root.join(organize_root).join(&tag));
+    if !folder.starts_with(&root) {
+        return Err(format!("Tag folder is outside the vault: {}", tag));
+    }
+    if note.parent() == Some(folder.as_path()) {
+        return Ok(Some(note.to_string_lossy().to_string()));
+    }
+    fs::create_dir_all(&folder).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let file_name = note
+        .file_name()
+        .ok_or_else(|| format!("Invalid note path: {}", note_path))?;
+    let mut destination = folder.join(file_name);
+    if destination.exists() {
+        destination = unique_path(&destination);
+    }
+    fs::rename(&note, &destination).map_err(|e| format!("Failed to move note: {}", e))?;
+    Ok(Some(destination.to_string_lossy().to_string()))
+}
+
+/// Collect note targets, tags and headings for autocomplete, each sorted
+/// and deduplicated
+pub fn autocomplete_data(root: &str, ignore: &IgnoreRules) -> Result<AutocompleteData, String> {
+    let root = canonical(root)?;
+    let notes = markdown_files_with(&root, ignore);
+
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for note in &notes {
+        *name_counts
+            .entry(note_name(note).to_lowercase())
+            .or_default() += 1;
+    }
+
+    let mut data = AutocompleteData::default();
+    let mut tags: HashSet<String> = HashSet::new();
+    let mut headings: HashSet<String> = HashSet::new();
+    let mut hasher = Sha256::new();
+    for note in &notes {
+        let name = note_name(note);
+        let relative = note.strip_prefix(&root).unwrap_or(note);
+        data.notes.push(if name_counts[&name.to_lowercase()] > 1 {
+            relative
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/")
+        } else {
+            name
+        });
+
+        let metadata = fs::metadata(note).ok();
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(metadata.as_ref().map_or(0, |m| m.len()).to_le_bytes());
+        hasher.update(
+            metadata
+                .and_then(|m| modified_millis(&m))
+                .unwrap_or(0)
+                .to_le_bytes(),
+        );
+
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        tags.extend(extract_tags(&content));
+        headings.extend(extract_headings(&content).into_iter().map(|h| h.text));
+    }
+
+    data.notes.sort();
+    data.notes.dedup();
+    data.tags = tags.into_iter().collect();
+    data.tags.sort();
+    data.headings = headings.into_iter().collect();
+    data.headings.sort();
+    data.generation = to_hex(&hasher.finalize());
+    Ok(data)
+}
+
+/// Tally the languages of fenced code blocks across the vault, most common
+/// first
+pub fn code_block_languages(root: &str, ignore: &IgnoreRules) -> Result<Vec<LangCount>, String> {
+    let root = canonical(root)?;
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        for block in extract_code_blocks(&content) {
+            let language = block
+                .language
+                .map_or_else(|| UNLABELED_LANGUAGE.to_string(), |l| l.to_lowercase());
+            *counts.entry(language).or_default() += 1;
+        }
+    }
+
+    let mut stats: Vec<LangCount> = counts
+        .into_iter()
+        .map(|(language, count)| LangCount { language, count })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.language.cmp(&b.language))
+    });
+    Ok(stats)
+}
+
+/// Sort key read from a note's frontmatter: numbers before strings, and
+/// notes without the field last
+#[derive(PartialEq, PartialOrd)]
+enum OrderKey {
+    Number(f64),
+    Text(String),
+    Missing,
+}
+
+fn order_key(path: &str, field: &str) -> OrderKey {
+    let frontmatter = read_file(path)
+        .ok()
+        .and_then(|content| parse_frontmatter(&content).frontmatter);
+    match frontmatter.as_ref().and_then(|fm| fm.get(field)) {
+        Some(serde_json::Value::Number(n)) => {
+            n.as_f64().map_or(OrderKey::Missing, OrderKey::Number)
+        }
+        Some(serde_json::Value::String(text)) => match text.trim().parse::<f64>() {
+            Ok(n) => OrderKey::Number(n),
+            Err(_) => OrderKey::Text(text.to_lowercase()),
+        },
+        _ => OrderKey::Missing,
+    }
+}
+
+/// Sort notes by a frontmatter field such as `order`. Ties, including the
+/// notes lacking the field, are broken by file name.
+pub fn order_by_frontmatter(paths: Vec<String>, field: &str) -> Vec<String> {
+    let mut keyed: Vec<(OrderKey, String, String)> = paths
+        .into_iter()
+        .map(|path| {
+            let name = Path::new(&path)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            (order_key(&path, field), name, path)
+        })
+        .collect();
+    keyed.sort_by(|a, b| {
+        a.0.partial_cmp(&b.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.1.cmp(&b.1))
+    });
+    keyed.into_iter().map(|(_, _, path)| path).collect()
+}
+
+/// Resolve a link to an existing note or attachment
+fn resolve_vault_link(
+    resolver: &NoteResolver,
+    attachments_by_name: &HashMap<String, Vec<PathBuf>>,
+    note: &Path,
+    link: &Link,
+) -> Option<PathBuf> {
+    if let Some(target) = resolver.resolve(note, link) {
+        return Some(target);
+    }
+    match link.kind {
+        LinkKind::Markdown => resolve_link_target(note, &link.target).filter(|path| path.is_file()),
+        LinkKind::Wiki => attachments_by_name
+            .get(&link.target.to_lowercase())
+            .and_then(|paths| paths.first())
+            .cloned(),
+    }
+}
+
+/// Notes grouped under each name shared by more than one of them
+fn duplicate_names(notes: &[PathBuf]) -> Vec<DuplicateName> {
+    let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+    for note in notes {
+        by_name
+            .entry(note_name(note).to_lowercase())
+            .or_default()
+            .push(note.to_string_lossy().to_string());
+    }
+    let mut duplicates: Vec<DuplicateName> = by_name
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(name, paths)| DuplicateName { name, paths })
+        .collect();
+    duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+    duplicates
+}
+
+/// Every `http(s)://` markdown link in the vault, in note and line order
+pub fn find_external_links(
+    root: &str,
+    ignore: &IgnoreRules,
+) -> Result<Vec<ExternalLinkRef>, String> {
+    let root = canonical(root)?;
+    let mut found = Vec::new();
+
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let mut links: Vec<Link> = extract_links(&content)
+            .into_iter()
+            .filter(|link| link.kind == LinkKind::Markdown && !link.embed)
+            .filter(|link| {
+                let target = link.target.to_lowercase();
+                target.starts_with("http://") || target.starts_with("https://")
+            })
+            .collect();
+        links.sort_by_key(|link| link.start);
+        for link in links {
+            found.push(ExternalLinkRef {
+                note: note.to_string_lossy().to_string(),
+                line: link.line,
+                url: match link.anchor {
+                    Some(anchor) => format!("{}#{}", link.target, anchor),
+                    None => link.target,
+                },
+            });
+        }
+    }
+
+    Ok(found)
+}
+
+/// Check the whole vault in one walk: broken links and embeds, notes that
+/// aren't UTF-8, ambiguous note names and attachments nothing references
+pub fn validate_vault(root: &str, ignore: &IgnoreRules) -> Result<VaultValidation, String> {
+    let root = canonical(root)?;
+    let (notes, attachments): (Vec<PathBuf>, Vec<PathBuf>) =
+        vault_files(&root, ignore).into_iter().partition(|path| {
+            is_markdown_file(&path.file_name().unwrap_or_default().to_string_lossy())
+        });
+
+    let resolver = NoteResolver::new(&root, &notes);
+    let mut attachments_by_name: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for attachment in &attachments {
+        let name = attachment.file_name().unwrap_or_default().to_string_lossy();
+        attachments_by_name
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(attachment.clone());
+    }
+
+    let mut validation = VaultValidation {
+        duplicate_names: duplicate_names(&notes),
+        ..Default::default()
+    };
+    let mut referenced: HashSet<PathBuf> = HashSet::new();
+
+    for note in &notes {
+        let Ok(bytes) = fs::read(note) else {
+            continue;
+        };
+        let Ok(content) = String::from_utf8(bytes) else {
+            validation.non_utf8.push(note.to_string_lossy().to_string());
+            continue;
+        };
+
+        for link in extract_links(&content) {
+            if link.target.is_empty() || is_external_link(&link.target) {
+                continue;
+            }
+            match resolve_vault_link(&resolver, &attachments_by_name, note, &link) {
+                Some(target) => {
+                    referenced.insert(target);
+                }
+                None => {
+                    let broken = BrokenLink {
+                        note: note.to_string_lossy().to_string(),
+                        line: link.line,
+                        target: link.target.clone(),
+                    };
+                    if link.embed {
+                        validation.broken_transclusions.push(broken);
+                    } else {
+                        validation.broken_links.push(broken);
+                    }
+                }
+            }
+        }
+    }
+
+    validation.orphan_attachments = attachments
+        .iter()
+        .filter(|attachment| !referenced.contains(*attachment))
+        .map(|attachment| attachment.to_string_lossy().to_string())
+        .collect();
+    Ok(validation)
+}
+
+/// Rewrite links between notes into `to_style` across the vault: wiki links
+/// become relative markdown links, or markdown links to notes become wiki
+/// links. Aliases, anchors and embeds carry over. Only notes with links in
+/// the source style are reported; a dry run counts without writing.
+pub fn convert_links(
+    root: &str,
+    to_style: LinkStyle,
+    dry_run: bool,
+    ignore: &IgnoreRules,
+) -> Result<Vec<ConvertResult>, String> {
+    let root = canonical(root)?;
+    let notes = markdown_files_with(&root, ignore);
+    let resolver = NoteResolver::new(&root, &notes);
+    let from_kind = match to_style {
+        LinkStyle::Wiki => LinkKind::Markdown,
+        LinkStyle::Relative => LinkKind::Wiki,
+    };
+
+    let mut results = Vec::new();
+    for note in &notes {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let note_dir = note.parent().unwrap_or(&root);
+        let mut rewritten = content.clone();
+        let mut result = ConvertResult {
+            path: note.to_string_lossy().to_string(),
+            converted: 0,
+            unresolved: 0,
+        };
+
+        let links = extract_links(&content);
+        for link in links.iter().rev().filter(|link| link.kind == from_kind) {
+            if link.target.is_empty() || is_external_link(&link.target) {
+                continue;
+            }
+            let Some(target) = resolver.resolve(note, link) else {
+                result.unresolved += 1;
+                continue;
+            };
+
+            let bang = if link.embed { "!" } else { "" };
+            let anchor = link
+                .anchor
+                .as_ref()
+                .map(|anchor| format!("#{}", anchor))
+                .unwrap_or_default();
+            let markup = match to_style {
+                LinkStyle::Relative => format!(
+                    "{}[{}]({}{})",
+                    bang,
+                    link.text,
+                    relative_link_target(note_dir, &target),
+                    anchor
+                ),
+                LinkStyle::Wiki => {
+                    let name = wiki_target(&root, &notes, &target);
+                    let alias = if link.text.is_empty() || link.text == name {
+                        String::new()
+                    } else {
+                        format!("|{}", link.text)
+                    };
+                    format!("{}[[{}{}{}]]", bang, name, anchor, alias)
+                }
+            };
+            rewritten.replace_range(link.start..link.end, &markup);
+            result.converted += 1;
+        }
+
+        if result.converted == 0 && result.unresolved == 0 {
+            continue;
+        }
+        if !dry_run && rewritten != content {
+            write_file_atomic(&note.to_string_lossy(), rewritten.as_bytes())?;
+        }
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Markdown files larger than `threshold_bytes`, largest first
+pub fn find_large_notes(
+    root: &str,
+    threshold_bytes: u64,
+    ignore: &IgnoreRules,
+) -> Result<Vec<FileItem>, String> {
+    let root = canonical(root)?;
+    let mut large: Vec<FileItem> = markdown_files_with(&root, ignore)
+        .into_iter()
+        .filter_map(|note| {
+            let metadata = fs::metadata(&note).ok()?;
+            let size = metadata.len();
+            (size > threshold_bytes).then(|| FileItem {
+                name: note
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                path: note.to_string_lossy().to_string(),
+                is_directory: false,
+                is_markdown: true,
+                kind: FileKind::Markdown,
+                is_text: true,
+                size,
+                modified: modified_millis(&metadata),
+                has_frontmatter: None,
+                title: None,
+                is_symlink: false,
+                symlink_target: None,
+                is_broken_symlink: false,
+                readonly: metadata.permissions().readonly(),
+            })
+        })
+        .collect();
+    large.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    Ok(large)
+}
+
+/// Group notes by content hash and apply `action` to every group of two or
+/// more, keeping the first copy by path. `trash` removes a file for
+/// `DedupAction::Trash`. A duplicate the action fails on is reported in its
+/// group's `errors` and the rest are still handled. Groups are sorted by
+/// their kept path.
+pub fn deduplicate_notes(
+    root: &str,
+    action: DedupAction,
+    ignore: &IgnoreRules,
+    trash: impl Fn(&str) -> Result<(), String>,
+) -> Result<Vec<DedupGroup>, String> {
+    let root = canonical(root)?;
+    let mut notes = markdown_files_with(&root, ignore);
+    notes.sort();
+    let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for note in notes {
+        let Ok(hash) = file_hash(&note.to_string_lossy()) else {
+            continue;
+        };
+        by_hash.entry(hash).or_default().push(note);
+    }
+
+    let mut groups = Vec::new();
+    for (hash, copies) in by_hash {
+        let Some((kept, duplicates)) = copies.split_first() else {
+            continue;
+        };
+        if duplicates.is_empty() {
+            continue;
+        }
+        let mut errors = Vec::new();
+        for duplicate in duplicates {
+            let path = duplicate.to_string_lossy();
+            let applied = match action {
+                DedupAction::Report => Ok(()),
+                DedupAction::Trash => trash(&path),
+                DedupAction::Link => {
+                    let dir = duplicate.parent().unwrap_or(&root);
+                    let link = format!(
+                        "[{}]({})\n",
+                        note_name(kept),
+                        relative_link_target(dir, kept)
+                    );
+                    write_file_atomic(&path, link.as_bytes()).map_err(String::from)
+                }
+            };
+            if let Err(error) = applied {
+                errors.push(DedupError {
+                    path: path.to_string(),
+                    error,
+                });
+            }
+        }
+        groups.push(DedupGroup {
+            hash,
+            kept: kept.to_string_lossy().to_string(),
+            duplicates: duplicates
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            errors,
+        });
+    }
+    groups.sort_by(|a, b| a.kept.cmp(&b.kept));
+    Ok(groups)
+}
+
+/// Lines mentioning any of `markers` as a whole word, in path then line
+/// order. With `skip_code`, lines inside fenced code blocks are passed over.
+pub fn find_markers(
+    root: &str,
+    markers: &[String],
+    skip_code: bool,
+    ignore: &IgnoreRules,
+) -> Result<Vec<MarkerHit>, String> {
+    let root = canonical(root)?;
+    if markers.is_empty() {
+        return Ok(Vec::new());
+    }
+    let alternatives: Vec<String> = markers.iter().map(|m| regex::escape(m)).collect();
+    let re = Regex::new(&format!(r"\b(?:{})\b", alternatives.join("|")))
+        .map_err(|e| format!("Invalid marker: {}", e))?;
+
+    let mut notes = markdown_files_with(&root, ignore);
+    notes.sort();
+    let mut hits = Vec::new();
+    for note in notes {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        for line in scan_lines(&content) {
+            if skip_code && line.in_code {
+                continue;
+            }
+            if let Some(marker) = re.find(line.text) {
+                hits.push(MarkerHit {
+                    path: note.to_string_lossy().to_string(),
+                    line: line.number,
+                    marker: marker.as_str().to_string(),
+                    text: line.text.trim().to_string(),
+                });
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Word totals for the root and every folder containing notes, each summed
+/// over all markdown descendants, sorted by path
+pub fn folder_word_counts(
+    root: &str,
+    ignore: &IgnoreRules,
+) -> Result<Vec<FolderWordCount>, String> {
+    let root = canonical(root)?;
+    let mut totals: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    totals.insert(root.clone(), 0);
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let words = count_words(&content);
+        // Credit every folder from the note's parent up to the root
+        for folder in note.ancestors().skip(1) {
+            *totals.entry(folder.to_path_buf()).or_default() += words;
+            if folder == root {
+                break;
+            }
+        }
+    }
+    Ok(totals
+        .into_iter()
+        .map(|(path, words)| FolderWordCount {
+            path: path.to_string_lossy().to_string(),
+            words,
+        })
+        .collect())
+}
+
+/// Notes whose frontmatter or H1 title doesn't slug to the same thing as
+/// their file name, sorted by path
+pub fn title_mismatches(root: &str, ignore: &IgnoreRules) -> Result<Vec<TitleMismatch>, String> {
+    let root = canonical(root)?;
+    let mut mismatches = Vec::new();
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let filename = note
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let title = derive_title(&content, &note);
+        if slugify(&title) != slugify(&filename) {
+            mismatches.push(TitleMismatch {
+                path: note.to_string_lossy().to_string(),
+                filename,
+                title,
+            });
+        }
+    }
+    mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(mismatches)
+}
+
+/// Write a markdown note at `output_path` linking every note in the vault,
+/// grouped under a heading per folder or per tag. Any previous index at that
+/// path is replaced, and the index never lists itself.
+pub fn generate_index_note(
+    root: &str,
+    output_path: &str,
+    grouping: Grouping,
+    style: LinkStyle,
+    ignore: &IgnoreRules,
+) -> Result<String, String> {
+    let root = canonical(root)?;
+    let output_path = Path::new(output_path);
+    let file_name = output_path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path: {}", output_path.display()))?;
+    let output_dir = output_path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create parent directories: {}", e))?;
+    // Canonical so the index can recognize and skip itself
+    let output_dir = canonical(&output_dir.to_string_lossy())?;
+    let output = output_dir.join(file_name);
+    let notes: Vec<PathBuf> = markdown_files_with(&root, ignore)
+        .into_iter()
+        .filter(|note| *note != output)
+        .collect();
+
+    let mut groups: BTreeMap<String, Vec<&PathBuf>> = BTreeMap::new();
+    for note in &notes {
+        match grouping {
+            Grouping::Folder => {
+                let folder = note
+                    .parent()
+                    .and_then(|dir| dir.strip_prefix(&root).ok())
+                    .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+                    .filter(|dir| !dir.is_empty())
+                    .unwrap_or_else(|| "/".to_string());
+                groups.entry(folder).or_default().push(note);
+            }
+            Grouping::Tag => {
+                let tags = read_file(&note.to_string_lossy())
+                    .map(|content| extract_tags(&content))
+                    .unwrap_or_default();
+                if tags.is_empty() {
+                    groups.entry("Untagged".to_string()).or_default().push(note);
+                }
+                for tag in tags {
+                    groups.entry(format!("#{}", tag)).or_default().push(note);
+                }
+            }
+        }
+    }
+
+    let mut index = String::from("# Index\n");
+    for (group, members) in &groups {
+        index.push_str(&format!("\n## {}\n\n", group));
+        for note in members {
+            let link = match style {
+                LinkStyle::Wiki => format!("[[{}]]", wiki_target(&root, &notes, note)),
+                LinkStyle::Relative => format!(
+                    "[{}]({})",
+                    note_name(note),
+                    relative_link_target(&output_dir, note)
+                ),
+            };
+            index.push_str(&format!("- {}\n", link));
+        }
+    }
+
+    write_file_atomic(&output.to_string_lossy(), index.as_bytes())?;
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// The `limit` tag pairs that most often appear on the same note. Tags are
+/// compared case-insensitively and reported lowercase.
+pub fn tag_cooccurrence(
+    root: &str,
+    limit: usize,
+    ignore: &IgnoreRules,
+) -> Result<Vec<TagPair>, String> {
+    let root = canonical(root)?;
+    let mut counts: HashMap<(String, String), usize> = HashMap::new();
+
+    for note in markdown_files_with(&root, ignore) {
+        let Ok(content) = read_file(&note.to_string_lossy()) else {
+            continue;
+        };
+        let mut tags: Vec<String> = extract_tags(&content)
+            .into_iter()
+            .map(|tag| tag.to_lowercase())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        for (i, first) in tags.iter().enumerate() {
+            for second in &tags[i + 1..] {
+                *counts.entry((first.clone(), second.clone())).or_default() += 1;
+            }
+        }
+    }
+
+    let mut pairs: Vec<TagPair> = counts
+        .into_iter()
+        .map(|((first, second), count)| TagPair {
+            first,
+            second,
+            count,
+        })
+        .collect();
+    pairs.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| (&a.first, &a.second).cmp(&(&b.first, &b.second)))
+    });
+    pairs.truncate(limit);
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_note_with_exclusive_asset() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("notes/img")).unwrap();
+        fs::create_dir_all(root.join("archive")).unwrap();
+        fs::write(root.join("notes/img/pic.png"), b"png").unwrap();
+        fs::write(root.join("notes/img/shared.png"), b"png").unwrap();
+        fs::write(
+            root.join("notes/a.md"),
+            "![pic](img/pic.png)\n![shared](img/shared.png)\n",
+        )
+        .unwrap();
+        fs::write(root.join("b.md"), "![](notes/img/shared.png)\n").unwrap();
+
+        let report = move_note_with_assets(
+            &root.to_string_lossy(),
+            &root.join("notes/a.md").to_string_lossy(),
+            &root.join("archive").to_string_lossy(),
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.assets.len(), 1);
+        assert!(root.join("archive/img/pic.png").exists());
+        assert!(!root.join("notes/img/pic.png").exists());
+        assert!(root.join("notes/img/shared.png").exists());
+        assert_eq!(report.shared_assets.len(), 1);
+
+        let moved = fs::read_to_string(root.join("archive/a.md")).unwrap();
+        assert!(moved.contains("![pic](img/pic.png)"));
+        assert!(moved.contains("![shared](../notes/img/shared.png)"));
+    }
+
+    #[test]
+    fn test_move_note_with_assets_skips_ignored_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("archive")).unwrap();
+        fs::create_dir_all(root.join("drafts")).unwrap();
+        fs::write(root.join("pic.png"), b"png").unwrap();
+        fs::write(root.join("a.md"), "![pic](pic.png)\n").unwrap();
+        fs::write(root.join("drafts/b.md"), "![](../pic.png)\n").unwrap();
+
+        let report = move_note_with_assets(
+            &root.to_string_lossy(),
+            &root.join("a.md").to_string_lossy(),
+            &root.join("archive").to_string_lossy(),
+            &IgnoreRules::new(&["drafts".to_string()]).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(report.assets.len(), 1);
+        assert!(report.shared_assets.is_empty());
+        assert!(root.join("archive/pic.png").exists());
+    }
+
+    #[test]
+    fn test_failed_move_note_with_assets_is_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("notes/img")).unwrap();
+        fs::create_dir_all(root.join("archive")).unwrap();
+        fs::write(root.join("notes/pic.png"), b"png").unwrap();
+        fs::write(root.join("notes/img/other.png"), b"png").unwrap();
+        fs::write(
+            root.join("notes/a.md"),
+            "![pic](pic.png)\n![other](img/other.png)\n",
+        )
+        .unwrap();
+        // A file where the second asset's folder would go
+        fs::write(root.join("archive/img"), "").unwrap();
+
+        let moved = move_note_with_assets(
+            &root.to_string_lossy(),
+            &root.join("notes/a.md").to_string_lossy(),
+            &root.join("archive").to_string_lossy(),
+            &IgnoreRules::default(),
+        );
+
+        assert!(moved.is_err());
+        assert!(root.join("notes/pic.png").exists());
+        assert!(!root.join("archive/pic.png").exists());
+        assert!(root.join("notes/img/other.png").exists());
+        assert!(root.join("notes/a.md").exists());
+        assert!(!root.join("archive/a.md").exists());
+    }
+
+    #[test]
+    fn test_link_reference_styles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a/from.md"), "").unwrap();
+        fs::write(root.join("b/My Note.md"), "").unwrap();
+        fs::write(root.join("b/Index.md"), "").unwrap();
+        fs::write(root.join("a/index.md"), "").unwrap();
+        let path = |p: &str| root.join(p).to_string_lossy().to_string();
+        let root_str = root.to_string_lossy().to_string();
+
+        let wiki = link_reference(
+            &root_str,
+            &path("a/from.md"),
+            &path("b/My Note.md"),
+            LinkStyle::Wiki,
+        );
+        assert_eq!(wiki.unwrap(), "[[My Note]]");
+
+        let relative = link_reference(
+            &root_str,
+            &path("a/from.md"),
+            &path("b/My Note.md"),
+            LinkStyle::Relative,
+        );
+        assert_eq!(relative.unwrap(), "[My Note](../b/My%20Note.md)");
+
+        // `Index` collides with `index`, so the wiki link spells out the path
+        let ambiguous = link_reference(
+            &root_str,
+            &path("a/from.md"),
+            &path("b/Index.md"),
+            LinkStyle::Wiki,
+        );
+        assert_eq!(ambiguous.unwrap(), "[[b/Index]]");
+    }
+
+    #[test]
+    fn test_repair_moved_image_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::create_dir_all(root.join("assets/2024")).unwrap();
+        fs::write(root.join("assets/2024/chart.png"), b"png").unwrap();
+        fs::write(
+            root.join("notes/report.md"),
+            "![chart](images/chart.png)\n![gone](x.png)\n",
+        )
+        .unwrap();
+
+        let report = repair_asset_links(
+            &root.to_string_lossy(),
+            &root.join("notes/report.md").to_string_lossy(),
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.repaired.len(), 1);
+        assert_eq!(report.repaired[0].to, "../assets/2024/chart.png");
+        assert_eq!(report.missing.len(), 1);
+        let content = fs::read_to_string(root.join("notes/report.md")).unwrap();
+        assert!(content.starts_with("![chart](../assets/2024/chart.png)"));
+    }
+
+    #[test]
+    fn test_resolve_asset_path_relative_absolute_and_encoded() {
+        let dir = tempfile::tempdir().unwrap();
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("images")).unwrap();
+        fs::write(notes.join("images/my diagram.png"), "").unwrap();
+        fs::write(dir.path().join("shared.png"), "").unwrap();
+        let note = notes.join("today.md").to_string_lossy().to_string();
+        let expected = notes.join("images/my diagram.png");
+        let expected = expected.to_string_lossy();
+
+        assert_eq!(
+            resolve_asset_path(&note, "./images/my%20diagram.png").unwrap(),
+            expected
+        );
+        assert_eq!(
+            resolve_asset_path(&note, "<images/my diagram.png#top>").unwrap(),
+            expected
+        );
+        let shared = dir.path().join("shared.png").to_string_lossy().to_string();
+        assert_eq!(resolve_asset_path(&note, "../shared.png").unwrap(), shared);
+        assert_eq!(resolve_asset_path(&note, &shared).unwrap(), shared);
+
+        assert!(resolve_asset_path(&note, "images/missing.png")
+            .unwrap_err()
+            .starts_with("Asset not found"));
+        assert!(resolve_asset_path(&note, "https://example.com/a.png").is_err());
+        let failed = ResolvedAsset::new("#top", resolve_asset_path(&note, "#top"));
+        assert_eq!(failed.path, None);
+        assert!(failed.error.is_some());
+    }
+
+    #[test]
+    fn test_convert_wiki_links_to_relative() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/target note.md"), "").unwrap();
+        let index = root.join("index.md");
+        fs::write(
+            &index,
+            "See [[target note#Part|the target]] and [[nowhere]].",
+        )
+        .unwrap();
+
+        let results = convert_links(
+            &root.to_string_lossy(),
+            LinkStyle::Relative,
+            false,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].converted, results[0].unresolved), (1, 1));
+        assert_eq!(
+            fs::read_to_string(&index).unwrap(),
+            "See [the target](sub/target%20note.md#Part) and [[nowhere]]."
+        );
+    }
+
+    #[test]
+    fn test_convert_relative_links_to_wiki() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/topic.md"), "[home](../index.md)").unwrap();
+        let index = root.join("index.md");
+        fs::write(&index, "[topic](sub/topic.md) ![chart](chart.png)").unwrap();
+
+        let results = convert_links(
+            &root.to_string_lossy(),
+            LinkStyle::Wiki,
+            false,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            fs::read_to_string(&index).unwrap(),
+            "[[topic]] ![chart](chart.png)"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("sub/topic.md")).unwrap(),
+            "[[index|home]]"
+        );
+    }
+
+    #[test]
+    fn test_convert_links_dry_run_counts_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("a.md"), "[[b]] [[b|again]]").unwrap();
+        fs::write(root.join("b.md"), "").unwrap();
+
+        let results = convert_links(
+            &root.to_string_lossy(),
+            LinkStyle::Relative,
+            true,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(results[0].converted, 2);
+        assert_eq!(
+            fs::read_to_string(root.join("a.md")).unwrap(),
+            "[[b]] [[b|again]]"
+        );
+    }
+
+    #[test]
+    fn test_generate_index_note_grouped_by_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("projects")).unwrap();
+        fs::write(root.join("inbox.md"), "").unwrap();
+        fs::write(root.join("projects/alpha.md"), "").unwrap();
+        let output = root.join("Index.md");
+        fs::write(&output, "stale index").unwrap();
+
+        generate_index_note(
+            &root.to_string_lossy(),
+            &output.to_string_lossy(),
+            Grouping::Folder,
+            LinkStyle::Wiki,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "# Index\n\n## /\n\n- [[inbox]]\n\n## projects\n\n- [[alpha]]\n"
+        );
+    }
+
+    #[test]
+    fn test_tag_cooccurrence_counts_pairs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.md"), "#rust #tauri").unwrap();
+        fs::write(root.join("b.md"), "---\ntags: [Rust, tauri, gui]\n---\n").unwrap();
+        fs::write(root.join("c.md"), "#gui alone? no, with #design").unwrap();
+
+        let pairs = tag_cooccurrence(&root.to_string_lossy(), 10, &IgnoreRules::default()).unwrap();
+
+        assert_eq!(
+            pairs[0],
+            TagPair {
+                first: "rust".to_string(),
+                second: "tauri".to_string(),
+                count: 2,
+            }
+        );
+        assert!(pairs[1..].iter().all(|pair| pair.count == 1));
+    }
+
+    #[test]
+    fn test_find_large_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("huge.md"), "x".repeat(2048)).unwrap();
+        fs::write(dir.path().join("small.md"), "tiny").unwrap();
+        fs::write(dir.path().join("huge.png"), "x".repeat(4096)).unwrap();
+
+        let large =
+            find_large_notes(&dir.path().to_string_lossy(), 1024, &IgnoreRules::default()).unwrap();
+
+        assert_eq!(large.len(), 1);
+        assert_eq!(large[0].name, "huge.md");
+        assert_eq!(large[0].size, 2048);
+    }
+
+    #[test]
+    fn test_render_with_backlinks_lists_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("topic.md"), "# Topic").unwrap();
+        fs::write(root.join("alpha.md"), "See [[topic]].").unwrap();
+        fs::write(root.join("beta.md"), "Also [topic](topic.md).").unwrap();
+        fs::write(root.join("gamma.md"), "Unrelated.").unwrap();
+        let root_str = root.to_string_lossy();
+
+        let rendered = render_with_backlinks(
+            &root_str,
+            &root.join("topic.md").to_string_lossy(),
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rendered,
+            format!(
+                "# Topic\n\n{}\n## Linked mentions\n\n- [[alpha]]\n- [[beta]]\n{}\n",
+                BACKLINKS_START, BACKLINKS_END
+            )
+        );
+        let unlinked = render_with_backlinks(
+            &root_str,
+            &root.join("gamma.md").to_string_lossy(),
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+        assert_eq!(unlinked, "Unrelated.");
+    }
+
+    #[test]
+    fn test_deduplicate_notes_trashes_all_but_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("copies")).unwrap();
+        fs::write(root.join("recipe.md"), "# Soup\n").unwrap();
+        fs::write(root.join("copies/recipe.md"), "# Soup\n").unwrap();
+        fs::write(root.join("other.md"), "# Bread\n").unwrap();
+        let root_str = root.to_string_lossy();
+        let trash = |path: &str| fs::remove_file(path).map_err(|e| e.to_string());
+
+        let report = deduplicate_notes(
+            &root_str,
+            DedupAction::Report,
+            &IgnoreRules::default(),
+            trash,
+        )
+        .unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(
+            report[0].kept,
+            root.join("copies/recipe.md").to_string_lossy()
+        );
+        assert_eq!(
+            report[0].duplicates,
+            vec![root.join("recipe.md").to_string_lossy()]
+        );
+        assert!(root.join("recipe.md").exists());
+
+        deduplicate_notes(
+            &root_str,
+            DedupAction::Trash,
+            &IgnoreRules::default(),
+            trash,
+        )
+        .unwrap();
+        assert!(root.join("copies/recipe.md").exists());
+        assert!(!root.join("recipe.md").exists());
+        assert!(root.join("other.md").exists());
+
+        // A failure is reported against its copy without stopping the rest
+        fs::write(root.join("a.md"), "same").unwrap();
+        fs::write(root.join("b.md"), "same").unwrap();
+        fs::write(root.join("c.md"), "same").unwrap();
+        let refuse_b = |path: &str| {
+            if path.ends_with("b.md") {
+                Err("Permission denied".to_string())
+            } else {
+                trash(path)
+            }
+        };
+        let groups = deduplicate_notes(
+            &root_str,
+            DedupAction::Trash,
+            &IgnoreRules::default(),
+            refuse_b,
+        )
+        .unwrap();
+        assert_eq!(
+            groups[0].errors,
+            vec![DedupError {
+                path: root.join("b.md").to_string_lossy().to_string(),
+                error: "Permission denied".to_string(),
+            }]
+        );
+        assert!(root.join("b.md").exists());
+        assert!(!root.join("c.md").exists());
+    }
+
+    #[test]
+    fn test_find_markers_skips_code_fences() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(
+            root.join("plan.md"),
+            "# Plan\n\nTODO: book venue\nTODOS are not markers\n\n```\n// TODO in code\n```\n",
+        )
+        .unwrap();
+        let markers: Vec<String> = DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect();
+        let root_str = root.to_string_lossy();
+
+        let hits = find_markers(&root_str, &markers, true, &IgnoreRules::default()).unwrap();
+
+        assert_eq!(
+            hits,
+            vec![MarkerHit {
+                path: root.join("plan.md").to_string_lossy().to_string(),
+                line: 3,
+                marker: "TODO".to_string(),
+                text: "TODO: book venue".to_string(),
+            }]
+        );
+        let with_code = find_markers(&root_str, &markers, false, &IgnoreRules::default()).unwrap();
+        assert_eq!(with_code.len(), 2);
+    }
+
+    #[test]
+    fn test_folder_word_counts_sum_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("projects/alpha")).unwrap();
+        fs::create_dir_all(root.join("projects/beta")).unwrap();
+        fs::write(root.join("projects/alpha/a.md"), "one two three").unwrap();
+        fs::write(root.join("projects/beta/b.md"), "four five").unwrap();
+        fs::write(root.join("projects/beta/c.md"), "six").unwrap();
+        fs::write(root.join("top.md"), "seven eight").unwrap();
+
+        let counts = folder_word_counts(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+        let words = |path: &Path| {
+            let path = path.to_string_lossy();
+            counts.iter().find(|c| c.path == path).unwrap().words
+        };
+
+        assert_eq!(words(&root.join("projects/alpha")), 3);
+        assert_eq!(words(&root.join("projects/beta")), 3);
+        assert_eq!(
+            words(&root.join("projects")),
+            words(&root.join("projects/alpha")) + words(&root.join("projects/beta"))
+        );
+        assert_eq!(words(&root), 8);
+    }
+
+    #[test]
+    fn test_title_mismatches_reports_filename_differing_from_h1() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("meeting-notes.md"), "# Weekly Sync\n").unwrap();
+        fs::write(root.join("Project Plan.md"), "# project plan\n").unwrap();
+        fs::write(root.join("untitled.md"), "no heading\n").unwrap();
+
+        let mismatches =
+            title_mismatches(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+
+        assert_eq!(
+            mismatches,
+            vec![TitleMismatch {
+                path: root.join("meeting-notes.md").to_string_lossy().to_string(),
+                filename: "meeting-notes".to_string(),
+                title: "Weekly Sync".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_vault_reports_several_categories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("used.png"), "png").unwrap();
+        fs::write(root.join("unused.png"), "png").unwrap();
+        fs::write(
+            root.join("index.md"),
+            "[[topic]] [[missing]]\n![[used.png]]\n![[gone.png]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("topic.md"), "").unwrap();
+        fs::write(root.join("sub/topic.md"), "").unwrap();
+        fs::write(root.join("latin1.md"), [0x63, 0x61, 0x66, 0xe9]).unwrap();
+
+        let validation = validate_vault(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+
+        let targets =
+            |links: &[BrokenLink]| links.iter().map(|l| l.target.clone()).collect::<Vec<_>>();
+        assert_eq!(targets(&validation.broken_links), vec!["missing"]);
+        assert_eq!(targets(&validation.broken_transclusions), vec!["gone.png"]);
+        assert_eq!(
+            validation.non_utf8,
+            vec![root.join("latin1.md").to_string_lossy().to_string()]
+        );
+        assert_eq!(validation.duplicate_names.len(), 1);
+        assert_eq!(validation.duplicate_names[0].name, "topic");
+        assert_eq!(
+            validation.orphan_attachments,
+            vec![root.join("unused.png").to_string_lossy().to_string()]
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_key_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.md"), "---\ntitle: A\ntags: [x]\n---\n").unwrap();
+        fs::write(root.join("b.md"), "---\ntitle: B\ntags: [y]\n---\n").unwrap();
+        fs::write(root.join("c.md"), "---\ntitle: C\ntag: z\n---\n").unwrap();
+        fs::write(root.join("plain.md"), "no frontmatter").unwrap();
+
+        let report =
+            frontmatter_key_report(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+
+        let stat = |key: &str, count| KeyStat {
+            key: key.to_string(),
+            count,
+        };
+        assert_eq!(
+            report,
+            vec![stat("title", 3), stat("tags", 2), stat("tag", 1)]
+        );
+    }
+
+    #[test]
+    fn test_organize_by_tag_moves_into_tag_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("sorted/recipes")).unwrap();
+        fs::write(root.join("sorted/recipes/soup.md"), "existing").unwrap();
+        fs::write(root.join("soup.md"), "#recipes #dinner").unwrap();
+        fs::write(root.join("untagged.md"), "plain").unwrap();
+        let root_str = root.to_string_lossy();
+
+        let moved =
+            organize_by_tag(&root_str, &root.join("soup.md").to_string_lossy(), "sorted").unwrap();
+
+        let expected = root.join("sorted/recipes/soup (1).md");
+        assert_eq!(moved, Some(expected.to_string_lossy().to_string()));
+        assert_eq!(fs::read_to_string(expected).unwrap(), "#recipes #dinner");
+        assert!(!root.join("soup.md").exists());
+        let untagged = root.join("untagged.md");
+        assert_eq!(
+            organize_by_tag(&root_str, &untagged.to_string_lossy(), "sorted").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_data_collects_all_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("plan.md"), "# Goals\n#work\n## Setup\n").unwrap();
+        fs::write(root.join("sub/plan.md"), "# Goals\n#home #work\n").unwrap();
+        fs::write(
+            root.join("ideas.md"),
+            "---\ntags: [later]\n---\nno headings",
+        )
+        .unwrap();
+        let root_str = root.to_string_lossy();
+
+        let data = autocomplete_data(&root_str, &IgnoreRules::default()).unwrap();
+
+        assert_eq!(data.notes, vec!["ideas", "plan", "sub/plan"]);
+        assert_eq!(data.tags, vec!["home", "later", "work"]);
+        assert_eq!(data.headings, vec!["Goals", "Setup"]);
+        let again = autocomplete_data(&root_str, &IgnoreRules::default()).unwrap();
+        assert_eq!(again.generation, data.generation);
+        fs::write(root.join("new.md"), "").unwrap();
+        let changed = autocomplete_data(&root_str, &IgnoreRules::default()).unwrap();
+        assert_ne!(changed.generation, data.generation);
+    }
+
+    #[test]
+    fn test_code_block_languages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(
+            root.join("a.md"),
+            "```rust\nfn a() {}\n```\n\n```python\nprint()\n```\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("b.md"),
+            "```Rust\nfn b() {}\n```\n\n```\nplain\n```\n",
+        )
+        .unwrap();
+
+        let languages =
+            code_block_languages(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+
+        let counts: Vec<(&str, usize)> = languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("rust", 2), (UNLABELED_LANGUAGE, 1), ("python", 1)]
+        );
+    }
+
+    #[test]
+    fn test_query_by_date_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("early.md"), "---\ndate: 2024-01-05\n---\n").unwrap();
+        fs::write(root.join("late.md"), "---\ndate: 2024/01/20\n---\n").unwrap();
+        fs::write(root.join("outside.md"), "---\ndate: 2024-03-01\n---\n").unwrap();
+        fs::write(root.join("undated.md"), "---\ndate: someday\n---\n").unwrap();
+
+        let notes = query_by_date_range(
+            &root.to_string_lossy(),
+            "date",
+            "2024-01-01",
+            "2024-01-31",
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        let names: Vec<String> = notes.iter().map(|n| note_name(Path::new(n))).collect();
+        assert_eq!(names, vec!["early", "late"]);
+    }
+
+    #[test]
+    fn test_creation_histogram_by_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("a.md"), "---\ncreated: 2024-05-01\n---\n").unwrap();
+        fs::write(
+            root.join("b.md"),
+            "---\ncreated: 2024-05-01T18:30:00\n---\n",
+        )
+        .unwrap();
+        fs::write(root.join("c.md"), "---\ncreated: 2024-05-03\n---\n").unwrap();
+
+        let histogram = creation_histogram(
+            &root.to_string_lossy(),
+            Bucket::Day,
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            histogram,
+            vec![
+                HistogramBucket {
+                    start: "2024-05-01".to_string(),
+                    count: 2,
+                },
+                HistogramBucket {
+                    start: "2024-05-03".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_path_between() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("a.md"), "[[b]] and [[x]]").unwrap();
+        fs::write(root.join("b.md"), "[[c]]").unwrap();
+        fs::write(root.join("c.md"), "[[d]]").unwrap();
+        fs::write(root.join("d.md"), "end").unwrap();
+        fs::write(root.join("x.md"), "dead end").unwrap();
+
+        let path_of = |from: &str, to: &str, directed: bool| {
+            link_path_between(
+                &root.to_string_lossy(),
+                &root.join(from).to_string_lossy(),
+                &root.join(to).to_string_lossy(),
+                directed,
+                &IgnoreRules::default(),
+            )
+            .unwrap()
+            .map(|path| {
+                path.iter()
+                    .map(|n| note_name(Path::new(n)))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            path_of("a.md", "d.md", true).unwrap(),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(path_of("d.md", "a.md", true), None);
+        assert_eq!(
+            path_of("d.md", "a.md", false).unwrap(),
+            vec!["d", "c", "b", "a"]
+        );
+    }
+
+    #[test]
+    fn test_find_orphan_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join("drafts")).unwrap();
+        fs::write(
+            root.join("index.md"),
+            "[[Linked]] and [other](sub/other.md)",
+        )
+        .unwrap();
+        fs::write(root.join("Linked.md"), "[[index]]").unwrap();
+        fs::write(root.join("sub/other.md"), "").unwrap();
+        fs::write(root.join("lonely.md"), "no links here").unwrap();
+        fs::write(root.join("drafts/wip.md"), "").unwrap();
+        let rules = IgnoreRules::new(&["drafts".to_string()]).unwrap();
+
+        let orphans = find_orphan_notes(&root.to_string_lossy(), OrphanMode::NoBacklinks, &rules);
+        assert_eq!(
+            orphans.unwrap(),
+            vec![root.join("lonely.md").to_string_lossy().to_string()]
+        );
+    }
+
+    #[test]
+    fn test_reading_order_follows_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for name in ["setup", "demo", "wrap-up"] {
+            fs::write(root.join(format!("{}.md", name)), name).unwrap();
+        }
+        let index = root.join("index.md");
+        fs::write(
+            &index,
+            "# Talk\n\n- [Setup](setup.md)\n- [[demo]], then [[setup]] again\n- [[wrap-up]] ([[index]])\n",
+        )
+        .unwrap();
+
+        let order = reading_order(
+            &root.to_string_lossy(),
+            &index.to_string_lossy(),
+            &IgnoreRules::default(),
+        )
+        .unwrap();
+
+        let path = |name: &str| root.join(name).to_string_lossy().to_string();
+        assert_eq!(
+            order,
+            vec![path("setup.md"), path("demo.md"), path("wrap-up.md")]
+        );
+    }
+
+    #[test]
+    fn test_order_by_frontmatter_puts_missing_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let chapter = note("chapter.md", "---\norder: 2\n---\nbody");
+        let appendix = note("appendix.md", "no frontmatter");
+        let preface = note("preface.md", "---\norder: 1\n---\nbody");
+
+        let ordered = order_by_frontmatter(
+            vec![appendix.clone(), chapter.clone(), preface.clone()],
+            "order",
+        );
+
+        assert_eq!(ordered, vec![preface, chapter, appendix]);
+    }
+
+    #[test]
+    fn test_find_external_links_skips_internal() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let note = root.join("reading.md");
+        fs::write(
+            &note,
+            "See [[other]] and [a note](other.md).\n\
+             [Docs](https://example.com/guide#setup) and ![logo](https://example.com/logo.png)\n\
+             ```\n[skip](https://in.code)\n```\n\
+             [Old](http://example.org)\n",
+        )
+        .unwrap();
+        fs::write(root.join("other.md"), "no links").unwrap();
+
+        let links = find_external_links(&root.to_string_lossy(), &IgnoreRules::default()).unwrap();
+
+        let note = note.to_string_lossy().to_string();
+        assert_eq!(
+            links,
+            vec![
+                ExternalLinkRef {
+                    note: note.clone(),
+                    line: 2,
+                    url: "https://example.com/guide#setup".to_string(),
+                },
+                ExternalLinkRef {
+                    note,
+                    line: 6,
+                    url: "http://example.org".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,3102 @@
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_full::file_id::{get_file_id, FileId};
+use notify_debouncer_full::{
+    new_debouncer_opt, DebounceEventHandler, DebounceEventResult, Debouncer, FileIdCache, FileIdMap,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::fs::{
+    canonical_path, create_file_exclusive, file_hash, is_markdown_name, normalize_path,
+    read_directory, rename_path, DirectoryOptions, FileItem,
+};
+use crate::index::VaultIndex;
+use crate::search::{IndexedHit, SearchIndex};
+use crate::vault::{vault_files, IgnoreRules};
+
+/// Options controlling what a directory watch reports
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WatchOptions {
+    /// Keep a running count of markdown files and emit `file-count-changed`
+    pub track_file_count: bool,
+    /// Track whether anything changed since the last `mark_clean` and emit
+    /// `vault-dirty` on the first change after it
+    pub track_dirty: bool,
+    /// Emit `preview-stale` only when a markdown file's content actually
+    /// changes, ignoring asset and metadata-only changes
+    pub preview_stale: bool,
+    /// Maintain the cached vault index, re-parsing only changed notes
+    pub track_index: bool,
+    /// Maintain a full-text search index, re-tokenizing only changed notes
+    pub track_search_index: bool,
+    /// Emit a single `vault-settled` once no events have arrived for this
+    /// many milliseconds after a burst
+    pub quiescence_ms: Option<u64>,
+    /// Watch only these directories, relative to the root, each
+    /// recursively; an empty list watches the whole root
+    pub subpaths: Vec<String>,
+    /// How long to coalesce raw filesystem events before handling them;
+    /// `DEFAULT_DEBOUNCE_MS` when unset
+    pub debounce_ms: Option<u64>,
+    /// Gitignore-style patterns, relative to the root, whose events are
+    /// dropped before anything is emitted
+    pub ignore: Vec<String>,
+    /// Watch only directories at most this many levels below each watched
+    /// path, each non-recursively, to stay under OS watch limits on deep
+    /// trees; 0 watches just the path itself. Unlimited when unset.
+    pub max_depth: Option<usize>,
+    /// Most events left waiting for the event thread before the rest are
+    /// dropped and a single `needs-full-refresh` is emitted instead;
+    /// `DEFAULT_MAX_BUFFERED_EVENTS` when unset
+    pub max_buffered_events: Option<usize>,
+    /// Extensions that count as markdown in `file-change` payloads;
+    /// `is_markdown_file`'s built-in set when unset
+    pub markdown_extensions: Option<Vec<String>>,
+}
+
+const DEFAULT_DEBOUNCE_MS: u64 = 500;
+const MAX_DEBOUNCE_MS: u64 = 10_000;
+
+const DEFAULT_MAX_BUFFERED_EVENTS: usize = 10_000;
+
+/// Batches of debounced events a directory watch's channel holds
+const EVENT_QUEUE_BATCHES: usize = 64;
+
+/// The debounce interval requested by `options`, rejecting zero and
+/// anything over `MAX_DEBOUNCE_MS`
+fn debounce_interval(options: &WatchOptions) -> Result<Duration, String> {
+    match options.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS) {
+        0 => Err("Debounce interval must be greater than 0 ms".to_string()),
+        ms if ms > MAX_DEBOUNCE_MS => Err(format!(
+            "Debounce interval must be at most {} ms",
+            MAX_DEBOUNCE_MS
+        )),
+        ms => Ok(Duration::from_millis(ms)),
+    }
+}
+
+/// An event to emit to the frontend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emission {
+    pub event: &'static str,
+    pub payload: Value,
+}
+
+/// What happened to the paths of a `file-change` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+/// A path touched by a `file-change` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangedPath {
+    pub path: PathBuf,
+    /// Whether the file name has a markdown extension, so the frontend can
+    /// skip reloading for assets
+    pub is_markdown: bool,
+}
+
+impl ChangedPath {
+    /// `path`, counted as markdown by `extensions` as `is_markdown_name`
+    /// does
+    fn new(path: &Path, extensions: Option<&[String]>) -> Self {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        ChangedPath {
+            path: path.to_path_buf(),
+            is_markdown: is_markdown_name(&name, extensions),
+        }
+    }
+}
+
+/// Payload of a `file-change` event, independent of notify's event types
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileChange {
+    #[serde(rename = "type")]
+    pub change_type: ChangeType,
+    pub paths: Vec<ChangedPath>,
+    /// Source and destination of a rename that reported both
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<PathBuf>,
+}
+
+impl FileChange {
+    /// Map a notify event, with markdown paths told apart by `extensions`;
+    /// pure access events are not changes and map to nothing
+    fn from_event(event: &Event, extensions: Option<&[String]>) -> Option<Self> {
+        let change_type = match event.kind {
+            EventKind::Access(_) => return None,
+            EventKind::Create(_) => ChangeType::Created,
+            EventKind::Remove(_) => ChangeType::Removed,
+            EventKind::Modify(ModifyKind::Name(_)) => ChangeType::Renamed,
+            EventKind::Modify(_) | EventKind::Any | EventKind::Other => ChangeType::Modified,
+        };
+        let (from, to) = match (event.kind, event.paths.as_slice()) {
+            (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                (Some(from.clone()), Some(to.clone()))
+            }
+            _ => (None, None),
+        };
+        Some(FileChange {
+            change_type,
+            paths: event
+                .paths
+                .iter()
+                .map(|path| ChangedPath::new(path, extensions))
+                .collect(),
+            from,
+            to,
+        })
+    }
+}
+
+/// The `file-change` events of one debounced flush, coalesced so each path
+/// is reported once. A save often produces a modify and a metadata change
+/// for the same file; the frontend only needs to re-read it once.
+#[derive(Default)]
+struct ChangeBatch {
+    /// Changes in the order they were first seen, each with whether later
+    /// events were merged into it
+    changes: Vec<(FileChange, bool)>,
+    /// Index in `changes` of the change each path was last reported in
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl ChangeBatch {
+    /// Merge `change` into the last change to the same paths, the later one
+    /// winning except that a modification doesn't undo a creation. Renames
+    /// naming both ends are kept as they are.
+    fn add(&mut self, change: FileChange) {
+        let merged = change
+            .paths
+            .first()
+            .filter(|_| change.from.is_none())
+            .and_then(|first| self.by_path.get(&first.path).copied())
+            .filter(|&i| {
+                let (kept, _) = &self.changes[i];
+                kept.from.is_none() && kept.paths == change.paths
+            });
+        if let Some(i) = merged {
+            let (kept, merged) = &mut self.changes[i];
+            if !(kept.change_type == ChangeType::Created
+                && change.change_type == ChangeType::Modified)
+            {
+                kept.change_type = change.change_type;
+            }
+            *merged = true;
+            return;
+        }
+        for path in &change.paths {
+            self.by_path.insert(path.path.clone(), self.changes.len());
+        }
+        self.changes.push((change, false));
+    }
+
+    /// The coalesced changes as `file-change` emissions. A path whose events
+    /// end in a creation or removal is reported by whether it exists now,
+    /// since the order a flush lists events in can't be relied on.
+    fn into_emissions(self) -> impl Iterator<Item = Emission> {
+        self.changes.into_iter().map(|(mut change, merged)| {
+            let created_or_removed = matches!(
+                change.change_type,
+                ChangeType::Created | ChangeType::Removed
+            );
+            if merged && created_or_removed {
+                let exists = change
+                    .paths
+                    .iter()
+                    .any(|changed| std::fs::symlink_metadata(&changed.path).is_ok());
+                change.change_type = if exists {
+                    ChangeType::Created
+                } else {
+                    ChangeType::Removed
+                };
+            }
+            Emission {
+                event: "file-change",
+                payload: json!(change),
+            }
+        })
+    }
+}
+
+/// Whether a path inside the watched root lives under a hidden directory
+pub(crate) fn is_hidden(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .map(|relative| {
+            relative
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+        .unwrap_or(false)
+}
+
+/// Directories to watch for `options`: the allowlisted subpaths, or none
+/// when the whole root is watched
+fn watch_targets(root: &Path, options: &WatchOptions) -> Vec<PathBuf> {
+    options
+        .subpaths
+        .iter()
+        .map(|subpath| normalize_path(&root.join(subpath)))
+        .collect()
+}
+
+/// Directories up to `depth` levels below `dir`, not following symlinks
+fn subdirectories(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    if depth == 0 {
+        return found;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return found;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            let path = entry.path();
+            let nested = subdirectories(&path, depth - 1);
+            found.push(path);
+            found.extend(nested);
+        }
+    }
+    found
+}
+
+/// Whether an event path is a directory (`tree-change`) or a file
+/// (`content-change`). The event kind is trusted when it says; otherwise the
+/// path is checked on disk, and paths that no longer exist count as tree
+/// changes since their type can't be known.
+fn change_event(event: &Event, path: &Path) -> &'static str {
+    match event.kind {
+        EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder) => {
+            "tree-change"
+        }
+        EventKind::Create(CreateKind::File) | EventKind::Remove(RemoveKind::File) => {
+            "content-change"
+        }
+        _ if path.is_file() => "content-change",
+        _ => "tree-change",
+    }
+}
+
+/// Whether an event takes a single path away without saying where it went
+fn is_rename_source(event: &Event) -> bool {
+    event.paths.len() == 1
+        && matches!(
+            event.kind,
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From))
+        )
+}
+
+/// Whether an event brings a single path in without saying where from
+fn is_rename_target(event: &Event) -> bool {
+    event.paths.len() == 1
+        && matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+        )
+}
+
+/// Whether a lone source and target are one entry being renamed: the target
+/// has the file id the source had before it went away
+fn is_same_entry(source: &Event, target: &Event, removed: &HashMap<PathBuf, FileId>) -> bool {
+    let (from, to) = (&source.paths[0], &target.paths[0]);
+    from != to
+        && removed
+            .get(from)
+            .is_some_and(|id| get_file_id(to).is_ok_and(|target_id| target_id == *id))
+}
+
+/// Rewrite the two halves of a rename within one debounced flush as a single
+/// `RenameMode::Both` event, for platforms where notify reports a rename as
+/// a removal and a creation. Halves tagged with the same tracker are always
+/// paired; otherwise a lone source is only paired with a later lone target
+/// that has the file id it had, as noted in `removed`. Anything else is
+/// left as separate events.
+fn pair_renames(events: Vec<Event>, removed: &HashMap<PathBuf, FileId>) -> Vec<Event> {
+    let sources: Vec<usize> = (0..events.len())
+        .filter(|&i| is_rename_source(&events[i]))
+        .collect();
+    let targets: Vec<usize> = (0..events.len())
+        .filter(|&i| is_rename_target(&events[i]))
+        .collect();
+
+    let mut pairs: HashMap<usize, usize> = HashMap::new();
+    for &source in &sources {
+        let Some(tracker) = events[source].tracker() else {
+            continue;
+        };
+        let paired = targets
+            .iter()
+            .find(|&&target| target > source && events[target].tracker() == Some(tracker));
+        if let Some(&target) = paired {
+            pairs.insert(target, source);
+        }
+    }
+    let lone_sources: Vec<usize> = sources
+        .into_iter()
+        .filter(|source| !pairs.values().any(|paired| paired == source))
+        .collect();
+    let mut lone_targets: Vec<usize> = targets
+        .into_iter()
+        .filter(|target| !pairs.contains_key(target))
+        .collect();
+    for source in lone_sources {
+        let found = lone_targets.iter().position(|&target| {
+            target > source && is_same_entry(&events[source], &events[target], removed)
+        });
+        if let Some(found) = found {
+            pairs.insert(lone_targets.remove(found), source);
+        }
+    }
+    if pairs.is_empty() {
+        return events;
+    }
+
+    let sources: HashMap<usize, PathBuf> = pairs
+        .values()
+        .map(|&source| (source, events[source].paths[0].clone()))
+        .collect();
+    let mut paired = Vec::with_capacity(events.len() - pairs.len());
+    for (i, event) in events.into_iter().enumerate() {
+        if sources.contains_key(&i) {
+            continue;
+        }
+        match pairs.get(&i) {
+            Some(source) => paired.push(
+                Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                    .add_path(sources[source].clone())
+                    .add_path(event.paths[0].clone()),
+            ),
+            None => paired.push(event),
+        }
+    }
+    paired
+}
+
+/// Paths an event takes away: removals and the source side of renames
+fn removed_paths(event: &Event) -> &[PathBuf] {
+    match (event.kind, event.paths.as_slice()) {
+        (EventKind::Remove(_), paths) => paths,
+        (EventKind::Modify(ModifyKind::Name(RenameMode::From)), paths) => paths,
+        (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, _]) => {
+            std::slice::from_ref(from)
+        }
+        _ => &[],
+    }
+}
+
+/// Tracks the set of notes under the watched root
+struct FileCountTracker {
+    notes: NoteFilter,
+    files: HashSet<PathBuf>,
+}
+
+impl FileCountTracker {
+    fn new(notes: NoteFilter) -> Self {
+        FileCountTracker {
+            files: notes.notes_in(&notes.root).into_iter().collect(),
+            notes,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.files.len()
+    }
+
+    fn add(&mut self, path: &Path) {
+        if path.is_dir() {
+            self.files.extend(self.notes.notes_in(path));
+        } else if self.notes.includes(path) {
+            self.files.insert(path.to_path_buf());
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        // A removed directory takes every file beneath it along
+        self.files.retain(|file| !file.starts_with(path));
+    }
+
+    /// Apply an event, returning whether the count changed
+    fn apply(&mut self, event: &Event) -> bool {
+        let before = self.count();
+        match event.kind {
+            EventKind::Create(_) => event.paths.iter().for_each(|p| self.add(p)),
+            EventKind::Remove(_) => event.paths.iter().for_each(|p| self.remove(p)),
+            EventKind::Modify(ModifyKind::Name(mode)) => match (mode, event.paths.as_slice()) {
+                (RenameMode::Both, [from, to]) => {
+                    self.remove(from);
+                    self.add(to);
+                }
+                (RenameMode::From, paths) => paths.iter().for_each(|p| self.remove(p)),
+                (RenameMode::To, paths) => paths.iter().for_each(|p| self.add(p)),
+                // Ambiguous renames: reconcile each path against the disk
+                (_, paths) => paths.iter().for_each(|p| {
+                    if p.exists() {
+                        self.add(p)
+                    } else {
+                        self.remove(p)
+                    }
+                }),
+            },
+            _ => {}
+        }
+        self.count() != before
+    }
+}
+
+/// Which files under a watched root count as notes: markdown by the
+/// configured extensions, as `is_markdown_name` decides, and neither hidden
+/// nor ignored
+#[derive(Clone)]
+struct NoteFilter {
+    root: PathBuf,
+    extensions: Option<Vec<String>>,
+    ignore: IgnoreRules,
+}
+
+impl NoteFilter {
+    fn includes(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        is_markdown_name(&name, self.extensions.as_deref())
+            && !path
+                .strip_prefix(&self.root)
+                .is_ok_and(|relative| self.ignore.is_ignored(relative))
+    }
+
+    /// The notes at or below `dir`, a directory under the root
+    fn notes_in(&self, dir: &Path) -> Vec<PathBuf> {
+        // The rules match paths relative to the root, so only a walk from
+        // the root can prune ignored folders as it goes
+        let ignore = match dir == self.root {
+            true => self.ignore.clone(),
+            false => IgnoreRules::default(),
+        };
+        vault_files(dir, &ignore)
+            .into_iter()
+            .filter(|path| self.includes(path))
+            .collect()
+    }
+}
+
+/// Content hashes of the notes under the watched root
+struct ContentHashCache {
+    notes: NoteFilter,
+    hashes: HashMap<PathBuf, String>,
+}
+
+impl ContentHashCache {
+    fn new(notes: NoteFilter) -> Self {
+        let hashes = notes
+            .notes_in(&notes.root)
+            .into_iter()
+            .filter_map(|path| {
+                let hash = file_hash(&path.to_string_lossy()).ok()?;
+                Some((path, hash))
+            })
+            .collect();
+        ContentHashCache { notes, hashes }
+    }
+
+    /// Re-hash `path`, returning whether its content differs from the cache
+    fn refresh(&mut self, path: &Path) -> bool {
+        if !self.notes.includes(path) {
+            return false;
+        }
+        match file_hash(&path.to_string_lossy()) {
+            Ok(hash) => self.hashes.insert(path.to_path_buf(), hash.clone()) != Some(hash),
+            // Gone (or unreadable): stale only if we knew its content
+            Err(_) => self.hashes.remove(path).is_some(),
+        }
+    }
+}
+
+/// What a directory watch keeps for the commands to read: whether its tree
+/// changed since it was marked clean, and the indexes it keeps current
+#[derive(Clone, Default)]
+struct WatchState {
+    dirty: Arc<AtomicBool>,
+    index: SharedIndex,
+    /// Kept current whenever there is one, whether the watch built it or a
+    /// command installed it
+    search_index: SharedSearchIndex,
+}
+
+/// What a `WatchSession` is started with besides its options
+#[derive(Default)]
+struct SessionParts {
+    /// Shared with the commands through the `DirectoryWatch`
+    state: WatchState,
+    /// Files open in the editor
+    open_files: OpenFiles,
+    /// Paths whose events are dropped
+    ignore: IgnoreRules,
+    /// File ids of removed paths, noted by the debouncer's cache
+    removed_ids: RemovedIds,
+}
+
+/// Per-watch state owned by the event thread
+struct WatchSession {
+    file_count: Option<FileCountTracker>,
+    dirty: Option<Arc<AtomicBool>>,
+    content_hashes: Option<ContentHashCache>,
+    index: Option<SharedIndex>,
+    search_index: SharedSearchIndex,
+    /// Files open in the editor
+    open_files: OpenFiles,
+    root: PathBuf,
+    /// Directories events must fall under; empty allows everything
+    allowlist: Vec<PathBuf>,
+    /// Paths whose events are dropped
+    ignore: IgnoreRules,
+    /// File ids of removed paths, noted by the debouncer's cache
+    removed_ids: RemovedIds,
+    /// See `WatchOptions::markdown_extensions`
+    markdown_extensions: Option<Vec<String>>,
+    /// Events seen since the last `vault-settled`
+    burst_events: usize,
+}
+
+impl WatchSession {
+    fn new(root: &Path, options: &WatchOptions, parts: SessionParts) -> Self {
+        let SessionParts {
+            state,
+            open_files,
+            ignore,
+            removed_ids,
+        } = parts;
+        let notes = NoteFilter {
+            root: root.to_path_buf(),
+            extensions: options.markdown_extensions.clone(),
+            ignore: ignore.clone(),
+        };
+        let tracked_index = options.track_index.then(|| {
+            *state.index.lock().unwrap() = Some(VaultIndex::build(root, &ignore));
+            state.index.clone()
+        });
+        if options.track_search_index {
+            *state.search_index.lock().unwrap() = Some(SearchIndex::build(root));
+        }
+        WatchSession {
+            file_count: options
+                .track_file_count
+                .then(|| FileCountTracker::new(notes.clone())),
+            dirty: options.track_dirty.then(|| state.dirty.clone()),
+            content_hashes: options
+                .preview_stale
+                .then(|| ContentHashCache::new(notes.clone())),
+            index: tracked_index,
+            search_index: state.search_index.clone(),
+            open_files,
+            root: root.to_path_buf(),
+            allowlist: watch_targets(root, options),
+            ignore,
+            removed_ids,
+            markdown_extensions: options.markdown_extensions.clone(),
+            burst_events: 0,
+        }
+    }
+
+    /// Emissions to send as soon as the watch is established
+    fn initial_emissions(&self) -> Vec<Emission> {
+        self.file_count
+            .iter()
+            .map(|tracker| file_count_emission(tracker.count()))
+            .collect()
+    }
+
+    /// Drop event paths outside the allowlist or matching an ignore
+    /// pattern, and events left with none
+    fn allowed_events(&self, events: &[Event]) -> Vec<Event> {
+        events
+            .iter()
+            .filter_map(|event| {
+                let mut event = event.clone();
+                let had_paths = !event.paths.is_empty();
+                if !self.allowlist.is_empty() {
+                    event
+                        .paths
+                        .retain(|path| self.allowlist.iter().any(|dir| path.starts_with(dir)));
+                }
+                event.paths.retain(|path| {
+                    path.strip_prefix(&self.root)
+                        .map_or(true, |relative| !self.ignore.matches_pattern(relative))
+                });
+                if had_paths && event.paths.is_empty() {
+                    return None;
+                }
+                Some(event)
+            })
+            .collect()
+    }
+
+    /// Translate a debounced batch of events into frontend emissions
+    fn handle_events(&mut self, events: &[Event]) -> Vec<Emission> {
+        let removed: HashMap<PathBuf, FileId> = {
+            let mut removed_ids = self.removed_ids.lock().unwrap();
+            events
+                .iter()
+                .flat_map(|event| &event.paths)
+                .filter_map(|path| removed_ids.remove_entry(path))
+                .collect()
+        };
+        let events = &pair_renames(self.allowed_events(events), &removed);
+        let mut changes = ChangeBatch::default();
+        let mut emissions: Vec<Emission> = Vec::new();
+        let mut emitted: HashSet<(&'static str, PathBuf)> = HashSet::new();
+        let mut count_changed = false;
+        let mut stale: Vec<PathBuf> = Vec::new();
+        self.burst_events += events.len();
+
+        let extensions = self.markdown_extensions.as_deref();
+        for event in events {
+            if let Some(change) = FileChange::from_event(event, extensions) {
+                changes.add(change);
+            }
+            let mut path_emissions = Vec::new();
+            for path in &event.paths {
+                path_emissions.push((change_event(event, path), path.clone()));
+            }
+            let open_files = self.open_files.lock().unwrap();
+            for removed in removed_paths(event) {
+                // A removed directory takes any open file beneath it along
+                for open in open_files.iter().filter(|open| open.starts_with(removed)) {
+                    path_emissions.push(("open-file-deleted", open.clone()));
+                }
+            }
+            drop(open_files);
+            for (event, path) in path_emissions {
+                if emitted.insert((event, path.clone())) {
+                    emissions.push(Emission {
+                        event,
+                        payload: json!({ "path": path }),
+                    });
+                }
+            }
+            if let Some(tracker) = self.file_count.as_mut() {
+                count_changed |= tracker.apply(event);
+            }
+            if let Some(index) = &self.index {
+                if let Some(index) = index.lock().unwrap().as_mut() {
+                    index.apply(event);
+                }
+            }
+            if let Some(index) = self.search_index.lock().unwrap().as_mut() {
+                index.apply(event);
+            }
+            if let Some(cache) = self.content_hashes.as_mut() {
+                for path in &event.paths {
+                    if !stale.contains(path) && cache.refresh(path) {
+                        stale.push(path.clone());
+                    }
+                }
+            }
+        }
+
+        let mut emissions: Vec<Emission> = changes.into_emissions().chain(emissions).collect();
+        for path in stale {
+            emissions.push(Emission {
+                event: "preview-stale",
+                payload: json!({ "path": path }),
+            });
+        }
+
+        if count_changed {
+            if let Some(tracker) = &self.file_count {
+                emissions.push(file_count_emission(tracker.count()));
+            }
+        }
+
+        if let Some(dirty) = &self.dirty {
+            if !events.is_empty() && !dirty.swap(true, Ordering::SeqCst) {
+                emissions.push(Emission {
+                    event: "vault-dirty",
+                    payload: json!({ "dirty": true }),
+                });
+            }
+        }
+
+        emissions
+    }
+
+    /// Start over from what is on disk after events were dropped: rebuild
+    /// the trackers and indexes, and have the frontend reload everything
+    /// with `needs-full-refresh`
+    fn resync(&mut self) -> Vec<Emission> {
+        let mut emissions = vec![Emission {
+            event: "needs-full-refresh",
+            payload: json!({ "path": self.root }),
+        }];
+        if let Some(tracker) = self.file_count.as_mut() {
+            *tracker = FileCountTracker::new(tracker.notes.clone());
+            emissions.push(file_count_emission(tracker.count()));
+        }
+        if let Some(cache) = self.content_hashes.as_mut() {
+            *cache = ContentHashCache::new(cache.notes.clone());
+        }
+        self.removed_ids.lock().unwrap().clear();
+        if let Some(index) = &self.index {
+            if let Some(index) = index.lock().unwrap().as_mut() {
+                *index = VaultIndex::build(&self.root, &self.ignore);
+            }
+        }
+        if let Some(index) = self.search_index.lock().unwrap().as_mut() {
+            if index.root().starts_with(&self.root) {
+                index.revalidate();
+            }
+        }
+        if let Some(dirty) = &self.dirty {
+            if !dirty.swap(true, Ordering::SeqCst) {
+                emissions.push(Emission {
+                    event: "vault-dirty",
+                    payload: json!({ "dirty": true }),
+                });
+            }
+        }
+        self.burst_events += 1;
+        emissions
+    }
+
+    /// Save the search index, if it is persisted and changed since it was
+    /// last saved
+    fn save_search_index(&self) {
+        if let Some(index) = self.search_index.lock().unwrap().as_mut() {
+            index.save_changes();
+        }
+    }
+
+    /// Called once the tree has been quiet for the quiescence period; emits
+    /// `vault-settled` if anything happened since the last one
+    fn settle(&mut self) -> Vec<Emission> {
+        if self.burst_events == 0 {
+            return Vec::new();
+        }
+        let events = std::mem::take(&mut self.burst_events);
+        vec![Emission {
+            event: "vault-settled",
+            payload: json!({ "events": events }),
+        }]
+    }
+}
+
+/// Emissions of a single-file watch for a debounced batch of events in its
+/// parent directory: a `watched-file-change` scoped to the file, coalesced
+/// like a directory watch's `file-change`, then one `file-modified` if it
+/// was written or recreated. The distinct name keeps these apart from the
+/// directory watches' events about the same file.
+fn file_watch_emissions(
+    watched: &Path,
+    events: &[Event],
+    extensions: Option<&[String]>,
+) -> Vec<Emission> {
+    let mut changes = ChangeBatch::default();
+    let mut modified = false;
+    for event in events
+        .iter()
+        .filter(|e| e.paths.iter().any(|p| p == watched))
+    {
+        let Some(mut change) = FileChange::from_event(event, extensions) else {
+            continue;
+        };
+        change.paths.retain(|changed| changed.path == watched);
+        modified |= matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+        changes.add(change);
+    }
+    let mut emissions: Vec<Emission> = changes
+        .into_emissions()
+        .map(|emission| Emission {
+            event: "watched-file-change",
+            ..emission
+        })
+        .collect();
+    if modified {
+        emissions.push(Emission {
+            event: "file-modified",
+            payload: json!({ "path": watched }),
+        });
+    }
+    emissions
+}
+
+/// Bytes appended to a tailed file, as the payload of a `file-tail` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TailChunk {
+    pub path: PathBuf,
+    pub content: String,
+    /// The file was truncated or replaced, so `content` starts it over
+    /// rather than continuing what was sent before
+    pub reset: bool,
+    /// Byte offset in the file just past `content`
+    pub offset: u64,
+}
+
+/// Most bytes of a tailed file read at once; a larger append is sent as
+/// several `file-tail` events
+const TAIL_READ_LIMIT: u64 = 1024 * 1024;
+
+/// How many bytes just before the offset a tail keeps to notice a file
+/// that was truncated and grew back past the offset between two reads
+const TAIL_CHECK_BYTES: usize = 64;
+
+/// How far a tailed file has been read
+struct FileTail {
+    path: PathBuf,
+    offset: u64,
+    /// Tells the file apart from another that replaced it at the same
+    /// path, e.g. after log rotation
+    id: Option<FileId>,
+    /// The last bytes read, up to `TAIL_CHECK_BYTES`
+    last_read: Vec<u8>,
+}
+
+impl FileTail {
+    /// Start following `path` from its current end
+    fn new(path: &Path) -> std::io::Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let offset = file.metadata()?.len();
+        let mut tail = FileTail {
+            path: path.to_path_buf(),
+            offset,
+            id: get_file_id(path).ok(),
+            last_read: Vec::new(),
+        };
+        tail.last_read = tail.bytes_before_offset(&mut file)?;
+        Ok(tail)
+    }
+
+    /// The bytes of `file` just before `offset`, up to `TAIL_CHECK_BYTES`
+    fn bytes_before_offset(&self, file: &mut std::fs::File) -> std::io::Result<Vec<u8>> {
+        use std::io::{Read, Seek, SeekFrom};
+        let start = self.offset.saturating_sub(TAIL_CHECK_BYTES as u64);
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.take(self.offset - start).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// What was appended since the last read, if anything, up to
+    /// `TAIL_READ_LIMIT` bytes. A file that was replaced, shrank, or no
+    /// longer holds the bytes last read before the offset is read again
+    /// from the start. A multi-byte character cut off by the writer is
+    /// left for the next read.
+    fn read_new(&mut self) -> std::io::Result<Option<TailChunk>> {
+        use std::io::{Read, Seek, SeekFrom};
+        let Ok(mut file) = std::fs::File::open(&self.path) else {
+            // Gone for now; a file created in its place starts over
+            self.id = None;
+            self.offset = 0;
+            self.last_read.clear();
+            return Ok(None);
+        };
+        let len = file.metadata()?.len();
+        let id = get_file_id(&self.path).ok();
+        let reset = id != self.id
+            || len < self.offset
+            || self.bytes_before_offset(&mut file)? != self.last_read;
+        if reset {
+            self.id = id;
+            self.offset = 0;
+        }
+        if len == self.offset && !reset {
+            return Ok(None);
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.by_ref()
+            .take((len - self.offset).min(TAIL_READ_LIMIT))
+            .read_to_end(&mut bytes)?;
+        let complete = match std::str::from_utf8(&bytes) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => bytes.len(),
+        };
+        bytes.truncate(complete);
+        self.offset += complete as u64;
+        self.last_read = self.bytes_before_offset(&mut file)?;
+        if bytes.is_empty() && !reset {
+            return Ok(None);
+        }
+        Ok(Some(TailChunk {
+            path: self.path.clone(),
+            content: String::from_utf8_lossy(&bytes).into_owned(),
+            reset,
+            offset: self.offset,
+        }))
+    }
+}
+
+/// A `watch-error` reporting `message` for the watch on `root`
+fn watch_error(root: &Path, message: &str) -> Emission {
+    Emission {
+        event: "watch-error",
+        payload: json!({ "path": root, "message": message }),
+    }
+}
+
+/// Describe a failure to watch `path`, spelling out when the OS limit on
+/// watches was reached since that needs a different fix
+fn watch_failure(path: &Path, error: &notify::Error) -> String {
+    match error.kind {
+        notify::ErrorKind::MaxFilesWatch => format!(
+            "Reached the OS limit on watched directories while watching {}; \
+             set max_depth or raise the limit (fs.inotify.max_user_watches on Linux)",
+            path.display()
+        ),
+        _ => format!("Failed to watch directory: {}", error),
+    }
+}
+
+fn watch_path(
+    debouncer: &mut FileDebouncer,
+    path: &Path,
+    mode: RecursiveMode,
+) -> Result<(), notify::Error> {
+    debouncer.watcher().watch(path, mode)?;
+    debouncer.cache().ids.add_root(path, mode);
+    Ok(())
+}
+
+/// Keep a depth-limited watch in step with the tree, watching directories
+/// created or moved in within `max_depth` of a target and dropping removed
+/// ones. Returns a `watch-error` if a new directory couldn't be watched.
+fn follow_directories(
+    watches: &Watches,
+    key: &Path,
+    id: u64,
+    targets: &[PathBuf],
+    max_depth: usize,
+    events: &[Event],
+) -> Vec<Emission> {
+    let mut registered = watches.lock().unwrap();
+    let Some(watch) = registered.get_mut(key).filter(|watch| watch.id == id) else {
+        return Vec::new();
+    };
+    let mut errors = Vec::new();
+    for event in events {
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+        ) {
+            continue;
+        }
+        for path in &event.paths {
+            if !path.exists() {
+                // Only directories within the limit were watched, so
+                // unwatching anything else fails harmlessly
+                let _ = watch.debouncer.watcher().unwatch(path);
+                watch.debouncer.cache().ids.remove_root(path);
+                continue;
+            }
+            let depth = targets
+                .iter()
+                .find_map(|target| path.strip_prefix(target).ok())
+                .map(|relative| relative.components().count());
+            let Some(depth) = depth.filter(|depth| *depth <= max_depth && path.is_dir()) else {
+                continue;
+            };
+            let mut dirs = vec![path.clone()];
+            dirs.extend(subdirectories(path, max_depth - depth));
+            for dir in dirs {
+                if let Err(e) = watch_path(&mut watch.debouncer, &dir, RecursiveMode::NonRecursive)
+                {
+                    errors.push(watch_error(key, &watch_failure(&dir, &e)));
+                    break;
+                }
+            }
+        }
+    }
+    errors
+}
+
+fn file_count_emission(count: usize) -> Emission {
+    Emission {
+        event: "file-count-changed",
+        payload: json!({ "count": count }),
+    }
+}
+
+/// Destination for watcher emissions; the app handle in production.
+/// Emitting must not panic, since event threads can outlive the app window
+/// during shutdown.
+pub trait EventSink: Send + 'static {
+    fn emit(&self, emission: Emission);
+}
+
+impl EventSink for AppHandle {
+    fn emit(&self, emission: Emission) {
+        if let Err(e) = self.emit_all(emission.event, emission.payload) {
+            eprintln!("Failed to emit {}: {}", emission.event, e);
+        }
+    }
+}
+
+fn emit_all(sink: &impl EventSink, emissions: Vec<Emission>) {
+    for emission in emissions {
+        sink.emit(emission);
+    }
+}
+
+/// Pause switch shared by every directory watch's event thread. Events
+/// keep flowing through the debouncer and sessions while paused, so caches
+/// stay current; only the emissions are dropped.
+#[derive(Default)]
+struct PauseState {
+    pauses: Mutex<Pauses>,
+}
+
+#[derive(Default)]
+struct Pauses {
+    /// Pauses not yet resumed; emitting resumes once the last one is
+    active: usize,
+    /// Whether anything was dropped since the outermost pause began
+    missed: bool,
+}
+
+impl PauseState {
+    /// Emit `emissions`, or drop them and note the miss while paused. A
+    /// flush's emissions go out or are dropped together under the lock, so
+    /// a pause takes effect between flushes and never splits one.
+    fn forward(&self, sink: &impl EventSink, emissions: Vec<Emission>) {
+        let mut pauses = self.pauses.lock().unwrap();
+        if pauses.active == 0 {
+            emit_all(sink, emissions);
+        } else if !emissions.is_empty() {
+            pauses.missed = true;
+        }
+    }
+
+    fn pause(&self) {
+        let mut pauses = self.pauses.lock().unwrap();
+        if pauses.active == 0 {
+            pauses.missed = false;
+        }
+        pauses.active += 1;
+    }
+
+    /// End one pause. Once the outermost one ends, returns whether anything
+    /// was dropped while paused; `None` while other pauses remain or if
+    /// nothing was paused.
+    fn resume(&self) -> Option<bool> {
+        let mut pauses = self.pauses.lock().unwrap();
+        pauses.active = pauses.active.checked_sub(1)?;
+        (pauses.active == 0).then(|| std::mem::take(&mut pauses.missed))
+    }
+
+    fn is_paused(&self) -> bool {
+        self.pauses.lock().unwrap().active > 0
+    }
+}
+
+/// Bookkeeping shared by an `EventQueue` and the event thread draining it
+#[derive(Default)]
+struct QueueState {
+    /// Events queued and not yet taken by the event thread
+    pending: AtomicUsize,
+    /// Events were dropped since the event thread last caught up
+    overflowed: AtomicBool,
+}
+
+impl QueueState {
+    /// Note that the event thread took `result` off the queue
+    fn taken(&self, result: &DebounceEventResult) {
+        self.pending
+            .fetch_sub(event_count(result), Ordering::SeqCst);
+    }
+
+    /// If events were dropped, discard whatever is still queued and return
+    /// true; the caller then reconciles with what is on disk. Events arriving
+    /// after this are queued normally again.
+    fn catch_up(&self, rx: &Receiver<DebounceEventResult>) -> bool {
+        if !self.overflowed.load(Ordering::SeqCst) {
+            return false;
+        }
+        while let Ok(result) = rx.try_recv() {
+            self.taken(&result);
+        }
+        self.overflowed.store(false, Ordering::SeqCst);
+        true
+    }
+}
+
+fn event_count(result: &DebounceEventResult) -> usize {
+    result.as_ref().map_or(0, |events| events.len())
+}
+
+/// Hands debounced events to a directory watch's event thread through a
+/// bounded channel, so a storm the thread can't keep up with, e.g. a git
+/// checkout, doesn't grow memory without limit. Once more than
+/// `max_pending` events are waiting, or the channel is full, events are
+/// dropped until the thread catches up.
+struct EventQueue {
+    tx: SyncSender<DebounceEventResult>,
+    state: Arc<QueueState>,
+    max_pending: usize,
+}
+
+impl EventQueue {
+    fn new(max_pending: usize) -> (Self, Receiver<DebounceEventResult>, Arc<QueueState>) {
+        let (tx, rx) = sync_channel(EVENT_QUEUE_BATCHES);
+        let state = Arc::new(QueueState::default());
+        let queue = EventQueue {
+            tx,
+            state: state.clone(),
+            max_pending,
+        };
+        (queue, rx, state)
+    }
+}
+
+impl DebounceEventHandler for EventQueue {
+    fn handle_event(&mut self, result: DebounceEventResult) {
+        let count = event_count(&result);
+        let state = &self.state;
+        if !state.overflowed.load(Ordering::SeqCst)
+            && state.pending.load(Ordering::SeqCst) + count <= self.max_pending
+        {
+            state.pending.fetch_add(count, Ordering::SeqCst);
+            if self.tx.try_send(result).is_ok() {
+                return;
+            }
+            state.pending.fetch_sub(count, Ordering::SeqCst);
+        }
+        if !state.overflowed.swap(true, Ordering::SeqCst) {
+            // Wake the event thread even if nothing else is queued
+            let _ = self.tx.try_send(Ok(Vec::new()));
+        }
+    }
+}
+
+type FileDebouncer = Debouncer<RecommendedWatcher, RenameCache>;
+type RemovedIds = Arc<Mutex<HashMap<PathBuf, FileId>>>;
+
+/// The debouncer's file id cache. For a directory watch it also notes the
+/// id each removed path had until the event thread handles its removal, so
+/// a removal and a creation can be recognized as one entry being renamed.
+struct RenameCache {
+    ids: FileIdMap,
+    removed: Option<RemovedIds>,
+}
+
+impl FileIdCache for RenameCache {
+    fn cached_file_id(&self, path: &Path) -> Option<&FileId> {
+        self.ids.cached_file_id(path)
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        self.ids.add_path(path);
+    }
+
+    fn remove_path(&mut self, path: &Path) {
+        if let (Some(removed), Some(id)) = (&self.removed, self.ids.cached_file_id(path)) {
+            removed.lock().unwrap().insert(path.to_path_buf(), *id);
+        }
+        self.ids.remove_path(path);
+    }
+
+    fn rescan(&mut self) {
+        self.ids.rescan();
+    }
+}
+
+fn new_file_debouncer(
+    timeout: Duration,
+    handler: impl DebounceEventHandler,
+    removed: Option<RemovedIds>,
+) -> Result<FileDebouncer, notify::Error> {
+    let cache = RenameCache {
+        ids: FileIdMap::new(),
+        removed,
+    };
+    new_debouncer_opt(timeout, None, handler, cache, notify::Config::default())
+}
+type SharedIndex = Arc<Mutex<Option<VaultIndex>>>;
+type SharedSearchIndex = Arc<Mutex<Option<SearchIndex>>>;
+type OpenFiles = Arc<Mutex<HashSet<PathBuf>>>;
+type Watches = Arc<Mutex<HashMap<PathBuf, DirectoryWatch>>>;
+
+/// A running watch, as reported by `watched_paths`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WatchedPath {
+    pub path: String,
+    /// Whether this is the `watch_file` watch rather than a directory watch
+    pub is_file: bool,
+    /// Whether everything below `path` is watched; false for file watches
+    /// and depth-limited directory watches
+    pub recursive: bool,
+    /// Depth limit of a directory watch; see `WatchOptions::max_depth`
+    pub max_depth: Option<usize>,
+    pub debounce_ms: u64,
+}
+
+/// A file followed by `tail_file`
+struct TailWatch {
+    _debouncer: FileDebouncer,
+}
+
+/// A watch on a single file
+struct FileWatch {
+    path: PathBuf,
+    /// Kept so the watch follows a rename with the same extensions
+    markdown_extensions: Option<Vec<String>>,
+    _debouncer: FileDebouncer,
+}
+
+/// A watch on a directory tree and the thread forwarding its events
+struct DirectoryWatch {
+    /// Tells this watch apart from a later one on the same path
+    id: u64,
+    debouncer: FileDebouncer,
+    thread: JoinHandle<()>,
+    state: WatchState,
+    max_depth: Option<usize>,
+    debounce_ms: u64,
+}
+
+impl DirectoryWatch {
+    /// Stop the watch and wait for its event thread to exit
+    fn stop(self) {
+        // Stopping joins the debouncer's own thread, which drops the channel
+        // sender and so ends the event thread's receive loop
+        self.debouncer.stop();
+        let _ = self.thread.join();
+    }
+}
+
+/// Watches directory trees and forwards change events to the frontend
+pub struct DirectoryWatcher {
+    watches: Watches,
+    next_watch_id: AtomicU64,
+    file_watch: Mutex<Option<FileWatch>>,
+    tails: Mutex<HashMap<PathBuf, TailWatch>>,
+    open_files: OpenFiles,
+    pause: Arc<PauseState>,
+}
+
+impl DirectoryWatcher {
+    pub fn new() -> Self {
+        DirectoryWatcher {
+            watches: Arc::new(Mutex::new(HashMap::new())),
+            next_watch_id: AtomicU64::new(0),
+            file_watch: Mutex::new(None),
+            tails: Mutex::new(HashMap::new()),
+            open_files: Arc::new(Mutex::new(HashSet::new())),
+            pause: Arc::new(PauseState::default()),
+        }
+    }
+
+    /// Start watching `path` recursively alongside any other watched
+    /// directories, replacing an existing watch on the same path.
+    ///
+    /// Once the watch is established `watch-ready` is emitted, even for an
+    /// empty directory and while paused. It follows the initial
+    /// `file-count-changed` and precedes every change event of this watch;
+    /// changes made during setup arrive after it, as ordinary events.
+    ///
+    /// If events pile up faster than they are handled, past
+    /// `max_buffered_events`, the backlog is dropped and a single
+    /// `needs-full-refresh` is emitted once the watch has caught up with
+    /// what is on disk.
+    ///
+    /// Watcher errors are emitted as `watch-error`. If the watched directory
+    /// itself is removed, the watch stops itself and emits `watch-stopped`
+    /// so the frontend can ask for another folder.
+    pub fn watch_directory(
+        &self,
+        sink: impl EventSink,
+        path: &str,
+        options: WatchOptions,
+    ) -> Result<(), String> {
+        let root = PathBuf::from(path);
+        if !root.is_dir() {
+            return Err(format!("Path is not a directory: {}", path));
+        }
+        let debounce = debounce_interval(&options)?;
+        let max_buffered = options
+            .max_buffered_events
+            .unwrap_or(DEFAULT_MAX_BUFFERED_EVENTS);
+        if max_buffered == 0 {
+            return Err("Event buffer must hold at least one event".to_string());
+        }
+        let ignore = IgnoreRules::new(&options.ignore)?;
+
+        let mut targets = watch_targets(&root, &options);
+        for target in &targets {
+            if !target.starts_with(&root) || !target.is_dir() {
+                return Err(format!(
+                    "Subpath is not a directory inside the root: {}",
+                    target.display()
+                ));
+            }
+        }
+        if targets.is_empty() {
+            targets.push(root.clone());
+        }
+
+        let (queue, rx, queue_state) = EventQueue::new(max_buffered);
+        let removed_ids = RemovedIds::default();
+        let mut debouncer = new_file_debouncer(debounce, queue, Some(removed_ids.clone()))
+            .map_err(|e| format!("Failed to create watcher: {}", e))?;
+        let mut setup_errors = Vec::new();
+        for target in &targets {
+            let Some(max_depth) = options.max_depth else {
+                watch_path(&mut debouncer, target, RecursiveMode::Recursive)
+                    .map_err(|e| watch_failure(target, &e))?;
+                continue;
+            };
+            watch_path(&mut debouncer, target, RecursiveMode::NonRecursive)
+                .map_err(|e| watch_failure(target, &e))?;
+            // Whatever was watched before hitting a limit keeps working
+            for dir in subdirectories(target, max_depth) {
+                if let Err(e) = watch_path(&mut debouncer, &dir, RecursiveMode::NonRecursive) {
+                    setup_errors.push(watch_error(&root, &watch_failure(&dir, &e)));
+                    break;
+                }
+            }
+        }
+
+        self.unwatch(&root);
+        let state = WatchState::default();
+        let mut session = WatchSession::new(
+            &root,
+            &options,
+            SessionParts {
+                state: state.clone(),
+                open_files: self.open_files.clone(),
+                ignore,
+                removed_ids,
+            },
+        );
+        let quiescence = options.quiescence_ms.map(Duration::from_millis);
+        let max_depth = options.max_depth;
+        let pause = self.pause.clone();
+        let id = self.next_watch_id.fetch_add(1, Ordering::SeqCst);
+        let key = normalize_path(&root);
+        let thread_key = key.clone();
+        let watches = self.watches.clone();
+        // Held until the watch is registered, so a watch that stops itself
+        // straight away can't miss its own entry
+        let mut registered = self.watches.lock().unwrap();
+        let thread = thread::spawn(move || {
+            pause.forward(&sink, session.initial_emissions());
+            for error in setup_errors {
+                sink.emit(error);
+            }
+            sink.emit(Emission {
+                event: "watch-ready",
+                payload: json!({ "path": session.root }),
+            });
+            // The channel closes when the watch is stopped
+            loop {
+                let result = match quiescence {
+                    Some(period) => match rx.recv_timeout(period) {
+                        Ok(result) => result,
+                        Err(RecvTimeoutError::Timeout) => {
+                            pause.forward(&sink, session.settle());
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => match rx.recv() {
+                        Ok(result) => result,
+                        Err(_) => break,
+                    },
+                };
+                queue_state.taken(&result);
+                if queue_state.catch_up(&rx) {
+                    // Events were dropped; start over from what is on disk
+                    if let Some(max_depth) = max_depth {
+                        let mut rescan = Event::new(EventKind::Create(CreateKind::Folder));
+                        rescan.paths = targets.clone();
+                        let errors = follow_directories(
+                            &watches,
+                            &thread_key,
+                            id,
+                            &targets,
+                            max_depth,
+                            &[rescan],
+                        );
+                        for error in errors {
+                            sink.emit(error);
+                        }
+                    }
+                    pause.forward(&sink, session.resync());
+                    session.save_search_index();
+                    continue;
+                }
+                match result {
+                    Ok(events) => {
+                        let events: Vec<Event> = events.into_iter().map(|e| e.event).collect();
+                        // Watch new directories before reporting them, so
+                        // anything the frontend does in response is seen
+                        if let Some(max_depth) = max_depth {
+                            let errors = follow_directories(
+                                &watches,
+                                &thread_key,
+                                id,
+                                &targets,
+                                max_depth,
+                                &events,
+                            );
+                            for error in errors {
+                                sink.emit(error);
+                            }
+                        }
+                        pause.forward(&sink, session.handle_events(&events));
+                        session.save_search_index();
+                    }
+                    Err(errors) => {
+                        for error in errors {
+                            sink.emit(watch_error(&session.root, &error.to_string()));
+                        }
+                    }
+                }
+                if !session.root.is_dir() {
+                    // Dropping our own entry stops the debouncer without
+                    // waiting on this thread
+                    let mut registered = watches.lock().unwrap();
+                    if registered
+                        .get(&thread_key)
+                        .is_some_and(|watch| watch.id == id)
+                    {
+                        registered.remove(&thread_key);
+                    }
+                    drop(registered);
+                    sink.emit(watch_error(&session.root, "Watched directory was removed"));
+                    sink.emit(Emission {
+                        event: "watch-stopped",
+                        payload: json!({ "path": session.root }),
+                    });
+                    break;
+                }
+            }
+        });
+
+        registered.insert(
+            key,
+            DirectoryWatch {
+                id,
+                debouncer,
+                thread,
+                state,
+                max_depth: options.max_depth,
+                debounce_ms: debounce.as_millis() as u64,
+            },
+        );
+        Ok(())
+    }
+
+    /// Start watching `path` as `watch_directory` does and return a listing
+    /// of it to build on. The watch is registered before the listing is
+    /// read, so a change in between shows up in the listing and may also
+    /// arrive as an event; nothing falls in the gap.
+    pub fn watch_directory_with_snapshot(
+        &self,
+        sink: impl EventSink,
+        path: &str,
+        options: WatchOptions,
+        directory_options: &DirectoryOptions,
+    ) -> Result<Vec<FileItem>, String> {
+        self.watch_directory(sink, path, options)?;
+        read_directory(path, directory_options).map_err(|e| {
+            self.unwatch(Path::new(path));
+            e.into()
+        })
+    }
+
+    /// Stop the watch on `root`, returning whether there was one
+    fn unwatch(&self, root: &Path) -> bool {
+        let removed = self.watches.lock().unwrap().remove(&normalize_path(root));
+        let Some(watch) = removed else {
+            return false;
+        };
+        watch.stop();
+        true
+    }
+
+    /// Stop watching `path`, leaving other watched directories running
+    pub fn unwatch_directory(&self, path: &str) -> Result<(), String> {
+        if self.unwatch(Path::new(path)) {
+            Ok(())
+        } else {
+            Err(format!("Directory is not being watched: {}", path))
+        }
+    }
+
+    /// Stop every directory watch
+    pub fn stop_watching(&self) {
+        let watches: Vec<DirectoryWatch> = self
+            .watches
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, watch)| watch)
+            .collect();
+        for watch in watches {
+            watch.stop();
+        }
+    }
+
+    /// Directories currently watched by `watch_directory`, sorted
+    pub fn watched_directories(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .watches
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Every running watch: the directory watches sorted by path, then the
+    /// file watch if there is one
+    pub fn watched_paths(&self) -> Vec<WatchedPath> {
+        let mut paths: Vec<WatchedPath> = self
+            .watches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, watch)| WatchedPath {
+                path: path.to_string_lossy().to_string(),
+                is_file: false,
+                recursive: watch.max_depth.is_none(),
+                max_depth: watch.max_depth,
+                debounce_ms: watch.debounce_ms,
+            })
+            .collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        paths.extend(self.watched_file().map(|path| WatchedPath {
+            path,
+            is_file: true,
+            recursive: false,
+            max_depth: None,
+            debounce_ms: DEFAULT_DEBOUNCE_MS,
+        }));
+        paths
+    }
+
+    /// Whether `path` is the root of a directory watch or the watched file
+    pub fn is_watching(&self, path: &str) -> bool {
+        let path = normalize_path(Path::new(path));
+        self.watches.lock().unwrap().contains_key(&path)
+            || self
+                .file_watch
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|watch| normalize_path(&watch.path) == path)
+    }
+
+    /// Drop directory watch emissions, e.g. during a bulk rename, until
+    /// `resume_watching`. The watches keep running underneath. Pauses nest:
+    /// each needs its own resume. Returns once any flush being emitted has
+    /// gone out, so nothing is emitted after it.
+    pub fn pause_watching(&self) {
+        self.pause.pause();
+    }
+
+    /// End a pause started by `pause_watching`; emitting resumes once every
+    /// pause has ended. Then, with `refresh`, a single `refresh` event
+    /// stands in for any changes dropped meanwhile so the frontend re-reads
+    /// the tree; returns whether it was emitted.
+    pub fn resume_watching(&self, sink: impl EventSink, refresh: bool) -> bool {
+        let Some(missed) = self.pause.resume() else {
+            return false;
+        };
+        if refresh && missed {
+            sink.emit(Emission {
+                event: "refresh",
+                payload: json!({ "paths": self.watched_directories() }),
+            });
+        }
+        refresh && missed
+    }
+
+    /// Whether directory watch emissions are paused
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// Watch a single file, emitting `watched-file-change` for each event
+    /// touching it and `file-modified` when it is written. Only one file is
+    /// watched at a time, alongside any directory watches. Whether it is
+    /// markdown goes by `markdown_extensions` as in `WatchOptions`.
+    ///
+    /// The parent directory is watched, non-recursively, rather than the
+    /// file itself so the watch survives editors that replace the file on
+    /// save and so follows the file when it is deleted and recreated.
+    pub fn watch_file(
+        &self,
+        sink: impl EventSink,
+        path: &str,
+        markdown_extensions: Option<Vec<String>>,
+    ) -> Result<(), String> {
+        // Resolved like the event paths, which come from the resolved parent
+        let file = canonical_path(path);
+        if !file.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+        let parent = file
+            .parent()
+            .ok_or_else(|| format!("File has no parent directory: {}", path))?
+            .to_path_buf();
+
+        let (tx, rx) = channel::<DebounceEventResult>();
+        let debounce = Duration::from_millis(DEFAULT_DEBOUNCE_MS);
+        let mut debouncer = new_file_debouncer(debounce, tx, None)
+            .map_err(|e| format!("Failed to create watcher: {}", e))?;
+        debouncer
+            .watcher()
+            .watch(&parent, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch file: {}", e))?;
+
+        let watched = file.clone();
+        let extensions = markdown_extensions.clone();
+        thread::spawn(move || {
+            for result in rx {
+                let Ok(events) = result else {
+                    continue;
+                };
+                let events: Vec<Event> = events.into_iter().map(|e| e.event).collect();
+                let emissions = file_watch_emissions(&watched, &events, extensions.as_deref());
+                emit_all(&sink, emissions);
+            }
+        });
+
+        *self.file_watch.lock().unwrap() = Some(FileWatch {
+            path: file,
+            markdown_extensions,
+            _debouncer: debouncer,
+        });
+        Ok(())
+    }
+
+    /// Stop the watch started by `watch_file`, returning whether there was
+    /// one
+    pub fn unwatch_file(&self) -> bool {
+        self.file_watch.lock().unwrap().take().is_some()
+    }
+
+    /// Path of the file currently watched by `watch_file`, if any
+    pub fn watched_file(&self) -> Option<String> {
+        self.file_watch
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|watch| watch.path.to_string_lossy().to_string())
+    }
+
+    /// Follow a file as it grows, emitting only the bytes appended since the
+    /// last `file-tail` event. Reading starts at the current end of the
+    /// file, which is returned so the frontend can load what came before.
+    /// A file that is truncated or replaced, as by log rotation, is sent
+    /// again from the start with `reset` set. Tailing a file again restarts
+    /// it from its end.
+    ///
+    /// Like `watch_file`, the parent directory is watched so replacing the
+    /// file doesn't end the tail.
+    pub fn tail_file(&self, sink: impl EventSink, path: &str) -> Result<u64, String> {
+        let file = canonical_path(path);
+        if !file.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+        let parent = file
+            .parent()
+            .ok_or_else(|| format!("File has no parent directory: {}", path))?
+            .to_path_buf();
+        let mut tail = FileTail::new(&file).map_err(|e| format!("Failed to read file: {}", e))?;
+        let offset = tail.offset;
+
+        let (tx, rx) = channel::<DebounceEventResult>();
+        let debounce = Duration::from_millis(DEFAULT_DEBOUNCE_MS);
+        let mut debouncer = new_file_debouncer(debounce, tx, None)
+            .map_err(|e| format!("Failed to create watcher: {}", e))?;
+        watch_path(&mut debouncer, &parent, RecursiveMode::NonRecursive)
+            .map_err(|e| watch_failure(&parent, &e))?;
+
+        let watched = file.clone();
+        thread::spawn(move || {
+            for result in rx {
+                let touched = result
+                    .is_ok_and(|events| events.iter().any(|e| e.event.paths.contains(&watched)));
+                if !touched {
+                    continue;
+                }
+                loop {
+                    match tail.read_new() {
+                        Ok(Some(chunk)) => sink.emit(Emission {
+                            event: "file-tail",
+                            payload: json!(chunk),
+                        }),
+                        Ok(None) => break,
+                        Err(e) => {
+                            sink.emit(watch_error(&watched, &e.to_string()));
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        self.tails.lock().unwrap().insert(
+            file,
+            TailWatch {
+                _debouncer: debouncer,
+            },
+        );
+        Ok(offset)
+    }
+
+    /// Stop following a file, returning whether it was being tailed
+    pub fn stop_tail(&self, path: &str) -> bool {
+        let file = canonical_path(path);
+        self.tails.lock().unwrap().remove(&file).is_some()
+    }
+
+    /// Create a note, failing if it already exists, and watch it right away
+    /// so no external edit can slip in before the watch starts. If the
+    /// watch can't be started the note is removed again.
+    pub fn create_and_watch(
+        &self,
+        sink: impl EventSink,
+        path: &str,
+        content: &str,
+        markdown_extensions: Option<Vec<String>>,
+    ) -> Result<(), String> {
+        create_file_exclusive(path, content)?;
+        self.watch_file(sink, path, markdown_extensions)
+            .inspect_err(|_| {
+                if let Err(e) = std::fs::remove_file(path) {
+                    eprintln!("Failed to remove {}: {}", path, e);
+                }
+            })
+    }
+
+    /// Rename a file or directory. When it is the file watched by
+    /// `watch_file`, the watch follows it to the new path and, once that
+    /// watch is running, `watch-retargeted` is emitted. The rename stands
+    /// if the new watch can't start; the old one is dropped and a
+    /// `watch-error` is emitted instead.
+    pub fn rename(
+        &self,
+        sink: impl EventSink + Clone,
+        old_path: &str,
+        new_path: &str,
+    ) -> Result<(), String> {
+        rename_path(old_path, new_path)?;
+
+        let retarget = self
+            .file_watch
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|watch| {
+                canonical_path(&watch.path.to_string_lossy()) == canonical_path(old_path)
+            })
+            .map(|watch| watch.markdown_extensions.clone());
+        if let Some(markdown_extensions) = retarget {
+            match self.watch_file(sink.clone(), new_path, markdown_extensions) {
+                Ok(()) => sink.emit(Emission {
+                    event: "watch-retargeted",
+                    payload: json!({ "from": old_path, "to": new_path }),
+                }),
+                Err(e) => {
+                    self.file_watch.lock().unwrap().take();
+                    sink.emit(watch_error(Path::new(new_path), &e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// State of the directory watch on `root`, if there is one
+    fn watch_state(&self, root: &Path) -> Option<WatchState> {
+        self.watches
+            .lock()
+            .unwrap()
+            .get(&normalize_path(root))
+            .map(|watch| watch.state.clone())
+    }
+
+    /// State of the closest directory watch whose whole tree includes
+    /// `root`, the one that keeps a search index of `root` current
+    fn covering_state(&self, root: &Path) -> Option<WatchState> {
+        self.watches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(path, watch)| watch.max_depth.is_none() && root.starts_with(path))
+            .max_by_key(|(path, _)| path.components().count())
+            .map(|(_, watch)| watch.state.clone())
+    }
+
+    /// Snapshot of the index maintained by the `track_index` watch on `root`
+    pub fn cached_index(&self, root: &Path) -> Option<VaultIndex> {
+        self.watch_state(root)?.index.lock().unwrap().clone()
+    }
+
+    /// Query the search index maintained by the watch on `root`
+    pub fn search_indexed(&self, root: &Path, query: &str) -> Option<Vec<IndexedHit>> {
+        let state = self.watch_state(root)?;
+        let index = state.search_index.lock().unwrap();
+        index.as_ref().map(|index| index.query(query))
+    }
+
+    /// Hand `index` to the closest watch whose whole tree includes its
+    /// root, which keeps it current from then on. Returns false, dropping
+    /// the index, when nothing watches that tree.
+    pub fn set_search_index(&self, index: SearchIndex) -> bool {
+        let Some(state) = self.covering_state(index.root()) else {
+            return false;
+        };
+        *state.search_index.lock().unwrap() = Some(index);
+        true
+    }
+
+    /// Whether a watch of its whole tree would keep a search index of
+    /// `root` current
+    pub fn watches_tree(&self, root: &Path) -> bool {
+        self.covering_state(root).is_some()
+    }
+
+    /// Query the search index of `root` kept current by a watch of its
+    /// whole tree. `None` when no such watch holds an index of `root`.
+    pub fn query_search_index(&self, root: &Path, query: &str) -> Option<Vec<IndexedHit>> {
+        let state = self.covering_state(root)?;
+        let index = state.search_index.lock().unwrap();
+        index
+            .as_ref()
+            .filter(|index| index.root() == root)
+            .map(|index| index.query(query))
+    }
+
+    /// Mark a file as open in the editor, so the directory watch emits
+    /// `open-file-deleted` if it is deleted or moved away externally
+    pub fn register_open_file(&self, path: &str) {
+        self.open_files.lock().unwrap().insert(PathBuf::from(path));
+    }
+
+    /// Forget a file registered with `register_open_file`
+    pub fn unregister_open_file(&self, path: &str) {
+        self.open_files.lock().unwrap().remove(Path::new(path));
+    }
+
+    /// Whether the tree watched at `root` changed since it was last marked
+    /// clean
+    pub fn is_dirty(&self, root: &Path) -> bool {
+        self.watch_state(root)
+            .is_some_and(|state| state.dirty.load(Ordering::SeqCst))
+    }
+
+    /// Acknowledge all changes so far to the tree watched at `root`, e.g.
+    /// after a sync completes
+    pub fn mark_clean(&self, root: &Path) {
+        if let Some(state) = self.watch_state(root) {
+            state.dirty.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for DirectoryWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    impl EventSink for std::sync::mpsc::Sender<Emission> {
+        fn emit(&self, emission: Emission) {
+            let _ = self.send(emission);
+        }
+    }
+
+    #[test]
+    fn test_create_and_watch_stores_file_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("focus.md");
+        let path = path.to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, _rx) = channel();
+
+        watcher
+            .create_and_watch(tx.clone(), &path, "# Focus", None)
+            .unwrap();
+
+        assert_eq!(watcher.watched_file(), Some(path.clone()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Focus");
+        // A second create must not clobber the existing note
+        assert!(watcher.create_and_watch(tx, &path, "other", None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_watch_through_symlinked_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("link")).unwrap();
+        fs::write(dir.path().join("real/note.md"), "# Note").unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+
+        let linked = dir.path().join("link/note.md");
+        watcher
+            .watch_file(tx, &linked.to_string_lossy(), None)
+            .unwrap();
+        fs::write(&linked, "# Edited").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let modified = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let emission: Emission = rx.recv_timeout(remaining).expect("no file-modified event");
+            if emission.event == "file-modified" {
+                break emission.payload;
+            }
+        };
+        assert_eq!(
+            modified["path"],
+            json!(fs::canonicalize(dir.path().join("real/note.md")).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_rename_retargets_file_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_path = dir.path().join("draft.md").to_string_lossy().to_string();
+        let new_path = dir.path().join("final.md").to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        watcher
+            .create_and_watch(tx.clone(), &old_path, "# Draft", None)
+            .unwrap();
+
+        watcher.rename(tx, &old_path, &new_path).unwrap();
+
+        assert_eq!(watcher.watched_file(), Some(new_path.clone()));
+        let emission = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(emission.event, "watch-retargeted");
+        assert_eq!(emission.payload["to"], new_path);
+    }
+
+    #[test]
+    fn test_file_watch_follows_replace_on_save() {
+        let dir = Path::new("/notes");
+        let watched = dir.join("focus.md");
+        let temp = dir.join(".focus.md.swp");
+        let saved = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(temp.clone())
+            .add_path(watched.clone());
+        let removed = Event::new(EventKind::Remove(RemoveKind::File)).add_path(watched.clone());
+        let recreated = Event::new(EventKind::Create(CreateKind::File)).add_path(watched.clone());
+        let other = Event::new(EventKind::Create(CreateKind::File)).add_path(temp);
+
+        let emissions = file_watch_emissions(&watched, &[other.clone(), saved], None);
+        let events: Vec<&str> = emissions.iter().map(|e| e.event).collect();
+        assert_eq!(events, vec!["watched-file-change", "file-modified"]);
+        assert_eq!(emissions[0].payload["type"], "renamed");
+        assert_eq!(emissions[0].payload["paths"][0]["path"], json!(watched));
+        assert_eq!(emissions[0].payload["paths"].as_array().unwrap().len(), 1);
+
+        let emissions = file_watch_emissions(&watched, &[removed], None);
+        assert_eq!(emissions.len(), 1);
+        assert_eq!(emissions[0].payload["type"], "removed");
+        let emissions = file_watch_emissions(&watched, &[recreated], None);
+        assert_eq!(emissions.last().unwrap().event, "file-modified");
+        assert!(file_watch_emissions(&watched, &[other], None).is_empty());
+    }
+
+    #[test]
+    fn test_file_tail_reads_appends_truncation_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("build.log");
+        fs::write(&log, "old\n").unwrap();
+        let mut tail = FileTail::new(&log).unwrap();
+        let append = |text: &[u8]| {
+            let mut file = fs::OpenOptions::new().append(true).open(&log).unwrap();
+            std::io::Write::write_all(&mut file, text).unwrap();
+        };
+        let read = |tail: &mut FileTail| {
+            tail.read_new()
+                .unwrap()
+                .map(|chunk| (chunk.content, chunk.reset))
+        };
+
+        assert_eq!(read(&mut tail), None);
+        append(b"one\n");
+        assert_eq!(read(&mut tail), Some(("one\n".to_string(), false)));
+        // Half of a two-byte character waits for the rest
+        append(b"caf\xc3");
+        assert_eq!(read(&mut tail), Some(("caf".to_string(), false)));
+        append(b"\xa9\n");
+        assert_eq!(read(&mut tail), Some(("é\n".to_string(), false)));
+
+        fs::write(&log, "new\n").unwrap();
+        assert_eq!(read(&mut tail), Some(("new\n".to_string(), true)));
+        let rotated = dir.path().join("build.log.next");
+        fs::write(&rotated, "rotated and longer\n").unwrap();
+        fs::rename(&rotated, &log).unwrap();
+        assert_eq!(
+            read(&mut tail),
+            Some(("rotated and longer\n".to_string(), true))
+        );
+        assert_eq!(tail.offset, 19);
+
+        // Truncated and written past the offset again in place
+        fs::write(&log, "rewritten in place, longer\n").unwrap();
+        assert_eq!(
+            read(&mut tail),
+            Some(("rewritten in place, longer\n".to_string(), true))
+        );
+
+        // A large append comes in pieces
+        append(&vec![b'x'; TAIL_READ_LIMIT as usize + 10]);
+        let first = tail.read_new().unwrap().unwrap();
+        assert_eq!(first.content.len() as u64, TAIL_READ_LIMIT);
+        assert_eq!(read(&mut tail), Some(("x".repeat(10), false)));
+        assert_eq!(read(&mut tail), None);
+    }
+
+    #[test]
+    fn test_tail_file_emits_appended_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("out.log");
+        fs::write(&log, "before\n").unwrap();
+        let path = log.to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+
+        assert_eq!(watcher.tail_file(tx, &path).unwrap(), 7);
+        let mut file = fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(&mut file, b"after\n").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let tail = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let emission: Emission = rx.recv_timeout(remaining).expect("no file-tail event");
+            if emission.event == "file-tail" {
+                break emission.payload;
+            }
+        };
+        assert_eq!(tail["content"], json!("after\n"));
+        assert_eq!(tail["offset"], json!(13));
+        assert!(watcher.stop_tail(&path));
+        assert!(!watcher.stop_tail(&path));
+    }
+
+    #[test]
+    fn test_debounce_interval_validation() {
+        let options = |debounce_ms| WatchOptions {
+            debounce_ms,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            debounce_interval(&options(None)),
+            Ok(Duration::from_millis(500))
+        );
+        assert_eq!(
+            debounce_interval(&options(Some(50))),
+            Ok(Duration::from_millis(50))
+        );
+        assert!(debounce_interval(&options(Some(0))).is_err());
+        assert!(debounce_interval(&options(Some(60_000))).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, _rx) = channel();
+        let watcher = DirectoryWatcher::new();
+        assert!(watcher
+            .watch_directory(tx, &dir.path().to_string_lossy(), options(Some(0)))
+            .is_err());
+        assert!(watcher.watched_directories().is_empty());
+    }
+
+    #[test]
+    fn test_unwatch_directory_leaves_other_watches_running() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let first_path = first.path().to_string_lossy().to_string();
+        let second_path = second.path().to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, _rx) = channel();
+
+        watcher
+            .watch_directory(tx.clone(), &first_path, WatchOptions::default())
+            .unwrap();
+        watcher
+            .watch_directory(tx, &second_path, WatchOptions::default())
+            .unwrap();
+        let mut both = vec![first_path.clone(), second_path.clone()];
+        both.sort();
+        assert_eq!(watcher.watched_directories(), both);
+
+        watcher.unwatch_directory(&first_path).unwrap();
+        assert_eq!(watcher.watched_directories(), vec![second_path]);
+        assert!(watcher.unwatch_directory(&first_path).is_err());
+
+        watcher.stop_watching();
+        assert!(watcher.watched_directories().is_empty());
+    }
+
+    #[test]
+    fn test_watched_paths_report_mode_and_debounce() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "").unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let note = note.to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, _rx) = channel();
+        let options = WatchOptions {
+            max_depth: Some(2),
+            debounce_ms: Some(200),
+            ..Default::default()
+        };
+
+        assert!(!watcher.is_watching(&root));
+        watcher.watch_directory(tx.clone(), &root, options).unwrap();
+        watcher.watch_file(tx, &note, None).unwrap();
+
+        assert_eq!(
+            watcher.watched_paths(),
+            vec![
+                WatchedPath {
+                    path: root.clone(),
+                    is_file: false,
+                    recursive: false,
+                    max_depth: Some(2),
+                    debounce_ms: 200,
+                },
+                WatchedPath {
+                    path: note.clone(),
+                    is_file: true,
+                    recursive: false,
+                    max_depth: None,
+                    debounce_ms: DEFAULT_DEBOUNCE_MS,
+                },
+            ]
+        );
+        assert!(watcher.is_watching(&root));
+        assert!(watcher.is_watching(&note));
+        assert!(!watcher.is_watching(&dir.path().join("other").to_string_lossy()));
+
+        watcher.stop_watching();
+        watcher.unwatch_file();
+        assert!(watcher.watched_paths().is_empty());
+    }
+
+    #[test]
+    fn test_watch_ready_emitted_for_empty_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        watcher.pause_watching();
+
+        watcher
+            .watch_directory(tx, &dir.path().to_string_lossy(), WatchOptions::default())
+            .unwrap();
+
+        let emission = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(emission.event, "watch-ready");
+        assert_eq!(emission.payload["path"], json!(dir.path()));
+        watcher.stop_watching();
+    }
+
+    #[test]
+    fn test_watch_with_snapshot_lists_directory_and_watches_it() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("existing.md"), "# Existing").unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+        let watcher = DirectoryWatcher::new();
+        let (tx, _rx) = channel();
+
+        let snapshot = watcher
+            .watch_directory_with_snapshot(
+                tx,
+                &path,
+                WatchOptions::default(),
+                &DirectoryOptions::default(),
+            )
+            .unwrap();
+
+        let names: Vec<_> = snapshot.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["existing.md"]);
+        assert_eq!(watcher.watched_directories(), vec![path]);
+        watcher.stop_watching();
+    }
+
+    #[test]
+    fn test_max_depth_limits_and_follows_watched_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        let options = WatchOptions {
+            debounce_ms: Some(50),
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        watcher
+            .watch_directory(tx, &dir.path().to_string_lossy(), options)
+            .unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap().event,
+            "watch-ready"
+        );
+        // Waits for a `file-change` naming `path` until events go quiet
+        let saw_change = |path: &str| {
+            let path = json!(dir.path().join(path));
+            while let Ok(emission) = rx.recv_timeout(Duration::from_millis(500)) {
+                let paths = &emission.payload["paths"];
+                if emission.event == "file-change" && paths[0]["path"] == path {
+                    return true;
+                }
+            }
+            false
+        };
+
+        fs::write(dir.path().join("a/b/deep.md"), "# Deep").unwrap();
+        assert!(!saw_change("a/b/deep.md"));
+        fs::write(dir.path().join("a/shallow.md"), "# Shallow").unwrap();
+        assert!(saw_change("a/shallow.md"));
+
+        // A directory created within the limit is watched from then on
+        fs::create_dir(dir.path().join("new")).unwrap();
+        assert!(saw_change("new"));
+        fs::write(dir.path().join("new/note.md"), "# New").unwrap();
+        assert!(saw_change("new/note.md"));
+        watcher.stop_watching();
+    }
+
+    #[test]
+    fn test_removing_watched_directory_stops_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("vault");
+        fs::create_dir(&root).unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        let options = WatchOptions {
+            debounce_ms: Some(50),
+            ..Default::default()
+        };
+        watcher
+            .watch_directory(tx, &root.to_string_lossy(), options)
+            .unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap().event,
+            "watch-ready"
+        );
+
+        fs::remove_dir(&root).unwrap();
+
+        let events: Vec<&str> = rx
+            .iter()
+            .map(|emission| emission.event)
+            .take_while(|event| *event != "watch-stopped")
+            .collect();
+        assert!(events.contains(&"watch-error"));
+        assert!(watcher.watched_directories().is_empty());
+    }
+
+    #[test]
+    fn test_pause_drops_emissions_and_resume_refreshes_once() {
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        let change = || {
+            vec![Emission {
+                event: "tree-change",
+                payload: json!({ "path": "notes" }),
+            }]
+        };
+
+        watcher.pause_watching();
+        assert!(watcher.is_paused());
+        watcher.pause.forward(&tx, change());
+        watcher.pause.forward(&tx, change());
+        assert!(rx.try_recv().is_err());
+
+        assert!(watcher.resume_watching(tx.clone(), true));
+        assert!(!watcher.is_paused());
+        assert_eq!(rx.try_recv().unwrap().event, "refresh");
+        assert!(rx.try_recv().is_err());
+
+        watcher.pause.forward(&tx, change());
+        assert_eq!(rx.try_recv().unwrap().event, "tree-change");
+
+        // Nothing missed, so there is nothing to refresh
+        watcher.pause_watching();
+        assert!(!watcher.resume_watching(tx.clone(), true));
+        assert!(rx.try_recv().is_err());
+
+        // A nested pause keeps emissions dropped until the outer one ends
+        watcher.pause_watching();
+        watcher.pause_watching();
+        watcher.pause.forward(&tx, change());
+        assert!(!watcher.resume_watching(tx.clone(), true));
+        assert!(watcher.is_paused());
+        watcher.pause.forward(&tx, change());
+        assert!(rx.try_recv().is_err());
+        assert!(watcher.resume_watching(tx.clone(), true));
+        assert_eq!(rx.try_recv().unwrap().event, "refresh");
+        assert!(!watcher.resume_watching(tx, true));
+    }
+
+    #[test]
+    fn test_file_count_tracks_create_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "a").unwrap();
+        let options = WatchOptions {
+            track_file_count: true,
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        assert_eq!(session.initial_emissions(), vec![file_count_emission(1)]);
+
+        let new_note = dir.path().join("b.md");
+        fs::write(&new_note, "b").unwrap();
+        let created = Event::new(EventKind::Create(CreateKind::File)).add_path(new_note.clone());
+        let emissions = session.handle_events(&[created]);
+        assert_eq!(emissions.last(), Some(&file_count_emission(2)));
+
+        fs::remove_file(&new_note).unwrap();
+        let removed = Event::new(EventKind::Remove(RemoveKind::File)).add_path(new_note);
+        let emissions = session.handle_events(&[removed]);
+        assert_eq!(emissions.last(), Some(&file_count_emission(1)));
+    }
+
+    #[test]
+    fn test_file_count_uses_extensions_and_ignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("drafts")).unwrap();
+        fs::write(dir.path().join("page.mdx"), "a").unwrap();
+        fs::write(dir.path().join("note.md"), "b").unwrap();
+        fs::write(dir.path().join("drafts/idea.mdx"), "c").unwrap();
+        let options = WatchOptions {
+            track_file_count: true,
+            markdown_extensions: Some(vec!["mdx".to_string()]),
+            ..Default::default()
+        };
+        let parts = SessionParts {
+            ignore: IgnoreRules::new(&["drafts".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, parts);
+        assert_eq!(session.initial_emissions(), vec![file_count_emission(1)]);
+
+        let draft = dir.path().join("drafts/other.mdx");
+        fs::write(&draft, "d").unwrap();
+        let created = Event::new(EventKind::Create(CreateKind::File)).add_path(draft);
+        let emissions = session.handle_events(&[created]);
+        assert!(!emissions.contains(&file_count_emission(2)));
+    }
+
+    #[test]
+    fn test_metadata_touch_does_not_mark_preview_stale() {
+        use notify::event::MetadataKind;
+
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        fs::write(&note, "original").unwrap();
+        let options = WatchOptions {
+            preview_stale: true,
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        let is_stale =
+            |emissions: &[Emission]| emissions.iter().any(|e| e.event == "preview-stale");
+
+        let touch = Event::new(EventKind::Modify(ModifyKind::Metadata(
+            MetadataKind::WriteTime,
+        )))
+        .add_path(note.clone());
+        assert!(!is_stale(&session.handle_events(&[touch])));
+
+        fs::write(&note, "edited").unwrap();
+        let edit = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(note);
+        assert!(is_stale(&session.handle_events(&[edit])));
+    }
+
+    #[test]
+    fn test_content_hashes_skip_ignored_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("drafts")).unwrap();
+        let note = dir.path().join("note.md");
+        let draft = dir.path().join("drafts/idea.md");
+        fs::write(&note, "note").unwrap();
+        fs::write(&draft, "draft").unwrap();
+
+        let mut cache = ContentHashCache::new(NoteFilter {
+            root: dir.path().to_path_buf(),
+            extensions: None,
+            ignore: IgnoreRules::new(&["drafts".to_string()]).unwrap(),
+        });
+
+        assert!(cache.hashes.contains_key(&note));
+        assert!(!cache.hashes.contains_key(&draft));
+        fs::write(&draft, "edited").unwrap();
+        assert!(!cache.refresh(&draft));
+    }
+
+    #[test]
+    fn test_preview_stale_uses_configured_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let page = dir.path().join("page.mdx");
+        let note = dir.path().join("note.md");
+        fs::write(&page, "original").unwrap();
+        fs::write(&note, "original").unwrap();
+        let options = WatchOptions {
+            preview_stale: true,
+            markdown_extensions: Some(vec!["mdx".to_string()]),
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        let is_stale =
+            |emissions: &[Emission]| emissions.iter().any(|e| e.event == "preview-stale");
+
+        fs::write(&note, "edited").unwrap();
+        let edit = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(note);
+        assert!(!is_stale(&session.handle_events(&[edit])));
+
+        fs::write(&page, "edited").unwrap();
+        let edit = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(page);
+        assert!(is_stale(&session.handle_events(&[edit])));
+    }
+
+    #[test]
+    fn test_dirty_flag_set_by_change_and_reset_by_mark_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = WatchOptions {
+            track_dirty: true,
+            ..Default::default()
+        };
+        let state = WatchState::default();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &options,
+            SessionParts {
+                state: state.clone(),
+                ..Default::default()
+            },
+        );
+        let change = || Event::new(EventKind::Any).add_path(dir.path().join("a.md"));
+        let dirty_emitted =
+            |emissions: &[Emission]| emissions.iter().any(|e| e.event == "vault-dirty");
+        let is_dirty = || state.dirty.load(Ordering::SeqCst);
+
+        assert!(!is_dirty());
+        assert!(dirty_emitted(&session.handle_events(&[change()])));
+        assert!(is_dirty());
+        // Only the first change after a clean mark emits
+        assert!(!dirty_emitted(&session.handle_events(&[change()])));
+
+        state.dirty.store(false, Ordering::SeqCst);
+        assert!(dirty_emitted(&session.handle_events(&[change()])));
+    }
+
+    #[test]
+    fn test_index_updates_only_changed_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let edited = root.join("edited.md");
+        let other = root.join("other.md");
+        fs::write(&edited, "#draft links to [[other]]").unwrap();
+        fs::write(&other, "#keep").unwrap();
+        fs::create_dir_all(root.join("archive/old")).unwrap();
+        fs::write(root.join("archive/old/stale.md"), "#stale").unwrap();
+        let options = WatchOptions {
+            track_index: true,
+            ..Default::default()
+        };
+        let state = WatchState::default();
+        let mut session = WatchSession::new(
+            &root,
+            &options,
+            SessionParts {
+                state: state.clone(),
+                ignore: IgnoreRules::new(&["archive".to_string()]).unwrap(),
+                ..Default::default()
+            },
+        );
+        let cached_index = || state.index.lock().unwrap().clone().unwrap();
+        let before = cached_index();
+        assert_eq!(before.notes[&edited].tags, vec!["draft"]);
+        assert_eq!(before.notes.len(), 2);
+
+        fs::write(&edited, "#final links to [[other]]").unwrap();
+        session.handle_events(&[
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(edited.clone())
+        ]);
+
+        let after = cached_index();
+        assert_eq!(after.notes[&edited].tags, vec!["final"]);
+        assert_eq!(after.notes[&other], before.notes[&other]);
+        assert_eq!(after.notes[&other].backlinks, vec![edited.clone()]);
+
+        // A folder that appears is indexed without its ignored notes
+        fs::create_dir_all(root.join("new/archive")).unwrap();
+        fs::write(root.join("new/archive/x.md"), "#x").unwrap();
+        fs::write(root.join("new/y.md"), "#y").unwrap();
+        session.handle_events(&[
+            Event::new(EventKind::Create(CreateKind::Folder)).add_path(root.join("new"))
+        ]);
+        let notes: Vec<PathBuf> = cached_index().notes.into_keys().collect();
+        assert_eq!(notes, vec![edited, root.join("new/y.md"), other]);
+    }
+
+    #[test]
+    fn test_search_index_reflects_changed_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let note = root.join("note.md");
+        fs::write(&note, "Planning the garden").unwrap();
+        fs::write(root.join("other.md"), "garden garden").unwrap();
+        let options = WatchOptions {
+            track_search_index: true,
+            ..Default::default()
+        };
+        let state = WatchState::default();
+        let mut session = WatchSession::new(
+            &root,
+            &options,
+            SessionParts {
+                state: state.clone(),
+                ..Default::default()
+            },
+        );
+        let paths = |query: &str| -> Vec<String> {
+            let index = state.search_index.lock().unwrap();
+            index
+                .as_ref()
+                .unwrap()
+                .query(query)
+                .into_iter()
+                .map(|hit| hit.path)
+                .collect()
+        };
+        assert_eq!(paths("planning"), vec![note.to_string_lossy().to_string()]);
+        assert_eq!(paths("garden").len(), 2);
+
+        fs::write(&note, "Harvest notes").unwrap();
+        session.handle_events(&[
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(note.clone())
+        ]);
+
+        assert!(paths("planning").is_empty());
+        assert_eq!(paths("harvest"), vec![note.to_string_lossy().to_string()]);
+        assert_eq!(paths("garden").len(), 1);
+    }
+
+    #[test]
+    fn test_installed_search_index_follows_covering_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let vault = root.join("vault");
+        fs::create_dir(&vault).unwrap();
+        let note = vault.join("note.md");
+        fs::write(&note, "Planning the garden").unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, _rx) = channel();
+        // Nothing watches the vault, so an index of it would go stale
+        assert!(!watcher.set_search_index(SearchIndex::build(&vault)));
+        assert_eq!(watcher.query_search_index(&vault, "garden"), None);
+
+        watcher
+            .watch_directory(tx, &root.to_string_lossy(), WatchOptions::default())
+            .unwrap();
+        assert!(watcher.watches_tree(&vault));
+        assert!(watcher.set_search_index(SearchIndex::build(&vault)));
+        assert_eq!(
+            watcher.query_search_index(&vault, "garden").unwrap().len(),
+            1
+        );
+        assert_eq!(watcher.query_search_index(&root, "garden"), None);
+
+        // The index goes with the watch that kept it current
+        watcher.unwatch_directory(&root.to_string_lossy()).unwrap();
+        assert_eq!(watcher.query_search_index(&vault, "garden"), None);
+    }
+
+    #[test]
+    fn test_installed_search_index_follows_session_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let note = root.join("note.md");
+        fs::write(&note, "Planning the garden").unwrap();
+        let state = WatchState::default();
+        *state.search_index.lock().unwrap() = Some(SearchIndex::build(&root));
+        let mut session = WatchSession::new(
+            &root,
+            &WatchOptions::default(),
+            SessionParts {
+                state: state.clone(),
+                ..Default::default()
+            },
+        );
+
+        fs::remove_file(&note).unwrap();
+        session.handle_events(&[Event::new(EventKind::Remove(RemoveKind::File)).add_path(note)]);
+        let index = state.search_index.lock().unwrap();
+        assert_eq!(index.as_ref().unwrap().query("garden"), Vec::new());
+    }
+
+    #[test]
+    fn test_watches_keep_separate_dirty_state() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let watcher = DirectoryWatcher::new();
+        let (tx, rx) = channel();
+        let options = WatchOptions {
+            track_dirty: true,
+            debounce_ms: Some(50),
+            ..Default::default()
+        };
+        for dir in [&first, &second] {
+            watcher
+                .watch_directory(tx.clone(), &dir.path().to_string_lossy(), options.clone())
+                .unwrap();
+        }
+        let ready = rx
+            .iter()
+            .filter(|emission| emission.event == "watch-ready")
+            .take(2)
+            .count();
+        assert_eq!(ready, 2);
+
+        fs::write(first.path().join("a.md"), "a").unwrap();
+        rx.iter()
+            .find(|emission| emission.event == "vault-dirty")
+            .unwrap();
+        assert!(watcher.is_dirty(first.path()));
+        assert!(!watcher.is_dirty(second.path()));
+
+        watcher.mark_clean(first.path());
+        assert!(!watcher.is_dirty(first.path()));
+        watcher.stop_watching();
+    }
+
+    #[test]
+    fn test_event_queue_overflows_into_full_refresh() {
+        use notify_debouncer_full::DebouncedEvent;
+        use std::time::Instant;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let batch = |count: usize| -> DebounceEventResult {
+            Ok((0..count)
+                .map(|i| {
+                    let event = Event::new(EventKind::Create(CreateKind::File))
+                        .add_path(root.join(format!("{}.md", i)));
+                    DebouncedEvent::new(event, Instant::now())
+                })
+                .collect())
+        };
+
+        let (mut queue, rx, state) = EventQueue::new(3);
+        queue.handle_event(batch(2));
+        assert!(!state.catch_up(&rx));
+        // Past the limit the batch is dropped and the thread woken
+        queue.handle_event(batch(2));
+        queue.handle_event(batch(1));
+        let first = rx.try_recv().unwrap();
+        assert_eq!(event_count(&first), 2);
+        state.taken(&first);
+        assert!(state.catch_up(&rx));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(state.pending.load(Ordering::SeqCst), 0);
+
+        queue.handle_event(batch(3));
+        assert!(!state.catch_up(&rx));
+        assert_eq!(event_count(&rx.try_recv().unwrap()), 3);
+
+        fs::write(root.join("note.md"), "").unwrap();
+        let options = WatchOptions {
+            track_file_count: true,
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(&root, &options, SessionParts::default());
+        fs::write(root.join("missed.md"), "").unwrap();
+        let emissions = session.resync();
+        assert_eq!(emissions[0].event, "needs-full-refresh");
+        assert_eq!(emissions[1], file_count_emission(2));
+    }
+
+    #[test]
+    fn test_burst_then_silence_settles_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = WatchOptions {
+            quiescence_ms: Some(100),
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        assert!(session.settle().is_empty());
+
+        let change = |name: &str| Event::new(EventKind::Any).add_path(dir.path().join(name));
+        session.handle_events(&[change("a.md"), change("b.md")]);
+        session.handle_events(&[change("c.md")]);
+
+        let settled = session.settle();
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].event, "vault-settled");
+        assert_eq!(settled[0].payload, json!({ "events": 3 }));
+        // Continued silence doesn't settle again
+        assert!(session.settle().is_empty());
+    }
+
+    #[test]
+    fn test_directory_and_file_changes_emit_distinct_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("folder");
+        let note = dir.path().join("note.md");
+        fs::create_dir(&folder).unwrap();
+        fs::write(&note, "edited").unwrap();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts::default(),
+        );
+        let classified = |emissions: Vec<Emission>| -> Vec<&'static str> {
+            emissions
+                .into_iter()
+                .map(|e| e.event)
+                .filter(|event| *event == "tree-change" || *event == "content-change")
+                .collect()
+        };
+
+        let created = Event::new(EventKind::Create(CreateKind::Folder)).add_path(folder);
+        let modified = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(note);
+        let removed =
+            Event::new(EventKind::Remove(RemoveKind::Any)).add_path(dir.path().join("gone"));
+
+        assert_eq!(
+            classified(session.handle_events(&[created, modified, removed])),
+            vec!["tree-change", "content-change", "tree-change"]
+        );
+    }
+
+    #[test]
+    fn test_deleting_open_file_emits_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = dir.path().join("open.md");
+        let closed = dir.path().join("closed.md");
+        let watcher = DirectoryWatcher::new();
+        watcher.register_open_file(&open.to_string_lossy());
+        watcher.register_open_file(&closed.to_string_lossy());
+        watcher.unregister_open_file(&closed.to_string_lossy());
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts {
+                open_files: watcher.open_files.clone(),
+                ..Default::default()
+            },
+        );
+        let deleted = |emissions: Vec<Emission>| -> Vec<Value> {
+            emissions
+                .into_iter()
+                .filter(|e| e.event == "open-file-deleted")
+                .map(|e| e.payload)
+                .collect()
+        };
+        let remove =
+            |path: &Path| Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.into());
+
+        assert!(deleted(session.handle_events(&[remove(&closed)])).is_empty());
+        assert_eq!(
+            deleted(session.handle_events(&[remove(&open)])),
+            vec![json!({ "path": open })]
+        );
+    }
+
+    #[test]
+    fn test_changes_outside_subpaths_are_not_emitted() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = WatchOptions {
+            subpaths: vec!["notes".to_string(), "journal".to_string()],
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        let change = |path: &str| Event::new(EventKind::Any).add_path(dir.path().join(path));
+
+        assert!(session
+            .handle_events(&[change("archive/old.md"), change("inbox.md")])
+            .is_empty());
+        let emissions = session.handle_events(&[change("journal/today.md")]);
+        assert_eq!(emissions[0].event, "file-change");
+        assert_eq!(
+            emissions[0].payload["paths"][0]["path"],
+            json!(dir.path().join("journal/today.md"))
+        );
+    }
+
+    #[test]
+    fn test_file_change_payload_is_typed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts::default(),
+        );
+        let from = dir.path().join("draft.md");
+        let to = dir.path().join("final.md");
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(from.clone())
+            .add_path(to.clone());
+        let create =
+            Event::new(EventKind::Create(CreateKind::File)).add_path(dir.path().join("new.md"));
+
+        let payloads: Vec<Value> = session
+            .handle_events(&[rename, create])
+            .into_iter()
+            .filter(|emission| emission.event == "file-change")
+            .map(|emission| emission.payload)
+            .collect();
+
+        assert_eq!(
+            payloads,
+            vec![
+                json!({
+                    "type": "renamed",
+                    "paths": [
+                        { "path": from, "is_markdown": true },
+                        { "path": to, "is_markdown": true },
+                    ],
+                    "from": from,
+                    "to": to,
+                }),
+                json!({
+                    "type": "created",
+                    "paths": [{ "path": dir.path().join("new.md"), "is_markdown": true }],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_change_paths_flag_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts::default(),
+        );
+        let change = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(dir.path().join("note.md"))
+            .add_path(dir.path().join("image.png"));
+
+        let emissions = session.handle_events(&[change]);
+
+        assert_eq!(emissions[0].event, "file-change");
+        let paths = &emissions[0].payload["paths"];
+        assert_eq!(paths[0]["is_markdown"], json!(true));
+        assert_eq!(paths[1]["is_markdown"], json!(false));
+
+        // A vault's configured extensions replace the built-in set
+        let options = WatchOptions {
+            markdown_extensions: Some(vec!["mdx".to_string()]),
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &options, SessionParts::default());
+        let change = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(dir.path().join("note.md"))
+            .add_path(dir.path().join("page.mdx"));
+
+        let emissions = session.handle_events(&[change]);
+
+        let paths = &emissions[0].payload["paths"];
+        assert_eq!(paths[0]["is_markdown"], json!(false));
+        assert_eq!(paths[1]["is_markdown"], json!(true));
+    }
+
+    #[test]
+    fn test_remove_and_create_pair_into_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts::default(),
+        );
+        let removed_ids = session.removed_ids.clone();
+        let path = |name: &str| dir.path().join(name);
+        // Note the id a file had as the debouncer's cache does, then take it
+        // away
+        let note_removed = |name: &str| {
+            let id = get_file_id(path(name)).unwrap();
+            removed_ids.lock().unwrap().insert(path(name), id);
+        };
+        let removed =
+            |name: &str| Event::new(EventKind::Remove(RemoveKind::File)).add_path(path(name));
+        let created =
+            |name: &str| Event::new(EventKind::Create(CreateKind::File)).add_path(path(name));
+        let mut changes = |events: &[Event]| -> Vec<Value> {
+            session
+                .handle_events(events)
+                .into_iter()
+                .filter(|e| e.event == "file-change")
+                .map(|e| e.payload)
+                .collect()
+        };
+
+        fs::write(path("draft.md"), "draft").unwrap();
+        note_removed("draft.md");
+        fs::rename(path("draft.md"), path("final.md")).unwrap();
+        let renamed = changes(&[removed("draft.md"), created("final.md")]);
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed[0]["type"], json!("renamed"));
+        assert_eq!(renamed[0]["from"], json!(path("draft.md")));
+        assert_eq!(renamed[0]["to"], json!(path("final.md")));
+
+        // Only the removed file with the created file's id is paired
+        fs::write(path("a.md"), "a").unwrap();
+        fs::write(path("b.md"), "b").unwrap();
+        note_removed("a.md");
+        note_removed("b.md");
+        fs::remove_file(path("a.md")).unwrap();
+        fs::rename(path("b.md"), path("c.md")).unwrap();
+        let paired = changes(&[removed("a.md"), removed("b.md"), created("c.md")]);
+        assert_eq!(paired.len(), 2);
+        assert_eq!(paired[1]["from"], json!(path("b.md")));
+        // A different file of the same name, or one with no known id, is
+        // not the same entry
+        fs::write(path("d.md"), "d").unwrap();
+        note_removed("d.md");
+        fs::remove_file(path("d.md")).unwrap();
+        fs::create_dir(path("sub")).unwrap();
+        fs::write(path("sub/d.md"), "d").unwrap();
+        let unrelated = changes(&[removed("d.md"), created("sub/d.md")]);
+        assert_eq!(unrelated.len(), 2);
+        let unknown = changes(&[removed("gone.md"), created("c.md")]);
+        assert_eq!(unknown.len(), 2);
+        // Removing and recreating one path is a replace, not a rename
+        let replaced = changes(&[removed("c.md"), created("c.md")]);
+        assert_eq!(replaced.len(), 1);
+        assert_ne!(replaced[0]["type"], json!("renamed"));
+        assert!(removed_ids.lock().unwrap().is_empty());
+
+        let moved = changes(&[
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                .add_path(path("a.md"))
+                .set_tracker(7),
+            created("other.md"),
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::To)))
+                .add_path(path("sub/b.md"))
+                .set_tracker(7),
+        ]);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(moved[0]["type"], json!("created"));
+        assert_eq!(moved[1]["from"], json!(path("a.md")));
+        assert_eq!(moved[1]["to"], json!(path("sub/b.md")));
+    }
+
+    #[test]
+    fn test_changes_to_one_path_are_coalesced() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = dir.path().join("note.md");
+        let gone = dir.path().join("gone.md");
+        fs::write(&note, "saved").unwrap();
+        let mut session = WatchSession::new(
+            dir.path(),
+            &WatchOptions::default(),
+            SessionParts::default(),
+        );
+        let event = |kind: EventKind, path: &Path| Event::new(kind).add_path(path.into());
+        let data = EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Content));
+        let metadata = EventKind::Modify(ModifyKind::Metadata(notify::event::MetadataKind::Any));
+
+        let emissions = session.handle_events(&[
+            event(data, &note),
+            event(EventKind::Create(CreateKind::File), &note),
+            event(metadata, &note),
+            event(data, &gone),
+            event(EventKind::Remove(RemoveKind::File), &gone),
+        ]);
+        let changes: Vec<(Value, Value)> = emissions
+            .iter()
+            .filter(|e| e.event == "file-change")
+            .map(|e| {
+                (
+                    e.payload["type"].clone(),
+                    e.payload["paths"][0]["path"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (json!("created"), json!(note)),
+                (json!("removed"), json!(gone)),
+            ]
+        );
+        let note_changes = emissions
+            .iter()
+            .filter(|e| e.event == "content-change" && e.payload["path"] == json!(note))
+            .count();
+        assert_eq!(note_changes, 1);
+    }
+
+    #[test]
+    fn test_removed_and_created_path_is_resolved_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.md");
+        let dropped = dir.path().join("dropped.md");
+        fs::write(&kept, "replaced").unwrap();
+        let removed = |path: &Path| file_change(ChangeType::Removed, path);
+        let created = |path: &Path| file_change(ChangeType::Created, path);
+        let mut batch = ChangeBatch::default();
+        // Listed out of order: the file that survived was removed last, the
+        // one that is gone was created last
+        batch.add(created(&kept));
+        batch.add(removed(&kept));
+        batch.add(removed(&dropped));
+        batch.add(created(&dropped));
+
+        let types: Vec<Value> = batch
+            .into_emissions()
+            .map(|emission| emission.payload["type"].clone())
+            .collect();
+        assert_eq!(types, vec![json!("created"), json!("removed")]);
+    }
+
+    fn file_change(change_type: ChangeType, path: &Path) -> FileChange {
+        FileChange {
+            change_type,
+            paths: vec![ChangedPath::new(path, None)],
+            from: None,
+            to: None,
+        }
+    }
+
+    #[test]
+    fn test_ignored_paths_are_not_emitted() {
+        let dir = tempfile::tempdir().unwrap();
+        let parts = SessionParts {
+            ignore: IgnoreRules::new(&["node_modules".to_string(), ".git".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let mut session = WatchSession::new(dir.path(), &WatchOptions::default(), parts);
+        let change = |path: &str| Event::new(EventKind::Any).add_path(dir.path().join(path));
+
+        assert!(session
+            .handle_events(&[change("node_modules/pkg/index.js"), change(".git/HEAD"),])
+            .is_empty());
+        let emissions = session.handle_events(&[change("notes/today.md")]);
+        assert_eq!(emissions[0].event, "file-change");
+    }
+}